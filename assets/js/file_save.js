@@ -1,5 +1,5 @@
 // File saving utility using File System Access API
-export async function save_stream_to_disk(stream) {
+export async function save_stream_to_disk(stream, suggestedName) {
     try {
         // Check if File System Access API is supported
         if (!window.showSaveFilePicker) {
@@ -8,7 +8,7 @@ export async function save_stream_to_disk(stream) {
         }
 
         const handle = await window.showSaveFilePicker({
-            suggestedName: 'serial_log.txt',
+            suggestedName: suggestedName || 'serial_log.txt',
             types: [{
                 description: 'Text Files',
                 accept: { 'text/plain': ['.txt'] }
@@ -62,3 +62,30 @@ export async function save_terminal_history(terminal) {
         }
     }
 }
+
+export async function save_json_to_disk(content, suggestedName) {
+    try {
+        if (!window.showSaveFilePicker) {
+            alert('Your browser does not support file saving. Please use Chrome/Edge.');
+            return;
+        }
+
+        const handle = await window.showSaveFilePicker({
+            suggestedName: suggestedName || 'export.json',
+            types: [{
+                description: 'JSON Files',
+                accept: { 'application/json': ['.json'] }
+            }],
+        });
+
+        const writable = await handle.createWritable();
+        await writable.write(content);
+        await writable.close();
+    } catch (err) {
+        if (err.name !== 'AbortError') {
+            const errorMsg = err.message || String(err);
+            console.error('Save failed:', errorMsg);
+            alert('Save failed: ' + errorMsg);
+        }
+    }
+}
@@ -0,0 +1,104 @@
+//! Timing and memory of the compact line index at README scale.
+//! Run with `cargo bench --bench line_index`.
+
+#[allow(dead_code, unused_imports)]
+#[path = "../src/worker/repository/index/types.rs"]
+mod types;
+
+#[allow(dead_code, unused_imports)]
+#[path = "../src/worker/repository/index/line_offsets.rs"]
+mod line_offsets;
+
+#[allow(dead_code, unused_imports)]
+#[path = "../src/worker/repository/index/line_set.rs"]
+mod line_set;
+
+use line_offsets::LineOffsets;
+use line_set::LineSet;
+use std::hint::black_box;
+use std::time::Instant;
+use types::ByteOffset;
+
+const LINES: usize = 10_000_000;
+/// Typical formatted line: timestamp plus a short message
+const LINE_LEN: u64 = 64;
+
+fn mib(bytes: usize) -> f64 {
+    bytes as f64 / (1024.0 * 1024.0)
+}
+
+fn time<T>(label: &str, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let out = f();
+    println!(
+        "{label:<32} {:>8.1} ms",
+        start.elapsed().as_secs_f64() * 1000.0
+    );
+    out
+}
+
+fn bench_offsets() {
+    let offsets = time("offsets: push", || {
+        let mut offsets = LineOffsets::new();
+        for i in 1..=LINES as u64 {
+            offsets.push(ByteOffset(i * LINE_LEN));
+        }
+        offsets
+    });
+    time("offsets: get (strided)", || {
+        let mut sum = 0u64;
+        for i in (0..LINES).step_by(7) {
+            sum = sum.wrapping_add(offsets.get(i).0);
+        }
+        black_box(sum)
+    });
+    time("offsets: partition_point x 100k", || {
+        for i in 0..100_000u64 {
+            black_box(offsets.partition_point(|off| off.0 < i * 97 * LINE_LEN));
+        }
+    });
+    let baseline = time("Vec<u64>: push", || {
+        let mut offsets = vec![0u64];
+        for i in 1..=LINES as u64 {
+            offsets.push(i * LINE_LEN);
+        }
+        offsets
+    });
+    println!(
+        "offsets: {:.1} MiB (Vec<u64>: {:.1} MiB)",
+        mib(offsets.memory_bytes()),
+        mib(baseline.capacity() * 8)
+    );
+}
+
+fn bench_set(label: &str, every: usize) {
+    let set = time(&format!("{label}: insert"), || {
+        let mut set = LineSet::new();
+        for line in (0..LINES).step_by(every) {
+            set.insert(line);
+        }
+        set
+    });
+    time(&format!("{label}: nth x 1M"), || {
+        for n in 0..1_000_000 {
+            black_box(set.nth(n * 13 % set.len()));
+        }
+    });
+    time(&format!("{label}: rank x 1M"), || {
+        for line in 0..1_000_000 {
+            black_box(set.rank(line * every));
+        }
+    });
+    println!(
+        "{label}: {} lines, {:.1} MiB (Vec<LineRange>: {:.1} MiB)",
+        set.len(),
+        mib(set.memory_bytes()),
+        mib(set.len() * 16)
+    );
+}
+
+fn main() {
+    bench_offsets();
+    bench_set("sparse filter", 100);
+    bench_set("dense filter", 2);
+}
@@ -1,7 +1,18 @@
 use crate::components::ui::CustomSelect;
-use crate::state::AppState;
+use crate::state::{
+    AppState, LowSpacePolicy, RxEncoding, SearchIndexSize, SessionLimit, SizeLimitPolicy,
+};
 use dioxus::prelude::*;
 
+/// Session size limits offered in settings, in MB (0 = unlimited)
+const SESSION_LIMITS: &[(&str, u64)] = &[
+    ("Unlimited", 0),
+    ("64 MB", 64),
+    ("256 MB", 256),
+    ("1 GB", 1024),
+    ("4 GB", 4096),
+];
+
 #[component]
 pub fn SettingsDropdown(is_open: bool, onclose: EventHandler<()>) -> Element {
     let state = use_context::<AppState>();
@@ -91,6 +102,113 @@ pub fn SettingsDropdown(is_open: bool, onclose: EventHandler<()>) -> Element {
                         disabled: state.conn.is_connected(),
                     }
                 }
+                div {
+                    class: "flex flex-col gap-1.5 col-span-2",
+                    title: "Character set of received text. Applies to data received after a change.",
+                    label { class: "text-[10px] font-bold text-gray-500 uppercase tracking-widest px-1",
+                        "RX Encoding"
+                    }
+                    CustomSelect {
+                        options: RxEncoding::ALL.map(RxEncoding::label).to_vec(),
+                        selected: (state.serial.rx_encoding)().to_string(),
+                        onchange: move |val: String| {
+                            if let Some(encoding) = RxEncoding::from_label(&val) {
+                                state.serial.set_rx_encoding(encoding);
+                            }
+                        },
+                    }
+                }
+                div { class: "flex flex-col gap-1.5",
+                    label { class: "text-[10px] font-bold text-gray-500 uppercase tracking-widest px-1",
+                        "Max Session"
+                    }
+                    CustomSelect {
+                        options: SESSION_LIMITS.iter().map(|(label, _)| *label).collect::<Vec<_>>(),
+                        selected: SESSION_LIMITS
+                            .iter()
+                            .find(|(_, mb)| mb * 1024 * 1024 == (state.log.session_limit)().max_bytes)
+                            .map(|(label, _)| label.to_string())
+                            .unwrap_or_default(),
+                        onchange: move |val: String| {
+                            if let Some((_, mb)) = SESSION_LIMITS.iter().find(|(label, _)| *label == val) {
+                                state.log.set_session_limit(SessionLimit {
+                                    max_bytes: mb * 1024 * 1024,
+                                    ..(state.log.session_limit)()
+                                });
+                            }
+                        },
+                    }
+                }
+                div { class: "flex flex-col gap-1.5",
+                    label { class: "text-[10px] font-bold text-gray-500 uppercase tracking-widest px-1",
+                        "When Full"
+                    }
+                    CustomSelect {
+                        options: vec!["Rollover", "Ring"],
+                        selected: (state.log.session_limit)().policy.to_string(),
+                        onchange: move |val: String| {
+                            let policy = match val.as_str() {
+                                "Ring" => SizeLimitPolicy::Ring,
+                                _ => SizeLimitPolicy::Rollover,
+                            };
+                            state.log.set_session_limit(SessionLimit {
+                                policy,
+                                ..(state.log.session_limit)()
+                            });
+                        },
+                        disabled: (state.log.session_limit)().max_bytes == 0,
+                    }
+                }
+                div { class: "flex flex-col gap-1.5 col-span-2",
+                    label { class: "text-[10px] font-bold text-gray-500 uppercase tracking-widest px-1",
+                        "When Storage Low"
+                    }
+                    CustomSelect {
+                        options: vec!["Warn", "Pause", "Delete Oldest Sessions"],
+                        selected: (state.log.low_space_policy)().to_string(),
+                        onchange: move |val: String| {
+                            let policy = match val.as_str() {
+                                "Pause" => LowSpacePolicy::Pause,
+                                "Delete Oldest Sessions" => LowSpacePolicy::DeleteOldestSessions,
+                                _ => LowSpacePolicy::Warn,
+                            };
+                            // Stored sessions are deleted without further asking
+                            if policy == LowSpacePolicy::DeleteOldestSessions {
+                                let confirmed = web_sys::window()
+                                    .and_then(|w| {
+                                        w.confirm_with_message(
+                                            "When storage runs low, the oldest saved sessions will be deleted permanently. Continue?",
+                                        )
+                                        .ok()
+                                    })
+                                    .unwrap_or(false);
+                                if !confirmed {
+                                    return;
+                                }
+                            }
+                            state.log.set_low_space_policy(policy);
+                        },
+                    }
+                }
+                div {
+                    class: "flex flex-col gap-1.5 col-span-2",
+                    title: "Lets filters skip parts of the log that cannot match. Uses up to 32 MB of memory, covering the newest lines.",
+                    label { class: "text-[10px] font-bold text-gray-500 uppercase tracking-widest px-1",
+                        "Search Index"
+                    }
+                    CustomSelect {
+                        options: vec!["Off", "Small", "Large"],
+                        selected: (state.log.search_index)().to_string(),
+                        onchange: move |val: String| {
+                            let size = match val.as_str() {
+                                "Small" => SearchIndexSize::Small,
+                                "Large" => SearchIndexSize::Large,
+                                _ => SearchIndexSize::Off,
+                            };
+                            state.log.set_search_index(size);
+                        },
+                    }
+                }
             }
         }
     }
@@ -1,7 +1,21 @@
-use crate::components::ui::{IconButton, LineEndSelector, PanelHeader};
+use crate::components::monitor::FindBar;
+use crate::components::ui::{FilterOptionButton, IconButton, LineEndSelector, PanelHeader};
 use crate::config::HIGHLIGHT_COLORS;
-use crate::state::AppState;
+use crate::state::{AppState, Highlight, HighlightStyle, LogLevel};
+use crate::utils::file_save::{read_file_text, save_json_to_disk};
+use crate::utils::highlight_rules::highlight_regex;
+use crate::utils::HighlightRules;
 use dioxus::prelude::*;
+use dioxus::web::WebEventExt;
+use wasm_bindgen::JsCast;
+
+/// Level filters offered in the bar: level, label, dot color and tooltip
+const LEVEL_FILTERS: &[(LogLevel, &str, &str, &str)] = &[
+    (LogLevel::Error, "E", "bg-red-500", "Errors"),
+    (LogLevel::Warn, "W", "bg-yellow-500", "Warnings and above"),
+    (LogLevel::Info, "I", "bg-sky-500", "Info and above"),
+    (LogLevel::Debug, "D", "bg-gray-500", "Debug and above"),
+];
 
 #[component]
 pub fn FilterBar() -> Element {
@@ -31,9 +45,30 @@ pub fn FilterBar() -> Element {
                             onclick: move |_| state.ui.toggle_hex_view(),
                             "HEX"
                         }
+
+                        // Line Number Gutter Button
+                        button {
+                            class: "px-2 py-1 rounded text-[10px] font-bold border transition-colors select-none",
+                            class: if (state.ui.show_line_numbers)() { "bg-primary/20 text-primary border-primary/30" } else { "text-gray-500 border-transparent hover:text-gray-300 bg-[#2a2e33]/50" },
+                            title: "Line Numbers",
+                            onclick: move |_| state.ui.toggle_line_numbers(),
+                            "LN"
+                        }
+
+                        // Structured View Button
+                        button {
+                            class: "px-2 py-1 rounded text-[10px] font-bold border transition-colors select-none",
+                            class: if (state.ui.structured_view)() { "bg-primary/20 text-primary border-primary/30" } else { "text-gray-500 border-transparent hover:text-gray-300 bg-[#2a2e33]/50" },
+                            title: "JSON Field Columns",
+                            onclick: move |_| state.ui.toggle_structured_view(),
+                            "JSON"
+                        }
                     }
 
+                    LevelFilter {}
 
+                    FindBar {}
+                    SearchStatus {}
                 }
 
                 // --- Divider (Matches InputBar Divider Position) ---
@@ -86,12 +121,40 @@ fn HighlightPanel(visible: bool, onclose: EventHandler<()>) -> Element {
     let state = use_context::<AppState>();
     let highlights = (state.log.highlights)();
 
+    let import = move |evt: FormEvent| {
+        let input = evt
+            .as_web_event()
+            .target()
+            .and_then(|t| t.dyn_into::<web_sys::HtmlInputElement>().ok());
+        let Some(input) = input else {
+            return;
+        };
+        let file = input.files().and_then(|files| files.get(0));
+        // Allow picking the same file again
+        input.set_value("");
+        if let Some(file) = file {
+            spawn(async move {
+                let rules = match read_file_text(&file).await {
+                    Ok(json) => HighlightRules::parse(&json),
+                    Err(_) => Err("Could not read the file".to_string()),
+                };
+                match rules {
+                    Ok(rules) => {
+                        state.success(&format!("Imported {} highlight rules", rules.len()));
+                        state.log.add_highlights(rules);
+                    }
+                    Err(e) => state.error(&e),
+                }
+            });
+        }
+    };
+
     rsx! {
         div {
             class: "fixed inset-0 z-40 cursor-default",
             onclick: move |_| onclose.call(()),
         }
-        div { class: "absolute top-full right-0 mt-2 w-80 z-50 bg-[#16181a] rounded-xl border border-white/10 shadow-2xl p-4 animate-in fade-in zoom-in-95 duration-200 origin-top-right",
+        div { class: "absolute top-full right-0 mt-2 w-96 z-50 bg-[#16181a] rounded-xl border border-white/10 shadow-2xl p-4 animate-in fade-in zoom-in-95 duration-200 origin-top-right",
             div { class: "flex flex-col gap-3",
                 PanelHeader {
                     title: "Active Highlights",
@@ -100,25 +163,54 @@ fn HighlightPanel(visible: bool, onclose: EventHandler<()>) -> Element {
 
                 div { class: "flex items-center justify-between",
                     span { class: "text-[10px] uppercase text-gray-500 font-bold", "Enable Highlighting" }
-                    crate::components::ui::ToggleSwitch {
-                        label: "",
-                        active: (state.ui.show_highlights)(),
-                        onclick: move |_| state.ui.toggle_highlights(),
+                    div { class: "flex items-center gap-2",
+                        input {
+                            id: "highlight-file-input",
+                            r#type: "file",
+                            accept: ".json",
+                            class: "hidden",
+                            onchange: import,
+                        }
+                        IconButton {
+                            icon: "upload",
+                            icon_class: "text-[16px]",
+                            title: "Import Rules",
+                            onclick: move |_| {
+                                let input = web_sys::window()
+                                    .and_then(|w| w.document())
+                                    .and_then(|doc| doc.get_element_by_id("highlight-file-input"))
+                                    .and_then(|el| el.dyn_into::<web_sys::HtmlElement>().ok());
+                                if let Some(input) = input {
+                                    input.click();
+                                }
+                            },
+                        }
+                        IconButton {
+                            icon: "download",
+                            icon_class: "text-[16px]",
+                            title: "Export Rules",
+                            onclick: move |_| {
+                                let json = HighlightRules::export(&state.log.highlights.read());
+                                save_json_to_disk(&json, "highlight_rules.json");
+                            },
+                        }
+                        crate::components::ui::ToggleSwitch {
+                            label: "",
+                            active: (state.ui.show_highlights)(),
+                            onclick: move |_| state.ui.toggle_highlights(),
+                        }
                     }
                 }
 
-                div { class: "flex flex-wrap gap-2 min-h-[40px] p-2 bg-[#0d0f10] rounded border border-[#2a2e33]",
+                div { class: "flex flex-wrap gap-2 min-h-[40px] max-h-48 overflow-y-auto p-2 bg-[#0d0f10] rounded border border-[#2a2e33]",
                     if highlights.is_empty() {
                         span { class: "text-xs text-gray-600 italic px-1", "No rules added" }
                     }
                     for h in highlights {
                         HighlightTag {
-                            color: h.color,
-                            label: h.text.clone(),
-                            onremove: move |_| {
-                                let state = use_context::<AppState>();
-                                state.log.remove_highlight(h.id);
-                            },
+                            key: "{h.id}",
+                            rule: h.clone(),
+                            onremove: move |_| state.log.remove_highlight(h.id),
                         }
                     }
                 }
@@ -128,15 +220,39 @@ fn HighlightPanel(visible: bool, onclose: EventHandler<()>) -> Element {
     }
 }
 
-use crate::components::monitor::utils::style::get_highlight_classes;
+use crate::components::monitor::utils::style::{css_color, get_highlight_classes};
+
+fn style_icon(style: HighlightStyle) -> &'static str {
+    match style {
+        HighlightStyle::Foreground => "format_color_text",
+        HighlightStyle::Background => "format_color_fill",
+        HighlightStyle::Line => "view_agenda",
+    }
+}
 
 #[component]
-fn HighlightTag(color: &'static str, label: String, onremove: EventHandler<MouseEvent>) -> Element {
-    let (border_class, text_class) = get_highlight_classes(color);
+fn HighlightTag(rule: Highlight, onremove: EventHandler<MouseEvent>) -> Element {
+    let (border_class, text_class) = get_highlight_classes(&rule.color);
+    let kind = if rule.regex { "Regex" } else { "Text" };
+    let case = if rule.match_case {
+        "case-sensitive"
+    } else {
+        "ignoring case"
+    };
 
     rsx! {
-        div { class: "flex items-center gap-2 pl-3 pr-2 py-1.5 bg-[#0d0f10] border {border_class} rounded-full group transition-colors",
-            span { class: "text-xs font-bold {text_class}", "{label}" }
+        div {
+            class: "flex items-center gap-1.5 pl-2 pr-2 py-1.5 bg-[#0d0f10] border {border_class} rounded-full group transition-colors",
+            title: "{kind}, {case}, colors the {rule.style.to_string().to_lowercase()}",
+            span { class: "material-symbols-outlined text-[14px] {text_class}", "{style_icon(rule.style)}" }
+            span {
+                class: "text-xs font-bold {text_class}",
+                class: if rule.regex { "font-mono" },
+                "{rule.text}"
+            }
+            if rule.match_case {
+                span { class: "text-[9px] font-mono text-gray-500", "Aa" }
+            }
             IconButton {
                 icon: "close",
                 icon_class: "text-[14px]",
@@ -149,45 +265,198 @@ fn HighlightTag(color: &'static str, label: String, onremove: EventHandler<Mouse
 
 #[component]
 fn HighlightInput() -> Element {
+    let state = use_context::<AppState>();
     let mut new_text = use_signal(String::new);
+    let mut use_regex = use_signal(|| false);
+    let mut match_case = use_signal(|| false);
+    let mut style = use_signal(HighlightStyle::default);
+    // Picked color; defaults to the first one no rule uses yet
+    let mut picked = use_signal(|| None::<&'static str>);
+    let color = picked().unwrap_or_else(|| {
+        let list = state.log.highlights.read();
+        HIGHLIGHT_COLORS
+            .iter()
+            .find(|&&c| !list.iter().any(|h| h.color == c))
+            .copied()
+            .unwrap_or_else(|| HIGHLIGHT_COLORS[list.len() % HIGHLIGHT_COLORS.len()])
+    });
+    let rule = use_memo(move || Highlight {
+        text: new_text.read().trim().to_string(),
+        color: color.to_string(),
+        regex: use_regex(),
+        match_case: match_case(),
+        style: style(),
+        ..Default::default()
+    });
+    let error = use_memo(move || {
+        let rule = rule();
+        (!rule.text.is_empty())
+            .then(|| highlight_regex(&rule).err())
+            .flatten()
+    });
+
     let mut add_highlight_logic = move || {
-        let text = new_text.read().trim().to_string();
-        if !text.is_empty() {
-            let state = use_context::<AppState>();
-            let list = state.log.highlights.read().clone();
-
-            let used_colors: std::collections::HashSet<&str> =
-                list.iter().map(|h| h.color).collect();
-            let color = HIGHLIGHT_COLORS
-                .iter()
-                .find(|&&c| !used_colors.contains(c))
-                .copied()
-                .unwrap_or_else(|| HIGHLIGHT_COLORS[list.len() % HIGHLIGHT_COLORS.len()]);
-
-            state.log.add_highlight(text, color);
-            new_text.set(String::new());
+        if rule.read().text.is_empty() || error().is_some() {
+            return;
+        }
+        state.log.add_highlight(rule());
+        new_text.set(String::new());
+        picked.set(None);
+    };
+
+    rsx! {
+        div { class: "pt-2 border-t border-white/5 flex flex-col gap-2",
+            div { class: "flex gap-2",
+                div { class: "flex-1 relative flex items-center min-w-0",
+                    input {
+                        class: "w-full bg-[#0d0f10] text-xs font-medium text-white placeholder-gray-600 pl-3 pr-16 py-2 rounded-lg border focus:shadow-glow outline-none transition-all",
+                        class: if error().is_some() { "border-red-500/70" } else { "border-[#2a2e33] focus:border-primary/50" },
+                        class: if use_regex() { "font-mono" },
+                        placeholder: if use_regex() { "Enter regex to highlight..." } else { "Enter keyword to highlight..." },
+                        title: error().unwrap_or_default(),
+                        "type": "text",
+                        value: "{new_text}",
+                        oninput: move |evt| new_text.set(evt.value()),
+                        onkeydown: move |evt| {
+                            if evt.key() == Key::Enter {
+                                add_highlight_logic();
+                            }
+                        },
+                    }
+                    div { class: "absolute right-1 flex items-center gap-0.5",
+                        FilterOptionButton {
+                            title: "Match Case",
+                            label: "Aa",
+                            active: match_case(),
+                            onclick: move |_| match_case.toggle(),
+                        }
+                        FilterOptionButton {
+                            title: "Regex",
+                            label: ".*",
+                            active: use_regex(),
+                            onclick: move |_| use_regex.toggle(),
+                        }
+                    }
+                }
+                button {
+                    class: "px-4 rounded-lg bg-primary text-surface font-bold hover:bg-white transition-all active:scale-95 flex items-center gap-2 disabled:opacity-40",
+                    disabled: error().is_some(),
+                    onclick: move |_| add_highlight_logic(),
+                    span { class: "material-symbols-outlined text-[18px]", "add" }
+                    span { class: "text-[10px] uppercase tracking-wider", "Add" }
+                }
+            }
+            if let Some(err) = error() {
+                span { class: "text-[10px] text-red-400", "{err}" }
+            }
+            div { class: "flex items-center justify-between gap-2",
+                div { class: "flex items-center gap-0.5 p-0.5 rounded bg-[#2a2e33]/50",
+                    for s in HighlightStyle::ALL {
+                        button {
+                            class: "flex items-center gap-1 px-1.5 py-0.5 rounded text-[10px] font-bold border transition-colors select-none",
+                            class: if style() == s { "bg-primary/20 text-primary border-primary/30" } else { "text-gray-500 border-transparent hover:text-gray-300" },
+                            title: "Color the {s.to_string().to_lowercase()} of matches",
+                            onclick: move |_| style.set(s),
+                            span { class: "material-symbols-outlined text-[14px]", "{style_icon(s)}" }
+                            "{s}"
+                        }
+                    }
+                }
+                div { class: "flex flex-wrap justify-end gap-1",
+                    for c in HIGHLIGHT_COLORS.iter().copied() {
+                        button {
+                            class: "w-3 h-3 rounded-full transition-transform hover:scale-125",
+                            class: if c == color { "ring-2 ring-white/70 ring-offset-1 ring-offset-[#16181a]" },
+                            style: "background-color: {css_color(c)};",
+                            title: "{c}",
+                            onclick: move |_| picked.set(Some(c)),
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// One-click level filters with the running count of each level.
+/// Combines with the text filter; clicking the active level shows all lines again.
+#[component]
+fn LevelFilter() -> Element {
+    let mut state = use_context::<AppState>();
+    let counts = (state.log.level_counts)();
+    let min_level = (state.log.min_level)();
+
+    rsx! {
+        div { class: "flex items-center gap-0.5 p-0.5 rounded bg-[#2a2e33]/50",
+            for (level , label , dot , title) in LEVEL_FILTERS.iter().copied() {
+                button {
+                    key: "{label}",
+                    class: "flex items-center gap-1 px-1.5 py-0.5 rounded text-[10px] font-bold font-mono border transition-colors select-none",
+                    class: if min_level == Some(level) { "bg-primary/20 text-primary border-primary/30" } else { "text-gray-500 border-transparent hover:text-gray-300" },
+                    title: "{title} ({counts.at_least(level)} lines)",
+                    onclick: move |_| {
+                        let next = if min_level == Some(level) { None } else { Some(level) };
+                        state.log.min_level.set(next);
+                    },
+                    span { class: "w-1.5 h-1.5 rounded-full {dot}" }
+                    "{label} {compact_count(counts.get(level))}"
+                }
+            }
         }
+    }
+}
+
+/// Count shortened to fit a button, e.g. `950`, `12k`, `3.4M`
+fn compact_count(n: usize) -> String {
+    match n {
+        0..1_000 => n.to_string(),
+        1_000..10_000 => format!("{:.1}k", n as f64 / 1e3),
+        10_000..1_000_000 => format!("{}k", n / 1_000),
+        _ => format!("{:.1}M", n as f64 / 1e6),
+    }
+}
+
+/// Progress and match count of the filter scan, with a cancel button while it runs
+#[component]
+fn SearchStatus() -> Element {
+    let state = use_context::<AppState>();
+    let bridge = crate::hooks::use_worker_controller();
+    let Some(progress) = (state.log.search_progress)() else {
+        return rsx! {};
+    };
+    let running = (state.log.search_running)();
+    let percent = (progress.scanned * 100)
+        .checked_div(progress.total)
+        .unwrap_or(100);
+    let noun = if progress.matches == 1 {
+        "match"
+    } else {
+        "matches"
     };
 
     rsx! {
-        div { class: "pt-2 border-t border-white/5 flex gap-2",
-            input {
-                class: "flex-1 bg-[#0d0f10] text-xs font-medium text-white placeholder-gray-600 px-3 py-2 rounded-lg border border-[#2a2e33] focus:border-primary/50 focus:shadow-glow outline-none transition-all",
-                placeholder: "Enter keyword to highlight...",
-                "type": "text",
-                value: "{new_text}",
-                oninput: move |evt| new_text.set(evt.value()),
-                onkeydown: move |evt| {
-                    if evt.key() == Key::Enter {
-                        add_highlight_logic();
+        div {
+            class: "flex items-center gap-1.5 text-[10px] font-mono whitespace-nowrap",
+            class: if running { "text-primary" } else { "text-gray-500" },
+            title: "Scanned {progress.scanned} of {progress.total} lines",
+            if running {
+                div { class: "w-16 h-1 rounded bg-white/10 overflow-hidden",
+                    div {
+                        class: "h-full bg-primary transition-all",
+                        style: "width: {percent}%;",
                     }
-                },
+                }
             }
-            button {
-                class: "px-4 rounded-lg bg-primary text-surface font-bold hover:bg-white transition-all active:scale-95 flex items-center gap-2",
-                onclick: move |_| add_highlight_logic(),
-                span { class: "material-symbols-outlined text-[18px]", "add" }
-                span { class: "text-[10px] uppercase tracking-wider", "Add" }
+            "{progress.matches} {noun}"
+            if running {
+                button {
+                    class: "material-symbols-outlined text-[14px] text-gray-500 hover:text-white transition-colors",
+                    title: "Stop Search",
+                    onclick: move |_| bridge.cancel_search(),
+                    "close"
+                }
+            } else if percent < 100 {
+                span { class: "text-yellow-500/80", "(partial)" }
             }
         }
     }
@@ -0,0 +1,76 @@
+use crate::config::TOP_BUFFER;
+use crate::state::AppState;
+use crate::utils::filter_error;
+use dioxus::prelude::*;
+
+/// Find-in-log: steps through matches of an expression while the view keeps all lines
+#[component]
+pub fn FindBar() -> Element {
+    let mut state = use_context::<AppState>();
+    let bridge = crate::hooks::use_worker_controller();
+    let error = use_memo(move || {
+        filter_error(
+            &(state.log.find_query)(),
+            (state.log.match_case)(),
+            (state.log.use_regex)(),
+        )
+    });
+    let has_query = !state.log.find_query.read().trim().is_empty();
+    // Continue from the match last reached while it is on screen, else from the top line
+    let find_next = move |forward: bool| {
+        if error.peek().is_some() || state.log.find_query.peek().trim().is_empty() {
+            return;
+        }
+        let logs = state.log.visible_logs.peek();
+        let first = logs.first().map(|(i, _)| *i).unwrap_or(0);
+        let last = logs.last().map(|(i, _)| *i).unwrap_or(0);
+        let (from, occurrence) = match *state.log.find_match.peek() {
+            Some(hit) if (first..=last).contains(&hit.line) => (hit.line, Some(hit.occurrence)),
+            _ => ((first + TOP_BUFFER).min(last), None),
+        };
+        bridge.find_next(from, occurrence, forward);
+    };
+    let border = if error().is_some() {
+        "border-red-500/70"
+    } else {
+        "border-[#2a2e33] focus-within:border-primary/50"
+    };
+
+    rsx! {
+        div { class: "relative flex items-center gap-0.5 h-7 w-56 min-w-0 rounded-md border bg-[#0d0f10] {border} transition-colors",
+            span { class: "material-symbols-outlined text-[14px] text-gray-600 pl-1.5", "find_in_page" }
+            input {
+                class: "flex-1 min-w-0 bg-transparent text-[11px] text-white placeholder-gray-600 px-1 outline-none",
+                placeholder: "Find (Enter / Shift+Enter)",
+                "type": "text",
+                title: error().unwrap_or_default(),
+                value: "{state.log.find_query}",
+                oninput: move |evt| state.log.find_query.set(evt.value()),
+                onkeydown: move |evt| match evt.key() {
+                    Key::Enter => find_next(!evt.modifiers().contains(Modifiers::SHIFT)),
+                    Key::Escape => state.log.find_query.set(String::new()),
+                    _ => {}
+                },
+            }
+            button {
+                class: "material-symbols-outlined text-[16px] text-gray-500 hover:text-white disabled:opacity-30 transition-colors",
+                title: "Previous Match",
+                disabled: !has_query,
+                onclick: move |_| find_next(false),
+                "keyboard_arrow_up"
+            }
+            button {
+                class: "material-symbols-outlined text-[16px] text-gray-500 hover:text-white disabled:opacity-30 pr-1 transition-colors",
+                title: "Next Match",
+                disabled: !has_query,
+                onclick: move |_| find_next(true),
+                "keyboard_arrow_down"
+            }
+            if let Some(err) = error() {
+                div { class: "absolute left-0 top-full mt-1 z-20 px-2 py-0.5 rounded bg-[#1a0f10] border border-red-500/40 text-[10px] text-red-400 whitespace-nowrap pointer-events-none",
+                    "{err}"
+                }
+            }
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use crate::hooks::WorkerController;
 use crate::state::AppState;
+use crate::utils::{filter_error, with_field_filters};
 use dioxus::prelude::*;
 
 pub fn use_settings_sync(bridge: WorkerController) {
@@ -9,6 +10,53 @@ pub fn use_settings_sync(bridge: WorkerController) {
         let show = (state.ui.show_timestamps)();
         bridge.set_timestamp_state(show);
     });
+
+    use_effect(move || {
+        let limit = (state.log.session_limit)();
+        bridge.set_session_limit(limit);
+    });
+
+    use_effect(move || {
+        let policy = (state.log.low_space_policy)();
+        bridge.set_low_space_policy(policy);
+    });
+
+    use_effect(move || {
+        let encoding = (state.serial.rx_encoding)();
+        bridge.set_encoding(encoding);
+    });
+
+    use_effect(move || {
+        let size = (state.log.search_index)();
+        bridge.set_search_index(size);
+    });
+
+    use_effect(move || {
+        let rules = if (state.ui.show_highlights)() {
+            (state.log.highlights)()
+        } else {
+            Vec::new()
+        };
+        bridge.set_highlights(rules);
+    });
+
+    use_effect(move || {
+        let columns = if (state.ui.structured_view)() {
+            (state.log.json_columns)()
+        } else {
+            Vec::new()
+        };
+        bridge.set_json_columns(columns);
+    });
+
+    use_effect(move || {
+        let sort = if (state.ui.structured_view)() {
+            (state.log.sort)()
+        } else {
+            None
+        };
+        bridge.sort_by(sort);
+    });
 }
 
 pub fn use_search_sync(bridge: WorkerController) {
@@ -19,11 +67,52 @@ pub fn use_search_sync(bridge: WorkerController) {
         let match_case = (state.log.match_case)();
         let use_regex = (state.log.use_regex)();
         let invert = (state.log.invert_filter)();
+        let context = ((state.log.context_before)(), (state.log.context_after)());
+        let min_level = (state.log.min_level)();
+        // Column filters apply while the table is shown
+        let field_filters = if (state.ui.structured_view)() {
+            (state.log.field_filters)()
+        } else {
+            Vec::new()
+        };
 
         async move {
             // Debounce 300ms
             gloo_timers::future::TimeoutFuture::new(300).await;
-            bridge.search(query, match_case, use_regex, invert);
+            // The search bar shows the parse error; keep the last valid filter meanwhile
+            if filter_error(&query, match_case, use_regex).is_some() {
+                return;
+            }
+            let (query, invert) = with_field_filters(&query, invert, &field_filters);
+            if query.trim().is_empty() {
+                { state.log.search_progress }.set(None);
+                { state.log.search_running }.set(false);
+            }
+            { state.log.search_error }.set(None);
+            bridge.search(query, match_case, use_regex, invert, context, min_level);
+            // View indices change with the filter
+            { state.log.find_match }.set(None);
+        }
+    });
+}
+
+/// Sends the find-in-log expression to the worker, which marks its matches in
+/// the windows it sends
+pub fn use_find_sync(bridge: WorkerController) {
+    let state = use_context::<AppState>();
+
+    use_resource(move || {
+        let query = (state.log.find_query)();
+        let match_case = (state.log.match_case)();
+        let use_regex = (state.log.use_regex)();
+
+        async move {
+            gloo_timers::future::TimeoutFuture::new(300).await;
+            if filter_error(&query, match_case, use_regex).is_some() {
+                return;
+            }
+            bridge.find(query, match_case, use_regex);
+            { state.log.find_match }.set(None);
         }
     });
 }
@@ -1,6 +1,7 @@
 use crate::components::monitor::hooks::data_request::use_data_request;
 use crate::components::monitor::utils::layout_utils::{
-    calculate_scroll_state, calculate_virtual_metrics, use_auto_scroller, use_window_resize,
+    calculate_scroll_state, calculate_virtual_metrics, use_auto_scroller, use_scroll_to_line,
+    use_window_resize,
 };
 use crate::config::{line_height_from_font, BOTTOM_BUFFER_EXTRA, TOP_BUFFER};
 use crate::state::AppState;
@@ -38,6 +39,13 @@ pub fn use_virtual_scroll() -> VirtualScroll {
     use_window_resize(console_height, state.ui.autoscroll, sentinel_handle);
     use_data_request(start_index, window_size, total_lines);
     use_auto_scroller(state.ui.autoscroll, total_lines, sentinel_handle);
+    use_scroll_to_line(
+        state.log.scroll_target,
+        state.ui.autoscroll,
+        total_lines,
+        console_height,
+        line_height,
+    );
 
     let (total_height, offset_top, scale_factor) =
         calculate_virtual_metrics(total_lines(), start_index(), console_height(), line_height);
@@ -0,0 +1,160 @@
+use crate::config::JSON_COLUMN_WIDTH_CH;
+use crate::state::{AppState, JsonRow};
+use dioxus::prelude::*;
+
+/// Column titles of the structured view: click to sort, type to filter a
+/// field (`> 80`, `= warn`, or text it contains), and add or remove columns
+#[component]
+pub fn JsonTableHeader() -> Element {
+    let state = use_context::<AppState>();
+    let font_size = *state.ui.font_size.read();
+    let columns = (state.log.json_columns)();
+    let show_numbers = (state.ui.show_line_numbers)();
+
+    rsx! {
+        div {
+            class: "shrink-0 flex items-end py-1 border-b border-[#2a2e33] bg-[#0d0f10] font-mono whitespace-nowrap overflow-hidden",
+            // Same gutters as the lines, so the columns line up with the cells
+            style: "font-size: {font_size}px;",
+            if show_numbers {
+                span { class: "inline-block min-w-[7ch] pr-2" }
+            }
+            span { class: "inline-block w-4" }
+            for (i , field) in columns.iter().cloned().enumerate() {
+                JsonColumnHeader {
+                    key: "{field}",
+                    field,
+                    last: i + 1 == columns.len(),
+                }
+            }
+            AddColumnInput {}
+        }
+    }
+}
+
+#[component]
+fn JsonColumnHeader(field: String, last: bool) -> Element {
+    let state = use_context::<AppState>();
+    let sort = (state.log.sort)().filter(|s| s.field == field);
+    let running = (state.log.sort_running)();
+    let filter = state
+        .log
+        .field_filters
+        .read()
+        .iter()
+        .find(|(f, _)| *f == field)
+        .map(|(_, input)| input.clone())
+        .unwrap_or_default();
+    let sort_icon = match &sort {
+        Some(s) if s.descending => "arrow_downward",
+        Some(_) => "arrow_upward",
+        None => "swap_vert",
+    };
+    let (sort_field, remove_field, filter_field) = (field.clone(), field.clone(), field.clone());
+
+    rsx! {
+        div {
+            class: "group/col flex flex-col gap-0.5 pr-3 shrink-0",
+            style: "width: {JSON_COLUMN_WIDTH_CH}ch;",
+            style: if last { "min-width: {JSON_COLUMN_WIDTH_CH}ch; width: auto;" },
+            div { class: "flex items-center gap-1 text-[10px] font-bold uppercase tracking-wider",
+                button {
+                    class: "flex items-center gap-0.5 min-w-0 transition-colors select-none",
+                    class: if sort.is_some() { "text-primary" } else { "text-gray-400 hover:text-white" },
+                    title: "Sort by {field}",
+                    onclick: move |_| state.log.cycle_sort(&sort_field),
+                    span { class: "truncate", "{field}" }
+                    span {
+                        class: "material-symbols-outlined text-[12px]",
+                        class: if sort.is_some() && running { "animate-pulse" },
+                        class: if sort.is_none() { "opacity-0 group-hover/col:opacity-100" },
+                        "{sort_icon}"
+                    }
+                }
+                button {
+                    class: "material-symbols-outlined text-[12px] text-gray-600 hover:text-white opacity-0 group-hover/col:opacity-100 transition-opacity",
+                    title: "Remove Column",
+                    onclick: move |_| state.log.remove_json_column(&remove_field),
+                    "close"
+                }
+            }
+            input {
+                class: "w-full bg-[#16181a] text-[10px] text-white placeholder-gray-600 px-1.5 py-0.5 rounded border border-[#2a2e33] focus:border-primary/50 outline-none",
+                placeholder: "filter, e.g. > 80",
+                "type": "text",
+                value: "{filter}",
+                oninput: move |evt| state.log.set_field_filter(&filter_field, evt.value()),
+            }
+        }
+    }
+}
+
+/// Adds a column; suggests the fields seen in the shown JSON lines
+#[component]
+fn AddColumnInput() -> Element {
+    let state = use_context::<AppState>();
+    let mut text = use_signal(String::new);
+    let columns = state.log.json_columns.read().clone();
+    let suggestions: Vec<String> = state
+        .log
+        .json_fields
+        .read()
+        .iter()
+        .filter(|f| !columns.contains(f))
+        .cloned()
+        .collect();
+
+    let mut add = move || {
+        state.log.add_json_column(&text.read());
+        text.set(String::new());
+    };
+
+    rsx! {
+        div { class: "flex items-center gap-1 pl-1 shrink-0 text-[10px]",
+            input {
+                class: "w-28 bg-[#16181a] text-white placeholder-gray-600 px-1.5 py-0.5 rounded border border-[#2a2e33] focus:border-primary/50 outline-none",
+                list: "json-field-list",
+                placeholder: "add field...",
+                "type": "text",
+                value: "{text}",
+                oninput: move |evt| text.set(evt.value()),
+                onkeydown: move |evt| {
+                    if evt.key() == Key::Enter {
+                        add();
+                    }
+                },
+            }
+            datalist { id: "json-field-list",
+                for field in suggestions {
+                    option { key: "{field}", value: "{field}" }
+                }
+            }
+            button {
+                class: "material-symbols-outlined text-[16px] text-gray-500 hover:text-white transition-colors",
+                title: "Add Column",
+                onclick: move |_| add(),
+                "add"
+            }
+        }
+    }
+}
+
+/// Field cells of a JSON line, sized like the header columns
+#[component]
+pub fn JsonTableCells(row: JsonRow) -> Element {
+    let count = row.cells.len();
+
+    rsx! {
+        for (i , cell) in row.cells.into_iter().enumerate() {
+            span {
+                class: "inline-block align-middle pr-3 overflow-hidden text-ellipsis",
+                style: if i + 1 < count { "width: {JSON_COLUMN_WIDTH_CH}ch;" } else { "min-width: {JSON_COLUMN_WIDTH_CH}ch;" },
+                if let Some(text) = cell {
+                    "{text}"
+                } else {
+                    span { class: "text-gray-600", "-" }
+                }
+            }
+        }
+    }
+}
@@ -1,16 +1,21 @@
 pub mod filter_bar;
+pub mod find_bar;
 pub mod hooks;
 pub mod input_bar;
+pub mod json_table;
 pub mod macro_bar;
 pub mod monitor_header;
 pub mod monitor_log_line;
 pub mod monitor_view;
 pub mod monitor_viewport;
+pub mod raw_bytes_panel;
 pub mod search_bar;
+pub mod sessions_panel;
 pub mod transmit_bar;
 pub mod utils;
 
 pub use filter_bar::FilterBar;
+pub use find_bar::FindBar;
 pub use input_bar::InputBar;
 pub use macro_bar::MacroBar;
 pub use monitor_view::Monitor;
@@ -1,30 +1,206 @@
+use crate::components::monitor::sessions_panel::{SessionMetadataLine, SessionsPanel};
 use crate::components::ui::console::UnifiedConsoleToolbar;
-use crate::state::AppState;
+use crate::config::TOP_BUFFER;
+use crate::hooks::WorkerController;
+use crate::state::{AppState, StorageUsage};
+use crate::utils::format_bytes;
 use dioxus::prelude::*;
+use dioxus::web::WebEventExt;
+use wasm_bindgen::JsCast;
 
 #[component]
 pub fn MonitorHeader(
     autoscroll: bool,
     count: usize,
     onexport: EventHandler<MouseEvent>,
+    onexport_raw: EventHandler<MouseEvent>,
     onclear: EventHandler<MouseEvent>,
     ontoggle_autoscroll: EventHandler<MouseEvent>,
 ) -> Element {
     let state = use_context::<AppState>();
+    let bridge = crate::hooks::use_worker_controller();
+    let mut show_sessions = use_signal(|| false);
+    let read_only = (state.log.read_only)();
+    let metadata = state
+        .log
+        .sessions
+        .read()
+        .iter()
+        .find(|s| s.is_open)
+        .and_then(|s| s.metadata.clone());
+    let import_progress = (state.log.import_progress)();
+    let bookmark_count = state.log.bookmarks.read().len();
+    // Navigate from the last bookmark reached while it is on screen, else from the top line
+    let seek_bookmark = move |forward: bool| {
+        let logs = state.log.visible_logs.peek();
+        let first = logs.first().map(|(i, _)| *i).unwrap_or(0);
+        let last = logs.last().map(|(i, _)| *i).unwrap_or(0);
+        let from = match *state.log.focused_line.peek() {
+            Some(line) if (first..=last).contains(&line) => line,
+            _ => (first + TOP_BUFFER).min(last),
+        };
+        bridge.seek_bookmark(from, forward);
+    };
 
     rsx! {
+        input {
+            id: "import-file-input",
+            r#type: "file",
+            accept: ".log,.txt,.bin",
+            class: "hidden",
+            onchange: move |evt: FormEvent| {
+                let input = evt
+                    .as_web_event()
+                    .target()
+                    .and_then(|t| t.dyn_into::<web_sys::HtmlInputElement>().ok());
+                if let Some(input) = input {
+                    if let Some(file) = input.files().and_then(|files| files.get(0)) {
+                        start_import(state, bridge, &file);
+                    }
+                    // Allow picking the same file again
+                    input.set_value("");
+                }
+            },
+        }
         UnifiedConsoleToolbar {
             left: rsx! {
                 span { class: "text-[10px] text-gray-500 font-mono", "[ LINES: {count} / OPFS ENABLED ]" }
+                if let Some(usage) = (state.log.storage_usage)() {
+                    StorageMeter { usage }
+                }
+                div { class: "relative flex items-center",
+                    button {
+                        class: "flex items-center gap-1 text-[10px] font-mono uppercase transition-colors",
+                        class: if show_sessions() { "text-primary" } else { "text-gray-500 hover:text-white" },
+                        onclick: move |_| show_sessions.toggle(),
+                        title: "Stored Sessions",
+                        span { class: "material-symbols-outlined text-[14px]", "folder_open" }
+                        "Sessions"
+                    }
+                    if show_sessions() {
+                        SessionsPanel { onclose: move |_| show_sessions.set(false) }
+                    }
+                }
+                if bookmark_count > 0 {
+                    div { class: "flex items-center gap-0.5 text-[10px] font-mono text-gray-500",
+                        button {
+                            class: "material-symbols-outlined text-[14px] hover:text-white transition-colors",
+                            title: "Previous Bookmark",
+                            onclick: move |_| seek_bookmark(false),
+                            "chevron_left"
+                        }
+                        span { class: "flex items-center text-primary", title: "Bookmarks",
+                            span { class: "material-symbols-outlined text-[12px]", "bookmark" }
+                            "{bookmark_count}"
+                        }
+                        button {
+                            class: "material-symbols-outlined text-[14px] hover:text-white transition-colors",
+                            title: "Next Bookmark",
+                            onclick: move |_| seek_bookmark(true),
+                            "chevron_right"
+                        }
+                    }
+                }
+                if let Some(metadata) = metadata {
+                    SessionMetadataLine { metadata }
+                }
+                if let Some((read, total)) = import_progress {
+                    div {
+                        class: "flex items-center gap-1.5 text-[9px] font-mono text-primary",
+                        title: "Importing file",
+                        div { class: "w-20 h-1 rounded bg-white/10 overflow-hidden",
+                            div {
+                                class: "h-full bg-primary transition-all",
+                                style: "width: {import_percent(read, total)}%;",
+                            }
+                        }
+                        "IMPORT {import_percent(read, total)}%"
+                    }
+                }
+                if read_only {
+                    button {
+                        class: "px-1.5 rounded text-[9px] font-bold font-mono bg-yellow-500/10 text-yellow-500 border border-yellow-500/30 hover:bg-yellow-500/20 transition-colors",
+                        title: "Return to the live session",
+                        onclick: move |_| {
+                            let live = state.log.sessions.peek().iter().find(|s| s.is_live).map(|s| s.name.clone());
+                            if let Some(name) = live {
+                                bridge.open_session(name);
+                            }
+                        },
+                        "READ ONLY"
+                    }
+                }
             },
             font_size: state.ui.font_size,
             is_autoscroll: autoscroll,
             is_tracking_interactive: true,
             on_toggle_autoscroll: move |evt| ontoggle_autoscroll.call(evt),
             on_clear: move |evt| onclear.call(evt),
+            on_import: move |_| {
+                if let Some(el) = web_sys::window()
+                    .and_then(|win| win.document())
+                    .and_then(|doc| doc.get_element_by_id("import-file-input"))
+                    .and_then(|el| el.dyn_into::<web_sys::HtmlElement>().ok())
+                {
+                    el.click();
+                }
+            },
             on_export: move |evt| onexport.call(evt),
+            on_export_raw: move |evt| onexport_raw.call(evt),
             min_font_size: 8,
             max_font_size: 36,
         }
     }
 }
+
+/// OPFS usage against the origin quota
+#[component]
+fn StorageMeter(usage: StorageUsage) -> Element {
+    let percent = (usage.fraction() * 100.0).min(100.0);
+    let low = usage.is_low();
+    let used = format_bytes(usage.usage);
+    let quota = format_bytes(usage.quota);
+    let free = format_bytes(usage.free());
+    let index = format_bytes(usage.index_bytes);
+    let search_index = format_bytes(usage.search_index_bytes);
+
+    rsx! {
+        div {
+            class: "flex items-center gap-1.5 text-[9px] font-mono",
+            class: if low { "text-red-400" } else { "text-gray-600" },
+            title: "Storage: {used} of {quota} used, {free} free\nLine index: {index} in memory\nSearch index: {search_index} in memory",
+            div { class: "w-12 h-1 rounded bg-white/10 overflow-hidden",
+                div {
+                    class: "h-full transition-all",
+                    class: if low { "bg-red-500" } else { "bg-gray-500" },
+                    style: "width: {percent:.1}%;",
+                }
+            }
+            "{percent:.0}%"
+            if usage.paused {
+                span { class: "px-1 rounded font-bold bg-red-500/10 border border-red-500/30",
+                    "PAUSED"
+                }
+            }
+        }
+    }
+}
+
+fn import_percent(read: u64, total: u64) -> u64 {
+    (read * 100).checked_div(total).unwrap_or(100)
+}
+
+/// Imports a picked or dropped file into a new session.
+/// Refused while capturing, since the import takes over the open session.
+pub(crate) fn start_import(state: AppState, bridge: WorkerController, file: &web_sys::File) {
+    if state.conn.is_connected() || (state.conn.is_simulating)() {
+        state.warning("Disconnect before importing a file");
+        return;
+    }
+    if state.log.import_progress.peek().is_some() {
+        state.warning("An import is already running");
+        return;
+    }
+    { state.log.import_progress }.set(Some((0, file.size() as u64)));
+    bridge.import_file(file);
+}
@@ -1,25 +1,157 @@
+use crate::components::monitor::json_table::JsonTableCells;
+use crate::components::monitor::utils::style::{css_background, css_color};
 use crate::config::line_height_from_font;
-use crate::state::{AppState, Highlight};
-use crate::utils::decode_ansi_text;
+use crate::state::{AppState, Bookmark, JsonRow, StyledLine, StyledSpan};
 use dioxus::prelude::*;
 
+/// A view line as styled by the worker; only maps its spans to elements
 #[component]
-pub fn MonitorLogLine(text: String, highlights: Vec<Highlight>, show_highlights: bool) -> Element {
+pub fn MonitorLogLine(
+    styled: StyledLine,
+    /// Index in the current view; `None` for the line still being received
+    #[props(default)]
+    line: Option<usize>,
+    #[props(default)] bookmark: Option<Bookmark>,
+    /// Line reached by navigation
+    #[props(default)]
+    focused: bool,
+    /// Find match reached by navigation, by position among the marks of the line
+    #[props(default)]
+    find_current: Option<usize>,
+    /// Shown only as context of a filter match
+    #[props(default)]
+    context: bool,
+    /// Lines were skipped between the previous view line and this one
+    #[props(default)]
+    gap: bool,
+    /// Shows the line number gutter
+    #[props(default)]
+    number_gutter: bool,
+    /// Absolute line number; the gutter stays blank without one
+    #[props(default)]
+    number: Option<usize>,
+    /// Field cells shown instead of the text in the structured view
+    #[props(default)]
+    row: Option<JsonRow>,
+) -> Element {
     let state = use_context::<AppState>();
+    let bridge = crate::hooks::use_worker_controller();
     let font_size = *state.ui.font_size.read();
     let line_height = line_height_from_font(font_size);
-    let segments = decode_ansi_text(&text, &highlights, show_highlights);
+    let line_background = styled.background.as_deref().map(css_background);
+    let is_bookmarked = bookmark.is_some();
 
     rsx! {
         div {
             style: "height: {line_height}px; line-height: {line_height}px;",
-            class: "text-gray-300 whitespace-pre font-mono",
+            class: "group/line text-gray-300 whitespace-pre font-mono",
+            class: if focused { "bg-primary/15" } else if is_bookmarked { "bg-primary/5" },
+            class: if context { "opacity-50" },
+            // Inset border keeps the fixed line height of the virtual scroll
+            class: if gap { "shadow-[inset_0_1px_0_rgba(148,163,184,0.35)]" },
             style: "font-size: {font_size}px;",
-            for (content , color) in segments {
-                if let Some(c) = color {
-                    span { class: "font-bold", style: "color: {c};", "{content}" }
-                } else {
-                    "{content}"
+            style: if let Some(bg) = &line_background { "background-color: {bg};" },
+            ondoubleclick: move |_| {
+                if let Some(line) = line {
+                    bridge.request_raw_line(line);
+                }
+            },
+            if number_gutter {
+                LineNumberGutter { number }
+            }
+            BookmarkGutter { line, bookmark }
+            if let Some(row) = row {
+                JsonTableCells { row }
+            } else {
+                for segment in styled.spans {
+                    if let Some(m) = segment.mark {
+                        span {
+                            class: "rounded-sm text-white",
+                            class: if find_current == Some(m) { "bg-orange-500/70" } else { "bg-yellow-500/30" },
+                            class: if segment.bold { "font-bold" },
+                            class: if segment.italic { "italic" },
+                            class: if segment.underline { "underline" },
+                            style: if let Some(c) = &segment.color { "color: {css_color(c)};" },
+                            "{segment.text}"
+                        }
+                    } else if segment != StyledSpan::plain(&segment.text) {
+                        span {
+                            class: if segment.bold { "font-bold" },
+                            class: if segment.italic { "italic" },
+                            class: if segment.underline { "underline" },
+                            style: if let Some(c) = &segment.color { "color: {css_color(c)};" },
+                            style: if let Some(c) = &segment.background { "background-color: {css_background(c)};" },
+                            "{segment.text}"
+                        }
+                    } else {
+                        "{segment.text}"
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Absolute line number; while filtering, a click shows the line in the full log
+#[component]
+fn LineNumberGutter(number: Option<usize>) -> Element {
+    let mut state = use_context::<AppState>();
+    let bridge = crate::hooks::use_worker_controller();
+    let field_filtering =
+        (state.ui.structured_view)() && !state.log.field_filters.read().is_empty();
+    let filtering = !state.log.filter_query.read().trim().is_empty()
+        || (state.log.min_level)().is_some()
+        || field_filtering;
+
+    rsx! {
+        span {
+            class: "inline-block min-w-[7ch] pr-2 text-right align-middle text-gray-600 select-none",
+            class: if filtering && number.is_some() { "cursor-pointer hover:text-primary hover:underline" },
+            title: if filtering && number.is_some() { "Show in full log" },
+            onclick: move |_| {
+                if let Some(number) = number.filter(|_| filtering) {
+                    bridge.reveal_line(number);
+                    state.log.filter_query.set(String::new());
+                    state.log.min_level.set(None);
+                    state.log.field_filters.set(Vec::new());
+                }
+            },
+            if let Some(number) = number {
+                "{number + 1}"
+            }
+        }
+    }
+}
+
+/// Gutter marker toggling the bookmark of a line
+#[component]
+fn BookmarkGutter(line: Option<usize>, bookmark: Option<Bookmark>) -> Element {
+    let bridge = crate::hooks::use_worker_controller();
+    let title = match &bookmark {
+        Some(b) if !b.note.is_empty() => format!("{} (click to remove)", b.note),
+        Some(_) => "Remove bookmark".to_string(),
+        None => "Add bookmark".to_string(),
+    };
+
+    rsx! {
+        span {
+            class: "inline-block w-4 align-middle text-center select-none",
+            if let Some(line) = line {
+                span {
+                    class: "material-symbols-outlined text-[12px] cursor-pointer transition-opacity",
+                    class: if bookmark.is_some() { "text-primary" } else { "text-gray-600 opacity-0 group-hover/line:opacity-100" },
+                    title: "{title}",
+                    onclick: move |_| {
+                        if let Some(b) = &bookmark {
+                            bridge.remove_bookmark(b.line);
+                        } else if let Some(note) = web_sys::window()
+                            .and_then(|w| w.prompt_with_message("Bookmark note (optional)").ok())
+                            .flatten()
+                        {
+                            bridge.add_bookmark(line, note);
+                        }
+                    },
+                    "bookmark"
                 }
             }
         }
@@ -1,11 +1,16 @@
-use crate::components::monitor::hooks::effects::{use_search_sync, use_settings_sync};
-use crate::components::monitor::monitor_header::MonitorHeader;
+use crate::components::monitor::hooks::effects::{
+    use_find_sync, use_search_sync, use_settings_sync,
+};
+use crate::components::monitor::json_table::JsonTableHeader;
+use crate::components::monitor::monitor_header::{start_import, MonitorHeader};
 use crate::components::monitor::monitor_viewport::MonitorViewport;
+use crate::components::monitor::raw_bytes_panel::RawBytesPanel;
 use crate::components::ui::buttons::ResumeScrollButton;
 use crate::components::ui::console::ConsoleFrame;
 use crate::hooks::use_worker_controller;
 use crate::state::AppState;
 use dioxus::prelude::*;
+use dioxus::web::WebEventExt;
 
 use crate::components::monitor::hooks::virtual_scroll::use_virtual_scroll;
 
@@ -18,13 +23,27 @@ pub fn Monitor() -> Element {
     // Initial log sync and effects
     use_settings_sync(bridge);
     use_search_sync(bridge);
+    use_find_sync(bridge);
+    // Bookmarks of the session restored on startup
+    use_hook(move || bridge.list_bookmarks());
 
     rsx! {
         ConsoleFrame {
+            ondrop: move |evt: DragEvent| {
+                let file = evt
+                    .as_web_event()
+                    .data_transfer()
+                    .and_then(|dt| dt.files())
+                    .and_then(|files| files.get(0));
+                if let Some(file) = file {
+                    start_import(state, bridge, &file);
+                }
+            },
             MonitorHeader {
                 autoscroll: (state.ui.autoscroll)(),
                 count: (state.log.total_lines)(),
                 onexport: move |_| bridge.export((state.ui.show_timestamps)()),
+                onexport_raw: move |_| bridge.export_raw(),
                 onclear: move |_| {
                     bridge.clear();
                     state.log.clear();
@@ -33,6 +52,10 @@ pub fn Monitor() -> Element {
                 ontoggle_autoscroll: move |_| state.ui.toggle_autoscroll(),
             }
 
+            if (state.ui.structured_view)() {
+                JsonTableHeader {}
+            }
+
             MonitorViewport {
                 total_height: vs.total_height,
                 offset_top: vs.offset_top,
@@ -45,13 +68,19 @@ pub fn Monitor() -> Element {
                 onmounted_sentinel: move |evt: MountedEvent| vs.sentinel_handle.set(Some(evt.data())),
             }
 
+            if let Some(raw) = (state.log.raw_line)() {
+                RawBytesPanel { raw, onclose: move |_| { state.log.raw_line }.set(None) }
+            }
+
             if !(state.ui.autoscroll)() {
                 ResumeScrollButton {
                     onclick: move |_| {
-                        web_sys::window()
+                        if let Some(el) = web_sys::window()
                             .and_then(|win| win.document())
                             .and_then(|doc| doc.get_element_by_id("console-output"))
-                            .map(|el| el.set_scroll_top(el.scroll_height()));
+                        {
+                            el.set_scroll_top(el.scroll_height());
+                        }
                     },
                 }
             }
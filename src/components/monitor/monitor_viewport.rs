@@ -50,14 +50,11 @@ pub fn MonitorViewport(
                 };
                 let port = state.conn.port.peek().as_ref().cloned();
                 let local_echo = *state.serial.tx_local_echo.peek();
-                let bridge = bridge.clone();
                 spawn(async move {
                     if let Some(p) = port {
-                        if serial::send_data(&p, &data).await.is_ok() {
-                            if local_echo {
-                                let array = Uint8Array::from(data.as_slice());
-                                bridge.append_chunk(array, false);
-                            }
+                        if serial::send_data(&p, &data).await.is_ok() && local_echo {
+                            let array = Uint8Array::from(data.as_slice());
+                            bridge.append_chunk(array, false);
                         }
                     }
                 });
@@ -69,31 +66,44 @@ pub fn MonitorViewport(
             div { style: "height: {total_height}px; width: 100%; position: absolute; top: 0; left: 0; pointer-events: none;" }
             div { style: "position: absolute; top: 0; left: 0; right: 0; transform: translateY({offset_top}px); padding: {CONSOLE_TOP_PADDING}px 0 {CONSOLE_BOTTOM_PADDING}px 0; pointer-events: auto; min-width: 100%; width: max-content;",
                 {
-                    let highlights = (state.log.highlights)().clone();
-                    let show_highlights = (state.ui.show_highlights)();
                     let active_line = (state.log.active_line)();
+                    let bookmarks = state.log.visible_bookmarks.read();
+                    let focused_line = (state.log.focused_line)();
+                    let find_match = (state.log.find_match)();
+                    let context_lines = state.log.visible_context.read();
+                    let gaps = state.log.visible_gaps.read();
                     let logs = visible_logs.read();
+                    let numbers = state.log.visible_line_numbers.read();
+                    let show_numbers = (state.ui.show_line_numbers)();
+                    let rows = state.log.visible_rows.read();
+                    let structured = (state.ui.structured_view)();
                     let is_at_bottom = logs
 
                         .last()
                         .map(|(idx, _)| *idx + 1 == total_lines())
                         .unwrap_or(total_lines() == 0);
                     rsx! {
-                        for (line_idx , text) in logs.iter() {
+                        for (pos , (line_idx , styled)) in logs.iter().enumerate() {
                             MonitorLogLine {
                                 key: "{line_idx}",
-                                text: text.clone(),
-                                highlights: highlights.clone(),
-                                show_highlights,
+                                number_gutter: show_numbers,
+                                number: numbers.get(pos).copied(),
+                                line: *line_idx,
+                                focused: focused_line == Some(*line_idx),
+                                bookmark: bookmarks.iter().find(|(i, _)| i == line_idx).map(|(_, b)| b.clone()),
+                                find_current: find_match.filter(|m| m.line == *line_idx).map(|m| m.occurrence),
+                                context: context_lines.contains(line_idx),
+                                gap: gaps.contains(line_idx),
+                                row: rows.get(pos).cloned().flatten().filter(|_| structured),
+                                styled: styled.clone(),
                             }
                         }
                         if is_at_bottom {
-                            if let Some(text) = active_line {
+                            if let Some(styled) = active_line {
                                 MonitorLogLine {
                                     key: "{0}",
-                                    text: text.clone(),
-                                    highlights: highlights.clone(),
-                                    show_highlights: false, // Maybe don't highlight active line to avoid flicker? // Maybe don't highlight active line to avoid flicker? // Maybe don't highlight active line to avoid flicker?  Maybe don't highlight active line to avoid flicker?
+                                    number_gutter: show_numbers,
+                                    styled,
                                 }
                             }
                         }
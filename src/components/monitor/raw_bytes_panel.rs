@@ -0,0 +1,41 @@
+use crate::components::monitor::sessions_panel::format_session_time;
+use crate::components::ui::console::ConsoleActionButton;
+use crate::components::ui::PanelHeader;
+use crate::state::RawLine;
+use crate::utils::format_hex_dump;
+use dioxus::prelude::*;
+
+/// Hex dump of the bytes a monitor line was decoded from
+#[component]
+pub fn RawBytesPanel(raw: RawLine, onclose: EventHandler<()>) -> Element {
+    let rows = format_hex_dump(&raw.bytes);
+    let mut subtitle = format!("Line {} · {} bytes", raw.line + 1, raw.bytes.len());
+    if let Some(ms) = raw.received_at {
+        subtitle.push_str(&format!(" · received {}", format_session_time(ms)));
+    }
+
+    rsx! {
+        div { class: "absolute bottom-4 left-4 right-4 z-30 bg-[#16181a] rounded-xl border border-white/10 shadow-2xl p-4 animate-in fade-in slide-in-from-bottom-2 duration-200",
+            div { class: "flex flex-col gap-3",
+                div { class: "flex items-start gap-2",
+                    div { class: "flex-1",
+                        PanelHeader { title: "Raw Bytes", subtitle: Some(subtitle) }
+                    }
+                    ConsoleActionButton {
+                        icon: "close",
+                        title: "Close",
+                        onclick: move |_| onclose.call(()),
+                    }
+                }
+                div { class: "max-h-48 overflow-y-auto custom-scrollbar font-mono text-[11px] text-gray-300 whitespace-pre",
+                    if rows.is_empty() {
+                        span { class: "text-gray-600 italic", "No raw bytes recorded for this line" }
+                    }
+                    for (i, row) in rows.into_iter().enumerate() {
+                        div { key: "{i}", "{row}" }
+                    }
+                }
+            }
+        }
+    }
+}
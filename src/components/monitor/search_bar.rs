@@ -1,10 +1,47 @@
 use crate::components::ui::FilterOptionButton;
+use crate::config::MAX_CONTEXT_LINES;
 use crate::state::AppState;
+use crate::utils::file_save::{read_file_text, save_json_to_disk};
+use crate::utils::{filter_error, FilterPresets, SavedFilter};
 use dioxus::prelude::*;
+use dioxus::web::WebEventExt;
+use wasm_bindgen::JsCast;
+
+/// The filter currently typed in the search bar
+fn current_filter(state: AppState) -> SavedFilter {
+    SavedFilter {
+        query: state.log.filter_query.peek().clone(),
+        match_case: *state.log.match_case.peek(),
+        use_regex: *state.log.use_regex.peek(),
+        invert: *state.log.invert_filter.peek(),
+    }
+}
+
+fn apply_filter(state: AppState, filter: &SavedFilter) {
+    { state.log.filter_query }.set(filter.query.clone());
+    { state.log.match_case }.set(filter.match_case);
+    { state.log.use_regex }.set(filter.use_regex);
+    { state.log.invert_filter }.set(filter.invert);
+}
 
 #[component]
 pub fn SearchBar() -> Element {
     let mut state = use_context::<AppState>();
+    let mut presets = use_signal(FilterPresets::load);
+    // Parse errors are caught here; the worker reports what fails when it runs
+    let error = use_memo(move || {
+        filter_error(
+            &(state.log.filter_query)(),
+            (state.log.match_case)(),
+            (state.log.use_regex)(),
+        )
+        .or((state.log.search_error)())
+    });
+    let border = if error().is_some() {
+        "border-red-500/70 focus:border-red-500/70"
+    } else {
+        "border-[#2a2e33] focus:border-primary/50"
+    };
 
     rsx! {
         div { class: "flex-[1.3] relative group flex items-center min-w-0",
@@ -12,13 +49,27 @@ pub fn SearchBar() -> Element {
                 "search"
             }
             input {
-                class: "w-full h-full bg-[#0d0f10] text-xs font-medium text-white placeholder-gray-600 pl-9 pr-24 rounded-lg border border-[#2a2e33] focus:border-primary/50 focus:shadow-glow outline-none shadow-inset-input transition-all",
-                placeholder: "Filter logs...",
+                class: "w-full h-full bg-[#0d0f10] text-xs font-medium text-white placeholder-gray-600 pl-9 pr-40 rounded-lg border {border} focus:shadow-glow outline-none shadow-inset-input transition-all",
+                placeholder: "Filter logs... (and, or, not, tag:, key>n)",
+                title: error().unwrap_or_default(),
                 "type": "text",
                 value: "{state.log.filter_query}",
                 oninput: move |evt| state.log.filter_query.set(evt.value()),
+                // Committed queries (Enter or leaving the field) go to the history
+                onchange: move |_| {
+                    let filter = current_filter(state);
+                    if filter_error(&filter.query, filter.match_case, filter.use_regex).is_none() {
+                        presets.write().remember(filter);
+                    }
+                },
+            }
+            if let Some(err) = error() {
+                div { class: "absolute left-2 top-full mt-1 z-20 px-2 py-0.5 rounded bg-[#1a0f10] border border-red-500/40 text-[10px] text-red-400 whitespace-nowrap pointer-events-none",
+                    "{err}"
+                }
             }
             div { class: "absolute right-1 flex items-center gap-0.5",
+                PresetMenu { presets }
                 FilterOptionButton {
                     title: "Match Case",
                     label: "Aa",
@@ -46,6 +97,309 @@ pub fn SearchBar() -> Element {
                         state.log.invert_filter.set(!v);
                     },
                 }
+                ContextButton {}
+            }
+        }
+    }
+}
+
+/// Named filter presets and recent filters, with JSON import/export to share them
+#[component]
+fn PresetMenu(presets: Signal<FilterPresets>) -> Element {
+    let state = use_context::<AppState>();
+    let mut open = use_signal(|| false);
+    // Preset being edited; saving overwrites it with the current filter
+    let mut editing = use_signal(|| None::<u64>);
+    let mut name = use_signal(String::new);
+    let has_query = !(state.log.filter_query)().trim().is_empty();
+
+    let mut save = move || {
+        let label = name.read().trim().to_string();
+        if label.is_empty() {
+            state.error("Enter a preset name");
+            return;
+        }
+        presets
+            .write()
+            .store(editing(), label, current_filter(state));
+        name.set(String::new());
+        editing.set(None);
+    };
+
+    let import = move |evt: FormEvent| {
+        let input = evt
+            .as_web_event()
+            .target()
+            .and_then(|t| t.dyn_into::<web_sys::HtmlInputElement>().ok());
+        let Some(input) = input else {
+            return;
+        };
+        let file = input.files().and_then(|files| files.get(0));
+        // Allow picking the same file again
+        input.set_value("");
+        if let Some(file) = file {
+            spawn(async move {
+                let result = match read_file_text(&file).await {
+                    Ok(json) => presets.write().import(&json),
+                    Err(_) => Err("Could not read the file".to_string()),
+                };
+                match result {
+                    Ok(n) => state.success(&format!("Imported {} presets", n)),
+                    Err(e) => state.error(&e),
+                }
+            });
+        }
+    };
+
+    rsx! {
+        div { class: "relative",
+            button {
+                class: "w-8 h-7 flex items-center justify-center rounded-md transition-all focus:outline-none",
+                class: if open() { "bg-primary/10 border border-primary/20 text-primary" } else { "text-gray-500 hover:text-white hover:bg-[#2a2e33]" },
+                title: "Filter Presets",
+                onclick: move |_| open.toggle(),
+                span { class: "material-symbols-outlined text-[16px]", "bookmarks" }
+            }
+            if open() {
+                div {
+                    class: "fixed inset-0 z-20 cursor-default",
+                    onclick: move |_| open.set(false),
+                }
+                div { class: "absolute right-0 top-full mt-2 z-30 w-72 p-3 rounded-lg bg-[#16181a] border border-[#2a2e33] shadow-xl flex flex-col gap-3 text-[11px] text-gray-400",
+                    div { class: "flex items-center justify-between",
+                        span { class: "text-[10px] font-bold uppercase tracking-widest text-gray-500",
+                            "Presets"
+                        }
+                        div { class: "flex items-center gap-1",
+                            input {
+                                id: "preset-file-input",
+                                r#type: "file",
+                                accept: ".json",
+                                class: "hidden",
+                                onchange: import,
+                            }
+                            button {
+                                class: "material-symbols-outlined text-[16px] text-gray-500 hover:text-white transition-colors",
+                                title: "Import Presets",
+                                onclick: move |_| {
+                                    let input = web_sys::window()
+                                        .and_then(|w| w.document())
+                                        .and_then(|doc| doc.get_element_by_id("preset-file-input"))
+                                        .and_then(|el| el.dyn_into::<web_sys::HtmlElement>().ok());
+                                    if let Some(input) = input {
+                                        input.click();
+                                    }
+                                },
+                                "upload"
+                            }
+                            button {
+                                class: "material-symbols-outlined text-[16px] text-gray-500 hover:text-white transition-colors",
+                                title: "Export Presets",
+                                onclick: move |_| save_json_to_disk(&presets.read().export(), "filter_presets.json"),
+                                "download"
+                            }
+                        }
+                    }
+
+                    div { class: "flex flex-col gap-0.5 max-h-48 overflow-y-auto",
+                        if presets.read().presets().is_empty() {
+                            span { class: "text-gray-600 italic px-1", "No presets saved" }
+                        }
+                        for preset in presets.read().presets().to_vec() {
+                            div {
+                                key: "{preset.id}",
+                                class: "group flex items-center gap-1 px-1 rounded hover:bg-[#2a2e33]/60",
+                                class: if editing() == Some(preset.id) { "bg-primary/10" },
+                                button {
+                                    class: "flex-1 min-w-0 flex flex-col items-start py-1 text-left",
+                                    title: "Apply",
+                                    onclick: {
+                                        let filter = preset.filter.clone();
+                                        move |_| {
+                                            apply_filter(state, &filter);
+                                            open.set(false);
+                                        }
+                                    },
+                                    span { class: "text-gray-300 font-bold truncate w-full", "{preset.name}" }
+                                    FilterSummary { filter: preset.filter.clone() }
+                                }
+                                button {
+                                    class: "material-symbols-outlined text-[14px] opacity-0 group-hover:opacity-100 text-gray-500 hover:text-white transition-all",
+                                    title: "Edit: load it, change the filter, then Update",
+                                    onclick: {
+                                        let preset = preset.clone();
+                                        move |_| {
+                                            apply_filter(state, &preset.filter);
+                                            name.set(preset.name.clone());
+                                            editing.set(Some(preset.id));
+                                        }
+                                    },
+                                    "edit"
+                                }
+                                button {
+                                    class: "material-symbols-outlined text-[14px] opacity-0 group-hover:opacity-100 text-gray-500 hover:text-red-400 transition-all",
+                                    title: "Delete",
+                                    onclick: move |_| {
+                                        presets.write().remove(preset.id);
+                                        if editing() == Some(preset.id) {
+                                            editing.set(None);
+                                        }
+                                    },
+                                    "delete"
+                                }
+                            }
+                        }
+                    }
+
+                    div { class: "flex gap-1",
+                        input {
+                            class: "flex-1 min-w-0 bg-[#0d0f10] border border-[#2a2e33] rounded px-2 py-1 text-white outline-none focus:border-primary/50",
+                            placeholder: "Preset name...",
+                            value: "{name}",
+                            oninput: move |evt| name.set(evt.value()),
+                            onkeydown: move |evt| {
+                                if evt.key() == Key::Enter && has_query {
+                                    save();
+                                }
+                            },
+                        }
+                        if editing().is_some() {
+                            button {
+                                class: "px-2 rounded text-gray-500 hover:text-white transition-colors",
+                                onclick: move |_| {
+                                    editing.set(None);
+                                    name.set(String::new());
+                                },
+                                "Cancel"
+                            }
+                        }
+                        button {
+                            class: "px-2 rounded bg-primary/20 text-primary font-bold disabled:opacity-40 transition-colors",
+                            disabled: !has_query,
+                            title: "Save the current filter",
+                            onclick: move |_| save(),
+                            if editing().is_some() { "Update" } else { "Save" }
+                        }
+                    }
+
+                    if !presets.read().recent().is_empty() {
+                        div { class: "flex flex-col gap-0.5 pt-2 border-t border-white/5",
+                            div { class: "flex items-center justify-between",
+                                span { class: "text-[10px] font-bold uppercase tracking-widest text-gray-500",
+                                    "Recent"
+                                }
+                                button {
+                                    class: "text-[10px] text-gray-600 hover:text-white transition-colors",
+                                    onclick: move |_| presets.write().clear_recent(),
+                                    "Clear"
+                                }
+                            }
+                            div { class: "flex flex-col max-h-40 overflow-y-auto",
+                                for filter in presets.read().recent().to_vec() {
+                                    button {
+                                        class: "px-1 py-0.5 rounded text-left hover:bg-[#2a2e33]/60",
+                                        onclick: {
+                                            let filter = filter.clone();
+                                            move |_| {
+                                                apply_filter(state, &filter);
+                                                open.set(false);
+                                            }
+                                        },
+                                        FilterSummary { filter: filter.clone() }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Query of a saved filter followed by the options it sets
+#[component]
+fn FilterSummary(filter: SavedFilter) -> Element {
+    let flags: String = [
+        (filter.match_case, " Aa"),
+        (filter.use_regex, " .*"),
+        (filter.invert, " !"),
+    ]
+    .iter()
+    .filter(|(on, _)| *on)
+    .map(|(_, flag)| *flag)
+    .collect();
+
+    rsx! {
+        span { class: "w-full truncate font-mono text-[10px] text-gray-500",
+            "{filter.query}"
+            span { class: "text-primary/70", "{flags}" }
+        }
+    }
+}
+
+/// Lines of context shown around each filter match, like `grep -B/-A`
+#[component]
+fn ContextButton() -> Element {
+    let mut state = use_context::<AppState>();
+    let mut open = use_signal(|| false);
+    let before = (state.log.context_before)();
+    let after = (state.log.context_after)();
+    let label = match (before, after) {
+        (0, 0) => "±".to_string(),
+        (b, a) if b == a => format!("±{}", b),
+        (b, a) => format!("-{}+{}", b, a),
+    };
+    let active = before > 0 || after > 0;
+
+    rsx! {
+        div { class: "relative",
+            button {
+                class: "min-w-8 h-7 px-1 flex items-center justify-center rounded-md transition-all focus:outline-none",
+                class: if active || open() { "bg-primary/10 border border-primary/20 text-primary" } else { "text-gray-500 hover:text-white hover:bg-[#2a2e33]" },
+                title: "Context Lines",
+                onclick: move |_| open.toggle(),
+                span { class: "text-[11px] font-bold font-mono", "{label}" }
+            }
+            if open() {
+                div { class: "absolute right-0 top-full mt-2 z-30 w-44 p-2 rounded-lg bg-[#16181a] border border-[#2a2e33] shadow-xl flex flex-col gap-2 text-[10px] font-mono text-gray-400",
+                    ContextInput { label: "Before (-B)", value: state.log.context_before }
+                    ContextInput { label: "After (-A)", value: state.log.context_after }
+                    div { class: "flex gap-1",
+                        for n in [0usize, 3, 10, 20] {
+                            button {
+                                class: "flex-1 py-0.5 rounded bg-[#2a2e33]/60 hover:text-white transition-colors",
+                                title: "Show {n} lines before and after (-C)",
+                                onclick: move |_| {
+                                    state.log.context_before.set(n);
+                                    state.log.context_after.set(n);
+                                },
+                                "C{n}"
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[component]
+fn ContextInput(label: &'static str, value: Signal<usize>) -> Element {
+    rsx! {
+        label { class: "flex items-center justify-between gap-2",
+            "{label}"
+            input {
+                class: "w-14 bg-[#0d0f10] border border-[#2a2e33] rounded px-1 py-0.5 text-right text-white outline-none focus:border-primary/50",
+                "type": "number",
+                min: "0",
+                max: "{MAX_CONTEXT_LINES}",
+                value: "{value}",
+                oninput: move |evt| {
+                    if let Ok(n) = evt.value().parse::<usize>() {
+                        value.set(n.min(MAX_CONTEXT_LINES));
+                    }
+                },
             }
         }
     }
@@ -0,0 +1,182 @@
+use crate::components::ui::console::ConsoleActionButton;
+use crate::components::ui::PanelHeader;
+use crate::state::{AppState, RxEncoding, SessionInfo, SessionMetadata};
+use crate::utils::format_bytes;
+use dioxus::prelude::*;
+
+#[component]
+pub fn SessionsPanel(onclose: EventHandler<()>) -> Element {
+    let state = use_context::<AppState>();
+    let bridge = crate::hooks::use_worker_controller();
+    let sessions = (state.log.sessions)();
+
+    // Refresh the list every time the panel opens
+    use_hook(move || bridge.list_sessions());
+
+    rsx! {
+        div {
+            class: "fixed inset-0 z-40 cursor-default",
+            onclick: move |_| onclose.call(()),
+        }
+        div { class: "absolute top-full left-0 mt-1 w-[28rem] z-50 bg-[#16181a] rounded-xl border border-white/10 shadow-2xl p-4 animate-in fade-in zoom-in-95 duration-200 origin-top-left",
+            div { class: "flex flex-col gap-3",
+                PanelHeader {
+                    title: "Sessions",
+                    subtitle: Some(format!("{} stored", sessions.len())),
+                }
+                div { class: "flex flex-col gap-1 max-h-80 overflow-y-auto custom-scrollbar",
+                    if sessions.is_empty() {
+                        span { class: "text-xs text-gray-600 italic px-1", "No sessions stored" }
+                    }
+                    for session in sessions {
+                        SessionRow { key: "{session.name}", session }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[component]
+fn SessionRow(session: SessionInfo) -> Element {
+    let bridge = crate::hooks::use_worker_controller();
+    let mut editing = use_signal(|| false);
+    let mut label = use_signal(|| session.label.clone().unwrap_or_default());
+
+    let created = format_session_time(session.created_at);
+    let size = format_bytes(session.size);
+    // The live session keeps capturing while an archived one is viewed
+    let can_open = !session.is_open;
+    let in_use = session.is_open || session.is_live;
+
+    let name = session.name.clone();
+    let mut commit_rename = move || {
+        bridge.rename_session(name.clone(), label());
+        editing.set(false);
+    };
+
+    rsx! {
+        div {
+            class: "flex items-center gap-2 px-2 py-1.5 rounded-lg border transition-colors",
+            class: if session.is_open { "bg-primary/10 border-primary/30" } else { "bg-[#0d0f10] border-[#2a2e33]" },
+            div { class: "flex-1 min-w-0 flex flex-col",
+                if editing() {
+                    input {
+                        class: "w-full bg-[#0b0c0d] border border-[#2a2e33] rounded px-1 text-[11px] text-white focus:border-primary outline-none",
+                        value: "{label}",
+                        placeholder: "Session label",
+                        autofocus: true,
+                        oninput: move |evt| label.set(evt.value()),
+                        onkeydown: move |evt| match evt.key() {
+                            Key::Enter => commit_rename(),
+                            Key::Escape => editing.set(false),
+                            _ => {}
+                        },
+                    }
+                } else {
+                    span { class: "text-[11px] font-bold text-gray-300 truncate",
+                        {session.label.clone().unwrap_or_else(|| created.clone())}
+                    }
+                }
+                div { class: "flex items-center gap-2 text-[9px] font-mono text-gray-500",
+                    span { "{created}" }
+                    span { "{size}" }
+                    if session.is_live {
+                        span { class: "text-emerald-500 font-bold", "LIVE" }
+                    }
+                    if session.is_open && !session.is_live {
+                        span { class: "text-yellow-500 font-bold", "VIEWING" }
+                    }
+                }
+                if let Some(metadata) = &session.metadata {
+                    SessionMetadataLine { metadata: metadata.clone() }
+                }
+            }
+            if can_open {
+                ConsoleActionButton {
+                    icon: "visibility",
+                    title: "Open Session",
+                    onclick: {
+                        let name = session.name.clone();
+                        move |_| bridge.open_session(name.clone())
+                    },
+                    hover_color_class: "hover:text-primary",
+                }
+            }
+            ConsoleActionButton {
+                icon: "edit",
+                title: "Rename Session",
+                onclick: move |_| editing.toggle(),
+            }
+            ConsoleActionButton {
+                icon: "download",
+                title: "Download Session",
+                onclick: {
+                    let name = session.name.clone();
+                    move |_| bridge.download_session(name.clone())
+                },
+                hover_color_class: "hover:text-primary",
+            }
+            if !in_use {
+                ConsoleActionButton {
+                    icon: "delete",
+                    title: "Delete Session",
+                    onclick: {
+                        let name = session.name.clone();
+                        move |_| {
+                            let confirmed = web_sys::window()
+                                .and_then(|w| w.confirm_with_message("Delete this session?").ok())
+                                .unwrap_or(false);
+                            if confirmed {
+                                bridge.delete_session(name.clone());
+                            }
+                        }
+                    },
+                    hover_color_class: "hover:text-red-500",
+                }
+            }
+        }
+    }
+}
+
+/// One-line summary of how a session was captured
+#[component]
+pub fn SessionMetadataLine(metadata: SessionMetadata) -> Element {
+    let started = format_session_time(metadata.started_at);
+    let ended = metadata
+        .ended_at
+        .map(format_session_time)
+        .unwrap_or_else(|| "capturing".to_string());
+
+    rsx! {
+        div {
+            class: "flex items-center gap-2 text-[9px] font-mono text-gray-600",
+            title: "Started {started}\nEnded {ended}\nFlow control: {metadata.flow_control}",
+            if let Some(file) = &metadata.imported_from {
+                span { class: "truncate", "Imported {file}" }
+            } else {
+                span { "{metadata.port_summary()}" }
+            }
+            if let Some(usb) = metadata.usb_id() {
+                span { "USB {usb}" }
+            }
+            if metadata.encoding != RxEncoding::Utf8 {
+                span { "{metadata.encoding}" }
+            }
+            if metadata.ended_at.is_some() {
+                span { "{metadata.line_count} lines" }
+            }
+        }
+    }
+}
+
+/// Formats a session timestamp (ms since epoch) in local time
+pub(crate) fn format_session_time(ms: i64) -> String {
+    chrono::DateTime::from_timestamp_millis(ms)
+        .map(|t| {
+            t.with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string()
+        })
+        .unwrap_or_default()
+}
@@ -79,8 +79,36 @@ pub fn use_auto_scroller(
     });
 }
 
-/// Helper to calculate new scroll state (start_index and autoscroll)
-/// Returns (new_start_index, should_autoscroll)
+/// Hook to scroll the console to a requested view index.
+/// The line lands in the upper third of the viewport and autoscroll is paused.
+pub fn use_scroll_to_line(
+    mut target: Signal<Option<usize>>,
+    mut autoscroll: Signal<bool>,
+    total_lines: Signal<usize>,
+    console_height: Signal<f64>,
+    line_height: f64,
+) {
+    use_effect(move || {
+        let Some(line) = target() else {
+            return;
+        };
+        target.set(None);
+        autoscroll.set(false);
+
+        let viewport = *console_height.peek();
+        let (_, _, scale_factor) =
+            calculate_virtual_metrics(*total_lines.peek(), 0, viewport, line_height);
+        let logical_y = (line as f64 * line_height - viewport / 3.0).max(0.0);
+
+        if let Some(el) = web_sys::window()
+            .and_then(|win| win.document())
+            .and_then(|doc| doc.get_element_by_id("console-output"))
+        {
+            el.set_scroll_top((logical_y / scale_factor) as i32);
+        }
+    });
+}
+
 /// Helper to calculate new scroll state (start_index and autoscroll)
 /// Returns (new_start_index, should_autoscroll)
 pub fn calculate_scroll_state(
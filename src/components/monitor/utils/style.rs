@@ -1,3 +1,5 @@
+use crate::config::HIGHLIGHT_COLORS;
+
 /// Returns (border_class, text_class) for a given highlight color name
 pub fn get_highlight_classes(color: &str) -> (&'static str, &'static str) {
     match color {
@@ -66,3 +68,37 @@ pub fn get_highlight_classes(color: &str) -> (&'static str, &'static str) {
         _ => ("border-primary/30 hover:border-primary/60", "text-primary"),
     }
 }
+
+/// CSS color of a highlight color name (the 400 shade); other colors pass through
+pub fn css_color(color: &str) -> &str {
+    match color {
+        "red" => "#f87171",
+        "blue" => "#60a5fa",
+        "yellow" => "#facc15",
+        "green" => "#4ade80",
+        "purple" => "#c084fc",
+        "orange" => "#fb923c",
+        "teal" => "#2dd4bf",
+        "pink" => "#f472b6",
+        "indigo" => "#818cf8",
+        "lime" => "#a3e635",
+        "cyan" => "#22d3ee",
+        "rose" => "#fb7185",
+        "fuchsia" => "#e879f9",
+        "amber" => "#fbbf24",
+        "emerald" => "#34d399",
+        "sky" => "#38bdf8",
+        "violet" => "#a78bfa",
+        c => c,
+    }
+}
+
+/// Background of a color. Highlight colors are made translucent so the text
+/// stays readable on them; terminal colors are drawn as sent.
+pub fn css_background(color: &str) -> String {
+    if HIGHLIGHT_COLORS.contains(&color) {
+        format!("{}40", css_color(color))
+    } else {
+        color.to_string()
+    }
+}
@@ -19,8 +19,11 @@ pub fn RusTerm() -> Element {
         bridge.set_mode(mode);
         match mode {
             ViewMode::Monitoring => {
-                bridge.clear();
-                app_state.log.clear();
+                // Archived sessions are read-only; only the live capture is reset
+                if !*app_state.log.read_only.peek() {
+                    bridge.clear();
+                    app_state.log.clear();
+                }
                 // Clear terminal instance when leaving Terminal mode to ensure re-attachment
                 term_instance.set(None);
             }
@@ -1,9 +1,25 @@
 use dioxus::prelude::*;
 
 #[component]
-pub fn ConsoleFrame(children: Element) -> Element {
+pub fn ConsoleFrame(
+    children: Element,
+    /// Optional handler for files dropped onto the console
+    ondrop: Option<EventHandler<DragEvent>>,
+) -> Element {
     rsx! {
-        main { class: "flex-1 min-h-0 mx-4 mb-0 mt-0 relative group/console",
+        main {
+            class: "flex-1 min-h-0 mx-4 mb-0 mt-0 relative group/console",
+            ondragover: move |evt: DragEvent| {
+                if ondrop.is_some() {
+                    evt.prevent_default();
+                }
+            },
+            ondrop: move |evt: DragEvent| {
+                if let Some(handler) = ondrop {
+                    evt.prevent_default();
+                    handler.call(evt);
+                }
+            },
             div { class: "absolute inset-0 bg-console-bg rounded-t-2xl border-t border-x border-[#222629] shadow-[inset_0_0_20px_rgba(0,0,0,0.8)] overflow-hidden flex flex-col",
                 div { class: "absolute inset-0 scanlines opacity-20 pointer-events-none z-10" }
                 {children}
@@ -131,6 +147,10 @@ pub fn UnifiedConsoleToolbar(
     on_clear: EventHandler<MouseEvent>,
     /// Optional handler for exporting logs (if None, button is hidden)
     on_export: Option<EventHandler<MouseEvent>>,
+    /// Optional handler for importing a log file (if None, button is hidden)
+    on_import: Option<EventHandler<MouseEvent>>,
+    /// Optional handler for exporting the raw byte capture (if None, button is hidden)
+    on_export_raw: Option<EventHandler<MouseEvent>>,
     /// Min font size
     #[props(default = 8)]
     min_font_size: u32,
@@ -158,6 +178,14 @@ pub fn UnifiedConsoleToolbar(
                     onclick: move |evt| on_clear.call(evt),
                     hover_color_class: "hover:text-red-500",
                 }
+                if let Some(import_handler) = on_import {
+                    ConsoleActionButton {
+                        icon: "upload_file",
+                        title: "Import Log File (.log / .bin)",
+                        onclick: move |evt| import_handler.call(evt),
+                        hover_color_class: "hover:text-primary",
+                    }
+                }
                 if let Some(export_handler) = on_export {
                     ConsoleActionButton {
                         icon: "download",
@@ -166,6 +194,14 @@ pub fn UnifiedConsoleToolbar(
                         hover_color_class: "hover:text-primary",
                     }
                 }
+                if let Some(export_raw_handler) = on_export_raw {
+                    ConsoleActionButton {
+                        icon: "memory",
+                        title: "Export Raw Capture (.bin)",
+                        onclick: move |evt| export_raw_handler.call(evt),
+                        hover_color_class: "hover:text-primary",
+                    }
+                }
             },
         }
     }
@@ -1,11 +1,24 @@
 /// --- Networking & Buffer Config ---
 pub const READ_BUFFER_SIZE: usize = 64 * 1024;
 pub const EXPORT_CHUNK_SIZE: u64 = 64 * 1024;
+pub const IMPORT_CHUNK_SIZE: u64 = 256 * 1024;
 pub const MAX_LINE_BYTES: usize = 256;
+/// Bytes per file moved down between messages after the ring buffer drops old lines
+pub const COMPACT_STEP_BYTES: u64 = 4 * 1024 * 1024;
+/// Memory the search index may use; the oldest blocks are released beyond it
+pub const SEARCH_INDEX_MAX_BYTES: usize = 32 * 1024 * 1024;
+/// Blocks of stored lines added to the search index between messages
+pub const SEARCH_INDEX_STEP_BLOCKS: usize = 8;
+/// Most lines of context the filter shows before or after a match
+pub const MAX_CONTEXT_LINES: usize = 1000;
 
 /// --- UI Timing & Intervals ---
 pub const TOAST_DURATION_MS: u32 = 3000;
 pub const WORKER_UPDATE_INTERVAL_MS: u32 = 16;
+pub const STORAGE_ESTIMATE_INTERVAL_MS: u32 = 2000;
+/// How often appended data is flushed to OPFS; at most this much is lost on a crash
+pub const FLUSH_INTERVAL_MS: u32 = 1000;
+pub const LOW_SPACE_MIN_BYTES: u64 = 64 * 1024 * 1024;
 pub const APP_SUBTITLE: &str = "RusTerm v3.0.0";
 
 /// --- Layout & Virtual Scroll ---
@@ -15,6 +28,8 @@ pub const BOTTOM_BUFFER_EXTRA: usize = 40;
 pub const CONSOLE_TOP_PADDING: f64 = 8.0; // 0.5rem
 pub const CONSOLE_BOTTOM_PADDING: f64 = 20.0;
 pub const VIRTUAL_SCROLL_THRESHOLD: f64 = 10_000_000.0;
+/// Width of a field column of the structured view, in characters
+pub const JSON_COLUMN_WIDTH_CH: usize = 18;
 
 /// Calculate line height from font size (font_size * 1.4 for readable spacing)
 pub fn line_height_from_font(font_size: u32) -> f64 {
@@ -1,5 +1,5 @@
 use crate::hooks::{use_worker_controller, WorkerController};
-use crate::state::AppState;
+use crate::state::{AppState, SessionMetadata};
 use dioxus::prelude::*;
 use gloo_timers::future::TimeoutFuture;
 use wasm_bindgen::JsCast;
@@ -62,7 +62,18 @@ impl SerialController {
                 return;
             };
 
-            bridge.new_session();
+            let (usb_vendor_id, usb_product_id) = crate::utils::serial_api::usb_ids(&port);
+            bridge.new_session(SessionMetadata {
+                baud_rate: (state.serial.baud_rate)(),
+                data_bits: (state.serial.data_bits)(),
+                stop_bits: (state.serial.stop_bits)(),
+                parity: (state.serial.parity)(),
+                flow_control: (state.serial.flow_control)(),
+                usb_vendor_id,
+                usb_product_id,
+                started_at: chrono::Utc::now().timestamp_millis(),
+                ..Default::default()
+            });
 
             // Start the read task explicitly
             start_read_task(state, bridge, port);
@@ -81,8 +92,9 @@ impl SerialController {
         // Lock immediately to prevent double-click
         state.conn.set_busy(true);
 
+        let bridge = self.bridge;
         spawn(async move {
-            cleanup_serial_connection(state).await;
+            cleanup_serial_connection(state, bridge).await;
             state.info("Disconnected");
             state.conn.set_busy(false);
         });
@@ -102,7 +114,7 @@ impl SerialController {
 }
 
 // Helper to cleanup serial connection (Reader + Port) safely
-async fn cleanup_serial_connection(state: AppState) {
+async fn cleanup_serial_connection(state: AppState, bridge: WorkerController) {
     // Note: Caller must have set busy=true before calling this
 
     // Yield to let UI update and previous events settle
@@ -141,6 +153,7 @@ async fn cleanup_serial_connection(state: AppState) {
 
     // 4. Final State Reset
     state.conn.set_connected(None, None);
+    bridge.end_session();
     // state.conn.set_busy(false); // Caller is now responsible for setting busy to false
 }
 
@@ -196,7 +209,7 @@ fn start_read_task(state: AppState, bridge: WorkerController, port: web_sys::Ser
                     if !(state.conn.is_busy)() {
                         state.conn.set_busy(true);
                         state.info("Connection Closed");
-                        cleanup_serial_connection(state).await;
+                        cleanup_serial_connection(state, bridge).await;
                         state.conn.set_busy(false); // Release busy lock
                     }
                 }
@@ -205,7 +218,7 @@ fn start_read_task(state: AppState, bridge: WorkerController, port: web_sys::Ser
                 if !(state.conn.is_busy)() {
                     state.conn.set_busy(true);
                     state.error(&format!("Connection Lost: {}", msg));
-                    cleanup_serial_connection(state).await;
+                    cleanup_serial_connection(state, bridge).await;
                     state.conn.set_busy(false); // Release busy lock
                 }
             }
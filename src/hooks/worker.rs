@@ -1,6 +1,12 @@
 use crate::state::AppState;
-use crate::types::WorkerMsg;
-use crate::utils::{send_chunk_to_worker, send_worker_msg};
+use crate::types::{
+    Highlight, LogLevel, LowSpacePolicy, RxEncoding, SearchIndexSize, SearchProgress, SessionLimit,
+    SessionMetadata, SortSpec, WorkerMsg,
+};
+use crate::utils::{
+    filter_error, format_bytes, send_chunk_to_worker, send_file_to_worker, send_worker_msg,
+    with_field_filters,
+};
 use dioxus::prelude::*;
 use wasm_bindgen::prelude::Closure;
 use wasm_bindgen::JsCast;
@@ -25,15 +31,32 @@ impl WorkerController {
         self.send(WorkerMsg::Clear);
     }
 
-    pub fn search(&self, query: String, match_case: bool, use_regex: bool, invert: bool) {
+    /// Filters the view; `context` is the number of lines shown before and after each match.
+    /// With `min_level`, only lines at that level or more severe are shown.
+    pub fn search(
+        &self,
+        query: String,
+        match_case: bool,
+        use_regex: bool,
+        invert: bool,
+        context: (usize, usize),
+        min_level: Option<LogLevel>,
+    ) {
         self.send(WorkerMsg::SearchLogs {
             query,
             match_case,
             use_regex,
             invert,
+            context_before: context.0,
+            context_after: context.1,
+            min_level,
         });
     }
 
+    pub fn cancel_search(&self) {
+        self.send(WorkerMsg::CancelSearch);
+    }
+
     pub fn export(&self, include_timestamp: bool) {
         self.send(WorkerMsg::ExportLogs { include_timestamp });
     }
@@ -44,6 +67,14 @@ impl WorkerController {
         }
     }
 
+    /// Imports a `.log` file as text, or a `.bin` capture through the hex view
+    pub fn import_file(&self, file: &web_sys::File) {
+        let is_hex = file.name().to_lowercase().ends_with(".bin");
+        if let Some(w) = self.worker_sig.read().as_ref() {
+            send_file_to_worker(w, file, is_hex);
+        }
+    }
+
     pub fn set_timestamp_state(&self, enabled: bool) {
         self.send(WorkerMsg::SetTimestampState(enabled));
     }
@@ -52,13 +83,114 @@ impl WorkerController {
         self.send(WorkerMsg::RequestWindow { start_line, count });
     }
 
-    pub fn new_session(&self) {
-        self.send(WorkerMsg::NewSession);
+    pub fn new_session(&self, metadata: SessionMetadata) {
+        self.send(WorkerMsg::StartSession(metadata));
+    }
+
+    pub fn export_raw(&self) {
+        self.send(WorkerMsg::ExportRaw);
+    }
+
+    pub fn request_raw_line(&self, line: usize) {
+        self.send(WorkerMsg::RequestRawLine(line));
+    }
+
+    pub fn add_bookmark(&self, line: usize, note: String) {
+        self.send(WorkerMsg::AddBookmark { line, note });
+    }
+
+    pub fn remove_bookmark(&self, line: usize) {
+        self.send(WorkerMsg::RemoveBookmark(line));
+    }
+
+    pub fn list_bookmarks(&self) {
+        self.send(WorkerMsg::ListBookmarks);
+    }
+
+    /// Clears the filter and scrolls to a line by its absolute number
+    pub fn reveal_line(&self, number: usize) {
+        self.send(WorkerMsg::RevealLine(number));
+    }
+
+    pub fn seek_bookmark(&self, from: usize, forward: bool) {
+        self.send(WorkerMsg::SeekBookmark { from, forward });
+    }
+
+    pub fn find(&self, query: String, match_case: bool, use_regex: bool) {
+        self.send(WorkerMsg::Find {
+            query,
+            match_case,
+            use_regex,
+        });
+    }
+
+    pub fn find_next(&self, from: usize, occurrence: Option<usize>, forward: bool) {
+        self.send(WorkerMsg::FindNext {
+            from,
+            occurrence,
+            forward,
+        });
+    }
+
+    pub fn set_session_limit(&self, limit: SessionLimit) {
+        self.send(WorkerMsg::SetSessionLimit(limit));
+    }
+
+    pub fn set_low_space_policy(&self, policy: LowSpacePolicy) {
+        self.send(WorkerMsg::SetLowSpacePolicy(policy));
+    }
+
+    /// Rules the worker applies to the lines it sends; empty turns highlighting off
+    pub fn set_highlights(&self, rules: Vec<Highlight>) {
+        self.send(WorkerMsg::SetHighlights(rules));
+    }
+
+    /// Encoding of received text, recorded on the session being captured
+    pub fn set_encoding(&self, encoding: RxEncoding) {
+        self.send(WorkerMsg::SetEncoding(encoding));
+    }
+
+    /// Fields the worker extracts from JSON lines; empty ends the structured view
+    pub fn set_json_columns(&self, columns: Vec<String>) {
+        self.send(WorkerMsg::SetJsonColumns(columns));
+    }
+
+    /// Orders the view by a JSON field; `None` restores the log order
+    pub fn sort_by(&self, sort: Option<SortSpec>) {
+        self.send(WorkerMsg::SortBy(sort));
+    }
+
+    pub fn set_search_index(&self, size: SearchIndexSize) {
+        self.send(WorkerMsg::SetSearchIndex(size));
+    }
+
+    pub fn end_session(&self) {
+        self.send(WorkerMsg::EndSession);
     }
 
     pub fn set_mode(&self, mode: crate::state::ViewMode) {
         self.send(WorkerMsg::SetMode(mode));
     }
+
+    pub fn list_sessions(&self) {
+        self.send(WorkerMsg::ListSessions);
+    }
+
+    pub fn open_session(&self, name: String) {
+        self.send(WorkerMsg::OpenSession(name));
+    }
+
+    pub fn rename_session(&self, name: String, label: String) {
+        self.send(WorkerMsg::RenameSession { name, label });
+    }
+
+    pub fn delete_session(&self, name: String) {
+        self.send(WorkerMsg::DeleteSession(name));
+    }
+
+    pub fn download_session(&self, name: String) {
+        self.send(WorkerMsg::DownloadSession(name));
+    }
 }
 
 pub fn use_worker_controller() -> WorkerController {
@@ -84,6 +216,7 @@ pub fn use_worker_controller() -> WorkerController {
 fn setup_worker_message_handler(worker: &web_sys::Worker, state: AppState) {
     let mut tl = state.log.total_lines;
     let mut vl = state.log.visible_logs;
+    let worker_handle = worker.clone();
 
     let callback = Closure::wrap(Box::new(move |event: web_sys::MessageEvent| {
         let data = event.data();
@@ -93,7 +226,11 @@ fn setup_worker_message_handler(worker: &web_sys::Worker, state: AppState) {
             if let Ok(msg_type) = js_sys::Reflect::get(&obj, &"type".into()) {
                 if msg_type.as_string() == Some("EXPORT_STREAM".to_string()) {
                     if let Ok(stream) = js_sys::Reflect::get(&obj, &"stream".into()) {
-                        crate::utils::file_save::save_stream_to_disk(stream);
+                        let name = js_sys::Reflect::get(&obj, &"name".into())
+                            .ok()
+                            .and_then(|v| v.as_string())
+                            .unwrap_or_else(|| "serial_log.txt".to_string());
+                        crate::utils::file_save::save_stream_to_disk(stream, &name);
                         return;
                     }
                 }
@@ -103,6 +240,8 @@ fn setup_worker_message_handler(worker: &web_sys::Worker, state: AppState) {
         // 2. Handle Structured Messages (WorkerMsg)
         if let Some(msg_str) = data.as_string() {
             if let Ok(msg) = serde_json::from_str::<WorkerMsg>(&msg_str) {
+                let is_current_search =
+                    |id: u32| state.log.search_progress.peek().is_none_or(|p| p.id <= id);
                 match msg {
                     WorkerMsg::TotalLines(count) => {
                         tl.set(count);
@@ -110,8 +249,71 @@ fn setup_worker_message_handler(worker: &web_sys::Worker, state: AppState) {
                             vl.set(Vec::new());
                         }
                     }
-                    WorkerMsg::LogWindow { lines, .. } => {
+                    WorkerMsg::LevelCounts(counts) => {
+                        { state.log.level_counts }.set(counts);
+                    }
+                    WorkerMsg::LogWindow {
+                        lines,
+                        line_numbers,
+                        bookmarks,
+                        context,
+                        gaps,
+                        rows,
+                        json_fields,
+                        ..
+                    } => {
                         vl.set(lines);
+                        { state.log.visible_rows }.set(rows);
+                        if !json_fields.is_empty() {
+                            { state.log.json_fields }.set(json_fields);
+                        }
+                        { state.log.visible_line_numbers }.set(line_numbers);
+                        { state.log.visible_bookmarks }.set(bookmarks);
+                        { state.log.visible_context }.set(context);
+                        { state.log.visible_gaps }.set(gaps);
+                    }
+                    WorkerMsg::SortRunning(running) => {
+                        { state.log.sort_running }.set(running);
+                    }
+                    WorkerMsg::BookmarkList(bookmarks) => {
+                        { state.log.bookmarks }.set(bookmarks);
+                    }
+                    WorkerMsg::ScrollToLine(line) => {
+                        { state.log.focused_line }.set(Some(line));
+                        { state.log.scroll_target }.set(Some(line));
+                    }
+                    // Reports of a superseded search may still be queued
+                    WorkerMsg::SearchProgress(progress) if is_current_search(progress.id) => {
+                        { state.log.search_progress }.set(Some(progress));
+                        { state.log.search_running }.set(true);
+                    }
+                    WorkerMsg::SearchDone {
+                        id,
+                        matches,
+                        completed,
+                    } => {
+                        let current = *state.log.search_progress.peek();
+                        if let Some(progress) = current.filter(|p| p.id == id) {
+                            { state.log.search_progress }.set(Some(SearchProgress {
+                                matches,
+                                scanned: if completed {
+                                    progress.total
+                                } else {
+                                    progress.scanned
+                                },
+                                ..progress
+                            }));
+                            { state.log.search_running }.set(false);
+                        }
+                    }
+                    WorkerMsg::SearchError { id, message } if is_current_search(id) => {
+                        { state.log.search_error }.set(Some(message));
+                        { state.log.search_running }.set(false);
+                    }
+                    WorkerMsg::FindMatch(hit) => {
+                        { state.log.find_match }.set(Some(hit));
+                        { state.log.focused_line }.set(Some(hit.line));
+                        { state.log.scroll_target }.set(Some(hit.line));
                     }
                     WorkerMsg::Error(msg) => {
                         state.error(&format!("Worker Error: {}", msg));
@@ -119,6 +321,74 @@ fn setup_worker_message_handler(worker: &web_sys::Worker, state: AppState) {
                     WorkerMsg::ActiveLine(line) => {
                         { state.log.active_line }.set(line);
                     }
+                    WorkerMsg::RawLine(raw) => {
+                        { state.log.raw_line }.set(Some(raw));
+                    }
+                    WorkerMsg::StorageUsage(usage) => {
+                        let was_low = state.log.storage_usage.peek().is_some_and(|u| u.is_low());
+                        if usage.is_low() && !was_low {
+                            state.warning(&format!(
+                                "Storage almost full: {} free",
+                                format_bytes(usage.free())
+                            ));
+                        }
+                        { state.log.storage_usage }.set(Some(usage));
+                    }
+                    WorkerMsg::Notice(msg) => {
+                        state.warning(&msg);
+                    }
+                    WorkerMsg::ImportProgress { read, total } => {
+                        { state.log.import_progress }.set(Some((read, total)));
+                    }
+                    WorkerMsg::ImportDone { lines, completed } => {
+                        { state.log.import_progress }.set(None);
+                        if completed {
+                            state.success(&format!("Imported {} lines", lines));
+                        }
+                    }
+                    WorkerMsg::SessionList(sessions) => {
+                        { state.log.sessions }.set(sessions);
+                    }
+                    WorkerMsg::SessionOpened { name, read_only } => {
+                        { state.log.current_session }.set(Some(name));
+                        { state.log.read_only }.set(read_only);
+                        { state.log.active_line }.set(None);
+                        vl.set(Vec::new());
+                        { state.log.focused_line }.set(None);
+                        { state.log.find_match }.set(None);
+                        send_worker_msg(&worker_handle, WorkerMsg::ListBookmarks);
+
+                        // The worker drops its filter when switching files; re-apply ours
+                        let query = state.log.filter_query.peek().clone();
+                        let match_case = *state.log.match_case.peek();
+                        let use_regex = *state.log.use_regex.peek();
+                        let min_level = *state.log.min_level.peek();
+                        let valid = filter_error(&query, match_case, use_regex).is_none();
+                        let field_filters = if *state.ui.structured_view.peek() {
+                            state.log.field_filters.peek().clone()
+                        } else {
+                            Vec::new()
+                        };
+                        let (query, invert) = with_field_filters(
+                            &query,
+                            *state.log.invert_filter.peek(),
+                            &field_filters,
+                        );
+                        if (!query.trim().is_empty() || min_level.is_some()) && valid {
+                            send_worker_msg(
+                                &worker_handle,
+                                WorkerMsg::SearchLogs {
+                                    query,
+                                    match_case,
+                                    use_regex,
+                                    invert,
+                                    context_before: *state.log.context_before.peek(),
+                                    context_after: *state.log.context_after.peek(),
+                                    min_level,
+                                },
+                            );
+                        }
+                    }
                     _ => {}
                 }
             }
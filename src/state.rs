@@ -9,10 +9,13 @@ pub struct UIState {
     pub show_settings: Signal<bool>,
     pub show_highlights: Signal<bool>,
     pub show_timestamps: Signal<bool>,
+    pub show_line_numbers: Signal<bool>,
     pub autoscroll: Signal<bool>,
     pub is_hex_view: Signal<bool>,
     pub view_mode: Signal<ViewMode>,
     pub font_size: Signal<u32>,
+    /// Shows JSON lines as a table of their field columns
+    pub structured_view: Signal<bool>,
 }
 
 #[derive(Clone, Copy)]
@@ -22,6 +25,7 @@ pub struct SerialSettings {
     pub stop_bits: Signal<u8>,
     pub parity: Signal<Parity>,
     pub flow_control: Signal<FlowControl>,
+    pub rx_encoding: Signal<RxEncoding>,
 
     pub tx_line_ending: Signal<LineEnding>,
     pub tx_local_echo: Signal<bool>,
@@ -40,14 +44,69 @@ pub struct ConnectionState {
 #[derive(Clone, Copy)]
 pub struct LogState {
     pub total_lines: Signal<usize>,
-    pub visible_logs: Signal<Vec<(usize, String)>>,
+    pub visible_logs: Signal<Vec<(usize, StyledLine)>>,
+    /// Absolute line number of each of `visible_logs`
+    pub visible_line_numbers: Signal<Vec<usize>>,
     pub filter_query: Signal<String>,
     pub match_case: Signal<bool>,
     pub use_regex: Signal<bool>,
     pub invert_filter: Signal<bool>,
+    /// Lines shown before each filter match
+    pub context_before: Signal<usize>,
+    /// Lines shown after each filter match
+    pub context_after: Signal<usize>,
+    /// Level filter: only lines this severe or more are shown
+    pub min_level: Signal<Option<LogLevel>>,
+    /// Session lines at each detected level
+    pub level_counts: Signal<LevelCounts>,
+    /// Latest report of the filter scan; cleared with the filter
+    pub search_progress: Signal<Option<SearchProgress>>,
+    /// Set while the worker is scanning for the filter
+    pub search_running: Signal<bool>,
+    /// Why the worker could not run the filter
+    pub search_error: Signal<Option<String>>,
     pub highlights: Signal<Vec<Highlight>>,
     pub toasts: Signal<Vec<ToastMessage>>,
-    pub active_line: Signal<Option<String>>,
+    pub active_line: Signal<Option<StyledLine>>,
+    pub sessions: Signal<Vec<SessionInfo>>,
+    pub current_session: Signal<Option<String>>,
+    pub read_only: Signal<bool>,
+    pub session_limit: Signal<SessionLimit>,
+    pub low_space_policy: Signal<LowSpacePolicy>,
+    pub search_index: Signal<SearchIndexSize>,
+    /// Latest OPFS usage reported by the worker
+    pub storage_usage: Signal<Option<StorageUsage>>,
+    /// Raw bytes of the line picked in the monitor
+    pub raw_line: Signal<Option<RawLine>>,
+    /// Bytes read and total size of the file being imported
+    pub import_progress: Signal<Option<(u64, u64)>>,
+    /// Bookmarks of the open session, sorted by line
+    pub bookmarks: Signal<Vec<Bookmark>>,
+    /// Bookmarks on the lines of `visible_logs`, by view index
+    pub visible_bookmarks: Signal<Vec<(usize, Bookmark)>>,
+    /// View index the monitor should scroll to
+    pub scroll_target: Signal<Option<usize>>,
+    /// View index last reached by navigation; highlighted in the monitor
+    pub focused_line: Signal<Option<usize>>,
+    /// Find-in-log expression; uses the match case and regex options of the filter
+    pub find_query: Signal<String>,
+    /// Find match last reached by navigation
+    pub find_match: Signal<Option<FindMatch>>,
+    /// View indices in `visible_logs` shown only as context of a filter match
+    pub visible_context: Signal<Vec<usize>>,
+    /// View indices in `visible_logs` that follow a gap in the filtered view
+    pub visible_gaps: Signal<Vec<usize>>,
+    /// JSON fields shown as columns of the structured view
+    pub json_columns: Signal<Vec<String>>,
+    /// Cells of each of `visible_logs`; `None` for lines that are not JSON
+    pub visible_rows: Signal<Vec<Option<JsonRow>>>,
+    /// Fields of the JSON lines in the window, offered as new columns
+    pub json_fields: Signal<Vec<String>>,
+    /// Filter typed for a column, by field; combined with the text filter
+    pub field_filters: Signal<Vec<(String, String)>>,
+    pub sort: Signal<Option<SortSpec>>,
+    /// Set while the worker is reading the sort keys of the view
+    pub sort_running: Signal<bool>,
 }
 
 #[derive(Clone, Copy)]
@@ -77,6 +136,9 @@ impl UIState {
     pub fn toggle_timestamps(&self) {
         { self.show_timestamps }.toggle();
     }
+    pub fn toggle_line_numbers(&self) {
+        { self.show_line_numbers }.toggle();
+    }
     pub fn toggle_autoscroll(&self) {
         { self.autoscroll }.toggle();
     }
@@ -89,6 +151,9 @@ impl UIState {
     pub fn set_view_mode(&self, mode: ViewMode) {
         { self.view_mode }.set(mode);
     }
+    pub fn toggle_structured_view(&self) {
+        { self.structured_view }.toggle();
+    }
 }
 
 impl SerialSettings {
@@ -107,6 +172,9 @@ impl SerialSettings {
     pub fn set_flow_control(&self, f: FlowControl) {
         { self.flow_control }.set(f);
     }
+    pub fn set_rx_encoding(&self, encoding: RxEncoding) {
+        { self.rx_encoding }.set(encoding);
+    }
 }
 
 impl ConnectionState {
@@ -135,9 +203,25 @@ impl ConnectionState {
 }
 
 impl LogState {
+    pub fn set_session_limit(&self, limit: SessionLimit) {
+        { self.session_limit }.set(limit);
+    }
+
+    pub fn set_low_space_policy(&self, policy: LowSpacePolicy) {
+        { self.low_space_policy }.set(policy);
+    }
+
+    pub fn set_search_index(&self, size: SearchIndexSize) {
+        { self.search_index }.set(size);
+    }
+
     pub fn clear(&self) {
         { self.total_lines }.set(0);
         { self.visible_logs }.set(Vec::new());
+        { self.visible_line_numbers }.set(Vec::new());
+        { self.visible_rows }.set(Vec::new());
+        { self.bookmarks }.set(Vec::new());
+        { self.visible_bookmarks }.set(Vec::new());
     }
 
     pub fn add_toast(&self, message: &str, type_: ToastType) {
@@ -156,19 +240,74 @@ impl LogState {
         });
     }
 
-    pub fn add_highlight(&self, text: String, color: &'static str) {
+    pub fn add_highlight(&self, rule: Highlight) {
+        self.add_highlights(vec![rule]);
+    }
+
+    /// Appends rules with fresh ids and saves the list
+    pub fn add_highlights(&self, rules: Vec<Highlight>) {
         let mut highlights = self.highlights;
         let mut list = highlights.write();
-        let next_id = list.last().map(|h| h.id).unwrap_or(0) + 1;
-        list.push(Highlight {
-            id: next_id,
-            text,
-            color,
-        });
+        for rule in rules {
+            let id = list.iter().map(|h| h.id).max().unwrap_or(0) + 1;
+            list.push(Highlight { id, ..rule });
+        }
+        crate::utils::HighlightRules::save(&list);
     }
 
     pub fn remove_highlight(&self, id: usize) {
-        { self.highlights }.write().retain(|h| h.id != id);
+        let mut highlights = self.highlights;
+        let mut list = highlights.write();
+        list.retain(|h| h.id != id);
+        crate::utils::HighlightRules::save(&list);
+    }
+
+    pub fn add_json_column(&self, field: &str) {
+        let field = field.trim();
+        let mut columns = self.json_columns;
+        let mut list = columns.write();
+        if field.is_empty() || list.iter().any(|c| c == field) {
+            return;
+        }
+        list.push(field.to_string());
+        crate::utils::JsonColumns::save(&list);
+    }
+
+    /// Drops the column with its filter, and the sort by it
+    pub fn remove_json_column(&self, field: &str) {
+        let mut columns = self.json_columns;
+        let mut list = columns.write();
+        list.retain(|c| c != field);
+        crate::utils::JsonColumns::save(&list);
+        { self.field_filters }.write().retain(|(f, _)| f != field);
+        if self.sort.peek().as_ref().is_some_and(|s| s.field == field) {
+            { self.sort }.set(None);
+        }
+    }
+
+    pub fn set_field_filter(&self, field: &str, input: String) {
+        let mut filters = self.field_filters;
+        let mut list = filters.write();
+        list.retain(|(f, _)| f != field);
+        if !input.trim().is_empty() {
+            list.push((field.to_string(), input));
+        }
+    }
+
+    /// Ascending, descending, then back to the log order
+    pub fn cycle_sort(&self, field: &str) {
+        let next = match self.sort.peek().as_ref() {
+            Some(s) if s.field == field && !s.descending => Some(SortSpec {
+                field: field.to_string(),
+                descending: true,
+            }),
+            Some(s) if s.field == field => None,
+            _ => Some(SortSpec {
+                field: field.to_string(),
+                descending: false,
+            }),
+        };
+        { self.sort }.set(next);
     }
 }
 
@@ -198,12 +337,14 @@ pub fn use_provide_app_state() -> AppState {
     let app_state = AppState {
         ui: UIState {
             show_settings: use_signal(|| false),
-            show_highlights: use_signal(|| false),
+            show_highlights: use_signal(|| true),
             show_timestamps: use_signal(|| false),
+            show_line_numbers: use_signal(|| true),
             autoscroll: use_signal(|| true),
             is_hex_view: use_signal(|| false),
             view_mode: use_signal(|| ViewMode::Monitoring),
             font_size: use_signal(|| 14),
+            structured_view: use_signal(|| false),
         },
         serial: SerialSettings {
             baud_rate: use_signal(|| 115200u32),
@@ -211,6 +352,7 @@ pub fn use_provide_app_state() -> AppState {
             stop_bits: use_signal(|| 1u8),
             parity: use_signal(|| Parity::None),
             flow_control: use_signal(|| FlowControl::None),
+            rx_encoding: use_signal(RxEncoding::default),
 
             tx_line_ending: use_signal(|| LineEnding::None),
             tx_local_echo: use_signal(|| false),
@@ -225,14 +367,45 @@ pub fn use_provide_app_state() -> AppState {
         },
         log: LogState {
             total_lines: use_signal(|| 0usize),
-            visible_logs: use_signal(Vec::<(usize, String)>::new),
+            visible_logs: use_signal(Vec::<(usize, StyledLine)>::new),
+            visible_line_numbers: use_signal(Vec::new),
             filter_query: use_signal(String::new),
             match_case: use_signal(|| false),
             use_regex: use_signal(|| false),
             invert_filter: use_signal(|| false),
-            highlights: use_signal(Vec::new),
+            context_before: use_signal(|| 0),
+            context_after: use_signal(|| 0),
+            min_level: use_signal(|| None),
+            level_counts: use_signal(LevelCounts::default),
+            search_progress: use_signal(|| None),
+            search_running: use_signal(|| false),
+            search_error: use_signal(|| None),
+            highlights: use_signal(crate::utils::HighlightRules::load),
             toasts: use_signal(Vec::new),
             active_line: use_signal(|| None),
+            sessions: use_signal(Vec::new),
+            current_session: use_signal(|| None),
+            read_only: use_signal(|| false),
+            session_limit: use_signal(SessionLimit::default),
+            low_space_policy: use_signal(LowSpacePolicy::default),
+            search_index: use_signal(SearchIndexSize::default),
+            storage_usage: use_signal(|| None),
+            raw_line: use_signal(|| None),
+            import_progress: use_signal(|| None),
+            bookmarks: use_signal(Vec::new),
+            visible_bookmarks: use_signal(Vec::new),
+            scroll_target: use_signal(|| None),
+            focused_line: use_signal(|| None),
+            find_query: use_signal(String::new),
+            find_match: use_signal(|| None),
+            visible_context: use_signal(Vec::new),
+            visible_gaps: use_signal(Vec::new),
+            json_columns: use_signal(crate::utils::JsonColumns::load),
+            visible_rows: use_signal(Vec::new),
+            json_fields: use_signal(Vec::new),
+            field_filters: use_signal(Vec::new),
+            sort: use_signal(|| None),
+            sort_running: use_signal(|| false),
         },
         terminal: TerminalState {
             received_data: use_signal(Vec::new),
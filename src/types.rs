@@ -1,11 +1,91 @@
+use crate::config::LOW_SPACE_MIN_BYTES;
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
-#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+/// A highlight rule; `text` is a literal or, with `regex`, a regular expression
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
 pub struct Highlight {
+    #[serde(default)]
     pub id: usize,
     pub text: String,
-    pub color: &'static str,
+    /// Name from `HIGHLIGHT_COLORS`
+    pub color: String,
+    #[serde(default)]
+    pub regex: bool,
+    #[serde(default)]
+    pub match_case: bool,
+    #[serde(default)]
+    pub style: HighlightStyle,
+}
+
+/// A run of line text drawn in one style, decoded by the worker
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct StyledSpan {
+    pub text: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub background: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub bold: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub italic: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub underline: bool,
+    /// Find match covering the span, by position among the matches of the line
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mark: Option<usize>,
+}
+
+impl StyledSpan {
+    /// Unstyled text
+    pub fn plain(text: &str) -> Self {
+        Self {
+            text: text.to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+/// A line ready to render: its spans and the color of a whole-line highlight
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct StyledLine {
+    pub spans: Vec<StyledSpan>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub background: Option<String>,
+}
+
+impl StyledLine {
+    /// Text of the line without its styles
+    pub fn text(&self) -> String {
+        self.spans.iter().map(|s| s.text.as_str()).collect()
+    }
+}
+
+/// What a highlight rule colors
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum HighlightStyle {
+    /// Text of each match
+    #[default]
+    Foreground,
+    /// Background of each match
+    Background,
+    /// Background of every line with a match
+    Line,
+}
+
+impl HighlightStyle {
+    pub const ALL: [HighlightStyle; 3] = [Self::Foreground, Self::Background, Self::Line];
+}
+
+impl fmt::Display for HighlightStyle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Foreground => write!(f, "Text"),
+            Self::Background => write!(f, "Background"),
+            Self::Line => write!(f, "Line"),
+        }
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Debug, Default, Serialize, Deserialize)]
@@ -58,15 +138,388 @@ impl fmt::Display for FlowControl {
     }
 }
 
+/// What happens when a session reaches its size limit
+#[derive(Clone, Copy, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub enum SizeLimitPolicy {
+    /// Continue in a new session file
+    #[default]
+    Rollover,
+    /// Drop the oldest lines
+    Ring,
+}
+
+impl fmt::Display for SizeLimitPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SizeLimitPolicy::Rollover => write!(f, "Rollover"),
+            SizeLimitPolicy::Ring => write!(f, "Ring"),
+        }
+    }
+}
+
+/// Maximum size of a session file; `max_bytes == 0` means unlimited
+#[derive(Clone, Copy, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct SessionLimit {
+    pub max_bytes: u64,
+    pub policy: SizeLimitPolicy,
+}
+
+/// What the worker does when OPFS free space runs low
+#[derive(Clone, Copy, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub enum LowSpacePolicy {
+    /// Only warn
+    #[default]
+    Warn,
+    /// Stop recording received data until space is freed
+    Pause,
+    /// Delete the oldest stored sessions to make room; only on request, as
+    /// the deleted captures are gone for good
+    DeleteOldestSessions,
+}
+
+impl fmt::Display for LowSpacePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LowSpacePolicy::Warn => write!(f, "Warn"),
+            LowSpacePolicy::Pause => write!(f, "Pause"),
+            LowSpacePolicy::DeleteOldestSessions => write!(f, "Delete Oldest Sessions"),
+        }
+    }
+}
+
+/// Severity detected on a line at ingest, least severe first
+#[derive(
+    Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default, Serialize, Deserialize,
+)]
+pub enum LogLevel {
+    /// No level marker found
+    #[default]
+    None,
+    Verbose,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    pub const ALL: [LogLevel; 6] = [
+        LogLevel::None,
+        LogLevel::Verbose,
+        LogLevel::Debug,
+        LogLevel::Info,
+        LogLevel::Warn,
+        LogLevel::Error,
+    ];
+
+    /// Level stored as `byte`; unknown values read as `None`
+    pub fn from_byte(byte: u8) -> Self {
+        Self::ALL.get(byte as usize).copied().unwrap_or_default()
+    }
+
+    pub fn as_byte(self) -> u8 {
+        self as u8
+    }
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogLevel::None => write!(f, "None"),
+            LogLevel::Verbose => write!(f, "Verbose"),
+            LogLevel::Debug => write!(f, "Debug"),
+            LogLevel::Info => write!(f, "Info"),
+            LogLevel::Warn => write!(f, "Warn"),
+            LogLevel::Error => write!(f, "Error"),
+        }
+    }
+}
+
+/// Lines of the session per level, indexed by [`LogLevel::as_byte`]
+#[derive(Clone, Copy, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct LevelCounts(pub [usize; 6]);
+
+impl LevelCounts {
+    pub fn get(&self, level: LogLevel) -> usize {
+        self.0[level.as_byte() as usize]
+    }
+
+    /// Lines at `level` or more severe
+    pub fn at_least(&self, level: LogLevel) -> usize {
+        self.0[level.as_byte() as usize..].iter().sum()
+    }
+}
+
+/// Size of the per-block trigram filters that let a filter scan skip blocks
+/// without a match. Larger filters skip more blocks for more memory.
+#[derive(Clone, Copy, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub enum SearchIndexSize {
+    /// No index; every filter change scans the whole log
+    #[default]
+    Off,
+    /// 1 byte per distinct trigram of each 1024 lines; about 5% of blocks
+    /// without a match are still read
+    Small,
+    /// 2 bytes per distinct trigram; about 1.5% of blocks are still read
+    Large,
+}
+
+impl SearchIndexSize {
+    /// Bloom filter bits per distinct trigram of a block
+    pub fn bits_per_gram(self) -> usize {
+        match self {
+            SearchIndexSize::Off => 0,
+            SearchIndexSize::Small => 8,
+            SearchIndexSize::Large => 16,
+        }
+    }
+}
+
+impl fmt::Display for SearchIndexSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SearchIndexSize::Off => write!(f, "Off"),
+            SearchIndexSize::Small => write!(f, "Small"),
+            SearchIndexSize::Large => write!(f, "Large"),
+        }
+    }
+}
+
+/// Field values of a JSON line for the structured view, one per selected column
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct JsonRow {
+    /// Value as text; `None` where the line has no such field
+    pub cells: Vec<Option<String>>,
+}
+
+/// Field of JSON lines the view is sorted by
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct SortSpec {
+    pub field: String,
+    pub descending: bool,
+}
+
+/// Character encoding of received text. Stored text is always UTF-8;
+/// other encodings are decoded in the worker before terminal processing.
+#[derive(Clone, Copy, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub enum RxEncoding {
+    #[default]
+    Utf8,
+    /// ISO-8859-1, every byte is the code point of the same value
+    Latin1,
+    Windows1252,
+    /// IBM PC code page, with box drawing characters
+    Cp437,
+    ShiftJis,
+    EucJp,
+    EucKr,
+    Gbk,
+    Big5,
+}
+
+impl RxEncoding {
+    pub const ALL: [RxEncoding; 9] = [
+        Self::Utf8,
+        Self::Latin1,
+        Self::Windows1252,
+        Self::Cp437,
+        Self::ShiftJis,
+        Self::EucJp,
+        Self::EucKr,
+        Self::Gbk,
+        Self::Big5,
+    ];
+
+    /// Display name, as offered in settings
+    pub fn label(self) -> &'static str {
+        match self {
+            RxEncoding::Utf8 => "UTF-8",
+            RxEncoding::Latin1 => "Latin-1",
+            RxEncoding::Windows1252 => "Windows-1252",
+            RxEncoding::Cp437 => "CP437",
+            RxEncoding::ShiftJis => "Shift_JIS",
+            RxEncoding::EucJp => "EUC-JP",
+            RxEncoding::EucKr => "EUC-KR",
+            RxEncoding::Gbk => "GBK",
+            RxEncoding::Big5 => "Big5",
+        }
+    }
+
+    /// Encoding with the given display name
+    pub fn from_label(label: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|e| e.label() == label)
+    }
+}
+
+impl fmt::Display for RxEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.label())
+    }
+}
+
+/// Origin storage usage as reported by `navigator.storage.estimate()`
+#[derive(Clone, Copy, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct StorageUsage {
+    pub usage: u64,
+    pub quota: u64,
+    /// Set while capture is paused by [`LowSpacePolicy::Pause`]; received data is dropped
+    pub paused: bool,
+    /// Worker memory held by the line index of the open session
+    #[serde(default)]
+    pub index_bytes: u64,
+    /// Worker memory held by the search index of the open session
+    #[serde(default)]
+    pub search_index_bytes: u64,
+}
+
+impl StorageUsage {
+    pub fn free(&self) -> u64 {
+        self.quota.saturating_sub(self.usage)
+    }
+
+    /// Used share of the quota, 0.0 - 1.0
+    pub fn fraction(&self) -> f64 {
+        if self.quota == 0 {
+            0.0
+        } else {
+            self.usage as f64 / self.quota as f64
+        }
+    }
+
+    /// Free space is below 5% of the quota or [`LOW_SPACE_MIN_BYTES`]
+    pub fn is_low(&self) -> bool {
+        self.quota > 0 && self.free() < (self.quota / 20).max(LOW_SPACE_MIN_BYTES)
+    }
+}
+
+/// How a session was captured; stored next to the session log
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionMetadata {
+    pub baud_rate: u32,
+    pub data_bits: u8,
+    pub stop_bits: u8,
+    pub parity: Parity,
+    pub flow_control: FlowControl,
+    pub usb_vendor_id: Option<u16>,
+    pub usb_product_id: Option<u16>,
+    /// Connection time (ms since epoch)
+    pub started_at: i64,
+    /// Disconnection time (ms since epoch); `None` while capturing
+    pub ended_at: Option<i64>,
+    pub line_count: usize,
+    /// Name of the file the session was imported from; `None` for serial captures
+    pub imported_from: Option<String>,
+    /// Encoding the received bytes were decoded with
+    pub encoding: RxEncoding,
+    /// Lines dropped from the front by the ring buffer; line numbers count on from here
+    pub dropped_lines: usize,
+}
+
+impl SessionMetadata {
+    /// Port settings in the usual `115200 8N1` notation
+    pub fn port_summary(&self) -> String {
+        let parity = match self.parity {
+            Parity::None => 'N',
+            Parity::Even => 'E',
+            Parity::Odd => 'O',
+        };
+        format!(
+            "{} {}{}{}",
+            self.baud_rate, self.data_bits, parity, self.stop_bits
+        )
+    }
+
+    /// USB `vid:pid` in hex, if known
+    pub fn usb_id(&self) -> Option<String> {
+        match (self.usb_vendor_id, self.usb_product_id) {
+            (Some(vid), Some(pid)) => Some(format!("{:04x}:{:04x}", vid, pid)),
+            (Some(vid), None) => Some(format!("{:04x}", vid)),
+            _ => None,
+        }
+    }
+}
+
+/// A capture session stored in OPFS
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct SessionInfo {
+    pub name: String,
+    pub label: Option<String>,
+    pub created_at: i64,
+    pub size: u64,
+    /// The session currently receiving serial data
+    pub is_live: bool,
+    /// The session currently shown in the monitor
+    pub is_open: bool,
+    pub metadata: Option<SessionMetadata>,
+}
+
+/// A marked line of a session, with an optional note
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Bookmark {
+    /// Absolute line number in the session, counting lines the ring buffer
+    /// dropped; independent of the active filter
+    pub line: usize,
+    pub note: String,
+    /// Creation time (ms since epoch)
+    pub created_at: i64,
+}
+
+/// Bytes a monitor line was decoded from, as received
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct RawLine {
+    pub line: usize,
+    pub bytes: Vec<u8>,
+    /// Arrival time (ms since epoch) of the chunk holding the first byte
+    pub received_at: Option<i64>,
+}
+
+/// Find-in-log hit: a line of the current view and the character columns of
+/// one occurrence in the line as displayed
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct FindMatch {
+    /// View index of the line
+    pub line: usize,
+    /// Position of the occurrence among the matches of the line
+    pub occurrence: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// How far the filter scan started by `SearchLogs` has got
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct SearchProgress {
+    /// Search the report belongs to; later searches have larger ids
+    pub id: u32,
+    /// Lines scanned so far, out of the `total` stored when the search started
+    pub scanned: usize,
+    pub total: usize,
+    /// Matching lines found so far, without context lines
+    pub matches: usize,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(tag = "type", content = "data")]
 pub enum WorkerMsg {
-    NewSession,
+    StartSession(SessionMetadata),
+    EndSession,
     AppendChunk {
         chunk: Vec<u8>,
         is_hex: bool,
     },
     SetTimestampState(bool),
+    /// Highlight rules applied to the lines of each window; empty turns highlighting off
+    SetHighlights(Vec<Highlight>),
+    /// Encoding of received text, applied from the next chunk on
+    SetEncoding(RxEncoding),
+    /// Fields of JSON lines sent with each window; empty turns the structured view off
+    SetJsonColumns(Vec<String>),
+    /// Orders the view by a field of its JSON lines; `None` restores log order
+    SortBy(Option<SortSpec>),
+    /// Set while the worker reads the view to sort it
+    SortRunning(bool),
+    SetSessionLimit(SessionLimit),
 
     RequestWindow {
         start_line: usize,
@@ -74,20 +527,128 @@ pub enum WorkerMsg {
     },
     LogWindow {
         start_line: usize,
-        lines: Vec<(usize, String)>,
+        lines: Vec<(usize, StyledLine)>,
+        /// Absolute line number of each of `lines`, counting lines dropped by the ring buffer
+        #[serde(default)]
+        line_numbers: Vec<usize>,
+        /// Bookmarks on the lines in the window, by view index
+        #[serde(default)]
+        bookmarks: Vec<(usize, Bookmark)>,
+        /// View indices of lines shown only as context of a filter match
+        #[serde(default)]
+        context: Vec<usize>,
+        /// View indices of lines that do not follow the previous view line
+        #[serde(default)]
+        gaps: Vec<usize>,
+        /// Selected fields of each of `lines`, while the structured view is on;
+        /// `None` for lines that are not JSON objects
+        #[serde(default)]
+        rows: Vec<Option<JsonRow>>,
+        /// Field paths found in the JSON lines of the window
+        #[serde(default)]
+        json_fields: Vec<String>,
     },
     TotalLines(usize),
+    /// Running count of the session lines at each level
+    LevelCounts(LevelCounts),
     Clear,
     SearchLogs {
         query: String,
         match_case: bool,
         use_regex: bool,
         invert: bool,
+        /// Lines shown before each match
+        #[serde(default)]
+        context_before: usize,
+        /// Lines shown after each match
+        #[serde(default)]
+        context_after: usize,
+        /// Only lines at this level or more severe are shown
+        #[serde(default)]
+        min_level: Option<LogLevel>,
+    },
+    SearchProgress(SearchProgress),
+    SearchDone {
+        id: u32,
+        matches: usize,
+        /// Unset when the scan was cancelled before reaching the start of the log
+        completed: bool,
     },
+    /// The search could not run, e.g. its regex failed to compile
+    SearchError {
+        id: u32,
+        message: String,
+    },
+    /// Stops the running filter scan; matches found so far stay in the view
+    CancelSearch,
     ExportLogs {
         include_timestamp: bool,
     },
-    ActiveLine(Option<String>),
+    ExportRaw,
+    RequestRawLine(usize),
+    RawLine(RawLine),
+    /// Marks a line of the current view
+    AddBookmark {
+        line: usize,
+        note: String,
+    },
+    /// Removes the bookmark on a session line
+    RemoveBookmark(usize),
+    ListBookmarks,
+    BookmarkList(Vec<Bookmark>),
+    /// Looks for the next (or previous) bookmark visible in the current view
+    SeekBookmark {
+        from: usize,
+        forward: bool,
+    },
+    /// View index of a session line, for scrolling to it
+    ScrollToLine(usize),
+    /// Clears the filter and scrolls to a line given by its absolute number
+    RevealLine(usize),
+    /// Sets the find-in-log expression; an empty query clears it
+    Find {
+        query: String,
+        match_case: bool,
+        use_regex: bool,
+    },
+    /// Looks for the next (or previous) find match in the current view, starting
+    /// at line `from`; with `occurrence`, past that match of the line
+    FindNext {
+        from: usize,
+        occurrence: Option<usize>,
+        forward: bool,
+    },
+    FindMatch(FindMatch),
+    StorageUsage(StorageUsage),
+    /// Something the user should know about that is not an error
+    Notice(String),
+    SetLowSpacePolicy(LowSpacePolicy),
+    /// Resizes the search index; blocks indexed so far are dropped
+    SetSearchIndex(SearchIndexSize),
+    ImportProgress {
+        read: u64,
+        total: u64,
+    },
+    ImportDone {
+        lines: usize,
+        /// Unset when the import failed or was interrupted
+        completed: bool,
+    },
+    ActiveLine(Option<StyledLine>),
     SetMode(ViewMode),
     Error(String),
+
+    ListSessions,
+    SessionList(Vec<SessionInfo>),
+    OpenSession(String),
+    SessionOpened {
+        name: String,
+        read_only: bool,
+    },
+    RenameSession {
+        name: String,
+        label: String,
+    },
+    DeleteSession(String),
+    DownloadSession(String),
 }
@@ -2,6 +2,14 @@ use wasm_bindgen::prelude::*;
 
 #[wasm_bindgen(module = "/assets/js/file_save.js")]
 extern "C" {
-    pub fn save_stream_to_disk(stream: JsValue);
+    pub fn save_stream_to_disk(stream: JsValue, suggested_name: &str);
     pub fn save_terminal_history(terminal: &JsValue);
+    pub fn save_json_to_disk(content: &str, suggested_name: &str);
+}
+
+/// Reads a picked file as UTF-8 text
+pub async fn read_file_text(file: &web_sys::File) -> Result<String, JsValue> {
+    let text = wasm_bindgen_futures::JsFuture::from(file.text()).await?;
+    text.as_string()
+        .ok_or_else(|| JsValue::from_str("File is not text"))
 }
@@ -0,0 +1,234 @@
+use crate::worker::repository::index::ActiveFilterBuilder;
+use serde::{Deserialize, Serialize};
+use web_sys::window;
+
+const PRESETS_KEY: &str = "filter_presets";
+const MAX_RECENT: usize = 20;
+
+/// A filter expression with the options it was written for
+#[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq)]
+pub struct SavedFilter {
+    pub query: String,
+    #[serde(default)]
+    pub match_case: bool,
+    #[serde(default)]
+    pub use_regex: bool,
+    #[serde(default)]
+    pub invert: bool,
+}
+
+/// Parse error of a filter expression, checked before it is sent to the worker
+pub fn filter_error(query: &str, match_case: bool, use_regex: bool) -> Option<String> {
+    if query.trim().is_empty() {
+        return None;
+    }
+    ActiveFilterBuilder::new(query.to_string())
+        .case_sensitive(match_case)
+        .regex(use_regex)
+        .build()
+        .err()
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FilterPreset {
+    #[serde(default)]
+    pub id: u64,
+    pub name: String,
+    #[serde(flatten)]
+    pub filter: SavedFilter,
+}
+
+/// Named filter presets and the recently applied filters, newest first
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+pub struct FilterPresets {
+    presets: Vec<FilterPreset>,
+    #[serde(default)]
+    recent: Vec<SavedFilter>,
+}
+
+/// Layout of an exported preset file
+#[derive(Serialize, Deserialize)]
+struct PresetFile {
+    presets: Vec<FilterPreset>,
+}
+
+impl FilterPresets {
+    pub fn load() -> Self {
+        if let Some(win) = window() {
+            if let Ok(Some(storage)) = win.local_storage() {
+                if let Ok(Some(json)) = storage.get_item(PRESETS_KEY) {
+                    if let Ok(presets) = serde_json::from_str(&json) {
+                        return presets;
+                    }
+                }
+            }
+        }
+        Self::default()
+    }
+
+    pub fn save(&self) {
+        if let Some(win) = window() {
+            if let Ok(Some(storage)) = win.local_storage() {
+                if let Ok(json) = serde_json::to_string(self) {
+                    let _ = storage.set_item(PRESETS_KEY, &json);
+                }
+            }
+        }
+    }
+
+    pub fn presets(&self) -> &[FilterPreset] {
+        &self.presets
+    }
+
+    pub fn recent(&self) -> &[SavedFilter] {
+        &self.recent
+    }
+
+    /// Stores `filter` as preset `id`, or as a new preset when `id` is None.
+    /// A new preset replaces an existing one of the same name.
+    pub fn store(&mut self, id: Option<u64>, name: String, filter: SavedFilter) {
+        let id = id.unwrap_or_else(|| js_sys::Date::now() as u64);
+        merge_preset(&mut self.presets, FilterPreset { id, name, filter });
+        self.save();
+    }
+
+    pub fn remove(&mut self, id: u64) {
+        self.presets.retain(|p| p.id != id);
+        self.save();
+    }
+
+    /// Records an applied filter at the top of the recent list
+    pub fn remember(&mut self, filter: SavedFilter) {
+        if filter.query.trim().is_empty() {
+            return;
+        }
+        if self.recent.first() == Some(&filter) {
+            return;
+        }
+        push_recent(&mut self.recent, filter);
+        self.save();
+    }
+
+    pub fn clear_recent(&mut self) {
+        self.recent.clear();
+        self.save();
+    }
+
+    /// Presets as a JSON file to share with [`FilterPresets::import`]
+    pub fn export(&self) -> String {
+        let file = PresetFile {
+            presets: self.presets.clone(),
+        };
+        serde_json::to_string_pretty(&file).unwrap_or_default()
+    }
+
+    /// Adds the presets of an exported file, replacing presets of the same name.
+    /// Returns how many were imported.
+    pub fn import(&mut self, json: &str) -> Result<usize, String> {
+        let imported = parse_presets(json)?;
+        let count = imported.len();
+        let base = js_sys::Date::now() as u64;
+        for (i, preset) in imported.into_iter().enumerate() {
+            merge_preset(
+                &mut self.presets,
+                FilterPreset {
+                    id: base + i as u64,
+                    ..preset
+                },
+            );
+        }
+        self.save();
+        Ok(count)
+    }
+}
+
+/// Reads an exported preset file, or a bare list of presets
+fn parse_presets(json: &str) -> Result<Vec<FilterPreset>, String> {
+    let presets = serde_json::from_str::<PresetFile>(json)
+        .map(|file| file.presets)
+        .or_else(|_| serde_json::from_str::<Vec<FilterPreset>>(json))
+        .map_err(|e| format!("Invalid preset file: {}", e))?;
+    if let Some(p) = presets.iter().find(|p| p.name.trim().is_empty()) {
+        return Err(format!("Preset without a name: {}", p.filter.query));
+    }
+    Ok(presets)
+}
+
+/// Updates the preset with the same id, else replaces the one with the same name, else appends
+fn merge_preset(presets: &mut Vec<FilterPreset>, preset: FilterPreset) {
+    let same = presets
+        .iter()
+        .position(|p| p.id == preset.id)
+        .or_else(|| presets.iter().position(|p| p.name == preset.name));
+    match same {
+        Some(i) => presets[i] = preset,
+        None => presets.push(preset),
+    }
+}
+
+fn push_recent(recent: &mut Vec<SavedFilter>, filter: SavedFilter) {
+    recent.retain(|f| f != &filter);
+    recent.insert(0, filter);
+    recent.truncate(MAX_RECENT);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(query: &str) -> SavedFilter {
+        SavedFilter {
+            query: query.into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_presets() {
+        let json = r#"{"presets":[{"id":1,"name":"wifi","query":"wifi.*lost","use_regex":true}]}"#;
+        let presets = parse_presets(json).unwrap();
+        assert_eq!(presets[0].name, "wifi");
+        assert!(presets[0].filter.use_regex);
+        assert!(!presets[0].filter.invert);
+
+        // A bare list is accepted too, ids are optional
+        let presets = parse_presets(r#"[{"name":"err","query":"error"}]"#).unwrap();
+        assert_eq!(presets[0].filter, filter("error"));
+
+        assert!(parse_presets(r#"[{"name":" ","query":"x"}]"#).is_err());
+        assert!(parse_presets("not json").is_err());
+
+        // Exports read back as they were written
+        let store = FilterPresets {
+            presets: presets.clone(),
+            recent: Vec::new(),
+        };
+        assert_eq!(parse_presets(&store.export()).unwrap(), presets);
+    }
+
+    #[test]
+    fn test_merge_and_recent() {
+        let mut presets = Vec::new();
+        let preset = |id, name: &str, query| FilterPreset {
+            id,
+            name: name.into(),
+            filter: filter(query),
+        };
+        merge_preset(&mut presets, preset(1, "a", "x"));
+        merge_preset(&mut presets, preset(2, "b", "y"));
+        merge_preset(&mut presets, preset(3, "a", "z"));
+        merge_preset(&mut presets, preset(2, "c", "w"));
+        assert_eq!(presets, [preset(3, "a", "z"), preset(2, "c", "w")]);
+
+        let mut recent = Vec::new();
+        for q in ["a", "b", "a"] {
+            push_recent(&mut recent, filter(q));
+        }
+        assert_eq!(recent, [filter("a"), filter("b")]);
+        for i in 0..MAX_RECENT {
+            push_recent(&mut recent, filter(&i.to_string()));
+        }
+        assert_eq!(recent.len(), MAX_RECENT);
+        assert_eq!(recent[0], filter(&(MAX_RECENT - 1).to_string()));
+    }
+}
@@ -9,7 +9,7 @@ pub fn parse_hex_string(input: &str) -> Result<Vec<u8>, String> {
         .replace("-", "")
         .replace("0x", "");
 
-    if clean.len() % 2 != 0 {
+    if !clean.len().is_multiple_of(2) {
         return Err("Hex string must have an even number of characters".to_string());
     }
 
@@ -35,6 +35,44 @@ pub fn format_hex_input(input: &str) -> String {
         .join(" ")
 }
 
+/// Formats a byte count with a binary unit suffix (e.g. "1.5 MB")
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// Formats bytes as hex dump rows: offset, 16 hex bytes and printable ASCII
+pub fn format_hex_dump(bytes: &[u8]) -> Vec<String> {
+    bytes
+        .chunks(16)
+        .enumerate()
+        .map(|(i, row)| {
+            let hex: Vec<String> = row.iter().map(|b| format!("{:02X}", b)).collect();
+            let ascii: String = row
+                .iter()
+                .map(|&b| {
+                    if b.is_ascii_graphic() || b == b' ' {
+                        b as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+            format!("{:04X}  {:<47}  {}", i * 16, hex.join(" "), ascii)
+        })
+        .collect()
+}
+
 /// Helper to send raw byte chunk to worker
 pub fn send_chunk_to_worker(worker: &web_sys::Worker, arr: js_sys::Uint8Array, is_hex: bool) {
     // 1. Get buffer (JS Heap)
@@ -53,6 +91,15 @@ pub fn send_chunk_to_worker(worker: &web_sys::Worker, arr: js_sys::Uint8Array, i
     let _ = worker.post_message_with_transfer(&obj, &transfer);
 }
 
+/// Hands a file to the worker for import. Files are passed by reference, not copied.
+pub fn send_file_to_worker(worker: &web_sys::Worker, file: &web_sys::File, is_hex: bool) {
+    let obj = js_sys::Object::new();
+    let _ = js_sys::Reflect::set(&obj, &"cmd".into(), &"ImportFile".into());
+    let _ = js_sys::Reflect::set(&obj, &"is_hex".into(), &is_hex.into());
+    let _ = js_sys::Reflect::set(&obj, &"file".into(), file);
+    let _ = worker.post_message(&obj);
+}
+
 /// Helper to send general control messages to worker
 pub fn send_worker_msg(worker: &web_sys::Worker, msg: crate::worker::types::WorkerMsg) {
     if let Ok(msg_str) = serde_json::to_string(&msg) {
@@ -105,4 +152,23 @@ mod tests {
         assert_eq!(format_hex_input("hello world"), "ED"); // h(skip), e(E), l(skip)... d(D).
                                                            // e, d. -> ED
     }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(0), "0 B");
+        assert_eq!(format_bytes(1023), "1023 B");
+        assert_eq!(format_bytes(1536), "1.5 KB");
+        assert_eq!(format_bytes(5 * 1024 * 1024), "5.0 MB");
+        assert_eq!(format_bytes(3 * 1024 * 1024 * 1024), "3.0 GB");
+    }
+
+    #[test]
+    fn test_format_hex_dump() {
+        let rows = format_hex_dump(b"OK\r\n\x1b[0m");
+        assert_eq!(rows.len(), 1);
+        assert_eq!(
+            rows[0],
+            format!("0000  {:<47}  OK...[0m", "4F 4B 0D 0A 1B 5B 30 6D")
+        );
+    }
 }
@@ -0,0 +1,102 @@
+use crate::types::Highlight;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use web_sys::window;
+
+const HIGHLIGHT_KEY: &str = "highlight_rules";
+
+/// Highlight rules as kept in local storage and in exported files
+#[derive(Serialize, Deserialize, Default)]
+pub struct HighlightRules {
+    rules: Vec<Highlight>,
+}
+
+impl HighlightRules {
+    pub fn load() -> Vec<Highlight> {
+        if let Some(win) = window() {
+            if let Ok(Some(storage)) = win.local_storage() {
+                if let Ok(Some(json)) = storage.get_item(HIGHLIGHT_KEY) {
+                    if let Ok(rules) = Self::parse(&json) {
+                        return rules;
+                    }
+                }
+            }
+        }
+        Vec::new()
+    }
+
+    pub fn save(rules: &[Highlight]) {
+        if let Some(win) = window() {
+            if let Ok(Some(storage)) = win.local_storage() {
+                let _ = storage.set_item(HIGHLIGHT_KEY, &Self::export(rules));
+            }
+        }
+    }
+
+    pub fn export(rules: &[Highlight]) -> String {
+        let file = Self {
+            rules: rules.to_vec(),
+        };
+        serde_json::to_string_pretty(&file).unwrap_or_default()
+    }
+
+    /// Reads an exported rule file, or a bare list of rules.
+    /// Fails on the first rule whose pattern does not compile.
+    pub fn parse(json: &str) -> Result<Vec<Highlight>, String> {
+        let rules = serde_json::from_str::<Self>(json)
+            .map(|file| file.rules)
+            .or_else(|_| serde_json::from_str::<Vec<Highlight>>(json))
+            .map_err(|e| format!("Invalid highlight file: {}", e))?;
+        for rule in &rules {
+            highlight_regex(rule).map_err(|e| format!("{}: {}", rule.text, e))?;
+        }
+        Ok(rules)
+    }
+}
+
+/// Compiles a rule; literals are escaped so every rule matches through a regex
+pub fn highlight_regex(rule: &Highlight) -> Result<Regex, String> {
+    if rule.text.is_empty() {
+        return Err("Empty pattern".to_string());
+    }
+    let pattern = if rule.regex {
+        rule.text.clone()
+    } else {
+        regex::escape(&rule.text)
+    };
+    RegexBuilder::new(&pattern)
+        .case_insensitive(!rule.match_case)
+        .build()
+        .map_err(|e| match e {
+            regex::Error::Syntax(msg) => msg.lines().last().unwrap_or_default().to_string(),
+            e => e.to_string(),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::HighlightStyle;
+
+    #[test]
+    fn test_parse_rules() {
+        let json =
+            r#"{"rules":[{"id":1,"text":"err(or)?","color":"red","regex":true,"style":"Line"}]}"#;
+        let rules = HighlightRules::parse(json).unwrap();
+        assert_eq!(rules[0].style, HighlightStyle::Line);
+        assert!(!rules[0].match_case);
+        assert_eq!(
+            HighlightRules::parse(&HighlightRules::export(&rules)).unwrap(),
+            rules
+        );
+
+        // A bare list with only the pattern and color is enough
+        let rules = HighlightRules::parse(r#"[{"text":"a.b","color":"blue"}]"#).unwrap();
+        assert_eq!(rules[0].style, HighlightStyle::Foreground);
+        let re = highlight_regex(&rules[0]).unwrap();
+        assert!(re.is_match("A.B") && !re.is_match("axb"));
+
+        assert!(HighlightRules::parse(r#"[{"text":"(","color":"red","regex":true}]"#).is_err());
+        assert!(HighlightRules::parse("[1]").is_err());
+    }
+}
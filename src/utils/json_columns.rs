@@ -0,0 +1,134 @@
+use web_sys::window;
+
+const JSON_COLUMNS_KEY: &str = "json_columns";
+
+/// Fields shown as columns of the structured view until the user picks others
+const DEFAULT_COLUMNS: [&str; 3] = ["ts", "level", "msg"];
+
+/// Field columns of the structured view, as kept in local storage
+pub struct JsonColumns;
+
+impl JsonColumns {
+    pub fn load() -> Vec<String> {
+        if let Some(win) = window() {
+            if let Ok(Some(storage)) = win.local_storage() {
+                if let Ok(Some(json)) = storage.get_item(JSON_COLUMNS_KEY) {
+                    if let Ok(columns) = serde_json::from_str(&json) {
+                        return columns;
+                    }
+                }
+            }
+        }
+        DEFAULT_COLUMNS.iter().map(|c| c.to_string()).collect()
+    }
+
+    pub fn save(columns: &[String]) {
+        if let Some(win) = window() {
+            if let Ok(Some(storage)) = win.local_storage() {
+                if let Ok(json) = serde_json::to_string(columns) {
+                    let _ = storage.set_item(JSON_COLUMNS_KEY, &json);
+                }
+            }
+        }
+    }
+}
+
+/// Filter term for the input of a column: `> 80` compares the field,
+/// other text must be contained in it. `None` when the input is empty or
+/// the field name cannot appear in a filter term.
+pub fn field_filter_term(field: &str, input: &str) -> Option<String> {
+    let input = input.trim();
+    if input.is_empty() {
+        return None;
+    }
+    let (op, value) = ["<=", ">=", "!=", "==", "=", "<", ">", "~"]
+        .iter()
+        .find_map(|op| input.strip_prefix(op).map(|rest| (*op, rest.trim())))
+        .unwrap_or(("~", input));
+    let valid_path = field.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && field
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '.' | '-'));
+    if value.is_empty() || !valid_path {
+        return None;
+    }
+    // Characters that would end the word of the term are escaped
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if c.is_whitespace() || matches!(c, '\\' | '(' | ')' | '"') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    Some(format!("@{}{}{}", field, op, escaped))
+}
+
+/// The text filter combined with the column filters, and whether the worker
+/// inverts the result. Only the text filter is inverted.
+pub fn with_field_filters(
+    query: &str,
+    invert: bool,
+    filters: &[(String, String)],
+) -> (String, bool) {
+    let terms: Vec<String> = filters
+        .iter()
+        .filter_map(|(field, input)| field_filter_term(field, input))
+        .collect();
+    if terms.is_empty() {
+        return (query.to_string(), invert);
+    }
+    let terms = terms.join(" ");
+    match query.trim() {
+        "" => (terms, false),
+        q if invert => (format!("not ({}) {}", q, terms), false),
+        q => (format!("({}) {}", q, terms), false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_field_filter_term() {
+        assert_eq!(
+            field_filter_term("temp", "> 80").as_deref(),
+            Some("@temp>80")
+        );
+        assert_eq!(
+            field_filter_term("temp", ">=80").as_deref(),
+            Some("@temp>=80")
+        );
+        assert_eq!(
+            field_filter_term("msg", "link up").as_deref(),
+            Some("@msg~link\\ up")
+        );
+        assert_eq!(field_filter_term("level", " "), None);
+        assert_eq!(field_filter_term("level", "="), None);
+        assert_eq!(field_filter_term("two words", "x"), None);
+    }
+
+    #[test]
+    fn test_with_field_filters() {
+        let filters = vec![
+            ("temp".to_string(), "> 80".to_string()),
+            ("msg".to_string(), String::new()),
+        ];
+        assert_eq!(
+            with_field_filters("", true, &filters),
+            ("@temp>80".to_string(), false)
+        );
+        assert_eq!(
+            with_field_filters("wifi or ble", false, &filters),
+            ("(wifi or ble) @temp>80".to_string(), false)
+        );
+        assert_eq!(
+            with_field_filters("boot", true, &filters),
+            ("not (boot) @temp>80".to_string(), false)
+        );
+        assert_eq!(
+            with_field_filters("boot", true, &[]),
+            ("boot".to_string(), true)
+        );
+    }
+}
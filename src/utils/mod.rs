@@ -1,16 +1,23 @@
-pub mod ansi_decoder;
 pub mod file_save;
+pub mod filter_presets;
 pub mod format;
+pub mod highlight_rules;
 pub mod history;
+pub mod json_columns;
 pub mod macros;
 pub mod scroll;
 pub mod serial_api;
 pub mod simulation;
 pub mod terminal_bindings;
 
-pub use ansi_decoder::decode_ansi_text;
-pub use format::{format_hex_input, parse_hex_string, send_chunk_to_worker, send_worker_msg};
+pub use filter_presets::{filter_error, FilterPresets, SavedFilter};
+pub use format::{
+    format_bytes, format_hex_dump, format_hex_input, parse_hex_string, send_chunk_to_worker,
+    send_file_to_worker, send_worker_msg,
+};
+pub use highlight_rules::HighlightRules;
 pub use history::CommandHistory;
+pub use json_columns::{with_field_filters, JsonColumns};
 pub use macros::MacroStorage;
 pub use scroll::{calculate_start_index, calculate_window_size};
 pub use serial_api as serial;
@@ -52,6 +52,12 @@ pub async fn open_port(
     JsFuture::from(promise).await.map(|_| ())
 }
 
+/// USB vendor and product IDs of a port, if it is a USB device
+pub fn usb_ids(port: &SerialPort) -> (Option<u16>, Option<u16>) {
+    let info = port.get_info();
+    (info.get_usb_vendor_id(), info.get_usb_product_id())
+}
+
 #[derive(PartialEq, Clone, Debug)]
 pub enum ReadStatus {
     Done,
@@ -0,0 +1,371 @@
+use crate::types::{Highlight, HighlightStyle, StyledLine, StyledSpan};
+use crate::utils::highlight_rules::highlight_regex;
+use crate::worker::sgr::Sgr;
+use regex::Regex;
+use std::cell::RefCell;
+
+/// Decodes a line for display: ANSI colors, highlight rules, and the column
+/// ranges of find matches
+pub fn style_line(
+    text: &str,
+    highlights: &[Highlight],
+    find_spans: &[(usize, usize)],
+) -> StyledLine {
+    let spans = decode_ansi_text(text, highlights, !highlights.is_empty());
+    let background = line_highlight(&spans, highlights);
+    StyledLine {
+        spans: mark_columns(spans, find_spans),
+        background,
+    }
+}
+
+/// Processes log text to remove timestamps and split into highlight segments including ANSI colors
+pub fn decode_ansi_text(
+    text: &str,
+    highlights: &[Highlight],
+    show_highlights: bool,
+) -> Vec<StyledSpan> {
+    let content = text;
+
+    // 2. ANSI Code Parsing
+    // We treat ANSI codes as the base segmentation, then apply user highlights on top.
+    let mut segments = Vec::new();
+
+    // Using thread_local for Regex to avoid recompilation
+    thread_local! {
+        // Matches CSI sequences: ESC [ params command
+        static ANSI_RE: Regex = Regex::new(r"\x1B\[([0-9;:]*)([A-Za-z])").unwrap();
+    }
+
+    let mut last_pos = 0;
+    let mut sgr = Sgr::default();
+
+    ANSI_RE.with(|re| {
+        for cap in re.captures_iter(content) {
+            let m = if let Some(m) = cap.get(0) {
+                m
+            } else {
+                continue;
+            };
+            let start = m.start();
+            let end = m.end();
+
+            // Push text before the code
+            if start > last_pos {
+                segments.push(sgr.span(&content[last_pos..start]));
+            }
+
+            // Command Processing
+            if let Some(cmd_match) = cap.get(2) {
+                let params = cap.get(1).map_or("", |m| m.as_str());
+                let cmd = cmd_match.as_str();
+
+                match cmd {
+                    // SGR - Select Graphic Rendition (colors and attributes)
+                    "m" => sgr.apply(params),
+                    "C" => {
+                        // CUF - Cursor Forward (Spaces)
+                        // \x1B[nC moves right n times. Default 1.
+                        let count = params.parse::<usize>().unwrap_or(1);
+                        segments.push(sgr.span(&" ".repeat(count)));
+                    }
+                    "K" => {
+                        // EL - Erase in Line
+                        // usually \x1B[K or \x1B[0K (clear to end).
+                        // HTML renderer doesn't need to explicitly clear "void".
+                    }
+                    _ => {
+                        // Unknown command, ignore.
+                    }
+                }
+            }
+
+            last_pos = end;
+        }
+    });
+
+    // Push remaining text; a line made only of codes has no segments
+    if last_pos < content.len() {
+        segments.push(sgr.span(&content[last_pos..]));
+    }
+
+    // Fallback if no ANSI codes were found, we treat the whole thing as one segment
+    if segments.is_empty() && !content.is_empty() {
+        segments.push(Sgr::default().span(content));
+    }
+    // If original content was empty, segments is empty, which is correct.
+
+    // 3. User Highlighting Overlay
+    // Rules match the plain text of the line, so a match may span color changes
+    if show_highlights && !highlights.is_empty() {
+        segments = apply_highlights(segments, highlights);
+    }
+
+    segments
+}
+
+/// Compiled highlight rules for the rule list they were built from.
+/// Rules that fail to compile stay `None` and match nothing.
+type CompiledRules = (Vec<Highlight>, Vec<Option<Regex>>);
+
+thread_local! {
+    static RULES: RefCell<CompiledRules> = const { RefCell::new((Vec::new(), Vec::new())) };
+}
+
+fn with_rules<T>(highlights: &[Highlight], f: impl FnOnce(&[Option<Regex>]) -> T) -> T {
+    RULES.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        if cache.0 != highlights {
+            let compiled = highlights.iter().map(|h| highlight_regex(h).ok()).collect();
+            *cache = (highlights.to_vec(), compiled);
+        }
+        f(&cache.1)
+    })
+}
+
+/// Recolors every match of the text and background rules; the first rule in
+/// the list wins where matches overlap
+fn apply_highlights(segments: Vec<StyledSpan>, highlights: &[Highlight]) -> Vec<StyledSpan> {
+    let plain: String = segments.iter().map(|s| s.text.as_str()).collect();
+    // Rule owning each byte of the line
+    let mut owner: Vec<Option<usize>> = vec![None; plain.len()];
+    with_rules(highlights, |rules| {
+        for (i, (rule, re)) in highlights.iter().zip(rules).enumerate() {
+            let Some(re) = re.as_ref().filter(|_| rule.style != HighlightStyle::Line) else {
+                continue;
+            };
+            for m in re.find_iter(&plain) {
+                for o in &mut owner[m.range()] {
+                    o.get_or_insert(i);
+                }
+            }
+        }
+    });
+    if owner.iter().all(Option::is_none) {
+        return segments;
+    }
+
+    let styled = |seg: &StyledSpan, text: &str, rule: Option<usize>| {
+        let mut piece = StyledSpan {
+            text: text.to_string(),
+            ..seg.clone()
+        };
+        if let Some(rule) = rule.map(|i| &highlights[i]) {
+            match rule.style {
+                HighlightStyle::Background => piece.background = Some(rule.color.clone()),
+                _ => {
+                    piece.color = Some(rule.color.clone());
+                    piece.bold = true;
+                }
+            }
+        }
+        piece
+    };
+    let mut out = Vec::with_capacity(segments.len());
+    let mut pos = 0;
+    for seg in &segments {
+        let mut start = 0;
+        for (i, _) in seg.text.char_indices().skip(1) {
+            if owner[pos + i] != owner[pos + start] {
+                out.push(styled(seg, &seg.text[start..i], owner[pos + start]));
+                start = i;
+            }
+        }
+        if start < seg.text.len() {
+            out.push(styled(seg, &seg.text[start..], owner[pos + start]));
+        }
+        pos += seg.text.len();
+    }
+    out
+}
+
+/// Color of the first whole-line rule matching the decoded line
+pub fn line_highlight(segments: &[StyledSpan], highlights: &[Highlight]) -> Option<String> {
+    if !highlights.iter().any(|h| h.style == HighlightStyle::Line) {
+        return None;
+    }
+    let plain: String = segments.iter().map(|s| s.text.as_str()).collect();
+    with_rules(highlights, |rules| {
+        highlights
+            .iter()
+            .zip(rules)
+            .find(|(rule, re)| {
+                rule.style == HighlightStyle::Line
+                    && re.as_ref().is_some_and(|re| re.is_match(&plain))
+            })
+            .map(|(rule, _)| rule.color.clone())
+    })
+}
+
+/// Splits decoded segments at the character columns of find matches,
+/// marking each piece with the index of the match covering it
+pub fn mark_columns(segments: Vec<StyledSpan>, spans: &[(usize, usize)]) -> Vec<StyledSpan> {
+    if spans.is_empty() {
+        return segments;
+    }
+    let mut marked = Vec::with_capacity(segments.len() + spans.len() * 2);
+    let mut column = 0;
+    for seg in segments {
+        let mut piece = String::new();
+        let mut piece_mark = None;
+        for ch in seg.text.chars() {
+            let mark = spans.iter().position(|&(s, e)| (s..e).contains(&column));
+            if mark != piece_mark && !piece.is_empty() {
+                let text = std::mem::take(&mut piece);
+                marked.push(StyledSpan {
+                    text,
+                    mark: piece_mark,
+                    ..seg.clone()
+                });
+            }
+            piece_mark = mark;
+            piece.push(ch);
+            column += 1;
+        }
+        if !piece.is_empty() {
+            marked.push(StyledSpan {
+                text: piece,
+                mark: piece_mark,
+                ..seg
+            });
+        }
+    }
+    marked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ansi_parsing() {
+        let highlights = vec![];
+
+        // Green text
+        let res = decode_ansi_text("\x1B[32mHello\x1B[0m", &highlights, false);
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].text, "Hello");
+        assert_eq!(res[0].color.as_deref(), Some("#10b981"));
+
+        // Mixed
+        let res = decode_ansi_text("A\x1B[31mB\x1B[0mC", &highlights, false);
+        assert_eq!(res.len(), 3);
+        assert_eq!(res[0].text, "A");
+        assert_eq!(res[0].color, None);
+        assert_eq!(res[1].text, "B");
+        assert_eq!(res[1].color.as_deref(), Some("#ef4444"));
+        assert_eq!(res[2].text, "C");
+        assert_eq!(res[2].color, None);
+    }
+
+    #[test]
+    fn test_sgr_attributes_per_segment() {
+        let res = decode_ansi_text(
+            "\x1B[1;38;5;208mA\x1B[22;4;48;2;0;0;255mB\x1B[0mC",
+            &[],
+            false,
+        );
+        assert_eq!(res.len(), 3);
+        assert!(res[0].bold && !res[0].underline);
+        assert_eq!(res[0].color.as_deref(), Some("#ff8700"));
+        assert!(!res[1].bold && res[1].underline);
+        assert_eq!(res[1].color.as_deref(), Some("#ff8700"));
+        assert_eq!(res[1].background.as_deref(), Some("#0000ff"));
+        assert_eq!(res[2], StyledSpan::plain("C"));
+    }
+
+    #[test]
+    fn test_invalid_sgr_param_is_skipped() {
+        let res = decode_ansi_text("\x1B[1;300mA", &[], false);
+        assert_eq!(res.len(), 1);
+        assert!(res[0].bold);
+    }
+
+    #[test]
+    fn test_highlight_overlay() {
+        let highlights = vec![Highlight {
+            id: 1,
+            text: "Error".to_string(),
+            color: "blue".to_string(),
+            match_case: true,
+            ..Default::default()
+        }];
+
+        // ANSI Green text containing "Error"
+        let res = decode_ansi_text("\x1B[32mNoErrorHere\x1B[0m", &highlights, true);
+        assert_eq!(res.len(), 3);
+        assert_eq!(res[0].text, "No");
+        assert_eq!(res[0].color.as_deref(), Some("#10b981")); // Green
+        assert_eq!(res[1].text, "Error");
+        assert_eq!(res[1].color.as_deref(), Some("blue")); // User Blue wins
+        assert_eq!(res[2].text, "Here");
+        assert_eq!(res[2].color.as_deref(), Some("#10b981")); // Green
+    }
+
+    #[test]
+    fn test_mark_columns() {
+        let segments = decode_ansi_text("ab\x1B[31mcd\x1B[0mef", &[], false);
+        let res = mark_columns(segments, &[(1, 3), (5, 6)]);
+        let pieces: Vec<(&str, Option<usize>)> =
+            res.iter().map(|s| (s.text.as_str(), s.mark)).collect();
+        assert_eq!(
+            pieces,
+            [
+                ("a", None),
+                ("b", Some(0)),
+                ("c", Some(0)),
+                ("d", None),
+                ("e", None),
+                ("f", Some(1))
+            ]
+        );
+        assert_eq!(res[2].color.as_deref(), Some("#ef4444"));
+    }
+
+    #[test]
+    fn test_highlight_every_occurrence() {
+        let rule = |text: &str, color: &str, regex, style| Highlight {
+            text: text.to_string(),
+            color: color.to_string(),
+            regex,
+            style,
+            ..Default::default()
+        };
+        let highlights = vec![
+            rule("err", "red", false, HighlightStyle::Foreground),
+            rule(r"id=\d+", "blue", true, HighlightStyle::Background),
+            rule("boot", "green", false, HighlightStyle::Line),
+        ];
+        let pieces = |res: &[StyledSpan]| -> Vec<(String, Option<String>, Option<String>)> {
+            res.iter()
+                .map(|s| (s.text.clone(), s.color.clone(), s.background.clone()))
+                .collect()
+        };
+        let some = |c: &str| Some(c.to_string());
+
+        // Case-insensitive, every occurrence, and across an ANSI color change
+        let res = decode_ansi_text("ERR a \x1B[32merr id=1\x1B[0m2 e", &highlights, true);
+        assert_eq!(
+            pieces(&res),
+            [
+                ("ERR".into(), some("red"), None),
+                (" a ".into(), None, None),
+                ("err".into(), some("red"), None),
+                (" ".into(), some("#10b981"), None),
+                ("id=1".into(), some("#10b981"), some("blue")),
+                ("2".into(), None, some("blue")),
+                (" e".into(), None, None),
+            ]
+        );
+        assert_eq!(line_highlight(&res, &highlights), None);
+
+        // Whole-line rules color the line, not the match
+        let res = decode_ansi_text("Boot done", &highlights, true);
+        assert_eq!(pieces(&res), [("Boot done".into(), None, None)]);
+        assert_eq!(line_highlight(&res, &highlights), some("green"));
+
+        // A rule that does not compile matches nothing
+        let broken = vec![rule("(", "red", true, HighlightStyle::Foreground)];
+        assert_eq!(decode_ansi_text("a(b", &broken, true).len(), 1);
+    }
+}
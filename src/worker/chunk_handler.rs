@@ -1,38 +1,73 @@
 use crate::config::MAX_LINE_BYTES;
+use crate::types::{LogLevel, RxEncoding};
+use crate::worker::encoding::RxDecoder;
 use crate::worker::formatter::LogFormatterStrategy;
+use crate::worker::level;
 use crate::worker::repository::index::{ByteOffset, LineRange};
 use std::borrow::Cow;
 use vt100::Parser;
 
+/// Lines produced from one chunk, ready to be appended to the repository
+#[derive(Default)]
+pub struct ProcessedBatch {
+    pub text: String,
+    /// End offset of each line, relative to the start of `text`
+    pub offsets: Vec<ByteOffset>,
+    /// Level detected on each line
+    pub levels: Vec<LogLevel>,
+    /// Lines matching the active filter, relative to the first line of the batch
+    pub filtered: Vec<usize>,
+    /// Raw capture byte range each line was decoded from
+    pub raw_ranges: Vec<LineRange>,
+    /// Receive time of the chunk in microseconds since the epoch, if known
+    pub received_at: Option<i64>,
+    pub active_line: Option<String>,
+}
+
 /// Handles streaming line processing with leftover buffer management
 pub struct StreamingLineProcessor {
     pub leftover_buffer: String,
     parser: Parser,
+    /// Decodes received text to UTF-8 before it reaches the parser
+    decoder: RxDecoder,
+    /// Raw capture offset where the line being received started
+    raw_line_start: ByteOffset,
 }
 
 impl StreamingLineProcessor {
     pub fn new() -> Self {
         Self {
             leftover_buffer: String::new(),
+            raw_line_start: ByteOffset(0),
             // Height 1 ensures we focus on a single line.
             // Width MAX_LINE_BYTES prevents arbitrary wrapping of long lines.
             // Scrollback 0 disables history as we extract confirmed lines immediately.
             parser: Parser::new(1, MAX_LINE_BYTES as u16, 0),
+            decoder: RxDecoder::new(RxEncoding::default()),
+        }
+    }
+
+    pub fn encoding(&self) -> RxEncoding {
+        self.decoder.encoding()
+    }
+
+    /// Decodes received text with `encoding` from the next chunk on
+    pub fn set_encoding(&mut self, encoding: RxEncoding) {
+        if encoding != self.decoder.encoding() {
+            self.decoder = RxDecoder::new(encoding);
         }
     }
 
+    /// Processes a text chunk stored at `raw_base` in the raw capture
     pub fn process_vt100(
         &mut self,
         chunk: &[u8],
+        raw_base: ByteOffset,
         formatter: &dyn LogFormatterStrategy,
-        timestamp: &str,
         is_filtering: bool,
-        filter_matcher: impl Fn(&str) -> bool,
-    ) -> (String, Vec<ByteOffset>, Vec<LineRange>, Option<String>) {
-        let mut batch = String::new();
-        let mut offsets = Vec::new();
-        let mut filtered = Vec::new();
-        let mut relative_offset = ByteOffset(0);
+        filter_matcher: impl Fn(&str, LogLevel) -> bool,
+    ) -> ProcessedBatch {
+        let mut batch = ProcessedBatch::default();
 
         let mut start = 0;
         let len = chunk.len();
@@ -40,8 +75,10 @@ impl StreamingLineProcessor {
         while start < len {
             if let Some((end, next_start)) = Self::find_next_line_ending(chunk, start) {
                 // Process content up to the newline char(s)
-                let line_bytes = &chunk[start..end];
-                self.parser.process(line_bytes);
+                // Line endings are single bytes in every supported encoding,
+                // so the raw split holds and the line ends any open sequence
+                let line_bytes = self.decoder.decode(&chunk[start..end], true);
+                self.parser.process(&line_bytes);
 
                 // Extract the formatted line immediately
                 if let Some(bytes) = self
@@ -55,15 +92,12 @@ impl StreamingLineProcessor {
                     self.process_single_line(
                         &line_str,
                         formatter,
-                        timestamp,
                         &mut batch,
-                        &mut offsets,
-                        &mut filtered,
-                        &mut relative_offset,
                         is_filtering,
                         &filter_matcher,
                     );
                 }
+                self.end_raw_line(&mut batch, raw_base + next_start as u64);
 
                 // Clear the line in the parser to prepare for the next line
                 // Carriage Return + Clear Line
@@ -78,32 +112,33 @@ impl StreamingLineProcessor {
 
         // Process any remaining bytes (incomplete line)
         if start < chunk.len() {
-            self.parser.process(&chunk[start..]);
+            let bytes = self.decoder.decode(&chunk[start..], false);
+            self.parser.process(&bytes);
         }
 
         // Get Current Active Line (Row 0)
         // If the chunk ended with a newline, this will be empty (which is correct)
-        let active_line = self
+        batch.active_line = self
             .parser
             .screen()
             .rows_formatted(0, MAX_LINE_BYTES as u16)
             .next()
             .map(|bytes| String::from_utf8_lossy(&bytes).to_string())
             .filter(|s| !s.trim().is_empty())
-            .filter(|s| !is_filtering || filter_matcher(s));
+            .filter(|s| !is_filtering || filter_matcher(s, level::classify(s)));
 
-        (batch, offsets, filtered, active_line)
+        batch
     }
 
-    /// Processes a hex chunk (Hex mode)
+    /// Processes a hex chunk (Hex mode) formatted from raw bytes stored at `raw_base`
     pub fn process_hex_lines(
         &mut self,
         chunk: &str,
+        raw_base: ByteOffset,
         formatter: &dyn LogFormatterStrategy,
-        timestamp: &str,
         is_filtering: bool,
-        filter_matcher: impl Fn(&str) -> bool,
-    ) -> (String, Vec<ByteOffset>, Vec<LineRange>) {
+        filter_matcher: impl Fn(&str, LogLevel) -> bool,
+    ) -> ProcessedBatch {
         let max_len = formatter.max_line_length();
 
         // 1. If leftover is already too long, force a split before even adding new chunk
@@ -117,14 +152,17 @@ impl StreamingLineProcessor {
             Cow::Owned(format!("{}{}", self.leftover_buffer, chunk))
         };
 
-        let mut batch = String::with_capacity(full_text.len() * 2);
-        let mut offsets = Vec::new(); // Capacity logic changed slightly, let vector handle realloc or use heuristic
-        let mut filtered = Vec::new();
-        let mut relative_offset = ByteOffset(0);
+        let mut batch = ProcessedBatch {
+            text: String::with_capacity(full_text.len() * 2),
+            ..Default::default()
+        };
 
         let text_bytes = full_text.as_bytes();
         let len = text_bytes.len();
         let mut start = 0;
+        // Maps hex text positions back to raw bytes: "XX " per byte, line endings as-is.
+        // The leftover text belongs to bytes before `raw_base`.
+        let (mut text_pos, mut raw_pos) = (self.leftover_buffer.len(), raw_base);
 
         while start < len {
             if let Some((end, next_start)) = Self::find_next_line_ending(text_bytes, start) {
@@ -133,59 +171,75 @@ impl StreamingLineProcessor {
                 self.process_single_line(
                     line_str,
                     formatter,
-                    timestamp,
                     &mut batch,
-                    &mut offsets,
-                    &mut filtered,
-                    &mut relative_offset,
                     is_filtering,
                     &filter_matcher,
                 );
 
+                while text_pos < next_start {
+                    text_pos += match text_bytes[text_pos] {
+                        b'\n' | b'\r' => 1,
+                        _ => 3,
+                    };
+                    raw_pos = raw_pos + 1;
+                }
+                self.end_raw_line(&mut batch, raw_pos);
+
                 start = next_start;
             } else {
                 // No more newlines, save the rest as leftover
                 self.leftover_buffer = full_text[start..].to_string();
-                return (batch, offsets, filtered);
+                return batch;
             }
         }
 
         // If loop finished exactly (ended with newline), clear leftover
         self.leftover_buffer.clear();
-        (batch, offsets, filtered)
+        batch
+    }
+
+    /// Assigns the raw range ending at `raw_end` to the lines produced since the last call
+    fn end_raw_line(&mut self, batch: &mut ProcessedBatch, raw_end: ByteOffset) {
+        let range = LineRange {
+            start: self.raw_line_start,
+            end: raw_end,
+        };
+        batch.raw_ranges.resize(batch.offsets.len(), range);
+        self.raw_line_start = raw_end;
+    }
+
+    /// Sets the raw capture offset where the next line starts (after attaching a session)
+    pub fn set_raw_position(&mut self, offset: ByteOffset) {
+        self.raw_line_start = offset;
+    }
+
+    /// Shifts the raw position after `cut` bytes were dropped from the raw capture
+    pub fn rebase_raw_position(&mut self, cut: u64) {
+        self.raw_line_start = ByteOffset(self.raw_line_start.0.saturating_sub(cut));
     }
 
-    #[allow(clippy::too_many_arguments)]
     fn process_single_line(
         &self,
         line: &str,
         formatter: &dyn LogFormatterStrategy,
-        timestamp: &str,
-        batch: &mut String,
-        offsets: &mut Vec<ByteOffset>,
-        filtered: &mut Vec<LineRange>,
-        current_relative_offset: &mut ByteOffset,
+        batch: &mut ProcessedBatch,
         is_filtering: bool,
-        filter_matcher: &impl Fn(&str) -> bool,
+        filter_matcher: &impl Fn(&str, LogLevel) -> bool,
     ) {
         let max_len = formatter.max_line_length();
         let mut start = 0;
+        // Parts of a line split for length share its level
+        let level = level::classify(line);
 
         // Handle empty line case
         if line.is_empty() {
-            let start_pos = batch.len();
-            let formatted = formatter.format("", timestamp);
-            batch.push_str(&formatted);
-            let line_len = (batch.len() - start_pos) as u64;
-
-            if is_filtering && filter_matcher(&batch[start_pos..]) {
-                filtered.push(LineRange {
-                    start: *current_relative_offset,
-                    end: *current_relative_offset + line_len,
-                });
-            }
-            *current_relative_offset = *current_relative_offset + line_len;
-            offsets.push(*current_relative_offset);
+            Self::push_line(
+                batch,
+                &formatter.format(""),
+                level,
+                is_filtering,
+                filter_matcher,
+            );
             return;
         }
 
@@ -196,28 +250,41 @@ impl StreamingLineProcessor {
             }
             let sub_line = &line[start..end];
 
-            let start_pos = batch.len();
-            let formatted = formatter.format(sub_line, timestamp);
-            batch.push_str(&formatted);
-            let line_len = (batch.len() - start_pos) as u64;
+            Self::push_line(
+                batch,
+                &formatter.format(sub_line),
+                level,
+                is_filtering,
+                filter_matcher,
+            );
+            start = end;
+        }
+    }
 
-            if is_filtering && filter_matcher(&batch[start_pos..]) {
-                filtered.push(LineRange {
-                    start: *current_relative_offset,
-                    end: *current_relative_offset + line_len,
-                });
-            }
+    /// Appends one formatted line to the batch
+    fn push_line(
+        batch: &mut ProcessedBatch,
+        formatted: &str,
+        level: LogLevel,
+        is_filtering: bool,
+        filter_matcher: &impl Fn(&str, LogLevel) -> bool,
+    ) {
+        batch.text.push_str(formatted);
+        let end = ByteOffset(batch.text.len() as u64);
 
-            *current_relative_offset = *current_relative_offset + line_len;
-            offsets.push(*current_relative_offset);
-            start = end;
+        if is_filtering && filter_matcher(formatted, level) {
+            batch.filtered.push(batch.offsets.len());
         }
+        batch.offsets.push(end);
+        batch.levels.push(level);
     }
 
     pub fn clear(&mut self) {
         self.leftover_buffer.clear();
+        self.raw_line_start = ByteOffset(0);
         // Reset parser state
         self.parser = Parser::new(1, MAX_LINE_BYTES as u16, 0);
+        self.decoder = RxDecoder::new(self.decoder.encoding());
     }
 
     /// Helper to find the next line ending from a byte slice.
@@ -1,9 +1,19 @@
+use crate::types::{
+    Highlight, LogLevel, LowSpacePolicy, RawLine, RxEncoding, SearchIndexSize, SessionLimit,
+    SessionMetadata, SortSpec,
+};
 use crate::worker::commands::command::WorkerCommand;
 use crate::worker::error::LogError;
 use crate::worker::export::LogExporter;
-use crate::worker::repository::index::LineIndex;
-use crate::worker::repository::storage::StorageBackend;
+use crate::worker::formatter::LogFormatter;
+use crate::worker::import::LogImporter;
+use crate::worker::json_line;
+use crate::worker::processor::LogProcessor;
+use crate::worker::repository::index::{ActiveFilterBuilder, ByteOffset, LineIndex};
+use crate::worker::repository::storage::{sidecar_file_name, StorageBackend};
 use crate::worker::search::LogSearcher;
+use crate::worker::session::SessionLibrary;
+use crate::worker::sort::LogSorter;
 use crate::worker::state::WorkerState;
 use crate::worker::types::WorkerMsg;
 use std::cell::RefCell;
@@ -11,19 +21,39 @@ use std::rc::Rc;
 use wasm_bindgen::prelude::JsValue;
 use wasm_bindgen_futures::spawn_local;
 
-pub struct NewSessionCommand;
+pub struct StartSessionCommand(pub SessionMetadata);
 
-impl WorkerCommand for NewSessionCommand {
+impl WorkerCommand for StartSessionCommand {
     fn execute(
         &self,
-        _state: &mut WorkerState,
+        state: &mut WorkerState,
         _state_rc: &Rc<RefCell<WorkerState>>,
     ) -> Result<bool, JsValue> {
+        state.pending_metadata = Some(self.0.clone());
+        // Hold back chunks until the new session is attached
+        state.pending_chunks.get_or_insert_with(Vec::new);
         // Needs async handling by caller
         Ok(false)
     }
 }
 
+pub struct EndSessionCommand;
+
+impl WorkerCommand for EndSessionCommand {
+    fn execute(
+        &self,
+        state: &mut WorkerState,
+        state_rc: &Rc<RefCell<WorkerState>>,
+    ) -> Result<bool, JsValue> {
+        // The live session may be capturing behind an archived one
+        if let Some(live) = state.live_proc() {
+            live.repository.finish_session()?;
+        }
+        spawn_session_task(state_rc, SessionLibrary::list(state_rc.clone()));
+        Ok(true)
+    }
+}
+
 pub struct AppendChunkCommand {
     pub chunk: Vec<u8>,
     pub is_hex: bool,
@@ -33,15 +63,49 @@ impl WorkerCommand for AppendChunkCommand {
     fn execute(
         &self,
         state: &mut WorkerState,
-        _state_rc: &Rc<RefCell<WorkerState>>,
+        state_rc: &Rc<RefCell<WorkerState>>,
     ) -> Result<bool, JsValue> {
-        let active_line = state.proc.append_chunk(&self.chunk, self.is_hex)?;
-        if let Some(line) = active_line {
-            state.send_msg(WorkerMsg::ActiveLine(Some(line)));
-        } else {
-            // Send None to clear if active line became empty (e.g. newline received)
-            state.send_msg(WorkerMsg::ActiveLine(None));
+        if let Some(pending) = state.pending_chunks.as_mut() {
+            pending.push((self.chunk.clone(), self.is_hex));
+            return Ok(true);
         }
+        // Dropped on purpose; the user was told capture is paused and is told
+        // how much was lost once it resumes
+        if state.storage_usage.paused {
+            state.paused_dropped += self.chunk.len() as u64;
+            return Ok(true);
+        }
+        let shown = !state.read_only;
+        // With an archive shown, capture goes on in the background
+        let Some(live) = state.live_proc() else {
+            return Ok(true);
+        };
+        let active_line = live.append_chunk(&self.chunk, self.is_hex)?;
+        if shown {
+            // None clears the active line once it is complete (e.g. newline received)
+            let styled = active_line.map(|line| state.proc.style_line(&line, &[], true));
+            state.send_msg(WorkerMsg::ActiveLine(styled));
+        }
+        state.enforce_size_limit(state_rc)?;
+        Ok(true)
+    }
+}
+
+pub struct ImportFileCommand {
+    pub file: web_sys::File,
+    pub is_hex: bool,
+}
+
+impl WorkerCommand for ImportFileCommand {
+    fn execute(
+        &self,
+        _state: &mut WorkerState,
+        state_rc: &Rc<RefCell<WorkerState>>,
+    ) -> Result<bool, JsValue> {
+        spawn_session_task(
+            state_rc,
+            LogImporter::import_async(state_rc.clone(), self.file.clone(), self.is_hex),
+        );
         Ok(true)
     }
 }
@@ -55,48 +119,218 @@ impl WorkerCommand for SetTimestampStateCommand {
         _state_rc: &Rc<RefCell<WorkerState>>,
     ) -> Result<bool, JsValue> {
         state.proc.set_timestamp_state(self.0);
+        // Times are rendered per window, so the visible lines change right away
+        if let Some((start_line, count)) = state.last_window {
+            RequestWindowCommand { start_line, count }.execute(state, _state_rc)?;
+        }
         Ok(true)
     }
 }
 
-pub struct RequestWindowCommand {
-    pub start_line: usize,
-    pub count: usize,
+pub struct SetHighlightsCommand(pub Vec<Highlight>);
+
+impl WorkerCommand for SetHighlightsCommand {
+    fn execute(
+        &self,
+        state: &mut WorkerState,
+        _state_rc: &Rc<RefCell<WorkerState>>,
+    ) -> Result<bool, JsValue> {
+        state.proc.highlights = self.0.clone();
+        // Spans are computed per window, so restyle the visible lines
+        if let Some((start_line, count)) = state.last_window {
+            RequestWindowCommand { start_line, count }.execute(state, _state_rc)?;
+        }
+        Ok(true)
+    }
 }
 
-impl WorkerCommand for RequestWindowCommand {
+pub struct SetEncodingCommand(pub RxEncoding);
+
+impl WorkerCommand for SetEncodingCommand {
+    fn execute(
+        &self,
+        state: &mut WorkerState,
+        _state_rc: &Rc<RefCell<WorkerState>>,
+    ) -> Result<bool, JsValue> {
+        // Only the processor that receives serial data decodes with it; a shown
+        // archive keeps its encoding
+        let receiver = match state.live.as_mut() {
+            Some(live) => live,
+            None => &mut state.proc,
+        };
+        receiver.set_encoding(self.0);
+        // Record the change on the session being captured
+        let Some(live) = state.live_proc() else {
+            return Ok(true);
+        };
+        let repo = &mut live.repository;
+        if let Some(metadata) = repo.metadata.as_ref().filter(|m| m.ended_at.is_none()) {
+            if metadata.encoding != self.0 {
+                let metadata = SessionMetadata {
+                    encoding: self.0,
+                    ..metadata.clone()
+                };
+                repo.set_metadata(metadata)?;
+            }
+        }
+        Ok(true)
+    }
+}
+
+pub struct SetJsonColumnsCommand(pub Vec<String>);
+
+impl WorkerCommand for SetJsonColumnsCommand {
+    fn execute(
+        &self,
+        state: &mut WorkerState,
+        state_rc: &Rc<RefCell<WorkerState>>,
+    ) -> Result<bool, JsValue> {
+        state.proc.json_columns = self.0.clone();
+        if let Some((start_line, count)) = state.last_window {
+            RequestWindowCommand { start_line, count }.execute(state, state_rc)?;
+        }
+        Ok(true)
+    }
+}
+
+pub struct SortByCommand(pub Option<SortSpec>);
+
+impl WorkerCommand for SortByCommand {
+    fn execute(
+        &self,
+        state: &mut WorkerState,
+        state_rc: &Rc<RefCell<WorkerState>>,
+    ) -> Result<bool, JsValue> {
+        state.proc.sort = self.0.clone();
+        if self.0.is_some() {
+            LogSorter::restart(state_rc);
+            return Ok(true);
+        }
+        // Back to log order; a running sort stops at its next batch
+        state.current_sort_id += 1;
+        state.proc.repository.index.sorted = None;
+        state.send_msg(WorkerMsg::SortRunning(false));
+        if let Some((start_line, count)) = state.last_window {
+            RequestWindowCommand { start_line, count }.execute(state, state_rc)?;
+        }
+        Ok(true)
+    }
+}
+
+pub struct SetLowSpacePolicyCommand(pub LowSpacePolicy);
+
+impl WorkerCommand for SetLowSpacePolicyCommand {
+    fn execute(
+        &self,
+        state: &mut WorkerState,
+        _state_rc: &Rc<RefCell<WorkerState>>,
+    ) -> Result<bool, JsValue> {
+        state.low_space_policy = self.0;
+        Ok(true)
+    }
+}
+
+pub struct SetSearchIndexCommand(pub SearchIndexSize);
+
+impl WorkerCommand for SetSearchIndexCommand {
     fn execute(
         &self,
         state: &mut WorkerState,
         _state_rc: &Rc<RefCell<WorkerState>>,
     ) -> Result<bool, JsValue> {
-        let total = state.proc.get_line_count() as usize;
+        // The live session captured behind a shown archive is indexed too
+        let bits_per_gram = self.0.bits_per_gram();
+        for proc in std::iter::once(&mut state.proc).chain(&mut state.live) {
+            proc.repository.set_search_index(bits_per_gram)?;
+        }
+        Ok(true)
+    }
+}
+
+pub struct SetSessionLimitCommand(pub SessionLimit);
+
+impl WorkerCommand for SetSessionLimitCommand {
+    fn execute(
+        &self,
+        state: &mut WorkerState,
+        _state_rc: &Rc<RefCell<WorkerState>>,
+    ) -> Result<bool, JsValue> {
+        state.size_limit = self.0;
+        Ok(true)
+    }
+}
+
+pub struct RequestWindowCommand {
+    pub start_line: usize,
+    pub count: usize,
+}
+
+impl RequestWindowCommand {
+    /// Reads the requested lines of the current view into a `LogWindow` message
+    pub fn window<B: StorageBackend + Default>(
+        &self,
+        proc: &LogProcessor<B>,
+    ) -> Result<WorkerMsg, LogError> {
+        let total = proc.get_line_count() as usize;
         let (s, e) = (
             self.start_line.min(total),
             (self.start_line + self.count).min(total),
         );
         let mut lines = Vec::with_capacity(e - s);
-        let repo = &state.proc.repository;
+        let mut line_numbers = Vec::with_capacity(e - s);
+        // Cells of the JSON columns and the fields seen, for the structured view
+        let mut rows = Vec::new();
+        let mut json_fields = Vec::new();
+        let repo = &proc.repository;
 
         for i in s..e {
             if let Some(range) = repo.get_line_range(LineIndex(i)) {
-                let buf = repo.read_line(range).map_err(JsValue::from)?;
-
-                let text = repo
-                    .storage
-                    .decoder
-                    .decode_with_u8_array(&buf)
-                    .map_err(LogError::from)?
-                    .trim_end_matches('\n')
-                    .to_string();
-                lines.push((i, text));
+                let buf = repo.read_line(range)?;
+                let text = String::from_utf8_lossy(&buf);
+                let text = text.trim_end_matches('\n');
+                let find_spans = proc.find_columns(LineIndex(i), text);
+                if !proc.json_columns.is_empty() {
+                    let row = json_line::row(text, &proc.json_columns).map(|(row, object)| {
+                        json_line::collect_fields(&object, "", &mut json_fields);
+                        row
+                    });
+                    rows.push(row);
+                }
+                let text = if proc.show_timestamps {
+                    LogFormatter::with_timestamp(text, repo.line_time(LineIndex(i)))
+                } else {
+                    text.to_string()
+                };
+                let styled =
+                    proc.style_line(&text, find_spans.as_deref().unwrap_or_default(), false);
+                lines.push((i, styled));
+                line_numbers.push(repo.line_number(LineIndex(i)).unwrap_or_default());
             }
         }
 
-        state.send_msg(WorkerMsg::LogWindow {
+        let (context, gaps) = repo.window_context(s, e);
+        Ok(WorkerMsg::LogWindow {
             start_line: self.start_line,
             lines,
-        });
+            line_numbers,
+            bookmarks: repo.window_bookmarks(s, e),
+            context,
+            gaps,
+            rows,
+            json_fields,
+        })
+    }
+}
+
+impl WorkerCommand for RequestWindowCommand {
+    fn execute(
+        &self,
+        state: &mut WorkerState,
+        _state_rc: &Rc<RefCell<WorkerState>>,
+    ) -> Result<bool, JsValue> {
+        let msg = self.window(&state.proc)?;
+        state.last_window = Some((self.start_line, self.count));
+        state.send_msg(msg);
         Ok(true)
     }
 }
@@ -109,6 +343,9 @@ impl WorkerCommand for ClearCommand {
         state: &mut WorkerState,
         _state_rc: &Rc<RefCell<WorkerState>>,
     ) -> Result<bool, JsValue> {
+        if state.read_only {
+            return Err(LogError::Storage("Archived sessions are read-only".into()).into());
+        }
         state.proc.clear()?;
         state.send_msg(WorkerMsg::TotalLines(0));
         Ok(true)
@@ -120,6 +357,10 @@ pub struct SearchLogsCommand {
     pub match_case: bool,
     pub use_regex: bool,
     pub invert: bool,
+    /// Lines shown before and after each match
+    pub context: (usize, usize),
+    /// Only lines at this level or more severe match
+    pub min_level: Option<LogLevel>,
 }
 
 impl WorkerCommand for SearchLogsCommand {
@@ -132,30 +373,51 @@ impl WorkerCommand for SearchLogsCommand {
         let match_case = self.match_case;
         let use_regex = self.use_regex;
         let invert = self.invert;
+        let context = self.context;
+        let min_level = self.min_level;
         let state_rc_clone = state_rc.clone();
 
         // Cancel previous search by incrementing search_id
         state.current_search_id += 1;
 
-        spawn_local(async move {
-            if let Err(e) = LogSearcher::search_async(
-                state_rc_clone.clone(),
-                query,
-                match_case,
-                use_regex,
-                invert,
-            )
-            .await
-            {
-                state_rc_clone.borrow().send_error(JsValue::from(e));
-            }
-        });
+        spawn_local(LogSearcher::search_async(
+            state_rc_clone,
+            query,
+            match_case,
+            use_regex,
+            invert,
+            context,
+            min_level,
+        ));
 
         Ok(true)
     }
 }
 
-pub struct ExportLogsCommand;
+pub struct CancelSearchCommand;
+
+impl WorkerCommand for CancelSearchCommand {
+    fn execute(
+        &self,
+        state: &mut WorkerState,
+        state_rc: &Rc<RefCell<WorkerState>>,
+    ) -> Result<bool, JsValue> {
+        let id = state.current_search_id;
+        // The scan stops at its next batch; the filter keeps what it found
+        state.current_search_id += 1;
+        state.send_msg(WorkerMsg::SearchDone {
+            id,
+            matches: state.proc.repository.index.match_count(),
+            completed: false,
+        });
+        LogSorter::restart(state_rc);
+        Ok(true)
+    }
+}
+
+pub struct ExportLogsCommand {
+    pub include_timestamp: bool,
+}
 
 impl WorkerCommand for ExportLogsCommand {
     fn execute(
@@ -164,24 +426,462 @@ impl WorkerCommand for ExportLogsCommand {
         _state_rc: &Rc<RefCell<WorkerState>>,
     ) -> Result<bool, JsValue> {
         let repo = &state.proc.repository;
+        // The export reads the file itself, so it must be in order
+        while repo.compact(u64::MAX)? {}
         let size = repo.storage.backend.get_file_size()?;
         let handle = repo
             .storage
             .backend
+            .inner()
             .handle
             .as_ref()
             .cloned()
             .ok_or_else(|| LogError::Storage("OPFS handle missing for export".into()))
             .map_err(JsValue::from)?;
 
-        let stream = LogExporter::export_logs(handle, size).map_err(JsValue::from)?;
+        let header = LogExporter::session_header(repo.current_metadata().as_ref(), &repo.bookmarks);
+        let stream = match repo.times.reader().filter(|_| self.include_timestamp) {
+            Some(times) => LogExporter::export_timestamped_logs(handle, size, times, header),
+            None => LogExporter::export_logs(handle, size, header),
+        }
+        .map_err(JsValue::from)?;
+        let name = state.filename.as_deref().unwrap_or("serial_log.txt");
+        state.post_export_stream(&stream, name);
+        Ok(true)
+    }
+}
+
+pub struct ExportRawCommand;
+
+impl WorkerCommand for ExportRawCommand {
+    fn execute(
+        &self,
+        state: &mut WorkerState,
+        _state_rc: &Rc<RefCell<WorkerState>>,
+    ) -> Result<bool, JsValue> {
+        let raw = &state.proc.repository.raw;
+        while raw.compact(u64::MAX)? {}
+        let handle = raw
+            .data_handle()
+            .cloned()
+            .ok_or_else(|| LogError::Storage("Raw capture is not available".into()))?;
 
-        let resp = js_sys::Object::new();
-        let _ = js_sys::Reflect::set(&resp, &"type".into(), &"EXPORT_STREAM".into());
-        let _ = js_sys::Reflect::set(&resp, &"stream".into(), &stream);
-        let _ = state
-            .scope
-            .post_message_with_transfer(&resp, &js_sys::Array::of1(&stream));
+        let stream = LogExporter::export_logs(handle, ByteOffset(raw.size()), None)?;
+        let name = state
+            .filename
+            .as_deref()
+            .map(|f| sidecar_file_name(f, "bin"))
+            .unwrap_or_else(|| "serial_log.bin".to_string());
+        state.post_export_stream(&stream, &name);
         Ok(true)
     }
 }
+
+pub struct RequestRawLineCommand(pub usize);
+
+impl WorkerCommand for RequestRawLineCommand {
+    fn execute(
+        &self,
+        state: &mut WorkerState,
+        _state_rc: &Rc<RefCell<WorkerState>>,
+    ) -> Result<bool, JsValue> {
+        let repo = &state.proc.repository;
+        let range = repo
+            .raw_line_range(LineIndex(self.0))
+            .ok_or_else(|| LogError::Storage("No raw bytes recorded for this line".into()))?;
+
+        let bytes = repo.raw.read(range)?;
+        let received_at = repo.raw.received_at(range.start);
+        state.send_msg(WorkerMsg::RawLine(RawLine {
+            line: self.0,
+            bytes,
+            received_at,
+        }));
+        Ok(true)
+    }
+}
+
+pub struct AddBookmarkCommand {
+    pub line: usize,
+    pub note: String,
+}
+
+impl WorkerCommand for AddBookmarkCommand {
+    fn execute(
+        &self,
+        state: &mut WorkerState,
+        state_rc: &Rc<RefCell<WorkerState>>,
+    ) -> Result<bool, JsValue> {
+        state
+            .proc
+            .repository
+            .add_bookmark(LineIndex(self.line), self.note.clone())?;
+        send_bookmarks(state, state_rc)?;
+        Ok(true)
+    }
+}
+
+pub struct RemoveBookmarkCommand(pub usize);
+
+impl WorkerCommand for RemoveBookmarkCommand {
+    fn execute(
+        &self,
+        state: &mut WorkerState,
+        state_rc: &Rc<RefCell<WorkerState>>,
+    ) -> Result<bool, JsValue> {
+        state.proc.repository.remove_bookmark(self.0)?;
+        send_bookmarks(state, state_rc)?;
+        Ok(true)
+    }
+}
+
+pub struct ListBookmarksCommand;
+
+impl WorkerCommand for ListBookmarksCommand {
+    fn execute(
+        &self,
+        state: &mut WorkerState,
+        _state_rc: &Rc<RefCell<WorkerState>>,
+    ) -> Result<bool, JsValue> {
+        state.send_msg(WorkerMsg::BookmarkList(
+            state.proc.repository.bookmarks.clone(),
+        ));
+        Ok(true)
+    }
+}
+
+pub struct SeekBookmarkCommand {
+    pub from: usize,
+    pub forward: bool,
+}
+
+impl WorkerCommand for SeekBookmarkCommand {
+    fn execute(
+        &self,
+        state: &mut WorkerState,
+        _state_rc: &Rc<RefCell<WorkerState>>,
+    ) -> Result<bool, JsValue> {
+        match state
+            .proc
+            .repository
+            .seek_bookmark(LineIndex(self.from), self.forward)
+        {
+            Some(line) => state.send_msg(WorkerMsg::ScrollToLine(line)),
+            None => state.send_msg(WorkerMsg::Notice("No more bookmarks in this view".into())),
+        }
+        Ok(true)
+    }
+}
+
+pub struct FindCommand {
+    pub query: String,
+    pub match_case: bool,
+    pub use_regex: bool,
+}
+
+impl WorkerCommand for FindCommand {
+    fn execute(
+        &self,
+        state: &mut WorkerState,
+        state_rc: &Rc<RefCell<WorkerState>>,
+    ) -> Result<bool, JsValue> {
+        state.proc.find = if self.query.trim().is_empty() {
+            None
+        } else {
+            Some(
+                ActiveFilterBuilder::new(self.query.clone())
+                    .case_sensitive(self.match_case)
+                    .regex(self.use_regex)
+                    .build()
+                    .map_err(LogError::Regex)?,
+            )
+        };
+        // Stop any walk for the previous expression
+        state.current_find_id += 1;
+        if let Some((start_line, count)) = state.last_window {
+            RequestWindowCommand { start_line, count }.execute(state, state_rc)?;
+        }
+        Ok(true)
+    }
+}
+
+pub struct FindNextCommand {
+    pub from: usize,
+    pub occurrence: Option<usize>,
+    pub forward: bool,
+}
+
+impl WorkerCommand for FindNextCommand {
+    fn execute(
+        &self,
+        _state: &mut WorkerState,
+        state_rc: &Rc<RefCell<WorkerState>>,
+    ) -> Result<bool, JsValue> {
+        let task =
+            LogSearcher::find_async(state_rc.clone(), self.from, self.occurrence, self.forward);
+        let state_rc = state_rc.clone();
+        spawn_local(async move {
+            if let Err(e) = task.await {
+                state_rc.borrow().send_error(JsValue::from(e));
+            }
+        });
+        Ok(true)
+    }
+}
+
+pub struct RevealLineCommand(pub usize);
+
+impl WorkerCommand for RevealLineCommand {
+    fn execute(
+        &self,
+        state: &mut WorkerState,
+        _state_rc: &Rc<RefCell<WorkerState>>,
+    ) -> Result<bool, JsValue> {
+        let Some(index) = self.reveal(&mut state.proc) else {
+            state.send_msg(WorkerMsg::Error("Line was dropped from the session".into()));
+            return Ok(true);
+        };
+
+        // Stop the scan of the filter being cleared
+        state.current_search_id += 1;
+        let count = state.proc.repository.get_line_count();
+        state.last_reported_count = count;
+        state.send_msg(WorkerMsg::TotalLines(count));
+        state.send_msg(WorkerMsg::ScrollToLine(index));
+        Ok(true)
+    }
+}
+
+impl RevealLineCommand {
+    /// Clears the filter and returns the view index of the line, or `None`
+    /// if the ring buffer dropped it
+    fn reveal<B: StorageBackend + Default>(&self, proc: &mut LogProcessor<B>) -> Option<usize> {
+        let repo = &mut proc.repository;
+        let line = self
+            .0
+            .checked_sub(repo.index.first_line)
+            .filter(|&l| l < repo.index.line_count)?;
+        repo.index.clear_filter();
+        repo.view_index(line)
+    }
+}
+
+/// Sends the bookmark list and refreshes the gutter markers of the shown window
+fn send_bookmarks(
+    state: &mut WorkerState,
+    state_rc: &Rc<RefCell<WorkerState>>,
+) -> Result<(), JsValue> {
+    state.send_msg(WorkerMsg::BookmarkList(
+        state.proc.repository.bookmarks.clone(),
+    ));
+    if let Some((start_line, count)) = state.last_window {
+        RequestWindowCommand { start_line, count }.execute(state, state_rc)?;
+    }
+    Ok(())
+}
+
+/// Runs a session library task in the background and reports its failure
+fn spawn_session_task<F>(state_rc: &Rc<RefCell<WorkerState>>, task: F)
+where
+    F: std::future::Future<Output = Result<(), LogError>> + 'static,
+{
+    let state_rc = state_rc.clone();
+    spawn_local(async move {
+        if let Err(e) = task.await {
+            state_rc.borrow().send_error(JsValue::from(e));
+        }
+    });
+}
+
+pub struct ListSessionsCommand;
+
+impl WorkerCommand for ListSessionsCommand {
+    fn execute(
+        &self,
+        _state: &mut WorkerState,
+        state_rc: &Rc<RefCell<WorkerState>>,
+    ) -> Result<bool, JsValue> {
+        spawn_session_task(state_rc, SessionLibrary::list(state_rc.clone()));
+        Ok(true)
+    }
+}
+
+pub struct OpenSessionCommand(pub String);
+
+impl WorkerCommand for OpenSessionCommand {
+    fn execute(
+        &self,
+        _state: &mut WorkerState,
+        state_rc: &Rc<RefCell<WorkerState>>,
+    ) -> Result<bool, JsValue> {
+        spawn_session_task(
+            state_rc,
+            SessionLibrary::open(state_rc.clone(), self.0.clone()),
+        );
+        Ok(true)
+    }
+}
+
+pub struct RenameSessionCommand {
+    pub name: String,
+    pub label: String,
+}
+
+impl WorkerCommand for RenameSessionCommand {
+    fn execute(
+        &self,
+        _state: &mut WorkerState,
+        state_rc: &Rc<RefCell<WorkerState>>,
+    ) -> Result<bool, JsValue> {
+        spawn_session_task(
+            state_rc,
+            SessionLibrary::rename(state_rc.clone(), self.name.clone(), self.label.clone()),
+        );
+        Ok(true)
+    }
+}
+
+pub struct DeleteSessionCommand(pub String);
+
+impl WorkerCommand for DeleteSessionCommand {
+    fn execute(
+        &self,
+        _state: &mut WorkerState,
+        state_rc: &Rc<RefCell<WorkerState>>,
+    ) -> Result<bool, JsValue> {
+        spawn_session_task(
+            state_rc,
+            SessionLibrary::delete(state_rc.clone(), self.0.clone()),
+        );
+        Ok(true)
+    }
+}
+
+pub struct DownloadSessionCommand(pub String);
+
+impl WorkerCommand for DownloadSessionCommand {
+    fn execute(
+        &self,
+        _state: &mut WorkerState,
+        state_rc: &Rc<RefCell<WorkerState>>,
+    ) -> Result<bool, JsValue> {
+        spawn_session_task(
+            state_rc,
+            SessionLibrary::download(state_rc.clone(), self.0.clone()),
+        );
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::worker::repository::storage::{MemoryBackend, SessionFiles};
+
+    fn window_texts(proc: &LogProcessor<MemoryBackend>, start_line: usize) -> Vec<String> {
+        let msg = RequestWindowCommand {
+            start_line,
+            count: 10,
+        }
+        .window(proc)
+        .unwrap();
+        let WorkerMsg::LogWindow { lines, .. } = msg else {
+            panic!("expected a window");
+        };
+        lines.into_iter().map(|(_, line)| line.text()).collect()
+    }
+
+    #[test]
+    fn test_window_renders_timestamps_on_demand() {
+        let mut proc = LogProcessor::<MemoryBackend>::new();
+        proc.set_sync_handle(SessionFiles::in_memory()).unwrap();
+        proc.append_chunk(b"boot\nready\n", false).unwrap();
+        proc.record_times = false;
+        proc.append_chunk(b"imported\n", false).unwrap();
+
+        assert_eq!(window_texts(&proc, 0), ["boot", "ready", "imported"]);
+        assert_eq!(window_texts(&proc, 2), ["imported"]);
+        assert!(window_texts(&proc, 5).is_empty());
+
+        // Times live outside the text, so they apply to lines already received
+        proc.show_timestamps = true;
+        let texts = window_texts(&proc, 0);
+        assert!(texts[0].starts_with('[') && texts[0].ends_with("] boot"));
+        assert_eq!(texts[2], "imported");
+    }
+
+    #[test]
+    fn test_window_carries_styled_spans() {
+        let mut proc = LogProcessor::<MemoryBackend>::new();
+        proc.set_sync_handle(SessionFiles::in_memory()).unwrap();
+        proc.append_chunk(b"\x1b[31mwifi\x1b[0m lost, wifi retry\n", false)
+            .unwrap();
+        proc.highlights = vec![Highlight {
+            text: "retry".into(),
+            color: "blue".into(),
+            ..Default::default()
+        }];
+        proc.find = Some(ActiveFilterBuilder::new("wifi".into()).build().unwrap());
+
+        let msg = RequestWindowCommand {
+            start_line: 0,
+            count: 1,
+        }
+        .window(&proc)
+        .unwrap();
+        let WorkerMsg::LogWindow { lines, .. } = msg else {
+            panic!("expected a window");
+        };
+        let pieces: Vec<(&str, Option<&str>, Option<usize>)> = lines[0]
+            .1
+            .spans
+            .iter()
+            .map(|s| (s.text.as_str(), s.color.as_deref(), s.mark))
+            .collect();
+        assert_eq!(
+            pieces,
+            [
+                ("wifi", Some("#ef4444"), Some(0)),
+                (" lost, ", None, None),
+                ("wifi", None, Some(1)),
+                (" ", None, None),
+                ("retry", Some("blue"), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reveal_line_in_full_log() {
+        let mut proc = LogProcessor::<MemoryBackend>::new();
+        proc.set_sync_handle(SessionFiles::in_memory()).unwrap();
+        let mut text = String::new();
+        for i in 0..20 {
+            let level = if i % 5 == 0 { 'E' } else { 'I' };
+            text.push_str(&format!("{} ({}) app: line {}\n", level, i, i));
+        }
+        proc.append_chunk(text.as_bytes(), false).unwrap();
+        proc.drop_oldest(proc.repository.log_size() / 2).unwrap();
+        let first = proc.repository.index.first_line;
+        assert!((1..15).contains(&first));
+
+        // Only errors are shown, among them absolute line 15
+        let repo = &mut proc.repository;
+        let scan = LogSearcher::begin(
+            repo,
+            String::new(),
+            false,
+            false,
+            false,
+            (0, 0),
+            Some(LogLevel::Error),
+        );
+        assert!(scan.unwrap().is_none());
+        let errors_before = (first..15).filter(|l| l % 5 == 0).count();
+        assert_eq!(repo.view_index(15 - first), Some(errors_before));
+
+        assert_eq!(RevealLineCommand(15).reveal(&mut proc), Some(15 - first));
+        assert!(!proc.repository.is_filtering());
+        assert_eq!(proc.repository.line_number(LineIndex(15 - first)), Some(15));
+        assert_eq!(RevealLineCommand(first - 1).reveal(&mut proc), None);
+    }
+}
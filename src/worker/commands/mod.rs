@@ -4,14 +4,23 @@ pub mod handlers;
 pub use command::WorkerCommand;
 pub use handlers::*;
 
+use crate::config::MAX_CONTEXT_LINES;
 use crate::worker::types::WorkerMsg;
 
 /// Factory to convert WorkerMsg into a specific Command
 pub fn create_command_from_msg(msg: WorkerMsg) -> Box<dyn WorkerCommand> {
     match msg {
-        WorkerMsg::NewSession => Box::new(NewSessionCommand),
+        WorkerMsg::StartSession(metadata) => Box::new(StartSessionCommand(metadata)),
+        WorkerMsg::EndSession => Box::new(EndSessionCommand),
         WorkerMsg::AppendChunk { chunk, is_hex } => Box::new(AppendChunkCommand { chunk, is_hex }),
         WorkerMsg::SetTimestampState(enabled) => Box::new(SetTimestampStateCommand(enabled)),
+        WorkerMsg::SetHighlights(rules) => Box::new(SetHighlightsCommand(rules)),
+        WorkerMsg::SetEncoding(encoding) => Box::new(SetEncodingCommand(encoding)),
+        WorkerMsg::SetJsonColumns(columns) => Box::new(SetJsonColumnsCommand(columns)),
+        WorkerMsg::SortBy(spec) => Box::new(SortByCommand(spec)),
+        WorkerMsg::SetSessionLimit(limit) => Box::new(SetSessionLimitCommand(limit)),
+        WorkerMsg::SetLowSpacePolicy(policy) => Box::new(SetLowSpacePolicyCommand(policy)),
+        WorkerMsg::SetSearchIndex(size) => Box::new(SetSearchIndexCommand(size)),
 
         WorkerMsg::RequestWindow { start_line, count } => {
             Box::new(RequestWindowCommand { start_line, count })
@@ -23,13 +32,58 @@ pub fn create_command_from_msg(msg: WorkerMsg) -> Box<dyn WorkerCommand> {
             match_case,
             use_regex,
             invert,
+            context_before,
+            context_after,
+            min_level,
         } => Box::new(SearchLogsCommand {
             query,
             match_case,
             use_regex,
             invert,
+            context: (
+                context_before.min(MAX_CONTEXT_LINES),
+                context_after.min(MAX_CONTEXT_LINES),
+            ),
+            min_level,
         }),
-        WorkerMsg::ExportLogs { .. } => Box::new(ExportLogsCommand),
+        WorkerMsg::CancelSearch => Box::new(CancelSearchCommand),
+        WorkerMsg::ExportLogs { include_timestamp } => {
+            Box::new(ExportLogsCommand { include_timestamp })
+        }
+        WorkerMsg::ExportRaw => Box::new(ExportRawCommand),
+        WorkerMsg::RequestRawLine(line) => Box::new(RequestRawLineCommand(line)),
+
+        WorkerMsg::AddBookmark { line, note } => Box::new(AddBookmarkCommand { line, note }),
+        WorkerMsg::RemoveBookmark(line) => Box::new(RemoveBookmarkCommand(line)),
+        WorkerMsg::ListBookmarks => Box::new(ListBookmarksCommand),
+        WorkerMsg::SeekBookmark { from, forward } => {
+            Box::new(SeekBookmarkCommand { from, forward })
+        }
+        WorkerMsg::RevealLine(line) => Box::new(RevealLineCommand(line)),
+        WorkerMsg::Find {
+            query,
+            match_case,
+            use_regex,
+        } => Box::new(FindCommand {
+            query,
+            match_case,
+            use_regex,
+        }),
+        WorkerMsg::FindNext {
+            from,
+            occurrence,
+            forward,
+        } => Box::new(FindNextCommand {
+            from,
+            occurrence,
+            forward,
+        }),
+
+        WorkerMsg::ListSessions => Box::new(ListSessionsCommand),
+        WorkerMsg::OpenSession(name) => Box::new(OpenSessionCommand(name)),
+        WorkerMsg::RenameSession { name, label } => Box::new(RenameSessionCommand { name, label }),
+        WorkerMsg::DeleteSession(name) => Box::new(DeleteSessionCommand(name)),
+        WorkerMsg::DownloadSession(name) => Box::new(DownloadSessionCommand(name)),
 
         _ => Box::new(NoOpCommand), // Fallback for handled/error messages
     }
@@ -1,4 +1,6 @@
-use crate::worker::commands::{create_command_from_msg, AppendChunkCommand, WorkerCommand};
+use crate::worker::commands::{
+    create_command_from_msg, AppendChunkCommand, ImportFileCommand, WorkerCommand,
+};
 use crate::worker::state::WorkerState;
 use crate::worker::types::WorkerMsg;
 use std::cell::RefCell;
@@ -12,7 +14,7 @@ pub fn handle_message(state_rc: Rc<RefCell<WorkerState>>, data: JsValue) {
             let command = create_command_from_msg(msg);
             match command.execute(&mut state, &state_rc) {
                 Ok(false) => {
-                    // NewSession needs async handling
+                    // StartSession needs async handling
                     drop(state);
                     WorkerState::handle_new_session(state_rc);
                 }
@@ -39,17 +41,29 @@ fn handle_object_message(
         .ok()
         .and_then(|v| v.as_string());
 
-    if let Some("AppendChunk") = cmd.as_deref() {
-        if let Ok(chunk_val) = js_sys::Reflect::get(data, &"chunk".into()) {
-            let chunk = js_sys::Uint8Array::new(&chunk_val).to_vec();
-            let is_hex = js_sys::Reflect::get(data, &"is_hex".into())
-                .ok()
-                .and_then(|v| v.as_bool())
-                .unwrap_or(false);
+    let is_hex = js_sys::Reflect::get(data, &"is_hex".into())
+        .ok()
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
 
-            let command = AppendChunkCommand { chunk, is_hex };
-            command.execute(&mut state, state_rc)?;
+    match cmd.as_deref() {
+        Some("AppendChunk") => {
+            if let Ok(chunk_val) = js_sys::Reflect::get(data, &"chunk".into()) {
+                let chunk = js_sys::Uint8Array::new(&chunk_val).to_vec();
+                let command = AppendChunkCommand { chunk, is_hex };
+                command.execute(&mut state, state_rc)?;
+            }
+        }
+        Some("ImportFile") => {
+            if let Ok(file) = js_sys::Reflect::get(data, &"file".into()) {
+                let command = ImportFileCommand {
+                    file: file.dyn_into()?,
+                    is_hex,
+                };
+                command.execute(&mut state, state_rc)?;
+            }
         }
+        _ => {}
     }
     Ok(())
 }
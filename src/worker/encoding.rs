@@ -0,0 +1,148 @@
+use crate::types::RxEncoding;
+use encoding_rs::{
+    CoderResult, Decoder, Encoding, BIG5, EUC_JP, EUC_KR, GBK, SHIFT_JIS, WINDOWS_1252,
+};
+use std::borrow::Cow;
+
+/// Code page 437 characters for bytes 0x80..=0xFF; lower bytes are ASCII
+#[rustfmt::skip]
+const CP437_HIGH: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å',
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ',
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»',
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐',
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧',
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀',
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩',
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{a0}',
+];
+
+/// Converts received bytes to UTF-8 for the terminal parser.
+/// Multibyte sequences split across chunks are completed with the next chunk.
+pub struct RxDecoder {
+    encoding: RxEncoding,
+    /// Streaming decoder for the multibyte encodings
+    decoder: Option<Decoder>,
+}
+
+impl RxDecoder {
+    pub fn new(encoding: RxEncoding) -> Self {
+        Self {
+            encoding,
+            decoder: codec(encoding).map(Encoding::new_decoder_without_bom_handling),
+        }
+    }
+
+    pub fn encoding(&self) -> RxEncoding {
+        self.encoding
+    }
+
+    /// Decodes `bytes` to UTF-8. `last` ends the input, as at a line ending:
+    /// an incomplete sequence is then replaced instead of waiting for more bytes.
+    pub fn decode<'a>(&mut self, bytes: &'a [u8], last: bool) -> Cow<'a, [u8]> {
+        match self.encoding {
+            RxEncoding::Utf8 => Cow::Borrowed(bytes),
+            _ if bytes.is_ascii() && self.decoder.is_none() => Cow::Borrowed(bytes),
+            RxEncoding::Latin1 => Cow::Owned(
+                bytes
+                    .iter()
+                    .map(|&b| b as char)
+                    .collect::<String>()
+                    .into_bytes(),
+            ),
+            RxEncoding::Cp437 => Cow::Owned(
+                bytes
+                    .iter()
+                    .map(|&b| match b {
+                        0x80.. => CP437_HIGH[b as usize - 0x80],
+                        _ => b as char,
+                    })
+                    .collect::<String>()
+                    .into_bytes(),
+            ),
+            _ => Cow::Owned(self.decode_multibyte(bytes, last).into_bytes()),
+        }
+    }
+
+    fn decode_multibyte(&mut self, bytes: &[u8], last: bool) -> String {
+        let Some(decoder) = self.decoder.as_mut() else {
+            return String::from_utf8_lossy(bytes).into_owned();
+        };
+        let mut out = String::with_capacity(
+            decoder
+                .max_utf8_buffer_length(bytes.len())
+                .unwrap_or(bytes.len() * 3),
+        );
+        let mut read = 0;
+        loop {
+            let (result, n, _) = decoder.decode_to_string(&bytes[read..], &mut out, last);
+            read += n;
+            match result {
+                CoderResult::InputEmpty => break,
+                CoderResult::OutputFull => out.reserve(bytes.len() - read + 16),
+            }
+        }
+        if last {
+            // A finished decoder must not be fed again
+            *self = Self::new(self.encoding);
+        }
+        out
+    }
+}
+
+/// encoding_rs codec of the encodings that are not decoded byte by byte
+fn codec(encoding: RxEncoding) -> Option<&'static Encoding> {
+    match encoding {
+        RxEncoding::Utf8 | RxEncoding::Latin1 | RxEncoding::Cp437 => None,
+        RxEncoding::Windows1252 => Some(WINDOWS_1252),
+        RxEncoding::ShiftJis => Some(SHIFT_JIS),
+        RxEncoding::EucJp => Some(EUC_JP),
+        RxEncoding::EucKr => Some(EUC_KR),
+        RxEncoding::Gbk => Some(GBK),
+        RxEncoding::Big5 => Some(BIG5),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(encoding: RxEncoding, chunks: &[&[u8]]) -> String {
+        let mut decoder = RxDecoder::new(encoding);
+        let mut out = Vec::new();
+        for (i, chunk) in chunks.iter().enumerate() {
+            out.extend_from_slice(&decoder.decode(chunk, i + 1 == chunks.len()));
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_single_byte_encodings() {
+        assert_eq!(decode(RxEncoding::Utf8, &["é".as_bytes()]), "é");
+        assert_eq!(
+            decode(RxEncoding::Latin1, &[b"caf\xe9 \x80"]),
+            "café \u{80}"
+        );
+        assert_eq!(
+            decode(RxEncoding::Windows1252, &[b"\x80 \x93x\x94"]),
+            "€ “x”"
+        );
+        assert_eq!(
+            decode(RxEncoding::Cp437, &[b"\xc9\xcd\xbb \x1b[1m"]),
+            "╔═╗ \x1b[1m"
+        );
+    }
+
+    #[test]
+    fn test_multibyte_split_across_chunks() {
+        // "日本" in Shift_JIS, split inside the second character
+        assert_eq!(
+            decode(RxEncoding::ShiftJis, &[b"\x93\xfa\x96", b"\x7b!"]),
+            "日本!"
+        );
+        assert_eq!(decode(RxEncoding::EucKr, &[b"\xc7", b"\xd1"]), "한");
+        // The end of input replaces a dangling lead byte
+        assert_eq!(decode(RxEncoding::ShiftJis, &[b"a\x93"]), "a\u{fffd}");
+        assert_eq!(decode(RxEncoding::Gbk, &[b"\xd6\xd0"]), "中");
+    }
+}
@@ -1,6 +1,10 @@
 use crate::config::EXPORT_CHUNK_SIZE;
+use crate::types::{Bookmark, RxEncoding, SessionMetadata};
 use crate::worker::error::LogError;
+use crate::worker::formatter::LogFormatter;
 use crate::worker::repository::index::ByteOffset;
+use crate::worker::repository::storage::TimeFile;
+use std::rc::Rc;
 use wasm_bindgen::prelude::*;
 use wasm_streams::ReadableStream;
 use web_sys::{FileSystemReadWriteOptions, FileSystemSyncAccessHandle};
@@ -13,15 +17,19 @@ impl LogExporter {
         Self
     }
 
-    /// Creates a ReadableStream for exporting logs
+    /// Creates a ReadableStream for exporting logs, optionally preceded by a header
     pub fn export_logs(
         handle: FileSystemSyncAccessHandle,
         file_size: ByteOffset,
+        header: Option<String>,
     ) -> Result<js_sys::Object, LogError> {
         let size = file_size;
         let backend = handle;
 
-        let stream = futures_util::stream::unfold(ByteOffset(0), move |off| {
+        let header = futures_util::stream::iter(
+            header.map(|h| Ok(JsValue::from(js_sys::Uint8Array::from(h.as_bytes())))),
+        );
+        let body = futures_util::stream::unfold(ByteOffset(0), move |off| {
             let h = backend.clone();
             async move {
                 if off.0 >= size.0 {
@@ -39,8 +47,114 @@ impl LogExporter {
                 Some((Ok(res), ByteOffset(off.0 + len as u64)))
             }
         });
+        let stream = futures_util::StreamExt::chain(header, body);
         Ok(ReadableStream::from_stream(stream).into_raw().into())
     }
+
+    /// Creates a ReadableStream of the log with each line prefixed by its receive time
+    pub fn export_timestamped_logs(
+        handle: FileSystemSyncAccessHandle,
+        file_size: ByteOffset,
+        times: TimeFile,
+        header: Option<String>,
+    ) -> Result<js_sys::Object, LogError> {
+        let size = file_size;
+        let backend = handle;
+        let times = Rc::new(times);
+
+        let header = futures_util::stream::iter(
+            header.map(|h| Ok(JsValue::from(js_sys::Uint8Array::from(h.as_bytes())))),
+        );
+        let body = futures_util::stream::unfold((ByteOffset(0), 0), move |(off, line)| {
+            let h = backend.clone();
+            let times = times.clone();
+            async move {
+                if off.0 >= size.0 {
+                    return None;
+                }
+                let len = (size.0 - off.0).min(EXPORT_CHUNK_SIZE) as usize;
+                let mut buf = vec![0u8; len];
+                let opts = FileSystemReadWriteOptions::new();
+                opts.set_at(off.0 as f64);
+                if h.read_with_u8_array_and_options(&mut buf, &opts).is_err() {
+                    return None;
+                }
+                // Stop at the last complete line so every line gets its own time
+                let end = match buf.iter().rposition(|&b| b == b'\n') {
+                    Some(pos) if off.0 + (len as u64) < size.0 => pos + 1,
+                    _ => len,
+                };
+
+                let lines: Vec<&[u8]> = buf[..end].split_inclusive(|&b| b == b'\n').collect();
+                let stamps = times.read(line, lines.len());
+                let mut out = Vec::with_capacity(end + lines.len() * 16);
+                for (i, text) in lines.iter().enumerate() {
+                    if let Some(us) = stamps.get(i).copied().flatten() {
+                        out.extend_from_slice(LogFormatter::format_timestamp(us).as_bytes());
+                        out.push(b' ');
+                    }
+                    out.extend_from_slice(text);
+                }
+
+                let res = JsValue::from(js_sys::Uint8Array::from(&out[..]));
+                Some((Ok(res), (off + end as u64, line + lines.len())))
+            }
+        });
+        let stream = futures_util::StreamExt::chain(header, body);
+        Ok(ReadableStream::from_stream(stream).into_raw().into())
+    }
+
+    /// Export header describing a session: capture metadata followed by its bookmarks
+    pub fn session_header(
+        metadata: Option<&SessionMetadata>,
+        bookmarks: &[Bookmark],
+    ) -> Option<String> {
+        let mut header = metadata.map(Self::metadata_header).unwrap_or_default();
+        if !bookmarks.is_empty() {
+            // Bookmarks are numbered by line of the exported file, which starts
+            // with this header: the metadata, the title, one line each and `#`
+            let header_lines = header.lines().count() + bookmarks.len() + 2;
+            let first_line = metadata.map_or(0, |m| m.dropped_lines);
+            header.push_str("# Bookmarks:\n");
+            for b in bookmarks {
+                // Line numbers are 1-based as shown in editors
+                let line = header_lines + b.line.saturating_sub(first_line) + 1;
+                header.push_str(&format!("#   line {}: {}\n", line, b.note));
+            }
+            header.push_str("#\n");
+        }
+        (!header.is_empty()).then_some(header)
+    }
+
+    /// Describes how a session was captured, as `#` comment lines
+    pub fn metadata_header(metadata: &SessionMetadata) -> String {
+        let time = |ms: i64| {
+            chrono::DateTime::from_timestamp_millis(ms)
+                .map(|t| t.with_timezone(&chrono::Local).to_rfc3339())
+                .unwrap_or_default()
+        };
+
+        let mut header = match &metadata.imported_from {
+            Some(file) => format!("# Imported from: {}\n", file),
+            None => format!(
+                "# Port: {}, flow control: {}\n",
+                metadata.port_summary(),
+                metadata.flow_control
+            ),
+        };
+        if let Some(usb) = metadata.usb_id() {
+            header.push_str(&format!("# Device: USB {}\n", usb));
+        }
+        if metadata.encoding != RxEncoding::Utf8 {
+            header.push_str(&format!("# Encoding: {}\n", metadata.encoding));
+        }
+        header.push_str(&format!("# Started: {}\n", time(metadata.started_at)));
+        if let Some(ended) = metadata.ended_at {
+            header.push_str(&format!("# Ended: {}\n", time(ended)));
+        }
+        header.push_str(&format!("# Lines: {}\n#\n", metadata.line_count));
+        header
+    }
 }
 
 impl Default for LogExporter {
@@ -1,8 +1,9 @@
 use chrono::Timelike;
+use regex::Regex;
 use std::fmt::Write;
 
 pub trait LogFormatterStrategy {
-    fn format(&self, text: &str, timestamp: &str) -> String;
+    fn format(&self, text: &str) -> String;
     fn format_chunk(&self, chunk: &[u8]) -> String;
     fn max_line_length(&self) -> usize;
 }
@@ -12,12 +13,8 @@ pub struct DefaultFormatter {
 }
 
 impl LogFormatterStrategy for DefaultFormatter {
-    fn format(&self, text: &str, timestamp: &str) -> String {
-        if timestamp.is_empty() {
-            format!("{}\n", text)
-        } else {
-            format!("{} {}\n", timestamp, text)
-        }
+    fn format(&self, text: &str) -> String {
+        format!("{}\n", text)
     }
 
     fn format_chunk(&self, _chunk: &[u8]) -> String {
@@ -34,14 +31,8 @@ pub struct HexFormatter {
 }
 
 impl LogFormatterStrategy for HexFormatter {
-    fn format(&self, text: &str, timestamp: &str) -> String {
-        if timestamp.is_empty() {
-            format!("{}\n", text)
-        } else if text.is_empty() {
-            format!("{}\n", timestamp)
-        } else {
-            format!("{} {}\n", timestamp, text)
-        }
+    fn format(&self, text: &str) -> String {
+        format!("{}\n", text)
     }
 
     fn format_chunk(&self, chunk: &[u8]) -> String {
@@ -68,17 +59,63 @@ impl LogFormatter {
         Self
     }
 
-    pub fn get_timestamp(&self) -> String {
-        let now = chrono::Utc::now();
+    /// Current time in microseconds since the epoch, using the high-resolution
+    /// `performance` clock when available
+    pub fn now_micros() -> i64 {
+        #[cfg(target_arch = "wasm32")]
+        {
+            use wasm_bindgen::JsCast;
+            let performance = js_sys::Reflect::get(&js_sys::global(), &"performance".into())
+                .ok()
+                .filter(|p| !p.is_undefined());
+            if let Some(p) = performance {
+                let p: web_sys::Performance = p.unchecked_into();
+                return ((p.time_origin() + p.now()) * 1000.0) as i64;
+            }
+        }
+        chrono::Utc::now().timestamp_micros()
+    }
+
+    /// Renders a receive time as the `[HH:MM:SS.mmm]` line prefix
+    pub fn format_timestamp(micros: i64) -> String {
+        let Some(time) = chrono::DateTime::from_timestamp_micros(micros) else {
+            return String::new();
+        };
         format!(
             "[{:02}:{:02}:{:02}.{:03}]",
-            now.hour(),
-            now.minute(),
-            now.second(),
-            now.timestamp_subsec_millis()
+            time.hour(),
+            time.minute(),
+            time.second(),
+            time.timestamp_subsec_millis()
         )
     }
 
+    /// Prefixes a line with its receive time, if known
+    pub fn with_timestamp(text: &str, micros: Option<i64>) -> String {
+        match micros {
+            Some(us) => format!("{} {}", Self::format_timestamp(us), text),
+            None => text.to_string(),
+        }
+    }
+
+    /// Text of a line as the monitor shows it: CSI sequences are dropped and
+    /// cursor-forward (`ESC[nC`) becomes spaces, so columns match the rendering
+    pub fn plain_text(text: &str) -> String {
+        thread_local! {
+            static CSI_RE: Regex = Regex::new(r"\x1B\[([0-9;:]*)([A-Za-z])").unwrap();
+        }
+        if !text.contains('\x1B') {
+            return text.to_string();
+        }
+        CSI_RE.with(|re| {
+            re.replace_all(text, |c: &regex::Captures| match &c[2] {
+                "C" => " ".repeat(c[1].parse::<usize>().unwrap_or(1)),
+                _ => String::new(),
+            })
+            .into_owned()
+        })
+    }
+
     pub fn create_strategy(&self, is_hex: bool, max_bytes: usize) -> Box<dyn LogFormatterStrategy> {
         if is_hex {
             Box::new(HexFormatter { max_bytes })
@@ -87,3 +124,27 @@ impl LogFormatter {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timestamp_prefix() {
+        // 01:02:03.456789 UTC
+        let us = ((3600 + 2 * 60 + 3) * 1_000_000) + 456_789;
+        assert_eq!(
+            LogFormatter::with_timestamp("boot", Some(us)),
+            "[01:02:03.456] boot"
+        );
+        assert_eq!(LogFormatter::with_timestamp("boot", None), "boot");
+    }
+
+    #[test]
+    fn test_plain_text() {
+        assert_eq!(
+            LogFormatter::plain_text("\x1B[0;32mI (5) wifi:\x1B[2Cup\x1B[0m\x1B[K"),
+            "I (5) wifi:  up"
+        );
+    }
+}
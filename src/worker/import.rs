@@ -0,0 +1,99 @@
+use crate::config::IMPORT_CHUNK_SIZE;
+use crate::types::SessionMetadata;
+use crate::worker::error::LogError;
+use crate::worker::session::SessionLibrary;
+use crate::worker::state::WorkerState;
+use crate::worker::types::WorkerMsg;
+use std::cell::RefCell;
+use std::rc::Rc;
+use wasm_bindgen_futures::JsFuture;
+
+/// Streams a user supplied `.log` / `.bin` file into a new session,
+/// apart from the live one
+pub struct LogImporter;
+
+impl LogImporter {
+    /// Imports `file` slice by slice through `LogProcessor::append_chunk`.
+    /// Each slice is read asynchronously, so window requests and searches are
+    /// served between slices and a large file never holds the worker.
+    pub async fn import_async(
+        state_rc: Rc<RefCell<WorkerState>>,
+        file: web_sys::File,
+        is_hex: bool,
+    ) -> Result<(), LogError> {
+        {
+            let mut s = state_rc.borrow_mut();
+            if s.importing {
+                return Err(LogError::Storage("An import is already running".into()));
+            }
+            s.importing = true;
+        }
+
+        let metadata = SessionMetadata {
+            started_at: chrono::Utc::now().timestamp_millis(),
+            imported_from: Some(file.name()),
+            ..Default::default()
+        };
+        let result = match SessionLibrary::open_import(&state_rc, metadata).await {
+            Ok(name) => Self::stream_file(&state_rc, &file, is_hex, name).await,
+            Err(e) => Err(e),
+        };
+
+        let lines = {
+            let mut s = state_rc.borrow_mut();
+            s.importing = false;
+            if result.is_ok() {
+                if let Err(e) = s.proc.repository.finish_session() {
+                    s.send_error(e.into());
+                }
+            }
+            s.proc.get_line_count() as usize
+        };
+        state_rc.borrow().send_msg(WorkerMsg::ImportDone {
+            lines,
+            completed: result.is_ok(),
+        });
+        result?;
+        SessionLibrary::list(state_rc).await
+    }
+
+    async fn stream_file(
+        state_rc: &Rc<RefCell<WorkerState>>,
+        file: &web_sys::File,
+        is_hex: bool,
+        session: String,
+    ) -> Result<(), LogError> {
+        // Import time says nothing about when the lines were captured
+        state_rc.borrow_mut().proc.record_times = false;
+
+        let total = file.size() as u64;
+        let mut read = 0;
+        let result = async {
+            while read < total {
+                let end = (read + IMPORT_CHUNK_SIZE).min(total);
+                let blob = file.slice_with_f64_and_f64(read as f64, end as f64)?;
+                let buffer = JsFuture::from(blob.array_buffer()).await?;
+                let chunk = js_sys::Uint8Array::new(&buffer).to_vec();
+
+                let mut s = state_rc.borrow_mut();
+                // Opening another session takes over the processor
+                if s.filename.as_deref() != Some(session.as_str()) {
+                    return Err(LogError::Storage("Import interrupted".into()));
+                }
+                if s.storage_usage.paused {
+                    return Err(LogError::Storage(
+                        "Import stopped: storage almost full".into(),
+                    ));
+                }
+                s.proc.append_chunk(&chunk, is_hex)?;
+                read = end;
+                s.send_msg(WorkerMsg::ImportProgress { read, total });
+            }
+            Ok(())
+        }
+        .await;
+
+        state_rc.borrow_mut().proc.record_times = true;
+        result
+    }
+}
@@ -0,0 +1,174 @@
+use crate::types::JsonRow;
+use crate::worker::formatter::LogFormatter;
+use serde_json::{Map, Value};
+use std::cmp::Ordering;
+
+/// Most field names collected from one window for the column picker
+const MAX_FIELD_NAMES: usize = 64;
+
+pub type JsonObject = Map<String, Value>;
+
+/// The JSON object a line consists of, if any. Color codes and text before
+/// the opening brace (e.g. a timestamp prefix) are ignored.
+pub fn parse_object(text: &str) -> Option<JsonObject> {
+    if !text.contains('{') {
+        return None;
+    }
+    let plain = LogFormatter::plain_text(text);
+    let body = plain.trim_end();
+    let start = body.find('{')?;
+    if !body.ends_with('}') {
+        return None;
+    }
+    serde_json::from_str(&body[start..]).ok()
+}
+
+/// Value at `path`: a key of the object, or dot-separated keys of nested objects
+pub fn field<'a>(object: &'a JsonObject, path: &str) -> Option<&'a Value> {
+    if let Some(value) = object.get(path) {
+        return Some(value);
+    }
+    let (head, rest) = path.split_once('.')?;
+    match object.get(head)? {
+        Value::Object(inner) => field(inner, rest),
+        _ => None,
+    }
+}
+
+/// Value as shown in a cell: strings without quotes, anything else as JSON
+pub fn cell_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        v => v.to_string(),
+    }
+}
+
+/// Numeric value of a number, or of a string holding one
+pub fn number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// Cells of the `columns` of a line; `None` when the line is not a JSON object
+pub fn row(text: &str, columns: &[String]) -> Option<(JsonRow, JsonObject)> {
+    let object = parse_object(text)?;
+    let cells = columns
+        .iter()
+        .map(|path| field(&object, path).map(cell_text))
+        .collect();
+    Some((JsonRow { cells }, object))
+}
+
+/// Adds the field paths of an object not listed yet; nested objects give
+/// dotted paths, arrays are one field
+pub fn collect_fields(object: &JsonObject, prefix: &str, out: &mut Vec<String>) {
+    for (key, value) in object {
+        if out.len() >= MAX_FIELD_NAMES {
+            return;
+        }
+        let path = match prefix {
+            "" => key.clone(),
+            p => format!("{}.{}", p, key),
+        };
+        match value {
+            Value::Object(inner) => collect_fields(inner, &path, out),
+            _ if !out.contains(&path) => out.push(path),
+            _ => {}
+        }
+    }
+}
+
+/// Value a line is sorted by
+#[derive(Clone, Debug, PartialEq)]
+pub enum SortKey {
+    Number(f64),
+    Text(String),
+    /// Not a JSON line, or the field is missing
+    Missing,
+}
+
+impl SortKey {
+    pub fn of_line(text: &str, path: &str) -> Self {
+        let Some(object) = parse_object(text) else {
+            return SortKey::Missing;
+        };
+        match field(&object, path) {
+            None | Some(Value::Null) => SortKey::Missing,
+            Some(value) => match number(value) {
+                Some(n) if !n.is_nan() => SortKey::Number(n),
+                _ => SortKey::Text(cell_text(value)),
+            },
+        }
+    }
+
+    /// Numbers before text; lines without the field always come last
+    pub fn compare(&self, other: &Self, descending: bool) -> Ordering {
+        let order = match (self, other) {
+            (SortKey::Missing, SortKey::Missing) => return Ordering::Equal,
+            (SortKey::Missing, _) => return Ordering::Greater,
+            (_, SortKey::Missing) => return Ordering::Less,
+            (SortKey::Number(a), SortKey::Number(b)) => a.total_cmp(b),
+            (SortKey::Number(_), SortKey::Text(_)) => Ordering::Less,
+            (SortKey::Text(_), SortKey::Number(_)) => Ordering::Greater,
+            (SortKey::Text(a), SortKey::Text(b)) => a.cmp(b),
+        };
+        if descending {
+            order.reverse()
+        } else {
+            order
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_fields() {
+        let line = "\x1b[32m12:00:01 {\"ts\":1.5,\"level\":\"warn\",\"net\":{\"rssi\":-71}}\x1b[0m";
+        let object = parse_object(line).unwrap();
+        assert_eq!(field(&object, "net.rssi").and_then(number), Some(-71.0));
+        assert_eq!(
+            field(&object, "level").map(cell_text).as_deref(),
+            Some("warn")
+        );
+        assert!(field(&object, "net.snr").is_none());
+        assert!(parse_object("boot {not json}").is_none());
+        assert!(parse_object("{\"a\":1} trailing").is_none());
+
+        let (row, object) = row(line, &["level".into(), "msg".into()]).unwrap();
+        assert_eq!(row.cells, [Some("warn".to_string()), None]);
+        let mut fields = vec!["level".to_string()];
+        collect_fields(&object, "", &mut fields);
+        assert_eq!(fields, ["level", "net.rssi", "ts"]);
+    }
+
+    #[test]
+    fn test_sort_keys() {
+        let key = |text: &str| SortKey::of_line(text, "temp");
+        let mut keys = vec![
+            key(r#"{"temp":"n/a"}"#),
+            key("plain line"),
+            key(r#"{"temp":81.5}"#),
+            key(r#"{"temp":"9"}"#),
+        ];
+        keys.sort_by(|a, b| a.compare(b, false));
+        assert_eq!(
+            keys,
+            [
+                SortKey::Number(9.0),
+                SortKey::Number(81.5),
+                SortKey::Text("n/a".into()),
+                SortKey::Missing
+            ]
+        );
+        keys.sort_by(|a, b| a.compare(b, true));
+        assert_eq!(keys[0], SortKey::Text("n/a".into()));
+        assert_eq!(keys[3], SortKey::Missing);
+    }
+}
@@ -0,0 +1,79 @@
+use crate::types::LogLevel;
+use regex::Regex;
+use std::ops::Range;
+
+thread_local! {
+    /// ESP-IDF (`E (123) tag:`) and Zephyr (`[00:00:01.250,000] <err> tag:`) prefixes,
+    /// after any leading color codes, and the component tag that follows them
+    static PREFIX_RE: Regex = Regex::new(
+        r"^(?:\x1B\[[0-9;:]*m)*(?:([EWIDV]) \(\d+\) |(?:\[[^\]]*\] )?<(err|wrn|inf|dbg)> )(?:([^:\s]+):)?",
+    )
+    .unwrap();
+    /// Upper-case level words of plain logs, e.g. `[ERROR]` or `12:00:01 WARN`
+    static KEYWORD_RE: Regex = Regex::new(
+        r"\b(FATAL|CRITICAL|PANIC|ERROR|ERR|WARNING|WARN|INFO|DEBUG|TRACE|VERBOSE)\b",
+    )
+    .unwrap();
+}
+
+/// Level of a received line. The ESP-IDF and Zephyr prefixes are trusted first;
+/// otherwise the leftmost upper-case level word decides.
+pub fn classify(line: &str) -> LogLevel {
+    let prefix = PREFIX_RE.with(|re| {
+        let c = re.captures(line)?;
+        let marker = c.get(1).or_else(|| c.get(2))?.as_str();
+        Some(match marker {
+            "E" | "err" => LogLevel::Error,
+            "W" | "wrn" => LogLevel::Warn,
+            "I" | "inf" => LogLevel::Info,
+            "D" | "dbg" => LogLevel::Debug,
+            _ => LogLevel::Verbose,
+        })
+    });
+    if let Some(level) = prefix {
+        return level;
+    }
+    KEYWORD_RE.with(|re| match re.find(line).map(|m| m.as_str()) {
+        Some("FATAL" | "CRITICAL" | "PANIC" | "ERROR" | "ERR") => LogLevel::Error,
+        Some("WARNING" | "WARN") => LogLevel::Warn,
+        Some("INFO") => LogLevel::Info,
+        Some("DEBUG") => LogLevel::Debug,
+        Some(_) => LogLevel::Verbose,
+        None => LogLevel::None,
+    })
+}
+
+/// Byte range of the component tag of an ESP-IDF or Zephyr line, e.g. `wifi`
+/// in `I (123) wifi: connected`
+pub fn tag(line: &str) -> Option<Range<usize>> {
+    PREFIX_RE.with(|re| re.captures(line)?.get(3).map(|m| m.range()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        let cases = [
+            (
+                "\x1b[0;31mE (1234) wifi: auth failed\x1b[0m",
+                LogLevel::Error,
+            ),
+            ("W (55) heap: low memory, INFO follows", LogLevel::Warn),
+            ("V (9) spi: xfer", LogLevel::Verbose),
+            ("[00:00:01.250,000] <wrn> bt: slow", LogLevel::Warn),
+            ("<dbg> sensor: raw=12", LogLevel::Debug),
+            ("2024-05-01 12:00:01 INFO boot ok", LogLevel::Info),
+            ("[ERROR] watchdog reset", LogLevel::Error),
+            ("Guru Meditation PANIC", LogLevel::Error),
+            ("TRACE enter main", LogLevel::Verbose),
+            ("ERR_TIMEOUT is not a level", LogLevel::None),
+            ("error: lower case is left alone", LogLevel::None),
+            ("", LogLevel::None),
+        ];
+        for (line, level) in cases {
+            assert_eq!(classify(line), level, "{:?}", line);
+        }
+    }
+}
@@ -1,13 +1,21 @@
+pub mod ansi_decoder;
 pub mod chunk_handler;
 pub mod commands;
 pub mod dispatcher;
+pub mod encoding;
 pub mod error;
 pub mod export;
 pub mod formatter;
+pub mod import;
+pub mod json_line;
+pub mod level;
 pub mod lifecycle;
 pub mod processor;
 pub mod repository;
 pub mod search;
+pub mod session;
+pub mod sgr;
+pub mod sort;
 pub mod state;
 pub mod types;
 
@@ -1,37 +1,51 @@
+use crate::types::{Highlight, RxEncoding, SortSpec, StyledLine};
+use crate::worker::ansi_decoder::style_line;
 use crate::worker::chunk_handler::StreamingLineProcessor;
 use crate::worker::error::LogError;
 
 use crate::worker::formatter::LogFormatter;
+use crate::worker::sort::LogSorter;
 
-use crate::worker::repository::LogRepository;
-
-use wasm_bindgen::prelude::*;
-use web_sys::FileSystemSyncAccessHandle;
+use crate::worker::repository::index::filter::ActiveFilter;
+use crate::worker::repository::index::sorted_view::SortedView;
+use crate::worker::repository::index::{ByteOffset, LineIndex};
+use crate::worker::repository::storage::{OpfsBackend, SessionFiles, StorageBackend};
+use crate::worker::repository::{LogRepository, Recovery};
 
 use crate::config::MAX_LINE_BYTES;
 
-#[wasm_bindgen]
-pub struct LogProcessor {
-    pub(crate) repository: LogRepository,
+/// Turns received chunks into indexed lines of the attached session
+pub struct LogProcessor<B: StorageBackend = OpfsBackend> {
+    pub(crate) repository: LogRepository<B>,
     pub(crate) formatter: LogFormatter,
+    /// Whether windows and exports show the receive time of each line
     pub(crate) show_timestamps: bool,
+    /// Whether appended chunks are stamped with the current time (off for imports)
+    pub(crate) record_times: bool,
+    /// Find-in-log expression; its matches are marked without filtering the view
+    pub(crate) find: Option<ActiveFilter>,
+    /// Highlight rules applied to the lines of each window
+    pub(crate) highlights: Vec<Highlight>,
+    /// Fields of JSON lines sent with each window for the structured view
+    pub(crate) json_columns: Vec<String>,
+    /// Field the view is ordered by; the order itself is kept in the index
+    pub(crate) sort: Option<SortSpec>,
     chunk_handler: StreamingLineProcessor,
 }
 
-#[wasm_bindgen]
-impl LogProcessor {
-    #[wasm_bindgen(constructor)]
-    pub fn new() -> Result<LogProcessor, JsValue> {
-        LogProcessor::new_internal().map_err(JsValue::from)
-    }
-
-    fn new_internal() -> Result<Self, LogError> {
-        Ok(LogProcessor {
-            repository: LogRepository::new()?,
+impl<B: StorageBackend + Default> LogProcessor<B> {
+    pub fn new() -> Self {
+        LogProcessor {
+            repository: LogRepository::new(),
             formatter: LogFormatter::new(),
             show_timestamps: false,
+            record_times: true,
+            find: None,
+            highlights: Vec::new(),
+            json_columns: Vec::new(),
+            sort: None,
             chunk_handler: StreamingLineProcessor::new(),
-        })
+        }
     }
 
     // --- Public API ---
@@ -39,60 +53,61 @@ impl LogProcessor {
         self.repository.get_line_count() as u32
     }
 
-    pub fn set_sync_handle(&mut self, handle: FileSystemSyncAccessHandle) -> Result<(), JsValue> {
-        self.set_sync_handle_internal(handle).map_err(JsValue::from)
-    }
-
-    fn set_sync_handle_internal(
-        &mut self,
-        handle: FileSystemSyncAccessHandle,
-    ) -> Result<(), LogError> {
-        self.repository.initialize_storage(handle)
-    }
-
-    pub fn append_chunk(&mut self, chunk: &[u8], is_hex: bool) -> Result<Option<String>, JsValue> {
-        self.append_chunk_internal(chunk, is_hex)
-            .map_err(JsValue::from)
-    }
-
-    fn append_chunk_internal(
-        &mut self,
-        chunk: &[u8],
-        is_hex: bool,
-    ) -> Result<Option<String>, LogError> {
+    /// Appends a received chunk; returns the line still being received, if any
+    pub fn append_chunk(&mut self, chunk: &[u8], is_hex: bool) -> Result<Option<String>, LogError> {
         let formatter = self.formatter.create_strategy(is_hex, MAX_LINE_BYTES);
-        let timestamp = if self.show_timestamps {
-            self.formatter.get_timestamp()
-        } else {
-            String::new()
-        };
+        let received_at = LogFormatter::now_micros();
+
+        // Keep the bytes as received before they are rendered
+        let raw_base = ByteOffset(
+            self.repository
+                .raw
+                .append_chunk(chunk, received_at / 1000)?,
+        );
 
         let repo = &self.repository;
         let is_filtering = repo.is_filtering();
-        let filter_matcher = |text: &str| repo.matches_active_filter(text);
+        let filter_matcher = |text: &str, level| repo.matches_active_filter(text, level);
 
-        let (batch, offsets, filtered, active_line) = if is_hex {
+        let mut batch = if is_hex {
             let text = formatter.format_chunk(chunk);
-            let (b, o, f) = self.chunk_handler.process_hex_lines(
+            self.chunk_handler.process_hex_lines(
                 &text,
+                raw_base,
                 &*formatter,
-                &timestamp,
                 is_filtering,
                 filter_matcher,
-            );
-            (b, o, f, None)
+            )
         } else {
             self.chunk_handler.process_vt100(
                 chunk,
+                raw_base,
                 &*formatter,
-                &timestamp,
                 is_filtering,
                 filter_matcher,
             )
         };
 
-        if !batch.is_empty() {
-            self.repository.append_lines(&batch, offsets, filtered)?;
+        batch.received_at = self.record_times.then_some(received_at);
+        let active_line = batch.active_line.take();
+        if !batch.text.is_empty() {
+            // Context shown before a new match may take in earlier lines
+            let index = &self.repository.index;
+            let first = index.line_count;
+            let earlier = first.saturating_sub(index.context.0)..first;
+            let hidden: Vec<usize> = earlier.filter(|&l| !index.in_view(l)).collect();
+
+            self.repository.append_lines(batch)?;
+
+            if let Some(spec) = &self.sort {
+                let index = &self.repository.index;
+                let joined: Vec<usize> = hidden
+                    .into_iter()
+                    .chain(first..index.line_count)
+                    .filter(|&l| index.in_view(l))
+                    .collect();
+                LogSorter::merge_lines(&mut self.repository, spec, &joined)?;
+            }
         }
         Ok(active_line)
     }
@@ -101,13 +116,211 @@ impl LogProcessor {
         self.show_timestamps = enabled;
     }
 
-    pub fn clear(&mut self) -> Result<(), JsValue> {
-        self.clear_internal().map_err(JsValue::from)
+    /// Takes over how `other` presents the log (timestamps, find, highlights,
+    /// columns and order) and its search index density, for a processor about
+    /// to be shown in its place
+    pub(crate) fn copy_view_settings(&mut self, other: &Self) {
+        self.show_timestamps = other.show_timestamps;
+        self.find = other.find.clone();
+        self.highlights = other.highlights.clone();
+        self.json_columns = other.json_columns.clone();
+        self.sort = other.sort.clone();
+        let bits_per_gram = other.repository.index.search_index.bits_per_gram();
+        let next_line = self.repository.index.first_line + self.repository.index.line_count;
+        let search_index = &mut self.repository.index.search_index;
+        if search_index.bits_per_gram() != bits_per_gram {
+            search_index.configure(bits_per_gram, next_line);
+        }
+    }
+
+    pub fn encoding(&self) -> RxEncoding {
+        self.chunk_handler.encoding()
     }
 
-    fn clear_internal(&mut self) -> Result<(), LogError> {
+    pub fn set_encoding(&mut self, encoding: RxEncoding) {
+        self.chunk_handler.set_encoding(encoding);
+    }
+
+    /// Character columns of the find matches in a stored line at `index` of the
+    /// view, as displayed (without color codes, after any timestamp).
+    /// `None` if the line does not match.
+    pub fn find_columns(&self, index: LineIndex, text: &str) -> Option<Vec<(usize, usize)>> {
+        let filter = self.find.as_ref()?;
+        let plain = LogFormatter::plain_text(text);
+        let spans = filter.find(&plain)?;
+        let shift = match self.show_timestamps {
+            true => self.repository.line_time(index).map_or(0, |us| {
+                LogFormatter::format_timestamp(us).chars().count() + 1
+            }),
+            false => 0,
+        };
+        let column = |byte: usize| shift + plain[..byte].chars().count();
+        Some(
+            spans
+                .into_iter()
+                .map(|r| (column(r.start), column(r.end)))
+                .collect(),
+        )
+    }
+
+    /// Decodes a view line for display. The line still being received is
+    /// shown without highlights, as it changes with every chunk.
+    pub fn style_line(
+        &self,
+        text: &str,
+        find_spans: &[(usize, usize)],
+        partial: bool,
+    ) -> StyledLine {
+        let highlights = if partial { &[][..] } else { &self.highlights };
+        style_line(text, highlights, find_spans)
+    }
+
+    pub fn clear(&mut self) -> Result<(), LogError> {
         self.repository.clear()?;
         self.chunk_handler.clear();
+        // An empty log is in order
+        if self.sort.is_some() {
+            self.repository.index.sorted = Some(SortedView::default());
+        }
         Ok(())
     }
+
+    /// Attaches the locked files of a session and restores its line index.
+    /// Returns what had to be repaired after an interrupted write.
+    pub(crate) fn set_sync_handle(&mut self, files: SessionFiles<B>) -> Result<Recovery, LogError> {
+        self.attach(files, true)
+    }
+
+    /// Attaches an archived session for viewing, without repairing its files
+    pub(crate) fn set_read_only_handle(&mut self, files: SessionFiles<B>) -> Result<(), LogError> {
+        self.attach(files, false).map(|_| ())
+    }
+
+    fn attach(&mut self, files: SessionFiles<B>, writable: bool) -> Result<Recovery, LogError> {
+        let recovery = self.repository.initialize_storage(files, writable)?;
+        // A line received before the switch has no raw bytes in this session
+        self.chunk_handler
+            .set_raw_position(ByteOffset(self.repository.raw.size()));
+        Ok(recovery)
+    }
+
+    /// Drops the oldest lines until the log fits in `target_size` bytes.
+    /// Returns the number of lines dropped.
+    pub(crate) fn drop_oldest(&mut self, target_size: u64) -> Result<usize, LogError> {
+        let (lines, raw_cut) = self.repository.drop_oldest(target_size)?;
+        self.chunk_handler.rebase_raw_position(raw_cut);
+        Ok(lines)
+    }
+}
+
+impl<B: StorageBackend + Default> Default for LogProcessor<B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::LogLevel;
+    use crate::worker::repository::index::LineIndex;
+    use crate::worker::repository::storage::MemoryBackend;
+
+    fn processor() -> (LogProcessor<MemoryBackend>, SessionFiles<MemoryBackend>) {
+        let files = SessionFiles::in_memory();
+        let mut proc = LogProcessor::new();
+        proc.set_sync_handle(files.clone()).unwrap();
+        (proc, files)
+    }
+
+    fn line(proc: &LogProcessor<MemoryBackend>, i: usize) -> String {
+        let range = proc.repository.get_line_range(LineIndex(i)).unwrap();
+        String::from_utf8(proc.repository.read_line(range).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_append_chunk_splits_lines() {
+        let (mut proc, files) = processor();
+        let active = proc.append_chunk(b"boot\r\nwifi up\nrss", false).unwrap();
+        assert_eq!(active.as_deref(), Some("rss"));
+        assert_eq!(proc.get_line_count(), 2);
+
+        proc.append_chunk(b"i -70\n", false).unwrap();
+        assert_eq!(proc.get_line_count(), 3);
+        assert_eq!(line(&proc, 2), "rssi -70\n");
+        assert_eq!(files.log.bytes(), b"boot\nwifi up\nrssi -70\n");
+        // The received bytes are kept as-is next to the text
+        assert_eq!(files.raw.unwrap().bytes(), b"boot\r\nwifi up\nrssi -70\n");
+    }
+
+    #[test]
+    fn test_append_chunk_decodes_encoding() {
+        let (mut proc, files) = processor();
+        proc.set_encoding(RxEncoding::ShiftJis);
+        // A character split across chunks, then a line of box drawing in CP437
+        let active = proc.append_chunk(b"\x93\xfa\x96", false).unwrap();
+        assert_eq!(active.as_deref(), Some("日"));
+        proc.append_chunk(b"\x7b\r\n", false).unwrap();
+        proc.set_encoding(RxEncoding::Cp437);
+        proc.append_chunk(b"\xc9\xcd\xbb\n", false).unwrap();
+        assert_eq!(line(&proc, 0), "日本\n");
+        assert_eq!(files.log.bytes(), "日本\n╔═╗\n".as_bytes());
+        // Raw ranges still point at the received bytes
+        let raw = proc.repository.raw_line_range(LineIndex(1));
+        assert_eq!(raw.map(|r| (r.start.0, r.end.0)), Some((6, 10)));
+    }
+
+    #[test]
+    fn test_reattach_restores_index() {
+        let (mut proc, files) = processor();
+        proc.append_chunk(b"one\ntwo\nthree\n", false).unwrap();
+        proc.repository.release_storage();
+
+        let mut reopened = LogProcessor::new();
+        reopened.set_sync_handle(files).unwrap();
+        assert_eq!(reopened.get_line_count(), 3);
+        assert_eq!(line(&reopened, 1), "two\n");
+        assert!(reopened.repository.line_time(LineIndex(1)).is_some());
+    }
+
+    #[test]
+    fn test_drop_oldest_keeps_newest_lines() {
+        let (mut proc, files) = processor();
+        for i in 0..10 {
+            proc.append_chunk(format!("line {}\n", i).as_bytes(), false)
+                .unwrap();
+        }
+        assert_eq!(proc.drop_oldest(21).unwrap(), 7);
+        assert_eq!(proc.get_line_count(), 3);
+        assert_eq!(line(&proc, 0), "line 7\n");
+        // The kept lines are moved down by compaction
+        while proc.repository.compact(u64::MAX).unwrap() {}
+        assert_eq!(files.log.bytes(), b"line 7\nline 8\nline 9\n");
+    }
+
+    #[test]
+    fn test_reattach_restores_levels() {
+        let (mut proc, files) = processor();
+        proc.append_chunk(b"E (1) boot: fail\nI (2) boot: ok\nplain\n", false)
+            .unwrap();
+        proc.repository.release_storage();
+        assert_eq!(files.levels.as_ref().unwrap().bytes().len(), 3);
+
+        let mut reopened = LogProcessor::new();
+        reopened.set_sync_handle(files.clone()).unwrap();
+        let counts = reopened.repository.level_counts();
+        assert_eq!(counts.get(LogLevel::Error), 1);
+        assert_eq!(counts.get(LogLevel::Info), 1);
+        reopened.repository.release_storage();
+
+        // Sessions recorded before levels were kept are classified on open
+        files.levels.as_ref().unwrap().truncate(0).unwrap();
+        let mut reopened = LogProcessor::new();
+        reopened.set_sync_handle(files.clone()).unwrap();
+        assert_eq!(
+            reopened.repository.level_counts().at_least(LogLevel::Info),
+            2
+        );
+        assert_eq!(files.levels.unwrap().bytes().len(), 3);
+    }
 }
@@ -1,30 +1,648 @@
-use regex::Regex;
+use crate::worker::json_line::{self, JsonObject};
+use crate::worker::level;
+use crate::worker::repository::index::trigram::Prefilter;
+use regex::{Regex, RegexBuilder};
+use serde_json::Value;
+use std::cell::OnceCell;
+use std::ops::Range;
 
-/// Active filter for log searching
-#[derive(Clone)]
+thread_local! {
+    /// `key<op>number` written as one word, e.g. `rssi<-70`
+    static COMPARISON_RE: Regex =
+        Regex::new(r"^([A-Za-z_][\w.\-]*?)(<=|>=|!=|==|=|<|>)(-?\d+(?:\.\d+)?)$").unwrap();
+    /// `@field<op>value` on the fields of a JSON line, e.g. `@temp>80`, `@msg~timeout`
+    static JSON_FIELD_RE: Regex =
+        Regex::new(r"^@([A-Za-z_][\w.\-]*?)(<=|>=|!=|==|=|<|>|~)(.+)$").unwrap();
+}
+
+/// Line being matched; the lowercase copy is made at most once, on demand
+struct Line<'a> {
+    text: &'a str,
+    lower: OnceCell<String>,
+    json: OnceCell<Option<JsonObject>>,
+}
+
+impl<'a> Line<'a> {
+    fn new(text: &'a str) -> Self {
+        Self {
+            text,
+            lower: OnceCell::new(),
+            json: OnceCell::new(),
+        }
+    }
+
+    fn lower(&self) -> &str {
+        self.lower.get_or_init(|| self.text.to_lowercase())
+    }
+
+    /// The line parsed as a JSON object, once for all field terms
+    fn json(&self) -> Option<&JsonObject> {
+        self.json
+            .get_or_init(|| json_line::parse_object(self.text))
+            .as_ref()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum CompareOp {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+}
+
+impl CompareOp {
+    fn parse(op: &str) -> Self {
+        match op {
+            "<" => CompareOp::Lt,
+            "<=" => CompareOp::Le,
+            ">" => CompareOp::Gt,
+            ">=" => CompareOp::Ge,
+            "!=" => CompareOp::Ne,
+            _ => CompareOp::Eq,
+        }
+    }
+
+    fn holds(self, a: f64, b: f64) -> bool {
+        match self {
+            CompareOp::Lt => a < b,
+            CompareOp::Le => a <= b,
+            CompareOp::Gt => a > b,
+            CompareOp::Ge => a >= b,
+            CompareOp::Eq => a == b,
+            CompareOp::Ne => a != b,
+        }
+    }
+}
+
+/// Leaf of a filter expression
+#[derive(Clone, Debug)]
+enum Term {
+    /// Substring; `text` is lowercased when matching ignores case
+    Literal {
+        text: String,
+        match_case: bool,
+    },
+    Regex(Regex),
+    /// `tag:NAME`, compared case-insensitively with the line's component tag
+    Tag(String),
+    /// `key<op>number`, true if any number following `key` satisfies it
+    Compare {
+        field: Regex,
+        op: CompareOp,
+        value: f64,
+    },
+    /// `@path<op>value` on a field of a JSON line; `op` is `None` for `~` (contains)
+    JsonField {
+        path: String,
+        op: Option<CompareOp>,
+        value: String,
+    },
+}
+
+/// Whether a JSON field value satisfies `op value`. Numbers compare as numbers,
+/// other values as text; `=` and `~` ignore case.
+fn json_field_holds(field: &Value, op: Option<CompareOp>, value: &str) -> bool {
+    let text = json_line::cell_text(field);
+    let Some(op) = op else {
+        return text.to_lowercase().contains(&value.to_lowercase());
+    };
+    if let (Some(a), Ok(b)) = (json_line::number(field), value.parse::<f64>()) {
+        return op.holds(a, b);
+    }
+    let order = match op {
+        CompareOp::Eq | CompareOp::Ne => {
+            let equal = text.to_lowercase() == value.to_lowercase();
+            return equal == (op == CompareOp::Eq);
+        }
+        _ => text.as_str().cmp(value),
+    };
+    op.holds(order as i8 as f64, 0.0)
+}
+
+impl Term {
+    fn matches(&self, line: &Line) -> bool {
+        match self {
+            Term::Literal { text, match_case } => {
+                if *match_case {
+                    line.text.contains(text.as_str())
+                } else {
+                    line.lower().contains(text.as_str())
+                }
+            }
+            Term::Regex(re) => re.is_match(line.text),
+            Term::Tag(tag) => level::tag(line.text)
+                .is_some_and(|range| line.text[range].eq_ignore_ascii_case(tag)),
+            Term::Compare { field, op, value } => field.captures_iter(line.text).any(|c| {
+                c[1].parse::<f64>()
+                    .is_ok_and(|number| op.holds(number, *value))
+            }),
+            Term::JsonField { path, op, value } => line
+                .json()
+                .and_then(|object| json_line::field(object, path))
+                .is_some_and(|field| json_field_holds(field, *op, value)),
+        }
+    }
+}
+
+/// Parsed filter expression
+#[derive(Clone, Debug)]
+enum FilterExpr {
+    Term(Term),
+    Not(Box<FilterExpr>),
+    And(Vec<FilterExpr>),
+    Or(Vec<FilterExpr>),
+}
+
+impl Term {
+    /// Byte ranges of the line matched by the term
+    fn spans(&self, line: &Line, out: &mut Vec<Range<usize>>) {
+        match self {
+            Term::Literal { text, match_case } => {
+                if text.is_empty() {
+                    return;
+                }
+                // Lowercasing rarely changes byte lengths; such lines get no spans
+                let haystack = if *match_case {
+                    line.text
+                } else if line.lower().len() == line.text.len() {
+                    line.lower()
+                } else {
+                    return;
+                };
+                out.extend(
+                    haystack
+                        .match_indices(text.as_str())
+                        .map(|(i, m)| i..i + m.len()),
+                );
+            }
+            Term::Regex(re) => out.extend(
+                re.find_iter(line.text)
+                    .filter(|m| !m.is_empty())
+                    .map(|m| m.range()),
+            ),
+            Term::Tag(tag) => out.extend(
+                level::tag(line.text)
+                    .filter(|range| line.text[range.clone()].eq_ignore_ascii_case(tag)),
+            ),
+            Term::Compare { field, op, value } => {
+                out.extend(field.captures_iter(line.text).filter_map(|c| {
+                    let holds = c[1]
+                        .parse::<f64>()
+                        .is_ok_and(|number| op.holds(number, *value));
+                    holds.then(|| c.get(0).map(|m| m.range())).flatten()
+                }));
+            }
+            // Field values are not located in the line text
+            Term::JsonField { .. } => {}
+        }
+    }
+}
+
+impl Term {
+    /// Text a line must contain for the term to match it
+    fn prefilter(&self) -> Prefilter {
+        match self {
+            Term::Literal { text, match_case } => Prefilter::literal(text, !match_case),
+            Term::Regex(re) => Prefilter::regex(re.as_str()),
+            // Tags are compared ignoring ASCII case only
+            Term::Tag(tag) => Prefilter::literal(tag, false),
+            Term::Compare { .. } => Prefilter::Any,
+            // The key is written out in the line
+            Term::JsonField { path, .. } => {
+                Prefilter::literal(path.rsplit('.').next().unwrap_or(path), false)
+            }
+        }
+    }
+}
+
+impl FilterExpr {
+    fn prefilter(&self) -> Prefilter {
+        match self {
+            FilterExpr::Term(term) => term.prefilter(),
+            FilterExpr::Not(_) => Prefilter::Any,
+            FilterExpr::And(exprs) => Prefilter::all(exprs.iter().map(Self::prefilter).collect()),
+            FilterExpr::Or(exprs) => Prefilter::any(exprs.iter().map(Self::prefilter).collect()),
+        }
+    }
+
+    fn matches(&self, line: &Line) -> bool {
+        match self {
+            FilterExpr::Term(term) => term.matches(line),
+            FilterExpr::Not(expr) => !expr.matches(line),
+            FilterExpr::And(exprs) => exprs.iter().all(|e| e.matches(line)),
+            FilterExpr::Or(exprs) => exprs.iter().any(|e| e.matches(line)),
+        }
+    }
+
+    /// Spans of the matching terms of a matching expression; negated terms add none
+    fn spans(&self, line: &Line, out: &mut Vec<Range<usize>>) {
+        match self {
+            FilterExpr::Term(term) => term.spans(line, out),
+            FilterExpr::Not(_) => {}
+            FilterExpr::And(exprs) => exprs.iter().for_each(|e| e.spans(line, out)),
+            FilterExpr::Or(exprs) => exprs
+                .iter()
+                .filter(|e| e.matches(line))
+                .for_each(|e| e.spans(line, out)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum TokenKind {
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    /// Bare word; adjacent words form one phrase
+    Word,
+    /// `"text"` with optional `i`/`c` flags
+    Quoted {
+        text: String,
+        flags: String,
+    },
+    /// `/pattern/` with optional `i`/`c` flags
+    Pattern {
+        pattern: String,
+        flags: String,
+    },
+}
+
+#[derive(Clone, Debug)]
+struct Token {
+    kind: TokenKind,
+    /// Byte range in the query
+    start: usize,
+    end: usize,
+}
+
+fn error_at(query: &str, pos: usize, msg: &str) -> String {
+    format!("{} at column {}", msg, query[..pos].chars().count() + 1)
+}
+
+/// Reads `i`/`c` flags following a quoted or `/.../` term
+fn read_flags(query: &str, mut pos: usize) -> (String, usize) {
+    let flags: String = query[pos..]
+        .chars()
+        .take_while(|c| c.is_ascii_alphabetic())
+        .collect();
+    pos += flags.len();
+    (flags, pos)
+}
+
+/// Finds the closing `delim` of a term opened at `start`, honoring backslash escapes
+fn find_closing(query: &str, start: usize, delim: char) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in query[start + 1..].char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            c if c == delim => return Some(start + 1 + i),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn tokenize(query: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+    while let Some(c) = query[pos..].chars().next() {
+        let start = pos;
+        let kind = match c {
+            c if c.is_whitespace() => {
+                pos += c.len_utf8();
+                continue;
+            }
+            '(' => {
+                pos += 1;
+                TokenKind::Open
+            }
+            ')' => {
+                pos += 1;
+                TokenKind::Close
+            }
+            '!' if query[pos + 1..].starts_with(|n: char| !n.is_whitespace() && n != '=') => {
+                pos += 1;
+                TokenKind::Not
+            }
+            '"' => {
+                let close = find_closing(query, start, '"')
+                    .ok_or_else(|| error_at(query, start, "Unterminated quote"))?;
+                let text = unescape(&query[start + 1..close]);
+                let (flags, next) = read_flags(query, close + 1);
+                pos = next;
+                TokenKind::Quoted { text, flags }
+            }
+            '/' if pattern_close(query, start).is_some() => {
+                let close = pattern_close(query, start).unwrap_or(start);
+                let pattern = query[start + 1..close].replace("\\/", "/");
+                let (flags, next) = read_flags(query, close + 1);
+                pos = next;
+                TokenKind::Pattern { pattern, flags }
+            }
+            _ => {
+                let mut escaped = false;
+                let len = query[start..]
+                    .char_indices()
+                    .find(|&(_, c)| {
+                        if escaped {
+                            escaped = false;
+                            return false;
+                        }
+                        escaped = c == '\\';
+                        c.is_whitespace() || c == '(' || c == ')' || c == '"'
+                    })
+                    .map(|(i, _)| i)
+                    .unwrap_or(query.len() - start);
+                pos = start + len;
+                match query[start..pos].to_ascii_lowercase().as_str() {
+                    "and" | "&&" => TokenKind::And,
+                    "or" | "||" => TokenKind::Or,
+                    "not" => TokenKind::Not,
+                    _ => TokenKind::Word,
+                }
+            }
+        };
+        tokens.push(Token {
+            kind,
+            start,
+            end: pos,
+        });
+    }
+    Ok(tokens)
+}
+
+/// Drops the backslash of escaped characters in a literal
+fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            if let Some(next) = chars.next() {
+                out.push(next);
+                continue;
+            }
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Closing slash of a `/pattern/flags` term opened at `start`. The flags must
+/// be followed by whitespace, `)` or the end of the query, so paths such as
+/// `/dev/ttyUSB0` or `/api/v1` stay plain words.
+fn pattern_close(query: &str, start: usize) -> Option<usize> {
+    let close = find_closing(query, start, '/')?;
+    let (flags, next) = read_flags(query, close + 1);
+    let valid = flags.chars().all(|f| f == 'i' || f == 'c');
+    let ends = query[next..]
+        .chars()
+        .next()
+        .is_none_or(|c| c.is_whitespace() || c == ')');
+    (valid && ends).then_some(close)
+}
+
+/// Recursive-descent parser over the tokens of a query:
+/// `or := and (OR and)*`, `and := unary (AND? unary)*`,
+/// `unary := NOT unary | ( or ) | term`
+struct Parser<'a> {
+    query: &'a str,
+    tokens: Vec<Token>,
+    pos: usize,
+    match_case: bool,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&TokenKind> {
+        self.tokens.get(self.pos).map(|t| &t.kind)
+    }
+
+    /// Query position of the next token, or the end of the query
+    fn here(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .map(|t| t.start)
+            .unwrap_or(self.query.len())
+    }
+
+    fn parse_or(&mut self) -> Result<FilterExpr, String> {
+        let mut items = vec![self.parse_and()?];
+        while self.peek() == Some(&TokenKind::Or) {
+            self.pos += 1;
+            items.push(self.parse_and()?);
+        }
+        Ok(if items.len() == 1 {
+            items.remove(0)
+        } else {
+            FilterExpr::Or(items)
+        })
+    }
+
+    fn parse_and(&mut self) -> Result<FilterExpr, String> {
+        let mut items = vec![self.parse_unary()?];
+        loop {
+            match self.peek() {
+                Some(TokenKind::And) => {
+                    self.pos += 1;
+                    items.push(self.parse_unary()?);
+                }
+                // Terms next to each other must all match
+                Some(TokenKind::Open | TokenKind::Not | TokenKind::Word)
+                | Some(TokenKind::Quoted { .. } | TokenKind::Pattern { .. }) => {
+                    items.push(self.parse_unary()?);
+                }
+                _ => break,
+            }
+        }
+        Ok(if items.len() == 1 {
+            items.remove(0)
+        } else {
+            FilterExpr::And(items)
+        })
+    }
+
+    fn parse_unary(&mut self) -> Result<FilterExpr, String> {
+        let Some(token) = self.tokens.get(self.pos).cloned() else {
+            return Err(error_at(self.query, self.here(), "Expected a term"));
+        };
+        match token.kind {
+            TokenKind::Not => {
+                self.pos += 1;
+                Ok(FilterExpr::Not(Box::new(self.parse_unary()?)))
+            }
+            TokenKind::Open => {
+                self.pos += 1;
+                let expr = self.parse_or()?;
+                if self.peek() != Some(&TokenKind::Close) {
+                    return Err(error_at(self.query, self.here(), "Missing `)`"));
+                }
+                self.pos += 1;
+                Ok(expr)
+            }
+            TokenKind::Word => self.parse_words(),
+            TokenKind::Quoted { text, flags } => {
+                self.pos += 1;
+                let match_case = self.flags(&flags, token.start)?;
+                Ok(FilterExpr::Term(Self::literal(&text, match_case)))
+            }
+            TokenKind::Pattern { pattern, flags } => {
+                self.pos += 1;
+                let match_case = self.flags(&flags, token.start)?;
+                self.regex(&pattern, match_case, token.start)
+            }
+            TokenKind::Close => Err(error_at(self.query, token.start, "Unexpected `)`")),
+            TokenKind::And | TokenKind::Or => {
+                Err(error_at(self.query, token.start, "Expected a term"))
+            }
+        }
+    }
+
+    /// A field term (`tag:`, `key<op>number`, `@path<op>value`) or a phrase of adjacent plain words
+    fn parse_words(&mut self) -> Result<FilterExpr, String> {
+        let first = self.tokens[self.pos].clone();
+        let word = &self.query[first.start..first.end];
+        if let Some(term) = Self::field_term(word) {
+            self.pos += 1;
+            return Ok(FilterExpr::Term(term));
+        }
+
+        let mut end = first.end;
+        self.pos += 1;
+        while let Some(token) = self.tokens.get(self.pos) {
+            let word = &self.query[token.start..token.end];
+            if token.kind != TokenKind::Word || Self::field_term(word).is_some() {
+                break;
+            }
+            end = token.end;
+            self.pos += 1;
+        }
+        let phrase = &self.query[first.start..end];
+        Ok(FilterExpr::Term(Self::literal(
+            &unescape(phrase),
+            self.match_case,
+        )))
+    }
+
+    fn field_term(word: &str) -> Option<Term> {
+        if let Some((path, op, value)) = JSON_FIELD_RE.with(|re| {
+            re.captures(word)
+                .map(|c| (c[1].to_string(), c[2].to_string(), unescape(&c[3])))
+        }) {
+            let op = (op != "~").then(|| CompareOp::parse(&op));
+            return Some(Term::JsonField { path, op, value });
+        }
+        if let Some(tag) = word
+            .get(..4)
+            .filter(|p| p.eq_ignore_ascii_case("tag:"))
+            .and_then(|_| word.get(4..))
+            .filter(|t| !t.is_empty())
+        {
+            return Some(Term::Tag(tag.to_string()));
+        }
+        let caps = COMPARISON_RE.with(|re| {
+            re.captures(word)
+                .map(|c| (c[1].to_string(), c[2].to_string(), c[3].to_string()))
+        })?;
+        let (key, op, value) = caps;
+        let field = RegexBuilder::new(&format!(
+            r"\b{}\b\s*[:=]?\s*(-?\d+(?:\.\d+)?)",
+            regex::escape(&key)
+        ))
+        .case_insensitive(true)
+        .build()
+        .ok()?;
+        Some(Term::Compare {
+            field,
+            op: CompareOp::parse(&op),
+            value: value.parse().ok()?,
+        })
+    }
+
+    fn literal(text: &str, match_case: bool) -> Term {
+        Term::Literal {
+            text: if match_case {
+                text.to_string()
+            } else {
+                text.to_lowercase()
+            },
+            match_case,
+        }
+    }
+
+    fn regex(&self, pattern: &str, match_case: bool, at: usize) -> Result<FilterExpr, String> {
+        RegexBuilder::new(pattern)
+            .case_insensitive(!match_case)
+            .build()
+            .map(|re| FilterExpr::Term(Term::Regex(re)))
+            .map_err(|_| error_at(self.query, at, "Invalid regex"))
+    }
+
+    /// Case sensitivity of a term from its `i` (ignore case) / `c` (match case) flags
+    fn flags(&self, flags: &str, at: usize) -> Result<bool, String> {
+        let mut match_case = self.match_case;
+        for flag in flags.chars() {
+            match flag {
+                'i' => match_case = false,
+                'c' => match_case = true,
+                _ => return Err(error_at(self.query, at, "Unknown flag (use i or c)")),
+            }
+        }
+        Ok(match_case)
+    }
+}
+
+/// Active filter for log searching.
+/// The query is a small expression language: terms combined with `and`, `or`,
+/// `not` (also `&&`, `||`, `!`) and parentheses; adjacent terms must all match.
+/// A term is a phrase of plain words, a `"quoted literal"`, a `/regex/`
+/// (quoted and regex terms take `i`/`c` flags to ignore or match case),
+/// `tag:NAME` for the ESP-IDF/Zephyr component tag, `key<op>number`
+/// (`<`, `<=`, `>`, `>=`, `=`, `!=`) comparing the number after `key`, or
+/// `@path<op>value` (the same operators and `~` for contains) testing a
+/// field of a JSON line. In regex mode the whole query is one regular expression.
+#[derive(Clone, Debug)]
 pub struct ActiveFilter {
-    pub query: String,
-    pub query_lower: String,
-    pub match_case: bool,
-    pub regex: Option<Regex>,
+    expr: FilterExpr,
     pub invert: bool,
+    prefilter: Prefilter,
 }
 
 impl ActiveFilter {
+    /// What the search index must find in a block for it to hold a match
+    pub fn prefilter(&self) -> &Prefilter {
+        &self.prefilter
+    }
+
     pub fn matches(&self, text: &str) -> bool {
-        let matched = if let Some(re) = &self.regex {
-            re.is_match(text)
-        } else if self.match_case {
-            text.contains(&self.query)
-        } else {
-            // Optimization: Use pre-calculated lowercased query
-            text.to_lowercase().contains(&self.query_lower)
-        };
-        if self.invert {
-            !matched
-        } else {
-            matched
+        let line = Line::new(text);
+        self.expr.matches(&line) != self.invert
+    }
+
+    /// Byte ranges of the text that make it match, sorted and merged.
+    /// `None` if it does not match; an inverted filter matches without spans.
+    pub fn find(&self, text: &str) -> Option<Vec<Range<usize>>> {
+        let line = Line::new(text);
+        if self.expr.matches(&line) == self.invert {
+            return None;
+        }
+        let mut spans = Vec::new();
+        if !self.invert {
+            self.expr.spans(&line, &mut spans);
         }
+        spans.sort_by_key(|r| r.start);
+        let mut merged: Vec<Range<usize>> = Vec::with_capacity(spans.len());
+        for span in spans {
+            match merged.last_mut() {
+                Some(last) if span.start <= last.end => last.end = last.end.max(span.end),
+                _ => merged.push(span),
+            }
+        }
+        Some(merged)
     }
 }
 
@@ -46,11 +664,13 @@ impl ActiveFilterBuilder {
         }
     }
 
+    /// Default case sensitivity of terms without flags
     pub fn case_sensitive(mut self, yes: bool) -> Self {
         self.match_case = yes;
         self
     }
 
+    /// Treats the whole query as one regular expression, without operators
     pub fn regex(mut self, yes: bool) -> Self {
         self.use_regex = yes;
         self
@@ -61,30 +681,188 @@ impl ActiveFilterBuilder {
         self
     }
 
+    /// Parses the query; the error names the problem and its column
     pub fn build(self) -> Result<ActiveFilter, String> {
-        let regex = if self.use_regex {
-            Some(
-                regex::RegexBuilder::new(&self.query)
-                    .case_insensitive(!self.match_case)
-                    .build()
-                    .map_err(|e| e.to_string())?,
-            )
+        let mut parser = Parser {
+            query: &self.query,
+            tokens: Vec::new(),
+            pos: 0,
+            match_case: self.match_case,
+        };
+        let expr = if self.use_regex {
+            // Parentheses, `|` and `/` belong to the pattern
+            parser.regex(&self.query, self.match_case, 0)?
         } else {
-            None
+            parser.tokens = tokenize(&self.query)?;
+            let expr = parser.parse_or()?;
+            if parser.pos < parser.tokens.len() {
+                let at = parser.here();
+                return Err(error_at(&self.query, at, "Unexpected `)`"));
+            }
+            expr
         };
-
-        let query_lower = if !self.match_case {
-            self.query.to_lowercase()
+        // Lines without the text match an inverted filter
+        let prefilter = if self.invert {
+            Prefilter::Any
         } else {
-            String::new()
+            expr.prefilter()
         };
-
         Ok(ActiveFilter {
-            query: self.query,
-            query_lower,
-            match_case: self.match_case,
-            regex,
+            expr,
             invert: self.invert,
+            prefilter,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(query: &str) -> ActiveFilter {
+        ActiveFilterBuilder::new(query.into())
+            .case_sensitive(false)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_plain_phrase() {
+        let f = filter("Connection  lost");
+        assert!(f.matches("wifi: connection  LOST, retrying"));
+        assert!(!f.matches("connection restored, lost 3 packets"));
+    }
+
+    #[test]
+    fn test_boolean_operators() {
+        let f = filter("(ERROR or WARN) and not heartbeat");
+        assert!(f.matches("E (10) app: ERROR opening file"));
+        assert!(f.matches("warn: low battery"));
+        assert!(!f.matches("WARN heartbeat late"));
+        assert!(!f.matches("INFO ready"));
+
+        let f = filter("!debug || (panic && \"core dump\"c)");
+        assert!(f.matches("info"));
+        assert!(!f.matches("debug: core Dump panic"));
+        assert!(f.matches("debug: core dump panic"));
+    }
+
+    #[test]
+    fn test_fields() {
+        let f = filter("tag:wifi and rssi<-70");
+        assert!(f.matches("\x1b[0;33mW (4211) wifi: weak signal, rssi=-78"));
+        assert!(f.matches("[00:00:01.250,000] <wrn> wifi: rssi: -71 dBm"));
+        assert!(!f.matches("W (4211) wifi: rssi=-60"));
+        assert!(!f.matches("W (4211) ble: rssi=-90"));
+        assert!(!f.matches("W (4211) wifi: rssi_avg=-90"));
+
+        assert!(filter("temp>=80.5").matches("temp 81 rh 40"));
+        assert!(filter("count!=0").matches("count: 2"));
+    }
+
+    #[test]
+    fn test_json_fields() {
+        let line = r#"{"ts":"2024-05-01T10:00:02Z","level":"WARN","temp":"85.5","net":{"rssi":-71},"msg":"wifi lost"}"#;
+        assert!(filter("@temp>80 and @level=warn").matches(line));
+        assert!(filter("@net.rssi<=-70 @msg~WiFi\\ lost").matches(line));
+        assert!(filter("@ts>2024-05-01T10:00:00Z").matches(line));
+        assert!(!filter("@temp>90").matches(line));
+        assert!(!filter("@level!=warn").matches(line));
+        // A missing field or a line that is not JSON never matches
+        assert!(!filter("@humidity<50").matches(line));
+        assert!(!filter("@temp>80").matches("temp=85"));
+        assert!(filter("not @temp>80").matches("temp=85"));
+        assert_eq!(
+            *filter("@net.rssi<0").prefilter(),
+            Prefilter::literal("rssi", false)
+        );
+    }
+
+    #[test]
+    fn test_term_options() {
+        let f = ActiveFilterBuilder::new(r#"/warn(ing)?\d/ or "Err"c"#.into())
+            .case_sensitive(false)
+            .build()
+            .unwrap();
+        assert!(f.matches("WARNING7"));
+        assert!(f.matches("Err 5"));
+        assert!(!f.matches("err 5"));
+
+        let f = ActiveFilterBuilder::new("(ERROR|WARN) \\d+".into())
+            .regex(true)
+            .invert(true)
+            .build()
+            .unwrap();
+        assert!(!f.matches("WARN 12"));
+        assert!(f.matches("WARN x"));
+    }
+
+    #[test]
+    fn test_find_spans() {
+        let f = filter("(wifi or ble) and not debug and rssi<-70");
+        let line = "W (42) wifi: ble coexist, rssi=-80";
+        let spans: Vec<&str> = f
+            .find(line)
+            .unwrap()
+            .into_iter()
+            .map(|r| &line[r])
+            .collect();
+        assert_eq!(spans, ["wifi", "ble", "rssi=-80"]);
+        assert!(f.find("wifi debug rssi=-80").is_none());
+
+        let f = filter("/a+/ or aab");
+        let spans = f.find("xaab").unwrap();
+        assert_eq!((spans.len(), &spans[0]), (1, &(1..4)));
+    }
+
+    #[test]
+    fn test_prefilter() {
+        let grams = |text: &str, folded| Prefilter::literal(text, folded);
+        assert_eq!(
+            *filter("wifi and not debug and rssi<-70").prefilter(),
+            grams("wifi", true)
+        );
+        assert_eq!(
+            *filter("tag:ble or \"Heap\"c").prefilter(),
+            Prefilter::Or(vec![grams("ble", false), grams("Heap", false)])
+        );
+        assert_eq!(*filter("ok or /(a|b)c/").prefilter(), Prefilter::Any);
+        let inverted = ActiveFilterBuilder::new("wifi".into()).invert(true).build();
+        assert_eq!(*inverted.unwrap().prefilter(), Prefilter::Any);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = |q: &str| ActiveFilterBuilder::new(q.into()).build().unwrap_err();
+        assert_eq!(err("(ERROR or WARN"), "Missing `)` at column 15");
+        assert_eq!(err("ERROR and"), "Expected a term at column 10");
+        assert_eq!(err("a) b"), "Unexpected `)` at column 2");
+        assert_eq!(err("\"open"), "Unterminated quote at column 1");
+        assert_eq!(err("/x(/"), "Invalid regex at column 1");
+        assert_eq!(err("\"x\"z"), "Unknown flag (use i or c) at column 1");
+    }
+
+    #[test]
+    fn test_slashes_in_words() {
+        let f = filter("/dev/ttyUSB0");
+        assert!(f.matches("opened /dev/ttyUSB0 at 115200"));
+        assert!(!f.matches("opened /dev/ttyUSB1"));
+        let f = filter("GET /api/v1");
+        assert!(f.matches("10:02 GET /api/v1/status 200"));
+        assert!(!f.matches("POST /api/v1"));
+        // A pattern still ends at whitespace or `)`
+        assert!(filter("(/v\\d/ or x)").matches("api v2"));
+    }
+
+    #[test]
+    fn test_regex_mode_takes_whole_query() {
+        let regex = |q: &str| ActiveFilterBuilder::new(q.into()).regex(true).build();
+        let f = regex("foo(bar)?").unwrap();
+        assert!(f.matches("foo") && f.matches("a foobar"));
+        let f = regex("(?i)err").unwrap();
+        assert!(f.matches("ERR: boot"));
+        let f = regex("(ERROR|WARN) \\d+").unwrap();
+        assert!(f.matches("WARN 12") && !f.matches("WARN x") && !f.matches("12"));
+        assert_eq!(regex("a(").unwrap_err(), "Invalid regex at column 1");
+    }
+}
@@ -0,0 +1,63 @@
+use crate::types::{LevelCounts, LogLevel};
+use crate::worker::repository::index::line_set::LineSet;
+
+/// Lines of the session by detected level; the set sizes are the running counts
+pub struct LevelIndex {
+    sets: [LineSet; 6],
+}
+
+impl LevelIndex {
+    pub fn new() -> Self {
+        Self {
+            sets: std::array::from_fn(|_| LineSet::new()),
+        }
+    }
+
+    pub fn push(&mut self, line: usize, level: LogLevel) {
+        self.sets[level.as_byte() as usize].insert(line);
+    }
+
+    /// Level of a session line; lines not recorded read as `None`
+    pub fn level(&self, line: usize) -> LogLevel {
+        LogLevel::ALL
+            .into_iter()
+            .rev()
+            .find(|&level| self.sets[level.as_byte() as usize].contains(line))
+            .unwrap_or_default()
+    }
+
+    /// Session lines at `level` or more severe
+    pub fn at_least(&self, level: LogLevel) -> LineSet {
+        let mut lines = LineSet::new();
+        for set in &self.sets[level.as_byte() as usize..] {
+            lines.union(set);
+        }
+        lines
+    }
+
+    pub fn counts(&self) -> LevelCounts {
+        LevelCounts(std::array::from_fn(|i| self.sets[i].len()))
+    }
+
+    pub fn drop_front(&mut self, lines: usize) {
+        for set in &mut self.sets {
+            set.drop_front(lines);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        for set in &mut self.sets {
+            set.clear();
+        }
+    }
+
+    pub fn memory_bytes(&self) -> usize {
+        self.sets.iter().map(|set| set.memory_bytes()).sum()
+    }
+}
+
+impl Default for LevelIndex {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,180 @@
+use super::types::ByteOffset;
+
+/// Offsets per block; each block stores one absolute base
+const BLOCK_LEN: usize = 1024;
+/// Delta marking an offset kept in `overflow` instead
+const DELTA_OVERFLOW: u32 = u32::MAX;
+
+/// Increasing byte offsets stored as a `u64` base per block of `BLOCK_LEN`
+/// entries plus a `u32` delta per entry, about 4 bytes per line instead of 8.
+/// Lines are bounded by the formatter, so a delta never comes close to 4 GiB;
+/// any that does is kept in a small side table.
+pub struct LineOffsets {
+    bases: Vec<u64>,
+    deltas: Vec<u32>,
+    overflow: Vec<(usize, u64)>,
+    /// Entries of the first block that were dropped
+    start: usize,
+    /// Bytes dropped from the front; stored offsets are this much larger
+    shift: u64,
+}
+
+impl LineOffsets {
+    /// An index holding the single offset 0
+    pub fn new() -> Self {
+        let mut offsets = Self {
+            bases: Vec::new(),
+            deltas: Vec::new(),
+            overflow: Vec::new(),
+            start: 0,
+            shift: 0,
+        };
+        offsets.push(ByteOffset(0));
+        offsets
+    }
+
+    pub fn len(&self) -> usize {
+        self.deltas.len() - self.start
+    }
+
+    pub fn push(&mut self, offset: ByteOffset) {
+        let i = self.deltas.len();
+        let stored = offset.0 + self.shift;
+        if i.is_multiple_of(BLOCK_LEN) {
+            self.bases.push(stored);
+        }
+        let base = self.bases[i / BLOCK_LEN];
+        match u32::try_from(stored - base) {
+            Ok(delta) if delta != DELTA_OVERFLOW => self.deltas.push(delta),
+            _ => {
+                self.deltas.push(DELTA_OVERFLOW);
+                self.overflow.push((i, stored));
+            }
+        }
+    }
+
+    pub fn get(&self, i: usize) -> ByteOffset {
+        let i = i + self.start;
+        let delta = self.deltas[i];
+        let stored = if delta == DELTA_OVERFLOW {
+            let pos = self
+                .overflow
+                .binary_search_by_key(&i, |&(at, _)| at)
+                .expect("overflowed offset is recorded");
+            self.overflow[pos].1
+        } else {
+            self.bases[i / BLOCK_LEN] + delta as u64
+        };
+        ByteOffset(stored - self.shift)
+    }
+
+    /// Index of the first offset for which `pred` is false (offsets must be partitioned)
+    pub fn partition_point(&self, pred: impl Fn(ByteOffset) -> bool) -> usize {
+        let (mut lo, mut hi) = (0, self.len());
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            if pred(self.get(mid)) {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        lo
+    }
+
+    /// Drops the first `count` entries and shifts the rest down by the first kept offset.
+    /// Returns that offset.
+    pub fn drop_front(&mut self, count: usize) -> ByteOffset {
+        let count = count.min(self.len().saturating_sub(1));
+        let cut = self.get(count);
+
+        // Whole blocks go; entries dropped from the block that is left are skipped
+        let first = self.start + count;
+        let blocks = first / BLOCK_LEN;
+        let entries = blocks * BLOCK_LEN;
+        self.bases.drain(..blocks);
+        self.deltas.drain(..entries);
+        self.overflow.retain(|&(at, _)| at >= entries);
+        for (at, _) in &mut self.overflow {
+            *at -= entries;
+        }
+        self.start = first - entries;
+        self.shift += cut.0;
+        cut
+    }
+
+    /// Heap bytes used by the index
+    pub fn memory_bytes(&self) -> usize {
+        self.bases.capacity() * 8 + self.deltas.capacity() * 4 + self.overflow.capacity() * 16
+    }
+}
+
+impl Default for LineOffsets {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_across_blocks() {
+        let mut offsets = LineOffsets::new();
+        for i in 1..=3 * BLOCK_LEN as u64 {
+            offsets.push(ByteOffset(i * 17));
+        }
+        assert_eq!(offsets.len(), 3 * BLOCK_LEN + 1);
+        for i in [0, 1, BLOCK_LEN - 1, BLOCK_LEN, 2 * BLOCK_LEN + 5] {
+            assert_eq!(offsets.get(i), ByteOffset(i as u64 * 17));
+        }
+        assert_eq!(offsets.partition_point(|off| off.0 < 17 * 1500), 1500);
+    }
+
+    #[test]
+    fn test_wide_delta_overflows() {
+        let mut offsets = LineOffsets::new();
+        offsets.push(ByteOffset(10));
+        offsets.push(ByteOffset(10 + (1 << 33)));
+        offsets.push(ByteOffset(11 + (1 << 33)));
+        assert_eq!(offsets.get(2), ByteOffset(10 + (1 << 33)));
+        assert_eq!(offsets.get(3), ByteOffset(11 + (1 << 33)));
+        assert_eq!(offsets.get(1), ByteOffset(10));
+    }
+
+    #[test]
+    fn test_drop_front_rebases() {
+        let mut offsets = LineOffsets::new();
+        for end in [4, 9, 15] {
+            offsets.push(ByteOffset(end));
+        }
+        assert_eq!(offsets.drop_front(2), ByteOffset(9));
+        assert_eq!(
+            (0..offsets.len())
+                .map(|i| offsets.get(i))
+                .collect::<Vec<_>>(),
+            [ByteOffset(0), ByteOffset(6)]
+        );
+    }
+
+    #[test]
+    fn test_drop_front_across_blocks() {
+        let mut offsets = LineOffsets::new();
+        for i in 1..=2 * BLOCK_LEN as u64 {
+            offsets.push(ByteOffset(i * 10));
+        }
+        let count = BLOCK_LEN + 5;
+        assert_eq!(offsets.drop_front(count), ByteOffset(count as u64 * 10));
+        assert_eq!(offsets.len(), BLOCK_LEN - 4);
+        assert_eq!(offsets.get(1), ByteOffset(10));
+        offsets.push(ByteOffset(1 << 33));
+        let last = offsets.len() - 1;
+        assert_eq!(offsets.get(last), ByteOffset(1 << 33));
+        assert_eq!(
+            offsets.get(last - 1),
+            ByteOffset((BLOCK_LEN as u64 - 5) * 10)
+        );
+        assert_eq!(offsets.partition_point(|off| off.0 < 25), 3);
+    }
+}
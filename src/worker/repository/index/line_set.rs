@@ -0,0 +1,525 @@
+/// Lines per container; the low 16 bits of a line number address into it
+const CONTAINER_BITS: u32 = 16;
+const CONTAINER_LEN: usize = 1 << CONTAINER_BITS;
+/// Above this many entries a sorted array costs more than a bitmap
+const ARRAY_MAX: usize = 4096;
+const BITMAP_WORDS: usize = CONTAINER_LEN / 64;
+
+/// Lines of one 65,536-line container
+#[derive(Clone)]
+enum Container {
+    /// Sorted low bits, 2 bytes per line; used for sparse matches
+    Array(Vec<u16>),
+    /// One bit per line, 8 KiB; used for dense matches
+    Bitmap { words: Box<[u64]>, len: usize },
+}
+
+impl Container {
+    fn len(&self) -> usize {
+        match self {
+            Container::Array(values) => values.len(),
+            Container::Bitmap { len, .. } => *len,
+        }
+    }
+
+    fn insert(&mut self, low: u16) {
+        match self {
+            Container::Array(values) => {
+                // Lines mostly arrive in order
+                if values.last().is_some_and(|&last| last < low) {
+                    values.push(low);
+                } else if let Err(pos) = values.binary_search(&low) {
+                    values.insert(pos, low);
+                }
+                if values.len() > ARRAY_MAX {
+                    let len = values.len();
+                    let words = bitmap_of(values);
+                    *self = Container::Bitmap { words, len };
+                }
+            }
+            Container::Bitmap { words, len } => {
+                let (word, bit) = (low as usize / 64, 1u64 << (low % 64));
+                if words[word] & bit == 0 {
+                    words[word] |= bit;
+                    *len += 1;
+                }
+            }
+        }
+    }
+
+    /// Adds the lines of `other`
+    fn union(&mut self, other: &Container) {
+        match (&mut *self, other) {
+            (Container::Array(values), Container::Array(more)) => {
+                let mut merged = Vec::with_capacity(values.len() + more.len());
+                let (mut a, mut b) = (values.iter().peekable(), more.iter().peekable());
+                while let (Some(&&x), Some(&&y)) = (a.peek(), b.peek()) {
+                    merged.push(x.min(y));
+                    if x <= y {
+                        a.next();
+                    }
+                    if y <= x {
+                        b.next();
+                    }
+                }
+                merged.extend(a.chain(b));
+                *self = match merged.len() > ARRAY_MAX {
+                    true => Container::Bitmap {
+                        words: bitmap_of(&merged),
+                        len: merged.len(),
+                    },
+                    false => Container::Array(merged),
+                };
+            }
+            (Container::Array(values), Container::Bitmap { words, .. }) => {
+                let mut words = words.clone();
+                for &v in values.iter() {
+                    words[v as usize / 64] |= 1 << (v % 64);
+                }
+                let len = words.iter().map(|w| w.count_ones() as usize).sum();
+                *self = Container::Bitmap { words, len };
+            }
+            (Container::Bitmap { words, len }, other) => {
+                match other {
+                    Container::Array(more) => {
+                        for &v in more {
+                            words[v as usize / 64] |= 1 << (v % 64);
+                        }
+                    }
+                    Container::Bitmap { words: more, .. } => {
+                        for (word, more) in words.iter_mut().zip(more.iter()) {
+                            *word |= more;
+                        }
+                    }
+                }
+                *len = words.iter().map(|w| w.count_ones() as usize).sum();
+            }
+        }
+    }
+
+    /// Returns whether `low` was in the container
+    fn remove(&mut self, low: u16) -> bool {
+        match self {
+            Container::Array(values) => match values.binary_search(&low) {
+                Ok(pos) => {
+                    values.remove(pos);
+                    true
+                }
+                Err(_) => false,
+            },
+            Container::Bitmap { words, len } => {
+                let (word, bit) = (low as usize / 64, 1u64 << (low % 64));
+                let present = words[word] & bit != 0;
+                if present {
+                    words[word] &= !bit;
+                    *len -= 1;
+                }
+                present
+            }
+        }
+    }
+
+    /// Removes the lines below `low`
+    fn remove_below(&mut self, low: u16) {
+        match self {
+            Container::Array(values) => {
+                let below = values.partition_point(|&v| v < low);
+                values.drain(..below);
+            }
+            Container::Bitmap { words, len } => {
+                let (word, bit) = (low as usize / 64, low % 64);
+                words[..word].fill(0);
+                words[word] &= !((1u64 << bit) - 1);
+                *len = words.iter().map(|w| w.count_ones() as usize).sum();
+            }
+        }
+    }
+
+    /// Low bits of the `n`th line
+    fn nth(&self, mut n: usize) -> Option<u16> {
+        match self {
+            Container::Array(values) => values.get(n).copied(),
+            Container::Bitmap { words, .. } => {
+                for (i, &word) in words.iter().enumerate() {
+                    let ones = word.count_ones() as usize;
+                    if n < ones {
+                        let mut w = word;
+                        for _ in 0..n {
+                            w &= w - 1;
+                        }
+                        return Some((i * 64) as u16 + w.trailing_zeros() as u16);
+                    }
+                    n -= ones;
+                }
+                None
+            }
+        }
+    }
+
+    /// Position of `low` among the lines of the container
+    fn rank(&self, low: u16) -> Option<usize> {
+        match self {
+            Container::Array(values) => values.binary_search(&low).ok(),
+            Container::Bitmap { words, .. } => {
+                let (word, bit) = (low as usize / 64, low % 64);
+                if words[word] & (1 << bit) == 0 {
+                    return None;
+                }
+                let before: u32 = words[..word].iter().map(|w| w.count_ones()).sum();
+                let below = (words[word] & ((1u64 << bit) - 1)).count_ones();
+                Some((before + below) as usize)
+            }
+        }
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = u16> + '_> {
+        match self {
+            Container::Array(values) => Box::new(values.iter().copied()),
+            Container::Bitmap { words, .. } => {
+                Box::new(words.iter().enumerate().flat_map(|(i, &word)| {
+                    (0..64u16)
+                        .filter(move |b| word & (1 << b) != 0)
+                        .map(move |b| i as u16 * 64 + b)
+                }))
+            }
+        }
+    }
+
+    fn memory_bytes(&self) -> usize {
+        match self {
+            Container::Array(values) => values.capacity() * 2,
+            Container::Bitmap { words, .. } => words.len() * 8,
+        }
+    }
+}
+
+fn bitmap_of(values: &[u16]) -> Box<[u64]> {
+    let mut words = vec![0u64; BITMAP_WORDS].into_boxed_slice();
+    for &v in values {
+        words[v as usize / 64] |= 1 << (v % 64);
+    }
+    words
+}
+
+/// Sorted set of line numbers, used for filter results.
+/// Sparse matches cost about 2 bytes per line and dense ones 1 bit per line,
+/// in place of a 16-byte range per match.
+#[derive(Default)]
+pub struct LineSet {
+    /// Containers by the high bits of their lines, in increasing order
+    containers: Vec<(usize, Container)>,
+    /// Number of lines in all containers before each one
+    counts_before: Vec<usize>,
+    len: usize,
+    /// Lines dropped from the front; stored lines are this much larger
+    base: usize,
+}
+
+impl LineSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.containers.clear();
+        self.counts_before.clear();
+        self.len = 0;
+        self.base = 0;
+    }
+
+    pub fn insert(&mut self, line: usize) {
+        let line = line + self.base;
+        let (key, low) = (line >> CONTAINER_BITS, line as u16);
+        let pos = match self.containers.last() {
+            // Live ingest appends to the last container
+            Some((last, _)) if *last == key => self.containers.len() - 1,
+            _ => match self.containers.binary_search_by_key(&key, |(k, _)| *k) {
+                Ok(pos) => pos,
+                Err(pos) => {
+                    let before = self.counts_before.get(pos).copied().unwrap_or(self.len);
+                    self.containers
+                        .insert(pos, (key, Container::Array(Vec::new())));
+                    self.counts_before.insert(pos, before);
+                    pos
+                }
+            },
+        };
+
+        let container = &mut self.containers[pos].1;
+        let before = container.len();
+        container.insert(low);
+        if container.len() > before {
+            self.len += 1;
+            for count in &mut self.counts_before[pos + 1..] {
+                *count += 1;
+            }
+        }
+    }
+
+    pub fn remove(&mut self, line: usize) {
+        let line = line + self.base;
+        let key = line >> CONTAINER_BITS;
+        let Ok(pos) = self.containers.binary_search_by_key(&key, |(k, _)| *k) else {
+            return;
+        };
+        if !self.containers[pos].1.remove(line as u16) {
+            return;
+        }
+        self.len -= 1;
+        for count in &mut self.counts_before[pos + 1..] {
+            *count -= 1;
+        }
+        if self.containers[pos].1.len() == 0 {
+            self.containers.remove(pos);
+            self.counts_before.remove(pos);
+        }
+    }
+
+    pub fn contains(&self, line: usize) -> bool {
+        self.rank(line).is_some()
+    }
+
+    /// Adds lines that all precede the lines already in the set
+    pub fn prepend(&mut self, lines: &[usize]) {
+        let mut front = LineSet {
+            base: self.base,
+            ..LineSet::new()
+        };
+        for &line in lines {
+            front.insert(line);
+        }
+        for (key, container) in std::mem::take(&mut self.containers) {
+            match front.containers.last_mut() {
+                // A backward search batch may end inside the first container
+                Some((last, merged)) if *last == key => {
+                    for low in container.iter() {
+                        merged.insert(low);
+                    }
+                }
+                _ => front.containers.push((key, container)),
+            }
+        }
+        front.recount();
+        *self = front;
+    }
+
+    /// Adds the lines of `other`, merging container by container
+    pub fn union(&mut self, other: &LineSet) {
+        if self.is_empty() {
+            self.base = other.base;
+        }
+        if self.base != other.base {
+            for line in other.iter() {
+                self.insert(line);
+            }
+            return;
+        }
+        let mut merged = Vec::with_capacity(self.containers.len() + other.containers.len());
+        let mut more = other.containers.iter().peekable();
+        for (key, mut container) in std::mem::take(&mut self.containers) {
+            while let Some((k, c)) = more.next_if(|(k, _)| *k <= key) {
+                if *k == key {
+                    container.union(c);
+                } else {
+                    merged.push((*k, c.clone()));
+                }
+            }
+            merged.push((key, container));
+        }
+        merged.extend(more.cloned());
+        self.containers = merged;
+        self.recount();
+    }
+
+    /// The `n`th line of the set
+    pub fn nth(&self, n: usize) -> Option<usize> {
+        if n >= self.len {
+            return None;
+        }
+        let pos = self.counts_before.partition_point(|&count| count <= n) - 1;
+        let (key, container) = &self.containers[pos];
+        let low = container.nth(n - self.counts_before[pos])?;
+        Some(((key << CONTAINER_BITS) | low as usize) - self.base)
+    }
+
+    /// Position of `line` in the set
+    pub fn rank(&self, line: usize) -> Option<usize> {
+        let line = line + self.base;
+        let key = line >> CONTAINER_BITS;
+        let pos = self
+            .containers
+            .binary_search_by_key(&key, |(k, _)| *k)
+            .ok()?;
+        Some(self.counts_before[pos] + self.containers[pos].1.rank(line as u16)?)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        let base = self.base;
+        self.containers.iter().flat_map(move |(key, container)| {
+            container
+                .iter()
+                .map(move |low| ((key << CONTAINER_BITS) | low as usize) - base)
+        })
+    }
+
+    /// Removes lines below `count` and shifts the rest down by `count`
+    pub fn drop_front(&mut self, count: usize) {
+        self.base += count;
+        let (key, low) = (self.base >> CONTAINER_BITS, self.base as u16);
+        let whole = self.containers.partition_point(|(k, _)| *k < key);
+        self.containers.drain(..whole);
+        if let Some((k, container)) = self.containers.first_mut() {
+            if *k == key {
+                container.remove_below(low);
+                if container.len() == 0 {
+                    self.containers.remove(0);
+                }
+            }
+        }
+        self.recount();
+    }
+
+    /// Heap bytes used by the set
+    pub fn memory_bytes(&self) -> usize {
+        self.containers
+            .iter()
+            .map(|(_, c)| c.memory_bytes() + std::mem::size_of::<(usize, Container)>())
+            .sum::<usize>()
+            + self.counts_before.capacity() * std::mem::size_of::<usize>()
+    }
+
+    fn recount(&mut self) {
+        self.counts_before.clear();
+        let mut total = 0;
+        for (_, container) in &self.containers {
+            self.counts_before.push(total);
+            total += container.len();
+        }
+        self.len = total;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sparse_and_dense_containers() {
+        let mut set = LineSet::new();
+        // Dense run turns the first container into a bitmap
+        for line in 0..10_000 {
+            set.insert(line);
+        }
+        // Sparse lines in later containers
+        for line in [70_000, 200_000, 200_001] {
+            set.insert(line);
+        }
+        assert_eq!(set.len(), 10_003);
+        assert_eq!(set.nth(9_999), Some(9_999));
+        assert_eq!(set.nth(10_000), Some(70_000));
+        assert_eq!(set.nth(10_002), Some(200_001));
+        assert_eq!(set.nth(10_003), None);
+        assert_eq!(set.rank(200_000), Some(10_001));
+        assert_eq!(set.rank(5_000), Some(5_000));
+        assert_eq!(set.rank(10_000), None);
+    }
+
+    #[test]
+    fn test_prepend_merges_shared_container() {
+        let mut set = LineSet::new();
+        set.insert(100);
+        set.insert(70_000);
+        set.prepend(&[3, 50]);
+        assert_eq!(set.iter().collect::<Vec<_>>(), [3, 50, 100, 70_000]);
+        assert_eq!(set.nth(2), Some(100));
+        assert_eq!(set.rank(70_000), Some(3));
+    }
+
+    #[test]
+    fn test_union_merges_containers() {
+        let mut set = LineSet::new();
+        for line in (0..6_000).step_by(2).chain([70_000, 200_000]) {
+            set.insert(line);
+        }
+        let mut other = LineSet::new();
+        // Odd lines turn the shared container into a bitmap
+        for line in (1..6_000).step_by(2).chain([100, 140_000, 200_001]) {
+            other.insert(line);
+        }
+        set.union(&other);
+        assert_eq!(set.len(), 6_004);
+        assert_eq!(set.rank(5_999), Some(5_999));
+        assert_eq!(set.nth(6_000), Some(70_000));
+        assert_eq!(
+            set.iter().skip(6_000).collect::<Vec<_>>(),
+            [70_000, 140_000, 200_000, 200_001]
+        );
+
+        // Sets with lines dropped from the front
+        let mut empty = LineSet::new();
+        other.drop_front(100);
+        empty.union(&other);
+        assert_eq!(empty.nth(0), Some(0));
+        assert_eq!(empty.nth(1), Some(1));
+        assert_eq!(empty.len(), other.len());
+    }
+
+    #[test]
+    fn test_remove_keeps_ranks() {
+        let mut set = LineSet::new();
+        for line in (0..6_000).chain([70_000, 70_001]) {
+            set.insert(line);
+        }
+        set.remove(10);
+        set.remove(70_000);
+        set.remove(70_001);
+        set.remove(80_000);
+        assert_eq!(set.len(), 5_999);
+        assert!(!set.contains(10));
+        assert_eq!(set.nth(10), Some(11));
+        assert_eq!(set.rank(5_999), Some(5_998));
+        assert_eq!(set.nth(5_999), None);
+    }
+
+    #[test]
+    fn test_drop_front_shifts_lines() {
+        let mut set = LineSet::new();
+        for line in [1, 5, 9] {
+            set.insert(line);
+        }
+        set.drop_front(5);
+        assert_eq!(set.iter().collect::<Vec<_>>(), [0, 4]);
+    }
+
+    #[test]
+    fn test_drop_front_across_containers() {
+        let mut set = LineSet::new();
+        // A bitmap container, a sparse one and lines past both
+        for line in (0..10_000).chain([70_000, 70_100, 140_000]) {
+            set.insert(line);
+        }
+        set.drop_front(70_050);
+        assert_eq!(set.iter().collect::<Vec<_>>(), [50, 69_950]);
+        set.insert(100);
+        assert_eq!(set.rank(100), Some(1));
+        assert_eq!(set.nth(2), Some(69_950));
+
+        // Cut inside a bitmap container
+        let mut set = LineSet::new();
+        for line in 0..10_000 {
+            set.insert(line);
+        }
+        set.drop_front(9_000);
+        assert_eq!(set.len(), 1_000);
+        assert_eq!(set.nth(0), Some(0));
+        assert_eq!(set.rank(999), Some(999));
+    }
+}
@@ -1,30 +1,100 @@
+use crate::config::SEARCH_INDEX_MAX_BYTES;
+use crate::types::{LogLevel, SearchIndexSize};
 use crate::worker::repository::index::filter::ActiveFilter;
+use crate::worker::repository::index::levels::LevelIndex;
+use crate::worker::repository::index::line_offsets::LineOffsets;
+use crate::worker::repository::index::line_set::LineSet;
+use crate::worker::repository::index::sorted_view::SortedView;
+use crate::worker::repository::index::trigram::TrigramIndex;
 use crate::worker::repository::index::types::{ByteOffset, LineIndex, LineRange};
+use std::ops::Range;
 
 /// Log index that tracks line offsets and filtering state
 pub struct LogIndex {
-    pub line_offsets: Vec<ByteOffset>,
+    /// Start offset of every line, followed by the end of the last one
+    pub line_offsets: LineOffsets,
     pub line_count: usize,
-    pub filtered_lines: Vec<LineRange>,
+    /// Number of lines dropped from the front of the session (ring buffer mode)
+    pub first_line: usize,
+    /// Lines matching the active filter, and the context shown around them
+    pub filtered_lines: LineSet,
+    /// Lines of `filtered_lines` shown only as context of a match
+    pub context_lines: LineSet,
+    /// Lines of context shown before and after each match
+    pub context: (usize, usize),
+    /// Trailing context of the last match not indexed yet; filled as lines arrive
+    context_pending: Range<usize>,
+    /// Set while a text or level filter is applied
     pub is_filtering: bool,
     pub active_filter: Option<ActiveFilter>,
+    /// Level filter: only lines this severe or more are shown
+    pub min_level: Option<LogLevel>,
+    /// Level detected on each session line
+    pub levels: LevelIndex,
+    /// Block summaries that let a filter scan skip blocks without a match
+    pub search_index: TrigramIndex,
+    /// Order of the view lines while the view is sorted by a field;
+    /// covers the same lines as the unsorted view
+    pub sorted: Option<SortedView>,
 }
 
 impl LogIndex {
     pub fn new() -> Self {
         Self {
-            line_offsets: vec![ByteOffset(0)],
+            line_offsets: LineOffsets::new(),
             line_count: 0,
-            filtered_lines: Vec::new(),
+            first_line: 0,
+            filtered_lines: LineSet::new(),
+            context_lines: LineSet::new(),
+            context: (0, 0),
+            context_pending: 0..0,
             is_filtering: false,
             active_filter: None,
+            min_level: None,
+            levels: LevelIndex::new(),
+            search_index: TrigramIndex::new(
+                SearchIndexSize::default().bits_per_gram(),
+                SEARCH_INDEX_MAX_BYTES,
+            ),
+            sorted: None,
         }
     }
 
     pub fn reset_base(&mut self) {
-        self.line_offsets = vec![ByteOffset(0)];
+        self.line_offsets = LineOffsets::new();
         self.line_count = 0;
+        self.first_line = 0;
         self.filtered_lines.clear();
+        self.context_lines.clear();
+        self.context_pending = 0..0;
+        self.levels.clear();
+        self.search_index.resume(0);
+        self.sorted = None;
+    }
+
+    /// Drops the first `lines` lines and rebases all offsets onto the remaining data.
+    /// Returns the number of bytes removed from the front.
+    pub fn drop_front(&mut self, lines: usize) -> u64 {
+        let lines = lines.min(self.line_count);
+        let cut = self.line_offsets.drop_front(lines);
+        self.line_count -= lines;
+        self.first_line += lines;
+        self.filtered_lines.drop_front(lines);
+        self.context_lines.drop_front(lines);
+        self.levels.drop_front(lines);
+        self.context_pending = self.context_pending.start.saturating_sub(lines)
+            ..self.context_pending.end.saturating_sub(lines);
+        self.search_index.drop_front(self.first_line);
+        let first_line = self.first_line;
+        if let Some(sorted) = &mut self.sorted {
+            sorted.drop_front(first_line);
+        }
+        cut.0
+    }
+
+    /// End of the last indexed line, i.e. the size of the indexed log
+    pub fn end_offset(&self) -> ByteOffset {
+        self.line_offsets.get(self.line_count)
     }
 
     pub fn push_line(&mut self, absolute_end_offset: ByteOffset) {
@@ -32,15 +102,58 @@ impl LogIndex {
         self.line_count += 1;
     }
 
-    pub fn push_filtered(&mut self, range: LineRange) {
-        self.filtered_lines.push(range);
+    /// Records a line matching the active filter
+    pub fn push_filtered(&mut self, line: usize) {
+        self.filtered_lines.insert(line);
+    }
+
+    /// Records the matches among the lines in `scanned` with the context around them.
+    /// Trailing context past the indexed lines is taken from lines scanned later,
+    /// so live ingest keeps extending it. Overlapping context merges.
+    pub fn add_matches(&mut self, scanned: Range<usize>, matches: &[usize]) {
+        let (before, after) = self.context;
+        for &line in matches {
+            // A backward search may have shown this line as context of a later match
+            if self.context_lines.contains(line) {
+                self.context_lines.remove(line);
+            } else {
+                self.filtered_lines.insert(line);
+            }
+        }
+        for &line in matches {
+            for context in line.saturating_sub(before)..line {
+                self.push_context(context);
+            }
+            let end = line + 1 + after;
+            for context in line + 1..end.min(self.line_count) {
+                self.push_context(context);
+            }
+            if end > self.line_count {
+                self.context_pending = self.line_count..end.max(self.context_pending.end);
+            }
+        }
+        let pending = scanned.start.max(self.context_pending.start)
+            ..scanned.end.min(self.context_pending.end);
+        for line in pending {
+            self.push_context(line);
+        }
+    }
+
+    fn push_context(&mut self, line: usize) {
+        if !self.filtered_lines.contains(line) {
+            self.filtered_lines.insert(line);
+            self.context_lines.insert(line);
+        }
     }
 
-    pub fn prepend_filtered(&mut self, ranges: Vec<LineRange>) {
+    /// Records matches that precede all matches found so far
+    pub fn prepend_filtered(&mut self, lines: Vec<usize>) {
         if self.filtered_lines.is_empty() {
-            self.filtered_lines = ranges;
+            for line in lines {
+                self.filtered_lines.insert(line);
+            }
         } else {
-            self.filtered_lines.splice(0..0, ranges);
+            self.filtered_lines.prepend(&lines);
         }
     }
 
@@ -53,22 +166,76 @@ impl LogIndex {
     }
 
     pub fn get_line_range(&self, index: LineIndex) -> Option<LineRange> {
+        self.line_range(self.line_at(index)?)
+    }
+
+    /// Byte range of a line of the session, regardless of the filter
+    pub fn line_range(&self, line: usize) -> Option<LineRange> {
+        (line < self.line_count).then(|| LineRange {
+            start: self.line_offsets.get(line),
+            end: self.line_offsets.get(line + 1),
+        })
+    }
+
+    /// Line of the session shown at `index` of the current view
+    pub fn line_at(&self, index: LineIndex) -> Option<usize> {
+        if let Some(sorted) = &self.sorted {
+            return sorted.get(index.0).map(|line| line - self.first_line);
+        }
         if self.is_filtering {
-            self.filtered_lines.get(index.0).cloned()
-        } else if index.0 < self.line_count {
-            Some(LineRange {
-                start: self.line_offsets[index.0],
-                end: self.line_offsets[index.0 + 1],
-            })
+            self.filtered_lines.nth(index.0)
         } else {
-            None
+            (index.0 < self.line_count).then_some(index.0)
         }
     }
 
+    /// Heap bytes used by line offsets, levels, filter results and the sort order;
+    /// the search index is reported apart
+    pub fn memory_bytes(&self) -> usize {
+        self.line_offsets.memory_bytes()
+            + self.filtered_lines.memory_bytes()
+            + self.context_lines.memory_bytes()
+            + self.levels.memory_bytes()
+            + self.sorted.as_ref().map_or(0, SortedView::memory_bytes)
+    }
+
+    /// Lines matching the active filter, without context lines
+    pub fn match_count(&self) -> usize {
+        self.filtered_lines.len() - self.context_lines.len()
+    }
+
+    /// Whether filter results include context lines
+    pub fn has_context(&self) -> bool {
+        self.is_filtering && self.context != (0, 0)
+    }
+
+    /// Whether a session line is part of the current view
+    pub fn in_view(&self, line: usize) -> bool {
+        line < self.line_count && (!self.is_filtering || self.filtered_lines.contains(line))
+    }
+
+    /// Whether a line passes the level filter
+    pub fn level_allows(&self, line: usize) -> bool {
+        self.min_level
+            .is_none_or(|min| self.levels.level(line) >= min)
+    }
+
+    /// Whether a new line with the given text and level passes the active filters
+    pub fn matches(&self, text: &str, level: LogLevel) -> bool {
+        self.is_filtering
+            && self.min_level.is_none_or(|min| level >= min)
+            && self.active_filter.as_ref().is_none_or(|f| f.matches(text))
+    }
+
     pub fn clear_filter(&mut self) {
         self.is_filtering = false;
         self.active_filter = None;
+        self.min_level = None;
         self.filtered_lines.clear();
+        self.context_lines.clear();
+        self.context = (0, 0);
+        self.context_pending = 0..0;
+        self.sorted = None;
     }
 }
 
@@ -77,3 +244,77 @@ impl Default for LogIndex {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drop_front_rebases_offsets() {
+        let mut index = LogIndex::new();
+        for end in [4, 9, 15] {
+            index.push_line(ByteOffset(end));
+        }
+        index.push_filtered(0);
+        index.push_filtered(2);
+
+        assert_eq!(index.drop_front(2), 9);
+        assert_eq!(index.line_count, 1);
+        assert_eq!(index.first_line, 2);
+        assert_eq!(
+            index.get_line_range(LineIndex(0)),
+            Some(LineRange {
+                start: ByteOffset(0),
+                end: ByteOffset(6)
+            })
+        );
+        assert_eq!(index.filtered_lines.iter().collect::<Vec<_>>(), [0]);
+    }
+
+    #[test]
+    fn test_filtered_view_maps_to_lines() {
+        let mut index = LogIndex::new();
+        for end in [4, 9, 15, 22] {
+            index.push_line(ByteOffset(end));
+        }
+        index.is_filtering = true;
+        index.push_filtered(3);
+        index.prepend_filtered(vec![1]);
+
+        assert_eq!(index.get_total_count(), 2);
+        assert_eq!(index.line_at(LineIndex(1)), Some(3));
+        assert_eq!(
+            index.get_line_range(LineIndex(0)),
+            Some(LineRange {
+                start: ByteOffset(4),
+                end: ByteOffset(9)
+            })
+        );
+        assert_eq!(index.get_line_range(LineIndex(2)), None);
+    }
+
+    #[test]
+    fn test_context_merges_and_follows_live_lines() {
+        let mut index = LogIndex::new();
+        for end in 1..=20 {
+            index.push_line(ByteOffset(end));
+        }
+        index.is_filtering = true;
+        index.context = (2, 1);
+        // Backward search: the later batch first, then the earlier one
+        index.add_matches(10..20, &[12, 19]);
+        index.add_matches(0..10, &[5, 9]);
+        let shown: Vec<usize> = index.filtered_lines.iter().collect();
+        assert_eq!(shown, [3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 17, 18, 19]);
+        let context: Vec<usize> = index.context_lines.iter().collect();
+        assert_eq!(context, [3, 4, 6, 7, 8, 10, 11, 13, 17, 18]);
+
+        // Trailing context of line 19 arrives with the next lines
+        for end in 21..=23 {
+            index.push_line(ByteOffset(end));
+        }
+        index.add_matches(20..23, &[]);
+        assert!(index.context_lines.contains(20));
+        assert!(!index.filtered_lines.contains(21));
+    }
+}
@@ -1,5 +1,10 @@
 pub mod filter;
+pub mod levels;
+pub mod line_offsets;
+pub mod line_set;
 pub mod log_index;
+pub mod sorted_view;
+pub mod trigram;
 pub mod types;
 
 // Re-export commonly used items
@@ -0,0 +1,219 @@
+use crate::worker::json_line::SortKey;
+
+/// Lines placed since the last merge before they are merged into the order
+const PENDING_MAX: usize = 4096;
+/// Reverse map entry of a line that is not in the view
+const ABSENT: u32 = u32::MAX;
+/// Reverse map entry of a line still waiting in `pending`
+const PENDING: u32 = u32::MAX - 1;
+
+/// Absolute line numbers of a view sorted by a field, with the key of each.
+/// Lines that join the view are kept apart and merged in batches, so an
+/// append does not rebuild the whole order.
+#[derive(Default)]
+pub struct SortedView {
+    /// Lines in sorted order, as of the last merge
+    order: Vec<usize>,
+    /// Lines placed since, as (position in `order` they precede, line);
+    /// ordered by position, lines at the same position in view order
+    pending: Vec<(usize, usize)>,
+    /// Position in `order` of each line from `base`, or a marker
+    positions: Vec<u32>,
+    /// Sort key of each line from `base`, so placing a line reads no other
+    keys: Vec<SortKey>,
+    /// Absolute line number of the first entry of `positions` and `keys`
+    base: usize,
+}
+
+impl SortedView {
+    /// A view of the lines of `order` with their keys, all lines at or
+    /// after absolute line `first_line`
+    pub fn new(order: Vec<(usize, SortKey)>, first_line: usize) -> Self {
+        let mut view = Self {
+            order: Vec::with_capacity(order.len()),
+            base: first_line,
+            ..Self::default()
+        };
+        for (line, key) in order {
+            view.set_key(line, key);
+            view.order.push(line);
+        }
+        view.index_positions();
+        view
+    }
+
+    pub fn len(&self) -> usize {
+        self.order.len() + self.pending.len()
+    }
+
+    /// Absolute line shown at `index` of the view
+    pub fn get(&self, index: usize) -> Option<usize> {
+        if index >= self.len() {
+            return None;
+        }
+        // Pending lines shown before `index`
+        let before = self.pending_before(index);
+        match self.pending.get(before) {
+            Some(&(pos, line)) if pos + before == index => Some(line),
+            _ => self.order.get(index - before).copied(),
+        }
+    }
+
+    /// Sort key of the line shown at `index`
+    pub fn key(&self, index: usize) -> Option<&SortKey> {
+        self.keys.get(self.get(index)? - self.base)
+    }
+
+    /// View index of absolute line `line`
+    pub fn position(&self, line: usize) -> Option<usize> {
+        let slot = *self.positions.get(line.checked_sub(self.base)?)?;
+        match slot {
+            ABSENT => None,
+            PENDING => self
+                .pending
+                .iter()
+                .enumerate()
+                .find_map(|(k, &(pos, l))| (l == line).then_some(pos + k)),
+            pos => {
+                let pos = pos as usize;
+                Some(pos + self.pending.partition_point(|&(p, _)| p <= pos))
+            }
+        }
+    }
+
+    /// Shows absolute line `line` with key `key` at `index`; later lines move down by one
+    pub fn insert(&mut self, index: usize, line: usize, key: SortKey) {
+        let before = self.pending_before(index);
+        self.pending.insert(before, (index - before, line));
+        self.set_slot(line, PENDING);
+        self.set_key(line, key);
+        if self.pending.len() >= PENDING_MAX {
+            self.merge();
+        }
+    }
+
+    /// Removes the lines before absolute line `first_line`
+    pub fn drop_front(&mut self, first_line: usize) {
+        if first_line <= self.base {
+            return;
+        }
+        self.merge();
+        self.order.retain(|&line| line >= first_line);
+        self.keys
+            .drain(..(first_line - self.base).min(self.keys.len()));
+        self.base = first_line;
+        self.index_positions();
+    }
+
+    /// Heap bytes used by the view
+    pub fn memory_bytes(&self) -> usize {
+        self.order.capacity() * std::mem::size_of::<usize>()
+            + self.pending.capacity() * std::mem::size_of::<(usize, usize)>()
+            + self.positions.capacity() * std::mem::size_of::<u32>()
+            + self.keys.capacity() * std::mem::size_of::<SortKey>()
+    }
+
+    /// Number of pending lines shown before view index `index`
+    fn pending_before(&self, index: usize) -> usize {
+        // `pos + k` grows with `k`, so the pending lines before `index` form a prefix
+        let (mut low, mut high) = (0, self.pending.len());
+        while low < high {
+            let mid = (low + high) / 2;
+            if self.pending[mid].0 + mid < index {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+        low
+    }
+
+    /// Moves the pending lines into the order
+    fn merge(&mut self) {
+        if self.pending.is_empty() {
+            return;
+        }
+        let mut order = Vec::with_capacity(self.len());
+        let mut from = 0;
+        for (pos, line) in std::mem::take(&mut self.pending) {
+            order.extend_from_slice(&self.order[from..pos]);
+            order.push(line);
+            from = pos;
+        }
+        order.extend_from_slice(&self.order[from..]);
+        self.order = order;
+        self.index_positions();
+    }
+
+    fn index_positions(&mut self) {
+        self.positions.clear();
+        for pos in 0..self.order.len() {
+            self.set_slot(self.order[pos], pos as u32);
+        }
+    }
+
+    fn set_slot(&mut self, line: usize, slot: u32) {
+        let i = line - self.base;
+        if i >= self.positions.len() {
+            self.positions.resize(i + 1, ABSENT);
+        }
+        self.positions[i] = slot;
+    }
+
+    fn set_key(&mut self, line: usize, key: SortKey) {
+        let i = line - self.base;
+        if i >= self.keys.len() {
+            self.keys.resize(i + 1, SortKey::Missing);
+        }
+        self.keys[i] = key;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A view of `order`, each line keyed by its position
+    fn view(order: &[usize], first_line: usize) -> SortedView {
+        let keyed = order.iter().enumerate();
+        let order = keyed.map(|(i, &line)| (line, SortKey::Number(i as f64)));
+        SortedView::new(order.collect(), first_line)
+    }
+
+    fn lines(view: &SortedView) -> Vec<usize> {
+        (0..view.len()).map(|i| view.get(i).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_insert_before_merge() {
+        let mut view = view(&[14, 10, 12], 10);
+        view.insert(0, 15, SortKey::Missing);
+        view.insert(2, 16, SortKey::Missing);
+        view.insert(5, 17, SortKey::Missing);
+        view.insert(3, 18, SortKey::Text("a".into()));
+        let expected = [15, 14, 16, 18, 10, 12, 17];
+        assert_eq!(lines(&view), expected);
+        for (index, &line) in expected.iter().enumerate() {
+            assert_eq!(view.position(line), Some(index));
+        }
+        assert_eq!(view.position(11), None);
+        assert_eq!(view.position(40), None);
+        assert_eq!(view.key(1), Some(&SortKey::Number(0.0)));
+        assert_eq!(view.key(3), Some(&SortKey::Text("a".into())));
+
+        view.merge();
+        assert_eq!(lines(&view), expected);
+        assert_eq!(view.position(18), Some(3));
+    }
+
+    #[test]
+    fn test_drop_front_merges_pending() {
+        let mut view = view(&[3, 1, 2], 0);
+        view.insert(1, 4, SortKey::Missing);
+        view.drop_front(2);
+        assert_eq!(lines(&view), [3, 4, 2]);
+        assert_eq!(view.key(2), Some(&SortKey::Number(2.0)));
+        assert_eq!(view.position(2), Some(2));
+        assert_eq!(view.position(1), None);
+    }
+}
@@ -0,0 +1,495 @@
+use std::collections::{HashSet, VecDeque};
+use std::ops::Range;
+
+/// Lines summarized by one bloom filter
+pub const BLOCK_LINES: usize = 1024;
+/// Bit positions set per trigram
+const HASHES: u32 = 2;
+
+/// Trigram of ASCII-lowercased bytes, packed into the low 24 bits
+type Gram = u32;
+
+fn gram(window: &[u8]) -> Gram {
+    let b = |i: usize| window[i].to_ascii_lowercase() as u32;
+    (b(0) << 16) | (b(1) << 8) | b(2)
+}
+
+/// What a block must contain for a filter to possibly match one of its lines
+#[derive(Clone, Debug, PartialEq)]
+pub enum Prefilter {
+    /// Nothing is known; every block is scanned
+    Any,
+    /// All of the trigrams. `folded` when the term ignores case beyond ASCII,
+    /// which blocks holding non-ASCII text cannot rule out.
+    Grams {
+        grams: Vec<Gram>,
+        folded: bool,
+    },
+    And(Vec<Prefilter>),
+    Or(Vec<Prefilter>),
+}
+
+impl Prefilter {
+    /// Trigrams of a substring; text shorter than a trigram tells nothing.
+    /// Windows with non-ASCII bytes are left out so ASCII-only folding stays exact.
+    pub fn literal(text: &str, folded: bool) -> Self {
+        let mut grams: Vec<Gram> = text
+            .as_bytes()
+            .windows(3)
+            .filter(|w| w.is_ascii())
+            .map(gram)
+            .collect();
+        grams.sort_unstable();
+        grams.dedup();
+        if grams.is_empty() {
+            Prefilter::Any
+        } else {
+            Prefilter::Grams { grams, folded }
+        }
+    }
+
+    /// Literal runs every match of a simple regex contains. Alternation, groups
+    /// and unknown escapes are not analysed and give `Any`.
+    pub fn regex(pattern: &str) -> Self {
+        let Some(runs) = regex_literals(pattern) else {
+            return Prefilter::Any;
+        };
+        Prefilter::all(
+            runs.iter()
+                .map(|run| Prefilter::literal(run, true))
+                .collect(),
+        )
+    }
+
+    /// Conjunction, dropping the parts that tell nothing
+    pub fn all(parts: Vec<Prefilter>) -> Self {
+        let mut parts: Vec<Prefilter> =
+            parts.into_iter().filter(|p| *p != Prefilter::Any).collect();
+        match parts.len() {
+            0 => Prefilter::Any,
+            1 => parts.pop().unwrap(),
+            _ => Prefilter::And(parts),
+        }
+    }
+
+    /// Disjunction; if any part tells nothing, neither does the whole
+    pub fn any(mut parts: Vec<Prefilter>) -> Self {
+        if parts.is_empty() || parts.contains(&Prefilter::Any) {
+            Prefilter::Any
+        } else if parts.len() == 1 {
+            parts.pop().unwrap()
+        } else {
+            Prefilter::Or(parts)
+        }
+    }
+
+    /// Whether a block holding the trigrams `contains` finds may match
+    fn may_match(&self, non_ascii: bool, contains: &dyn Fn(Gram) -> bool) -> bool {
+        match self {
+            Prefilter::Any => true,
+            Prefilter::Grams { grams, folded } => {
+                (*folded && non_ascii) || grams.iter().all(|&g| contains(g))
+            }
+            Prefilter::And(parts) => parts.iter().all(|p| p.may_match(non_ascii, contains)),
+            Prefilter::Or(parts) => parts.iter().any(|p| p.may_match(non_ascii, contains)),
+        }
+    }
+}
+
+/// Literal runs of a regex without alternation or groups, or `None`
+fn regex_literals(pattern: &str) -> Option<Vec<String>> {
+    let mut runs = Vec::new();
+    let mut run = String::new();
+    let mut chars = pattern.chars().peekable();
+    let end_run = |run: &mut String, runs: &mut Vec<String>| {
+        if !run.is_empty() {
+            runs.push(std::mem::take(run));
+        }
+    };
+    while let Some(c) = chars.next() {
+        match c {
+            '|' | '(' | ')' => return None,
+            '\\' => match chars.next()? {
+                'd' | 'D' | 'w' | 'W' | 's' | 'S' | 'b' | 'B' => end_run(&mut run, &mut runs),
+                e if e.is_ascii_alphanumeric() => return None,
+                e => run.push(e),
+            },
+            // The quantified character may be absent, or repeated apart from the rest
+            '?' | '*' | '{' | '+' => {
+                if c != '+' {
+                    run.pop();
+                }
+                if c == '{' {
+                    chars.find(|&c| c == '}')?;
+                }
+                end_run(&mut run, &mut runs);
+                // Lazy quantifier
+                chars.next_if_eq(&'?');
+            }
+            '[' => {
+                end_run(&mut run, &mut runs);
+                let mut first = true;
+                loop {
+                    match chars.next()? {
+                        '\\' => {
+                            chars.next()?;
+                        }
+                        '[' => return None,
+                        ']' if !first => break,
+                        _ => {}
+                    }
+                    first = false;
+                }
+            }
+            '.' | '^' | '$' => end_run(&mut run, &mut runs),
+            _ => run.push(c),
+        }
+    }
+    end_run(&mut run, &mut runs);
+    Some(runs)
+}
+
+/// Bloom filter of the trigrams in one complete block of lines, sized to the
+/// number of distinct trigrams the block turned out to hold
+struct Block {
+    words: Box<[u64]>,
+    /// Set if any line has non-ASCII text, whose case folding the filter cannot follow
+    non_ascii: bool,
+}
+
+impl Block {
+    fn new(grams: &HashSet<Gram>, non_ascii: bool, bits_per_gram: usize) -> Self {
+        let words = (grams.len() * bits_per_gram).div_ceil(64).max(1);
+        let mut block = Self {
+            words: vec![0u64; words].into_boxed_slice(),
+            non_ascii,
+        };
+        for &g in grams {
+            for bit in block.positions(g) {
+                block.words[bit / 64] |= 1 << (bit % 64);
+            }
+        }
+        block
+    }
+
+    fn positions(&self, g: Gram) -> impl Iterator<Item = usize> {
+        let bits = self.words.len() * 64;
+        let h = (g as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        (0..HASHES).map(move |i| ((h >> (i * 32)) as u32 as usize) % bits)
+    }
+
+    fn contains(&self, g: Gram) -> bool {
+        self.positions(g)
+            .all(|bit| self.words[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+
+    fn bytes(&self) -> usize {
+        self.words.len() * 8
+    }
+}
+
+/// Trigrams of the block still receiving lines, counted exactly until it is full
+#[derive(Default)]
+struct OpenBlock {
+    grams: HashSet<Gram>,
+    non_ascii: bool,
+}
+
+/// Optional search index: a trigram bloom filter per block of [`BLOCK_LINES`]
+/// lines, so a filter scan can skip blocks that cannot hold a match.
+/// Blocks are numbered by absolute line, so dropping old lines keeps them valid.
+/// Lines pushed while the index is on are covered from the next block boundary;
+/// stored lines are added block by block in front, newest first (see
+/// [`Self::backfill_lines`]). Lines outside the blocks are always scanned. Once
+/// the filters take more than `max_bytes`, the oldest blocks are released.
+pub struct TrigramIndex {
+    /// Filter bits per distinct trigram of a block; 0 turns the index off
+    bits_per_gram: usize,
+    max_bytes: usize,
+    /// Absolute block number of `blocks[0]`; the open block follows the last one
+    first_block: usize,
+    /// Absolute number of the next line to index
+    next_line: usize,
+    blocks: VecDeque<Block>,
+    open: Option<OpenBlock>,
+    /// Bytes of the filters in `blocks`
+    bytes: usize,
+    /// While stored lines are added in front, the first absolute line they may start at
+    backfill_from: Option<usize>,
+}
+
+impl TrigramIndex {
+    pub fn new(bits_per_gram: usize, max_bytes: usize) -> Self {
+        Self {
+            bits_per_gram,
+            max_bytes,
+            first_block: 0,
+            next_line: 0,
+            blocks: VecDeque::new(),
+            open: None,
+            bytes: 0,
+            backfill_from: None,
+        }
+    }
+
+    pub fn bits_per_gram(&self) -> usize {
+        self.bits_per_gram
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.bits_per_gram > 0
+    }
+
+    /// Changes the filter density; existing blocks are dropped and indexing
+    /// resumes at the block after absolute line `next_line`
+    pub fn configure(&mut self, bits_per_gram: usize, next_line: usize) {
+        self.bits_per_gram = bits_per_gram;
+        self.blocks = VecDeque::new();
+        self.resume(next_line);
+    }
+
+    /// Drops all blocks; lines from absolute line `next_line` on are indexed
+    /// once a block boundary is reached
+    pub fn resume(&mut self, next_line: usize) {
+        self.blocks.clear();
+        self.open = None;
+        self.bytes = 0;
+        self.backfill_from = None;
+        self.next_line = next_line;
+        self.first_block = next_line.div_ceil(BLOCK_LINES);
+    }
+
+    /// Indexes the text of absolute line `line`
+    pub fn push_line(&mut self, line: usize, text: &[u8]) {
+        if !self.is_enabled() {
+            return;
+        }
+        if line != self.next_line {
+            self.resume(line);
+        }
+        self.next_line = line + 1;
+        let block = line / BLOCK_LINES;
+        if block < self.first_block {
+            return;
+        }
+        if block > self.first_block + self.blocks.len() {
+            self.seal();
+        }
+        let open = self.open.get_or_insert_with(OpenBlock::default);
+        open.non_ascii |= !text.is_ascii();
+        open.grams.extend(text.windows(3).map(gram));
+    }
+
+    /// Turns the open block into a filter, releasing the oldest blocks over the cap
+    fn seal(&mut self) {
+        if let Some(open) = self.open.take() {
+            let block = Block::new(&open.grams, open.non_ascii, self.bits_per_gram);
+            self.bytes += block.bytes();
+            self.blocks.push_back(block);
+        }
+        while self.bytes > self.max_bytes {
+            let Some(block) = self.blocks.pop_front() else {
+                break;
+            };
+            self.bytes -= block.bytes();
+            self.first_block += 1;
+            // Released blocks are not built again
+            self.backfill_from = None;
+        }
+    }
+
+    /// Starts adding the stored lines before the indexed blocks, down to absolute
+    /// line `first_line`; `None` stops it
+    pub fn backfill(&mut self, first_line: Option<usize>) {
+        self.backfill_from = first_line.filter(|_| self.is_enabled());
+    }
+
+    /// Absolute lines of the block to add in front next, if any
+    pub fn backfill_lines(&self) -> Option<Range<usize>> {
+        let block = self.first_block.checked_sub(1)?;
+        let lines = block * BLOCK_LINES..self.first_block * BLOCK_LINES;
+        (lines.start >= self.backfill_from?).then_some(lines)
+    }
+
+    /// Adds the block of [`Self::backfill_lines`], made of the `lines` given.
+    /// Once it would not fit in the memory cap, adding stops.
+    pub fn prepend_block<'a>(&mut self, lines: impl Iterator<Item = &'a [u8]>) {
+        let mut open = OpenBlock::default();
+        for text in lines {
+            open.non_ascii |= !text.is_ascii();
+            open.grams.extend(text.windows(3).map(gram));
+        }
+        let block = Block::new(&open.grams, open.non_ascii, self.bits_per_gram);
+        if self.bytes + block.bytes() > self.max_bytes {
+            self.backfill_from = None;
+            return;
+        }
+        self.bytes += block.bytes();
+        self.blocks.push_front(block);
+        self.first_block -= 1;
+    }
+
+    /// Releases the blocks that end before absolute line `first_line`
+    pub fn drop_front(&mut self, first_line: usize) {
+        if let Some(from) = &mut self.backfill_from {
+            *from = (*from).max(first_line);
+        }
+        while self.first_block < first_line / BLOCK_LINES {
+            let Some(block) = self.blocks.pop_front() else {
+                break;
+            };
+            self.bytes -= block.bytes();
+            self.first_block += 1;
+        }
+    }
+
+    /// Whether the block holding absolute line `line` may contain a match.
+    /// Lines outside the indexed blocks always may.
+    pub fn may_match(&self, prefilter: &Prefilter, line: usize) -> bool {
+        let Some(i) = (line / BLOCK_LINES).checked_sub(self.first_block) else {
+            return true;
+        };
+        match (self.blocks.get(i), &self.open) {
+            (Some(b), _) => prefilter.may_match(b.non_ascii, &|g| b.contains(g)),
+            (None, Some(open)) if i == self.blocks.len() => {
+                prefilter.may_match(open.non_ascii, &|g| open.grams.contains(&g))
+            }
+            _ => true,
+        }
+    }
+
+    /// Heap bytes used by the block filters and the trigrams of the open block
+    pub fn memory_bytes(&self) -> usize {
+        let open = self.open.as_ref().map_or(0, |o| {
+            o.grams.capacity() * (std::mem::size_of::<Gram>() + 1)
+        });
+        self.blocks.capacity() * std::mem::size_of::<Block>() + self.bytes + open
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index(lines: &[&str]) -> TrigramIndex {
+        // One interesting line per block
+        let mut index = TrigramIndex::new(16, usize::MAX);
+        for line in 0..lines.len() * BLOCK_LINES {
+            let text = match line % BLOCK_LINES {
+                0 => lines[line / BLOCK_LINES],
+                _ => "",
+            };
+            index.push_line(line, text.as_bytes());
+        }
+        index
+    }
+
+    #[test]
+    fn test_regex_literals() {
+        assert_eq!(regex_literals(r"temp=\d+ C").unwrap(), ["temp=", " C"]);
+        assert_eq!(
+            regex_literals(r"wifi?.*conn\.ok").unwrap(),
+            ["wif", "conn.ok"]
+        );
+        assert_eq!(
+            regex_literals(r"err[0-9]{2,3}x+y").unwrap(),
+            ["err", "x", "y"]
+        );
+        assert_eq!(regex_literals(r"a*?bc").unwrap(), ["bc"]);
+        assert!(regex_literals(r"boot|reset").is_none());
+        assert!(regex_literals(r"(?i)boot").is_none());
+        assert!(regex_literals(r"\x41BC").is_none());
+    }
+
+    #[test]
+    fn test_blocks_rule_out_missing_text() {
+        let index = index(&["I (5) wifi: connected", "E (9) heap: Alloc failed", "ok ÄÖ"]);
+        let may = |p: &Prefilter| -> Vec<bool> {
+            (0..4)
+                .map(|b| index.may_match(p, b * BLOCK_LINES))
+                .collect()
+        };
+        // The last block is not indexed yet, so it always may match
+        // Ignoring case, the block with non-ASCII text cannot be ruled out
+        assert_eq!(
+            may(&Prefilter::literal("wifi", true)),
+            [true, false, true, true]
+        );
+        assert_eq!(
+            may(&Prefilter::literal("Alloc", false)),
+            [false, true, false, true]
+        );
+        assert_eq!(
+            may(&Prefilter::regex(r"heap: \w+ failed")),
+            [false, true, true, true]
+        );
+        let either = Prefilter::any(vec![
+            Prefilter::literal("wifi", false),
+            Prefilter::literal("heap", false),
+        ]);
+        assert_eq!(may(&either), [true, true, false, true]);
+        assert_eq!(may(&Prefilter::literal("ok", true)), [true; 4]);
+    }
+
+    #[test]
+    fn test_resume_and_drop_front() {
+        let mut index = TrigramIndex::new(8, usize::MAX);
+        // Indexing starts at the next block boundary
+        index.resume(10);
+        for line in 10..BLOCK_LINES * 3 {
+            index.push_line(line, b"idle");
+        }
+        let boot = Prefilter::literal("boot", true);
+        assert!(index.may_match(&boot, 0));
+        assert!(!index.may_match(&boot, BLOCK_LINES));
+        // The filters are sized to the two trigrams of a block
+        assert_eq!(index.bytes, 8);
+
+        index.drop_front(BLOCK_LINES * 2 + 5);
+        assert_eq!(index.blocks.len(), 0);
+        assert!(index.may_match(&boot, BLOCK_LINES));
+        assert!(!index.may_match(&boot, BLOCK_LINES * 2));
+
+        index.configure(0, BLOCK_LINES * 3);
+        index.push_line(BLOCK_LINES * 3, b"idle");
+        assert_eq!(
+            index.memory_bytes(),
+            index.blocks.capacity() * std::mem::size_of::<Block>()
+        );
+        assert!(index.may_match(&boot, BLOCK_LINES * 2));
+    }
+
+    #[test]
+    fn test_memory_cap_releases_oldest_blocks() {
+        let mut index = TrigramIndex::new(8, 3 * 1024);
+        for line in 0..BLOCK_LINES * 8 {
+            // About a thousand distinct trigrams per block
+            index.push_line(line, format!("v{:04}", line).as_bytes());
+        }
+        assert!(index.bytes <= 3 * 1024);
+        assert!(index.first_block > 0);
+        // Released blocks are scanned again, the newest ones are still ruled out
+        let missing = Prefilter::literal("boot", true);
+        assert!(index.may_match(&missing, 0));
+        assert!(!index.may_match(&missing, BLOCK_LINES * 7));
+        assert!(!index.may_match(&missing, BLOCK_LINES * 6));
+    }
+
+    #[test]
+    fn test_backfill_stops_at_memory_cap() {
+        let mut index = TrigramIndex::new(8, 3 * 1024);
+        index.resume(BLOCK_LINES * 8);
+        index.backfill(Some(BLOCK_LINES));
+        while let Some(lines) = index.backfill_lines() {
+            let texts: Vec<String> = lines.map(|line| format!("v{:04}", line)).collect();
+            index.prepend_block(texts.iter().map(|t| t.as_bytes()));
+        }
+        // Three blocks of about a kilobyte fit, newest first
+        assert_eq!(index.first_block, 5);
+        assert_eq!(index.blocks.len(), 3);
+        let missing = Prefilter::literal("boot", true);
+        assert!(index.may_match(&missing, BLOCK_LINES * 4));
+        assert!(!index.may_match(&missing, BLOCK_LINES * 5));
+        assert!(!index.may_match(&missing, BLOCK_LINES * 7));
+    }
+}
@@ -2,81 +2,516 @@ pub mod index;
 pub mod storage;
 
 const NEWLINE: u8 = b'\n';
+/// Lines read at once when levels or the search index are rebuilt from the log
+const SCAN_LINES: usize = 10_000;
 
+use self::index::trigram::BLOCK_LINES;
 use self::index::{ByteOffset, LineIndex, LineRange, LogIndex};
-use self::storage::{LogStorage, StorageBackend};
+use self::storage::backend::Compactable;
+use self::storage::{
+    BookmarkFile, CutFile, IndexFile, LevelFile, LogStorage, MetaFile, OpfsBackend, RawCapture,
+    SessionFiles, StorageBackend, TimeFile,
+};
 use crate::config::READ_BUFFER_SIZE;
+use crate::types::{Bookmark, LevelCounts, LogLevel, SessionMetadata};
+use crate::worker::chunk_handler::ProcessedBatch;
 use crate::worker::error::LogError;
-use web_sys::FileSystemSyncAccessHandle;
+use crate::worker::level;
+use std::ops::Range;
+use std::rc::Rc;
+
+/// Damage repaired while attaching a session
+#[derive(Debug, Default, PartialEq)]
+pub struct Recovery {
+    /// Bytes of a partial line left by an interrupted write, cut from the log
+    pub torn_bytes: u64,
+}
+
+impl Recovery {
+    /// Message for the user, if anything had to be repaired
+    pub fn notice(&self) -> Option<String> {
+        (self.torn_bytes > 0).then(|| {
+            format!(
+                "Session recovered: removed a partial line ({} bytes) left by an interrupted write",
+                self.torn_bytes
+            )
+        })
+    }
+}
 
 /// Repository that manages log storage and indexing together
-/// Ensures consistency between storage writes and index updates
-pub struct LogRepository {
-    pub storage: LogStorage,
+/// Ensures consistency between storage writes and index updates.
+/// Generic over the storage backend so the pipeline also runs outside the browser.
+pub struct LogRepository<B: StorageBackend = OpfsBackend> {
+    pub storage: LogStorage<B>,
     pub index: LogIndex,
+    pub index_file: IndexFile<B>,
+    pub meta_file: MetaFile<B>,
+    pub raw: RawCapture<B>,
+    pub bookmark_file: BookmarkFile<B>,
+    /// Receive time of each line, kept out of the log text
+    pub times: TimeFile<B>,
+    /// Level detected on each line
+    pub levels: LevelFile<B>,
+    /// Ring buffer drops the files are still being compacted for
+    cuts: Rc<CutFile<B>>,
+    /// Capture metadata of the attached session
+    pub metadata: Option<SessionMetadata>,
+    /// Bookmarks of the attached session, sorted by line
+    pub bookmarks: Vec<Bookmark>,
+    /// Set when appended data has not been flushed since the last checkpoint
+    dirty: bool,
 }
 
-impl LogRepository {
-    pub fn new() -> Result<Self, LogError> {
-        Ok(Self {
-            storage: LogStorage::new()?,
+impl<B: StorageBackend + Default> LogRepository<B> {
+    pub fn new() -> Self {
+        Self {
+            storage: LogStorage::new(),
             index: LogIndex::new(),
-        })
+            index_file: IndexFile::new(),
+            meta_file: MetaFile::new(),
+            raw: RawCapture::new(),
+            bookmark_file: BookmarkFile::new(),
+            times: TimeFile::new(),
+            levels: LevelFile::new(),
+            cuts: Rc::new(CutFile::new()),
+            metadata: None,
+            bookmarks: Vec::new(),
+            dirty: false,
+        }
     }
 
+    /// Attaches a session and restores its line index.
+    /// Offsets are taken from the sidecar when it matches the log; only the
+    /// unindexed tail is scanned. Sidecar offsets past the end of the log are
+    /// dropped and a mismatched sidecar is rebuilt. A partial line left at the
+    /// end of the log by an interrupted write is cut off and reported, and a
+    /// compaction cut short is taken up again. All of this is written back only
+    /// when the session is `writable`; archives are repaired in memory, their
+    /// files are left as they are.
     pub fn initialize_storage(
         &mut self,
-        handle: FileSystemSyncAccessHandle,
-    ) -> Result<(), LogError> {
-        self.storage.backend.handle = Some(handle);
+        files: SessionFiles<B>,
+        writable: bool,
+    ) -> Result<Recovery, LogError> {
+        // Release the previous session so it can be reopened from the library later
+        self.release_storage();
+        self.cuts = Rc::new(CutFile::open(files.cuts, writable));
+        self.storage.backend = Compactable::new(files.log, None).journaled(&self.cuts, "log")?;
+        self.index_file.attach(files.index, writable, &self.cuts)?;
+        self.meta_file.attach(files.meta);
+        self.metadata = self.meta_file.load();
+        self.raw.attach(
+            files.raw,
+            files.raw_chunks,
+            files.raw_lines,
+            writable,
+            &self.cuts,
+        )?;
         let size = self.storage.backend.get_file_size()?;
 
-        if size.0 > 0 {
-            self.reset_index();
-            let (mut off, mut buf) = (ByteOffset(0), vec![0u8; READ_BUFFER_SIZE]);
-            while off.0 < size.0 {
-                let len = (size.0 - off.0).min(buf.len() as u64) as usize;
-                self.storage.backend.read_at(off, &mut buf[..len])?;
-                for (i, &b) in buf[..len].iter().enumerate() {
-                    if b == NEWLINE {
-                        self.index.push_line(off + (i as u64 + 1));
+        let restored = match self.index_file.load()? {
+            Some(mut offsets) => {
+                // The sidecar may have been flushed further than the log
+                let valid = offsets.partition_point(|&off| off <= size);
+                if valid < offsets.len() {
+                    offsets.truncate(valid);
+                    if writable {
+                        self.index_file.truncate(valid)?;
+                    }
+                }
+                if self.is_consistent(&offsets, size)? {
+                    offsets
+                } else {
+                    if writable {
+                        self.index_file.reset()?;
                     }
+                    Vec::new()
                 }
-                off = off + (len as u64);
             }
+            None => {
+                if writable {
+                    self.index_file.reset()?;
+                }
+                Vec::new()
+            }
+        };
+        let scanned_to = restored.last().copied().unwrap_or(ByteOffset(0));
+        // A trim is recorded with its drops first, so the metadata may lag behind
+        let dropped_lines = self.cuts.dropped_lines();
+        if let Some(metadata) = self
+            .metadata
+            .as_mut()
+            .filter(|m| m.dropped_lines < dropped_lines)
+        {
+            metadata.dropped_lines = dropped_lines;
+            if writable {
+                self.meta_file.save(metadata)?;
+            }
+        }
+        self.index.first_line = self
+            .metadata
+            .as_ref()
+            .map_or(dropped_lines, |m| m.dropped_lines);
+        for off in restored {
+            self.index.push_line(off);
         }
+
+        let tail = self.scan_lines(scanned_to, size)?;
+        if writable {
+            self.index_file.append(&tail);
+        }
+        for off in tail {
+            self.index.push_line(off);
+        }
+
+        self.rebuild_search_index()?;
+
+        let mut recovery = Recovery::default();
+        let end = self.index.end_offset();
+        if end < size && writable {
+            // Every stored line ends with a newline, so the rest is a torn write
+            self.storage.backend.truncate(end.0)?;
+            self.storage.backend.flush()?;
+            recovery.torn_bytes = size - end;
+        }
+
+        self.bookmark_file.attach(files.bookmarks);
+        self.bookmarks = self.bookmark_file.load();
+        self.bookmarks.sort_by_key(|b| b.line);
+        let line_count = self.index.line_count;
+        let lines = self.index.first_line..self.index.first_line + line_count;
+        self.bookmarks.retain(|b| lines.contains(&b.line));
+
+        self.times.attach(files.times, writable, &self.cuts)?;
+        if writable {
+            self.times.sync_lines(line_count)?;
+            self.raw.sync_lines(line_count)?;
+        }
+        self.levels.attach(files.levels, &self.cuts)?;
+        self.restore_levels(writable)?;
+        Ok(recovery)
+    }
+
+    /// Restarts the search index over the stored lines, e.g. once a session is
+    /// reopened or the index is turned on. Only the block receiving lines is read
+    /// here; the earlier blocks are added by [`Self::backfill_search_index`].
+    pub fn rebuild_search_index(&mut self) -> Result<(), LogError> {
+        let (first, line_count) = (self.index.first_line, self.index.line_count);
+        let end = first + line_count;
+        if !self.index.search_index.is_enabled() {
+            self.index.search_index.resume(end);
+            return Ok(());
+        }
+        let start = (end - end % BLOCK_LINES).max(first);
+        self.index.search_index.resume(start);
+        let bytes = self.read_lines(start - first..line_count)?;
+        for (i, text) in bytes.split_inclusive(|&b| b == NEWLINE).enumerate() {
+            self.index.search_index.push_line(start + i, text);
+        }
+        self.index.search_index.backfill(Some(first));
         Ok(())
     }
 
+    /// Changes the density of the search index and rebuilds it. Sent again on
+    /// every start, so what is indexed is kept if nothing changed.
+    pub fn set_search_index(&mut self, bits_per_gram: usize) -> Result<(), LogError> {
+        let index = &mut self.index;
+        if index.search_index.bits_per_gram() == bits_per_gram {
+            return Ok(());
+        }
+        let next_line = index.first_line + index.line_count;
+        index.search_index.configure(bits_per_gram, next_line);
+        self.rebuild_search_index()
+    }
+
+    /// Adds up to `blocks` blocks of stored lines to the search index, newest
+    /// first. Returns whether more are left to add.
+    pub fn backfill_search_index(&mut self, blocks: usize) -> Result<bool, LogError> {
+        let first = self.index.first_line;
+        for _ in 0..blocks {
+            let Some(lines) = self.index.search_index.backfill_lines() else {
+                return Ok(false);
+            };
+            let bytes = self
+                .read_lines(lines.start - first..lines.end - first)
+                .inspect_err(|_| self.index.search_index.backfill(None))?;
+            let texts = bytes.split_inclusive(|&b| b == NEWLINE);
+            self.index.search_index.prepend_block(texts);
+        }
+        Ok(self.index.search_index.backfill_lines().is_some())
+    }
+
+    /// Text of the session lines in `lines`
+    fn read_lines(&self, lines: Range<usize>) -> Result<Vec<u8>, LogError> {
+        let offsets = &self.index.line_offsets;
+        self.read_line(LineRange {
+            start: offsets.get(lines.start),
+            end: offsets.get(lines.end),
+        })
+    }
+
+    /// Loads the level of every line. Lines the sidecar does not cover, e.g. of
+    /// sessions recorded before levels were stored, are classified from the text
+    /// and stored when `writable`.
+    fn restore_levels(&mut self, writable: bool) -> Result<(), LogError> {
+        let line_count = self.index.line_count;
+        if writable {
+            self.levels.truncate(line_count)?;
+        }
+        let stored = self.levels.count().min(line_count);
+        let mut line = 0;
+        while line < stored {
+            for level in self
+                .levels
+                .read(line, READ_BUFFER_SIZE.min(stored - line))?
+            {
+                self.index.levels.push(line, level);
+                line += 1;
+            }
+        }
+        while line < line_count {
+            let end = (line + SCAN_LINES).min(line_count);
+            let offsets = &self.index.line_offsets;
+            let bytes = self.read_line(LineRange {
+                start: offsets.get(line),
+                end: offsets.get(end),
+            })?;
+            let text = String::from_utf8_lossy(&bytes);
+            let levels: Vec<LogLevel> = text
+                .split_terminator('\n')
+                .map(level::classify)
+                .chain(std::iter::repeat(LogLevel::None))
+                .take(end - line)
+                .collect();
+            if writable {
+                self.levels.append(&levels)?;
+            }
+            for (i, &level) in levels.iter().enumerate() {
+                self.index.levels.push(line + i, level);
+            }
+            line = end;
+        }
+        Ok(())
+    }
+
+    /// Number of session lines at each level
+    pub fn level_counts(&self) -> LevelCounts {
+        self.index.levels.counts()
+    }
+
+    /// Checks that persisted offsets are increasing line ends within a log of `size` bytes
+    fn is_consistent(&self, offsets: &[ByteOffset], size: ByteOffset) -> Result<bool, LogError> {
+        let Some(&last) = offsets.last() else {
+            return Ok(true);
+        };
+        let increasing = offsets
+            .iter()
+            .try_fold(ByteOffset(0), |prev, &off| (off > prev).then_some(off))
+            .is_some();
+        if !increasing || last > size {
+            return Ok(false);
+        }
+        let mut byte = [0u8; 1];
+        self.storage
+            .backend
+            .read_at(ByteOffset(last.0 - 1), &mut byte)?;
+        Ok(byte[0] == NEWLINE)
+    }
+
+    /// Scans `[from, to)` of the log for line ends
+    fn scan_lines(&self, from: ByteOffset, to: ByteOffset) -> Result<Vec<ByteOffset>, LogError> {
+        let mut ends = Vec::new();
+        let (mut off, mut buf) = (from, vec![0u8; READ_BUFFER_SIZE]);
+        while off < to {
+            let len = (to - off).min(buf.len() as u64) as usize;
+            self.storage.backend.read_at(off, &mut buf[..len])?;
+            for (i, &b) in buf[..len].iter().enumerate() {
+                if b == NEWLINE {
+                    ends.push(off + (i as u64 + 1));
+                }
+            }
+            off = off + (len as u64);
+        }
+        Ok(ends)
+    }
+
+    /// Size of the attached log, tracked by the index rather than asked of the file
+    pub fn log_size(&self) -> u64 {
+        self.index.end_offset().0
+    }
+
+    /// Bytes the session takes in storage: the log and the sidecars growing with it
+    pub fn session_size(&self) -> u64 {
+        self.log_size()
+            + self.raw.stored_size()
+            + self.times.size()
+            + self.levels.size()
+            + self.index_file.size()
+    }
+
     /// Appends lines to storage and updates index atomically
     /// This ensures storage and index remain synchronized
-    pub fn append_lines(
-        &mut self,
-        text: &str,
-        offsets: Vec<ByteOffset>,
-        filtered: Vec<LineRange>,
-    ) -> Result<(), LogError> {
-        let start = self.storage.backend.get_file_size()?;
+    pub fn append_lines(&mut self, batch: ProcessedBatch) -> Result<(), LogError> {
+        let start = self.index.end_offset();
+        self.dirty = true;
 
         // Write to storage first
         self.storage
             .backend
-            .write_at(start, self.storage.encoder.encode_with_input(text).as_ref())?;
+            .write_at(start, batch.text.as_bytes())?;
 
         // Only update index if write succeeded
-        for off in offsets {
-            self.index.push_line(start + off.0);
+        self.raw.append_lines(&batch.raw_ranges)?;
+        self.times.append(batch.received_at, batch.offsets.len())?;
+        self.levels.append(&batch.levels)?;
+        let first = self.index.line_count;
+        for (i, &level) in batch.levels.iter().enumerate() {
+            self.index.levels.push(first + i, level);
+        }
+        let mut line_start = 0;
+        for (i, off) in batch.offsets.iter().enumerate() {
+            let end = off.0 as usize;
+            let text = &batch.text.as_bytes()[line_start..end];
+            let line = self.index.first_line + first + i;
+            self.index.search_index.push_line(line, text);
+            line_start = end;
+        }
+        let ends: Vec<ByteOffset> = batch.offsets.into_iter().map(|off| start + off.0).collect();
+        self.index_file.append(&ends);
+        for off in ends {
+            self.index.push_line(off);
+        }
+
+        if self.index.has_context() {
+            let matches: Vec<usize> = batch.filtered.iter().map(|l| first + l).collect();
+            self.index
+                .add_matches(first..self.index.line_count, &matches);
+        } else {
+            for line in batch.filtered {
+                self.index.push_filtered(first + line);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Drops the oldest lines until the log fits in `target_size` bytes.
+    /// The remaining data is moved to the front of each file by [`Self::compact`];
+    /// until then it is read where it is. The drops are recorded before any
+    /// data moves.
+    /// Returns the number of lines and raw capture bytes dropped.
+    pub fn drop_oldest(&mut self, target_size: u64) -> Result<(usize, u64), LogError> {
+        let size = self.index.end_offset();
+        if size.0 <= target_size {
+            return Ok((0, 0));
+        }
+        let excess = size.0 - target_size;
+        let lines = self
+            .index
+            .line_offsets
+            .partition_point(|off| off.0 < excess)
+            .min(self.index.line_count);
+        if lines == 0 {
+            return Ok((0, 0));
+        }
+
+        let cut = self.index.line_offsets.get(lines);
+        self.storage.backend.drop_front(cut.0, 0)?;
+        let raw_cut = self.raw.drop_lines(lines)?;
+        self.times.drop_front(lines)?;
+        self.levels.drop_front(lines)?;
+        self.index_file.drop_front(lines, cut.0)?;
+        self.index.drop_front(lines);
+
+        // Line numbers stay as they are; the session now starts further on
+        let first_line = self.index.first_line;
+        self.cuts.set_dropped_lines(first_line);
+        self.cuts.save()?;
+        if let Some(metadata) = &mut self.metadata {
+            metadata.dropped_lines = first_line;
+            self.meta_file.save(metadata)?;
         }
+        if self.bookmarks.first().is_some_and(|b| b.line < first_line) {
+            self.bookmarks.retain(|b| b.line >= first_line);
+            self.bookmark_file.save(&self.bookmarks)?;
+        }
+        Ok((lines, raw_cut))
+    }
 
-        for mut r in filtered {
-            r.start = start + r.start.0;
-            r.end = start + r.end.0;
-            self.index.push_filtered(r);
+    /// Moves the data kept by [`Self::drop_oldest`] up to `budget` bytes further
+    /// down in each file. Returns whether any is left to move.
+    pub fn compact(&self, budget: u64) -> Result<bool, LogError> {
+        let pending = [
+            self.storage.backend.step(budget)?,
+            self.raw.compact(budget)?,
+            self.times.compact(budget)?,
+            self.levels.compact(budget)?,
+            self.index_file.compact(budget)?,
+        ];
+        Ok(pending.contains(&true))
+    }
+
+    /// Flushes the log and its sidecars if anything was appended since the last call.
+    /// The log goes first so the persisted index never runs ahead of it.
+    pub fn checkpoint(&mut self) -> Result<(), LogError> {
+        if !self.dirty {
+            return Ok(());
         }
+        self.storage.backend.flush()?;
+        self.index_file.flush()?;
+        self.times.flush()?;
+        self.levels.flush()?;
+        self.raw.flush()?;
+        self.dirty = false;
+        Ok(())
+    }
 
+    /// Releases the storage lock so the file can be moved or reopened
+    pub fn release_storage(&mut self) {
+        // Best effort; what did not reach the disk is repaired on the next open
+        let _ = self.checkpoint();
+        self.storage.close();
+        self.index_file.close();
+        self.meta_file.close();
+        self.raw.close();
+        self.bookmark_file.close();
+        self.times.close();
+        self.levels.close();
+        // Closing the files completes their drops, which is recorded
+        self.cuts.close();
+        self.metadata = None;
+        self.bookmarks.clear();
+        self.reset_index();
+    }
+
+    /// Records how the attached session is being captured
+    pub fn set_metadata(&mut self, metadata: SessionMetadata) -> Result<(), LogError> {
+        self.meta_file.save(&metadata)?;
+        self.metadata = Some(metadata);
         Ok(())
     }
 
+    /// Stamps the end time and final line count of a session still being captured
+    pub fn finish_session(&mut self) -> Result<(), LogError> {
+        self.checkpoint()?;
+        let line_count = self.index.line_count;
+        let Some(metadata) = self.metadata.as_mut().filter(|m| m.ended_at.is_none()) else {
+            return Ok(());
+        };
+        metadata.ended_at = Some(chrono::Utc::now().timestamp_millis());
+        metadata.line_count = line_count;
+        self.meta_file.save(metadata)
+    }
+
+    /// Metadata of the attached session with an up-to-date line count
+    pub fn current_metadata(&self) -> Option<SessionMetadata> {
+        self.metadata.clone().map(|mut m| {
+            m.line_count = self.index.line_count;
+            m
+        })
+    }
+
     /// Gets the current line count (filtered or total)
     pub fn get_line_count(&self) -> usize {
         self.index.get_total_count()
@@ -87,6 +522,131 @@ impl LogRepository {
         self.index.get_line_range(index)
     }
 
+    /// Session line shown at `index` of the current view (filtered or not)
+    pub fn session_line(&self, index: LineIndex) -> Option<usize> {
+        self.index.line_at(index)
+    }
+
+    /// Absolute number of the line at `index` of the current view; unlike session
+    /// lines it does not change when the ring buffer drops old lines
+    pub fn line_number(&self, index: LineIndex) -> Option<usize> {
+        Some(self.index.first_line + self.session_line(index)?)
+    }
+
+    /// View index of a session line; `None` if the active filter hides it
+    pub fn view_index(&self, line: usize) -> Option<usize> {
+        if line >= self.index.line_count {
+            return None;
+        }
+        if let Some(sorted) = &self.index.sorted {
+            return sorted.position(self.index.first_line + line);
+        }
+        if self.index.is_filtering {
+            self.index.filtered_lines.rank(line)
+        } else {
+            Some(line)
+        }
+    }
+
+    /// Raw capture range of a line in the current view (filtered or not)
+    pub fn raw_line_range(&self, index: LineIndex) -> Option<LineRange> {
+        self.raw.line_range(self.session_line(index)?)
+    }
+
+    /// Receive time (µs since the epoch) of a line in the current view, if known
+    pub fn line_time(&self, index: LineIndex) -> Option<i64> {
+        self.times.get(self.session_line(index)?)
+    }
+
+    /// Bookmarks the line at `index` of the current view, replacing any existing note
+    pub fn add_bookmark(&mut self, index: LineIndex, note: String) -> Result<(), LogError> {
+        let line = self
+            .line_number(index)
+            .ok_or_else(|| LogError::Storage("Line not found".into()))?;
+        let bookmark = Bookmark {
+            line,
+            note,
+            created_at: chrono::Utc::now().timestamp_millis(),
+        };
+        match self.bookmarks.binary_search_by_key(&line, |b| b.line) {
+            Ok(i) => self.bookmarks[i] = bookmark,
+            Err(i) => self.bookmarks.insert(i, bookmark),
+        }
+        self.bookmark_file.save(&self.bookmarks)
+    }
+
+    /// Removes the bookmark on a line, by its absolute number
+    pub fn remove_bookmark(&mut self, line: usize) -> Result<(), LogError> {
+        if let Ok(i) = self.bookmarks.binary_search_by_key(&line, |b| b.line) {
+            self.bookmarks.remove(i);
+            self.bookmark_file.save(&self.bookmarks)?;
+        }
+        Ok(())
+    }
+
+    /// View index of the nearest bookmark after (or before) view index `from`,
+    /// skipping bookmarks hidden by the active filter
+    pub fn seek_bookmark(&self, from: LineIndex, forward: bool) -> Option<usize> {
+        let from_line = self.line_number(from);
+        let view_index = |b: &Bookmark| self.view_index(b.line - self.index.first_line);
+        if forward {
+            self.bookmarks
+                .iter()
+                .filter(|b| from_line.is_none_or(|l| b.line > l))
+                .find_map(view_index)
+        } else {
+            self.bookmarks
+                .iter()
+                .rev()
+                .filter(|b| from_line.is_none_or(|l| b.line < l))
+                .find_map(view_index)
+        }
+    }
+
+    /// Bookmarks on the view lines in `[start, end)`, by view index
+    pub fn window_bookmarks(&self, start: usize, end: usize) -> Vec<(usize, Bookmark)> {
+        if self.bookmarks.is_empty() {
+            return Vec::new();
+        }
+        (start..end)
+            .filter_map(|i| {
+                let line = self.line_number(LineIndex(i))?;
+                let pos = self
+                    .bookmarks
+                    .binary_search_by_key(&line, |b| b.line)
+                    .ok()?;
+                Some((i, self.bookmarks[pos].clone()))
+            })
+            .collect()
+    }
+
+    /// Context lines, and lines following a gap, among the view lines in
+    /// `[start, end)`, by view index. Empty unless the filter shows context.
+    pub fn window_context(&self, start: usize, end: usize) -> (Vec<usize>, Vec<usize>) {
+        if !self.index.has_context() {
+            return (Vec::new(), Vec::new());
+        }
+        let mut context = Vec::new();
+        let mut gaps = Vec::new();
+        let mut prev = start
+            .checked_sub(1)
+            .and_then(|i| self.session_line(LineIndex(i)));
+        for i in start..end {
+            let Some(line) = self.session_line(LineIndex(i)) else {
+                break;
+            };
+            if self.index.context_lines.contains(line) {
+                context.push(i);
+            }
+            // A sorted view has no log order to break
+            if prev.is_some_and(|p| p + 1 != line) && self.index.sorted.is_none() {
+                gaps.push(i);
+            }
+            prev = Some(line);
+        }
+        (context, gaps)
+    }
+
     /// Reads a line from storage
     pub fn read_line(&self, range: LineRange) -> Result<Vec<u8>, LogError> {
         let mut buf = vec![0u8; (range.end.0 - range.start.0) as usize];
@@ -98,6 +658,14 @@ impl LogRepository {
     pub fn clear(&mut self) -> Result<(), LogError> {
         self.storage.backend.truncate(0)?;
         self.storage.backend.flush()?;
+        self.index_file.reset()?;
+        self.raw.reset()?;
+        self.times.reset()?;
+        self.levels.reset()?;
+        self.cuts.set_dropped_lines(0);
+        self.cuts.save()?;
+        self.bookmarks.clear();
+        self.bookmark_file.save(&self.bookmarks)?;
         self.index.reset_base();
         Ok(())
     }
@@ -112,14 +680,172 @@ impl LogRepository {
         self.index.is_filtering
     }
 
-    /// Checks if text matches the active filter
-    pub fn matches_active_filter(&self, text: &str) -> bool {
-        if !self.index.is_filtering {
-            return false;
+    /// Checks if a new line matches the active text and level filters
+    pub fn matches_active_filter(&self, text: &str, level: LogLevel) -> bool {
+        self.index.matches(text, level)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::worker::repository::index::trigram::Prefilter;
+    use crate::worker::repository::storage::MemoryBackend;
+
+    fn reopen(files: &SessionFiles<MemoryBackend>) -> (LogRepository<MemoryBackend>, Recovery) {
+        let mut repo = LogRepository::new();
+        let recovery = repo.initialize_storage(files.clone(), true).unwrap();
+        (repo, recovery)
+    }
+
+    fn write_log(files: &SessionFiles<MemoryBackend>, text: &str) {
+        files.log.write_at(ByteOffset(0), text.as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn test_torn_line_is_cut_on_open() {
+        let files = SessionFiles::in_memory();
+        write_log(&files, "boot\nready\nwifi con");
+
+        // Archives are shown without the partial line but not modified
+        let mut archive = LogRepository::new();
+        let recovery = archive.initialize_storage(files.clone(), false).unwrap();
+        assert_eq!(recovery, Recovery::default());
+        assert_eq!(archive.index.line_count, 2);
+        archive.release_storage();
+        assert_eq!(files.log.bytes(), b"boot\nready\nwifi con");
+
+        let (repo, recovery) = reopen(&files);
+        assert_eq!(recovery.torn_bytes, 8);
+        assert!(recovery.notice().is_some());
+        assert_eq!(repo.index.line_count, 2);
+        assert_eq!(files.log.bytes(), b"boot\nready\n");
+
+        // The repaired log opens cleanly
+        let (_, recovery) = reopen(&files);
+        assert_eq!(recovery, Recovery::default());
+    }
+
+    #[test]
+    fn test_archive_sidecars_are_not_written() {
+        let files = SessionFiles::in_memory();
+        write_log(&files, "boot\nERROR fail\nready\n");
+        let index = files.index.clone().unwrap();
+        // A stale offset and a torn record
+        let mut records: Vec<u8> = [5u64, 40].iter().flat_map(|o| o.to_le_bytes()).collect();
+        records.extend_from_slice(&[1, 2, 3]);
+        index.write_at(ByteOffset(0), &records).unwrap();
+        let sidecars = |files: &SessionFiles<MemoryBackend>| {
+            [&files.index, &files.times, &files.levels, &files.raw_lines]
+                .map(|file| file.as_ref().unwrap().bytes())
+        };
+        let before = sidecars(&files);
+
+        let mut archive = LogRepository::new();
+        archive.initialize_storage(files.clone(), false).unwrap();
+        assert_eq!(archive.index.line_count, 3);
+        assert_eq!(archive.level_counts().at_least(LogLevel::Error), 1);
+        archive.release_storage();
+        assert_eq!(sidecars(&files), before);
+
+        // Opened for capture, the sidecars are repaired
+        let (repo, _) = reopen(&files);
+        assert_eq!(repo.index.line_count, 3);
+        assert_eq!(index.bytes().len(), 24);
+        assert_eq!(files.levels.as_ref().unwrap().bytes().len(), 3);
+    }
+
+    #[test]
+    fn test_search_index_is_backfilled_on_reopen() {
+        let files = SessionFiles::in_memory();
+        let mut text = String::new();
+        for line in 0..BLOCK_LINES * 5 + 10 {
+            match line {
+                1500 => text.push_str("boot\n"),
+                _ => text.push_str(&format!("v{}\n", line)),
+            }
         }
-        self.index
-            .active_filter
-            .as_ref()
-            .is_some_and(|f| f.matches(text))
+        write_log(&files, &text);
+
+        let mut repo = LogRepository::new();
+        repo.index.search_index.configure(8, 0);
+        repo.initialize_storage(files.clone(), true).unwrap();
+        // Only the block receiving lines is read on open
+        let boot = Prefilter::literal("boot", false);
+        let may = |repo: &LogRepository<MemoryBackend>| -> Vec<bool> {
+            (0..6)
+                .map(|b| repo.index.search_index.may_match(&boot, b * BLOCK_LINES))
+                .collect()
+        };
+        assert_eq!(may(&repo), [true, true, true, true, true, false]);
+
+        assert!(repo.backfill_search_index(2).unwrap());
+        assert_eq!(may(&repo), [true, true, true, false, false, false]);
+        while repo.backfill_search_index(2).unwrap() {}
+        assert_eq!(may(&repo), [false, true, false, false, false, false]);
+    }
+
+    #[test]
+    fn test_index_sidecar_ahead_of_log_is_trimmed() {
+        let files = SessionFiles::in_memory();
+        write_log(&files, "a\nbb\n");
+        let index = files.index.clone().unwrap();
+        // Offsets 2, 5 and one the log never received, then a torn record
+        let mut records: Vec<u8> = [2u64, 5, 9].iter().flat_map(|o| o.to_le_bytes()).collect();
+        records.extend_from_slice(&[1, 2, 3]);
+        index.write_at(ByteOffset(0), &records).unwrap();
+
+        let (repo, recovery) = reopen(&files);
+        assert_eq!(recovery, Recovery::default());
+        assert_eq!(repo.index.line_count, 2);
+        assert_eq!(index.bytes().len(), 16);
+        assert_eq!(repo.log_size(), 5);
+    }
+
+    #[test]
+    fn test_compaction_resumes_after_crash() {
+        let files = SessionFiles::in_memory();
+        let text: String = (0..100).map(|i| format!("line {:02}\n", i)).collect();
+        write_log(&files, &text);
+        let (mut repo, _) = reopen(&files);
+        repo.set_metadata(SessionMetadata::default()).unwrap();
+        assert_eq!(repo.drop_oldest(300).unwrap().0, 63);
+        assert!(repo.compact(64).unwrap());
+        // The tab is closed with the files half moved
+        drop(repo);
+
+        let (repo, recovery) = reopen(&files);
+        assert_eq!(recovery, Recovery::default());
+        assert_eq!(repo.index.line_count, 37);
+        assert_eq!(repo.line_number(LineIndex(0)), Some(63));
+        let first = repo.get_line_range(LineIndex(0)).unwrap();
+        assert_eq!(repo.read_line(first).unwrap(), b"line 63\n");
+        while repo.compact(64).unwrap() {}
+        assert_eq!(files.log.bytes(), &text.as_bytes()[63 * 8..]);
+        let index = files.index.as_ref().unwrap().bytes();
+        assert_eq!(index[..8], 8u64.to_le_bytes());
+    }
+
+    #[test]
+    fn test_bookmarks_keep_numbers_when_lines_are_dropped() {
+        let files = SessionFiles::in_memory();
+        write_log(&files, "a\nb\nc\nd\n");
+        let (mut repo, _) = reopen(&files);
+        repo.set_metadata(SessionMetadata::default()).unwrap();
+        repo.add_bookmark(LineIndex(1), "b".into()).unwrap();
+        repo.add_bookmark(LineIndex(3), "d".into()).unwrap();
+
+        assert_eq!(repo.drop_oldest(4).unwrap().0, 2);
+        let lines: Vec<usize> = repo.bookmarks.iter().map(|b| b.line).collect();
+        assert_eq!(lines, [3]);
+        assert_eq!(repo.seek_bookmark(LineIndex(0), true), Some(1));
+        repo.release_storage();
+
+        // The numbering carries over when the session is reopened
+        let (repo, _) = reopen(&files);
+        assert_eq!(repo.line_number(LineIndex(0)), Some(2));
+        let marks = repo.window_bookmarks(0, 2);
+        assert_eq!(marks.len(), 1);
+        assert_eq!((marks[0].0, marks[0].1.line), (1, 3));
     }
 }
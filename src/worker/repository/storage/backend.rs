@@ -1,5 +1,10 @@
+use crate::config::READ_BUFFER_SIZE;
 use crate::worker::error::LogError;
 use crate::worker::repository::index::ByteOffset;
+use crate::worker::repository::storage::cut_file::CutFile;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::rc::Rc;
 
 /// Trait for storage backend operations
 pub trait StorageBackend {
@@ -8,4 +13,392 @@ pub trait StorageBackend {
     fn get_file_size(&self) -> Result<ByteOffset, LogError>;
     fn truncate(&self, size: u64) -> Result<(), LogError>;
     fn flush(&self) -> Result<(), LogError>;
+
+    /// Releases the file (and any lock held on it); further operations fail
+    fn close(&mut self) {}
+}
+
+/// Layout of fixed-size records holding little-endian u64 offsets into another
+/// file; the offsets shift when that file's front is cut
+#[derive(Clone, Copy, Debug)]
+pub struct Rebase {
+    pub record: usize,
+    /// Byte position of each offset field within a record
+    pub fields: &'static [usize],
+}
+
+impl Rebase {
+    fn apply(&self, buf: &mut [u8], by: u64, shift: fn(u64, u64) -> u64) {
+        for rec in buf.chunks_exact_mut(self.record) {
+            for &at in self.fields {
+                let field = &mut rec[at..at + 8];
+                let value = u64::from_le_bytes(field.try_into().unwrap_or_default());
+                field.copy_from_slice(&shift(value, by).to_le_bytes());
+            }
+        }
+    }
+}
+
+/// Front of a file being dropped: bytes `cut..moved` already sit at
+/// `0..moved - cut`, and the offset fields of the kept records shift by `by`
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FrontDrop {
+    pub cut: u64,
+    pub moved: u64,
+    pub by: u64,
+}
+
+impl FrontDrop {
+    /// Offset past the data already moved down
+    fn boundary(&self) -> u64 {
+        self.moved - self.cut
+    }
+}
+
+/// A backend whose front can be dropped in steps. The kept bytes move down one
+/// bounded step at a time, and reads and writes meanwhile go to wherever the
+/// data currently lives, so the file stays usable while it is compacted.
+/// With a [`CutFile`], every step is recorded so a compaction cut short by a
+/// crash is resumed when the session is reopened.
+#[derive(Clone, Default)]
+pub struct Compactable<B: StorageBackend> {
+    inner: B,
+    rebase: Option<Rebase>,
+    /// Pending drops in the order they were made. Only the first moves data;
+    /// each later one applies to the file as the ones before it leave it.
+    drops: RefCell<Vec<FrontDrop>>,
+    /// Record of the pending drops, kept under `name`
+    cuts: Option<Rc<CutFile<B>>>,
+    name: &'static str,
+}
+
+impl<B: StorageBackend> Compactable<B> {
+    pub fn new(inner: B, rebase: Option<Rebase>) -> Self {
+        Self {
+            inner,
+            rebase,
+            drops: RefCell::new(Vec::new()),
+            cuts: None,
+            name: "",
+        }
+    }
+
+    /// Records the drops of this file in `cuts` under `name`, resuming those
+    /// left pending by an earlier run
+    pub fn journaled(
+        mut self,
+        cuts: &Rc<CutFile<B>>,
+        name: &'static str,
+    ) -> Result<Self, LogError> {
+        let mut drops = cuts.drops(name);
+        let size = self.inner.get_file_size()?.0;
+        // All moved and cut off, only not yet recorded as done
+        if drops.first().is_some_and(|front| size < front.moved) {
+            drops.remove(0);
+            cuts.set_drops(name, &drops);
+        }
+        self.drops = RefCell::new(drops);
+        self.cuts = Some(cuts.clone());
+        self.name = name;
+        Ok(self)
+    }
+
+    pub fn inner(&self) -> &B {
+        &self.inner
+    }
+
+    /// Starts dropping the first `cut` bytes, shifting the offset fields of the
+    /// kept records down by `by`. The data is moved by [`Self::step`]; the drop
+    /// reaches the disk with the next save of the [`CutFile`].
+    pub fn drop_front(&self, cut: u64, by: u64) -> Result<(), LogError> {
+        if cut == 0 {
+            return Ok(());
+        }
+        {
+            let mut drops = self.drops.borrow_mut();
+            match drops.last_mut() {
+                // What the last drop moved so far is dropped too, so one drop covers both
+                Some(last) if last.boundary() <= cut => {
+                    last.cut += cut;
+                    last.moved = last.moved.max(last.cut);
+                    last.by += by;
+                }
+                _ => drops.push(FrontDrop {
+                    cut,
+                    moved: cut,
+                    by,
+                }),
+            }
+        }
+        self.record(false)
+    }
+
+    /// Moves up to `budget` more bytes of the first pending drop down: at least
+    /// one record, and no more than it cuts, so the data still to move is never
+    /// overwritten before the step is recorded. Returns whether a drop is still
+    /// pending. The drops of an archive wait for it to be opened for writing.
+    pub fn step(&self, budget: u64) -> Result<bool, LogError> {
+        let Some(mut front) = self.drops.borrow().first().copied() else {
+            return Ok(false);
+        };
+        if self.cuts.as_ref().is_some_and(|cuts| !cuts.is_writable()) {
+            return Ok(false);
+        }
+        let end = self.inner.get_file_size()?.0;
+        let record = self.rebase.map_or(1, |r| r.record);
+        let block = (READ_BUFFER_SIZE - READ_BUFFER_SIZE % record) as u64;
+        let mut buf = vec![0u8; block as usize];
+        let record = record as u64;
+        let span = (budget.max(record) - budget.max(record) % record).min(front.cut);
+        let stop = front.moved.saturating_add(span).min(end);
+        while front.moved < stop {
+            let len = (stop - front.moved).min(block) as usize;
+            let buf = &mut buf[..len];
+            self.inner.read_at(ByteOffset(front.moved), buf)?;
+            if let Some(rebase) = self.rebase {
+                rebase.apply(buf, front.by, u64::saturating_sub);
+            }
+            self.inner.write_at(ByteOffset(front.boundary()), buf)?;
+            front.moved += len as u64;
+        }
+        // The moved data is on disk before the record says so
+        self.inner.flush()?;
+        self.drops.borrow_mut()[0] = front;
+        self.record(true)?;
+        if front.moved < end {
+            return Ok(true);
+        }
+        self.inner.truncate(end - front.cut)?;
+        self.inner.flush()?;
+        self.drops.borrow_mut().remove(0);
+        self.record(true)?;
+        Ok(!self.drops.borrow().is_empty())
+    }
+
+    /// Completes the pending drops
+    pub fn finish(&self) -> Result<(), LogError> {
+        while self.step(u64::MAX)? {}
+        Ok(())
+    }
+
+    /// Updates the record of the pending drops, and saves it if `save`
+    fn record(&self, save: bool) -> Result<(), LogError> {
+        let Some(cuts) = &self.cuts else {
+            return Ok(());
+        };
+        cuts.set_drops(self.name, &self.drops.borrow());
+        if save {
+            cuts.save()?;
+        }
+        Ok(())
+    }
+
+    /// Reads at `offset` of the file as left by `drops`
+    fn read_through(
+        &self,
+        drops: &[FrontDrop],
+        offset: ByteOffset,
+        buf: &mut [u8],
+    ) -> Result<usize, LogError> {
+        let Some((front, below)) = drops.split_last() else {
+            return self.inner.read_at(offset, buf);
+        };
+        let split = (front.boundary().saturating_sub(offset.0) as usize).min(buf.len());
+        let (moved, unmoved) = buf.split_at_mut(split);
+        let mut read = 0;
+        if !moved.is_empty() {
+            read = self.read_through(below, offset, moved)?;
+            if read < moved.len() {
+                return Ok(read);
+            }
+        }
+        if !unmoved.is_empty() {
+            let at = ByteOffset(offset.0 + split as u64 + front.cut);
+            let n = self.read_through(below, at, unmoved)?;
+            if let Some(rebase) = self.rebase {
+                rebase.apply(&mut unmoved[..n], front.by, u64::saturating_sub);
+            }
+            read += n;
+        }
+        Ok(read)
+    }
+
+    /// Writes at `offset` of the file as left by `drops`
+    fn write_through(
+        &self,
+        drops: &[FrontDrop],
+        offset: ByteOffset,
+        data: &[u8],
+    ) -> Result<usize, LogError> {
+        let Some((front, below)) = drops.split_last() else {
+            return self.inner.write_at(offset, data);
+        };
+        let split = (front.boundary().saturating_sub(offset.0) as usize).min(data.len());
+        let (moved, unmoved) = data.split_at(split);
+        let mut written = 0;
+        if !moved.is_empty() {
+            written = self.write_through(below, offset, moved)?;
+        }
+        if !unmoved.is_empty() {
+            // Stored like the data around it, so moving it down shifts it back
+            let mut unmoved = unmoved.to_vec();
+            if let Some(rebase) = self.rebase {
+                rebase.apply(&mut unmoved, front.by, u64::saturating_add);
+            }
+            let at = ByteOffset(offset.0 + split as u64 + front.cut);
+            written += self.write_through(below, at, &unmoved)?;
+        }
+        Ok(written)
+    }
+}
+
+impl<B: StorageBackend> StorageBackend for Compactable<B> {
+    fn read_at(&self, offset: ByteOffset, buf: &mut [u8]) -> Result<usize, LogError> {
+        self.read_through(&self.drops.borrow(), offset, buf)
+    }
+
+    fn write_at(&self, offset: ByteOffset, data: &[u8]) -> Result<usize, LogError> {
+        self.write_through(&self.drops.borrow(), offset, data)
+    }
+
+    fn get_file_size(&self) -> Result<ByteOffset, LogError> {
+        let size = self.inner.get_file_size()?;
+        let cut: u64 = self.drops.borrow().iter().map(|front| front.cut).sum();
+        Ok(ByteOffset(size.0.saturating_sub(cut)))
+    }
+
+    fn truncate(&self, size: u64) -> Result<(), LogError> {
+        if size == 0 {
+            // Nothing kept, nothing to move
+            self.drops.borrow_mut().clear();
+            self.record(true)?;
+        }
+        self.finish()?;
+        self.inner.truncate(size)
+    }
+
+    fn flush(&self) -> Result<(), LogError> {
+        self.inner.flush()
+    }
+
+    /// Completes the pending drops so the file is left in order
+    fn close(&mut self) {
+        let _ = self.finish();
+        self.inner.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::worker::repository::storage::MemoryBackend;
+
+    const REBASE: Rebase = Rebase {
+        record: 8,
+        fields: &[0],
+    };
+
+    fn read(file: &impl StorageBackend, offset: u64, len: usize) -> Vec<u8> {
+        let mut buf = vec![0u8; len];
+        let n = file.read_at(ByteOffset(offset), &mut buf).unwrap();
+        buf.truncate(n);
+        buf
+    }
+
+    fn records(values: &[u64]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn test_drop_front_in_steps() {
+        let mem = MemoryBackend::new();
+        let file = Compactable::new(mem.clone(), None);
+        file.write_at(ByteOffset(0), b"0123456789abcdef").unwrap();
+        file.drop_front(6, 0).unwrap();
+        assert_eq!(file.get_file_size().unwrap(), ByteOffset(10));
+
+        // Reads and appends see the dropped file at every step
+        assert!(file.step(3).unwrap());
+        assert_eq!(read(&file, 0, 10), b"6789abcdef");
+        file.write_at(ByteOffset(10), b"gh").unwrap();
+        assert!(file.step(4).unwrap());
+        assert_eq!(read(&file, 2, 10), b"89abcdefgh");
+
+        file.finish().unwrap();
+        assert!(!file.step(4).unwrap());
+        assert_eq!(mem.bytes(), b"6789abcdefgh");
+    }
+
+    #[test]
+    fn test_drop_front_rebases_records() {
+        let mem = MemoryBackend::new();
+        let file = Compactable::new(mem.clone(), Some(REBASE));
+        file.write_at(ByteOffset(0), &records(&[50, 100, 150, 200]))
+            .unwrap();
+        file.drop_front(8, 100).unwrap();
+        // Less than a record still moves one
+        assert!(file.step(3).unwrap());
+        // Records already moved and those still in place read the same way
+        assert_eq!(read(&file, 0, 24), records(&[0, 50, 100]));
+        // Appended in the new coordinates
+        file.write_at(ByteOffset(24), &records(&[130])).unwrap();
+        file.finish().unwrap();
+        assert_eq!(mem.bytes(), records(&[0, 50, 100, 130]));
+    }
+
+    #[test]
+    fn test_drop_while_another_is_pending() {
+        let mem = MemoryBackend::new();
+        let file = Compactable::new(mem.clone(), Some(REBASE));
+        file.write_at(ByteOffset(0), &records(&[10, 20, 30, 40, 50, 60]))
+            .unwrap();
+        file.drop_front(8, 10).unwrap();
+        assert!(file.step(8).unwrap());
+        assert!(file.step(8).unwrap());
+        // Keeps part of what was moved, so it waits for the first drop
+        file.drop_front(8, 5).unwrap();
+        assert_eq!(file.drops.borrow().len(), 2);
+        assert_eq!(read(&file, 0, 32), records(&[15, 25, 35, 45]));
+        // Each step stays within its budget
+        assert!(file.step(8).unwrap());
+        assert_eq!(read(&file, 0, 32), records(&[15, 25, 35, 45]));
+
+        file.finish().unwrap();
+        assert_eq!(mem.bytes(), records(&[15, 25, 35, 45]));
+    }
+
+    #[test]
+    fn test_interrupted_drop_is_resumed() {
+        let mem = MemoryBackend::new();
+        let record = MemoryBackend::new();
+        let cuts = Rc::new(CutFile::open(Some(record.clone()), true));
+        let file = Compactable::new(mem.clone(), Some(REBASE))
+            .journaled(&cuts, "idx")
+            .unwrap();
+        file.write_at(ByteOffset(0), &records(&[100, 200, 300, 400, 500, 600]))
+            .unwrap();
+        file.drop_front(16, 25).unwrap();
+        cuts.save().unwrap();
+        assert!(file.step(16).unwrap());
+        // The tab is closed here, with half of the kept records moved
+        drop(file);
+        assert_eq!(mem.bytes(), records(&[275, 375, 300, 400, 500, 600]));
+
+        // An archive reads it in place
+        let archive = Rc::new(CutFile::open(Some(record.clone()), false));
+        let file = Compactable::new(mem.clone(), Some(REBASE))
+            .journaled(&archive, "idx")
+            .unwrap();
+        assert_eq!(read(&file, 0, 32), records(&[275, 375, 475, 575]));
+        assert!(!file.step(16).unwrap());
+
+        let cuts = Rc::new(CutFile::open(Some(record.clone()), true));
+        let file = Compactable::new(mem.clone(), Some(REBASE))
+            .journaled(&cuts, "idx")
+            .unwrap();
+        assert_eq!(read(&file, 0, 32), records(&[275, 375, 475, 575]));
+        file.finish().unwrap();
+        assert_eq!(mem.bytes(), records(&[275, 375, 475, 575]));
+        assert!(cuts.drops("idx").is_empty());
+    }
 }
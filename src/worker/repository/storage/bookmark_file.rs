@@ -0,0 +1,59 @@
+use crate::types::Bookmark;
+use crate::worker::error::LogError;
+use crate::worker::repository::index::ByteOffset;
+use crate::worker::repository::storage::backend::StorageBackend;
+use crate::worker::repository::storage::opfs::OpfsBackend;
+
+/// Sidecar JSON list of the bookmarks placed in a session
+pub struct BookmarkFile<B: StorageBackend = OpfsBackend> {
+    backend: Option<B>,
+}
+
+impl<B: StorageBackend> BookmarkFile<B> {
+    pub fn new() -> Self {
+        Self { backend: None }
+    }
+
+    pub fn attach(&mut self, backend: Option<B>) {
+        self.close();
+        self.backend = backend;
+    }
+
+    pub fn close(&mut self) {
+        if let Some(mut backend) = self.backend.take() {
+            backend.close();
+        }
+    }
+
+    /// Reads the stored bookmarks; a missing or damaged sidecar yields none
+    pub fn load(&self) -> Vec<Bookmark> {
+        let Some(backend) = &self.backend else {
+            return Vec::new();
+        };
+        let Ok(size) = backend.get_file_size() else {
+            return Vec::new();
+        };
+        let mut buf = vec![0u8; size.0 as usize];
+        if backend.read_at(ByteOffset(0), &mut buf).is_err() {
+            return Vec::new();
+        }
+        serde_json::from_slice(&buf).unwrap_or_default()
+    }
+
+    /// Replaces the stored bookmarks
+    pub fn save(&self, bookmarks: &[Bookmark]) -> Result<(), LogError> {
+        let Some(backend) = &self.backend else {
+            return Ok(());
+        };
+        let data = serde_json::to_vec(bookmarks).map_err(|e| LogError::Encoding(e.to_string()))?;
+        backend.truncate(0)?;
+        backend.write_at(ByteOffset(0), &data)?;
+        backend.flush()
+    }
+}
+
+impl<B: StorageBackend> Default for BookmarkFile<B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,115 @@
+use crate::worker::error::LogError;
+use crate::worker::repository::index::ByteOffset;
+use crate::worker::repository::storage::backend::{FrontDrop, StorageBackend};
+use crate::worker::repository::storage::opfs::OpfsBackend;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct Cuts {
+    /// Lines dropped from the front of the session, counting the pending drops
+    dropped_lines: usize,
+    /// Pending front drops of each session file, by file extension
+    files: BTreeMap<String, Vec<FrontDrop>>,
+}
+
+/// Sidecar JSON record of the front drops still moving data down in the
+/// session files. A ring trim is saved here before any data moves and every
+/// compaction step after its data, so a compaction cut short by a crash is
+/// resumed when the session is reopened. Shared by the files of a session.
+pub struct CutFile<B: StorageBackend = OpfsBackend> {
+    backend: RefCell<Option<B>>,
+    writable: bool,
+    cuts: RefCell<Cuts>,
+}
+
+impl<B: StorageBackend> CutFile<B> {
+    pub fn new() -> Self {
+        Self {
+            backend: RefCell::new(None),
+            writable: false,
+            cuts: RefCell::new(Cuts::default()),
+        }
+    }
+
+    /// Loads the record of a session; it is saved again only when `writable`.
+    /// A missing or damaged record holds no drops.
+    pub fn open(backend: Option<B>, writable: bool) -> Self {
+        let cuts = backend
+            .as_ref()
+            .and_then(|backend| {
+                let size = backend.get_file_size().ok()?;
+                let mut buf = vec![0u8; size.0 as usize];
+                backend.read_at(ByteOffset(0), &mut buf).ok()?;
+                // Anything after the record is left over from a longer one
+                let mut records = serde_json::Deserializer::from_slice(&buf).into_iter();
+                records.next()?.ok()
+            })
+            .unwrap_or_default();
+        Self {
+            backend: RefCell::new(backend),
+            writable,
+            cuts: RefCell::new(cuts),
+        }
+    }
+
+    pub fn close(&self) {
+        if let Some(mut backend) = self.backend.borrow_mut().take() {
+            backend.close();
+        }
+    }
+
+    pub fn is_writable(&self) -> bool {
+        self.writable
+    }
+
+    /// Pending drops of the file `name`
+    pub fn drops(&self, name: &str) -> Vec<FrontDrop> {
+        self.cuts
+            .borrow()
+            .files
+            .get(name)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn set_drops(&self, name: &str, drops: &[FrontDrop]) {
+        let files = &mut self.cuts.borrow_mut().files;
+        if drops.is_empty() {
+            files.remove(name);
+        } else {
+            files.insert(name.into(), drops.to_vec());
+        }
+    }
+
+    pub fn dropped_lines(&self) -> usize {
+        self.cuts.borrow().dropped_lines
+    }
+
+    pub fn set_dropped_lines(&self, lines: usize) {
+        self.cuts.borrow_mut().dropped_lines = lines;
+    }
+
+    /// Replaces the stored record
+    pub fn save(&self) -> Result<(), LogError> {
+        let backend = self.backend.borrow();
+        let Some(backend) = backend.as_ref().filter(|_| self.writable) else {
+            return Ok(());
+        };
+        let data = serde_json::to_vec(&*self.cuts.borrow())
+            .map_err(|e| LogError::Encoding(e.to_string()))?;
+        // Written over the old record before it is cut to size, so it is never
+        // left empty; a crash in between leaves it followed by the old tail
+        backend.write_at(ByteOffset(0), &data)?;
+        backend.truncate(data.len() as u64)?;
+        backend.flush()
+    }
+}
+
+impl<B: StorageBackend> Default for CutFile<B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
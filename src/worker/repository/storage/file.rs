@@ -0,0 +1,135 @@
+use crate::worker::error::LogError;
+use crate::worker::repository::index::ByteOffset;
+use crate::worker::repository::storage::backend::StorageBackend;
+use crate::worker::repository::storage::opfs::{sidecar_file_name, SessionFiles};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+fn io_error(e: std::io::Error) -> LogError {
+    LogError::Storage(e.to_string())
+}
+
+/// Storage in a regular file, for running the worker pipeline natively
+#[derive(Default)]
+pub struct FileBackend {
+    file: Option<File>,
+}
+
+impl FileBackend {
+    /// Opens `path` for reading and writing, creating it if needed
+    pub fn open(path: &Path) -> Result<Self, LogError> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .map_err(io_error)?;
+        Ok(Self { file: Some(file) })
+    }
+
+    fn file(&self) -> Result<&File, LogError> {
+        self.file
+            .as_ref()
+            .ok_or_else(|| LogError::Storage("No file".into()))
+    }
+}
+
+impl StorageBackend for FileBackend {
+    fn read_at(&self, offset: ByteOffset, buf: &mut [u8]) -> Result<usize, LogError> {
+        let mut file = self.file()?;
+        file.seek(SeekFrom::Start(offset.0)).map_err(io_error)?;
+        let mut read = 0;
+        while read < buf.len() {
+            match file.read(&mut buf[read..]).map_err(io_error)? {
+                0 => break,
+                n => read += n,
+            }
+        }
+        Ok(read)
+    }
+
+    fn write_at(&self, offset: ByteOffset, data: &[u8]) -> Result<usize, LogError> {
+        let mut file = self.file()?;
+        file.seek(SeekFrom::Start(offset.0)).map_err(io_error)?;
+        file.write_all(data).map_err(io_error)?;
+        Ok(data.len())
+    }
+
+    fn get_file_size(&self) -> Result<ByteOffset, LogError> {
+        let metadata = self.file()?.metadata().map_err(io_error)?;
+        Ok(ByteOffset(metadata.len()))
+    }
+
+    fn truncate(&self, size: u64) -> Result<(), LogError> {
+        self.file()?.set_len(size).map_err(io_error)
+    }
+
+    fn flush(&self) -> Result<(), LogError> {
+        self.file()?.sync_data().map_err(io_error)
+    }
+
+    fn close(&mut self) {
+        self.file = None;
+    }
+}
+
+impl SessionFiles<FileBackend> {
+    /// Opens (or creates) the session `name` and its sidecars in `dir`,
+    /// laid out like the OPFS session library
+    pub fn open_dir(dir: &Path, name: &str) -> Result<Self, LogError> {
+        let sidecar = |ext: &str| FileBackend::open(&dir.join(sidecar_file_name(name, ext))).ok();
+        Ok(Self {
+            log: FileBackend::open(&dir.join(name))?,
+            index: sidecar("idx"),
+            meta: sidecar("json"),
+            raw: sidecar("bin"),
+            raw_chunks: sidecar("chunks"),
+            raw_lines: sidecar("rawmap"),
+            bookmarks: sidecar("marks"),
+            times: sidecar("times"),
+            levels: sidecar("levels"),
+            cuts: sidecar("cuts"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::worker::commands::RequestWindowCommand;
+    use crate::worker::processor::LogProcessor;
+    use crate::worker::types::WorkerMsg;
+
+    #[test]
+    fn test_session_survives_reopen() {
+        let dir = std::env::temp_dir().join(format!("rusterm-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let name = "logs_1700000000000.txt";
+
+        let mut proc = LogProcessor::<FileBackend>::new();
+        proc.set_sync_handle(SessionFiles::open_dir(&dir, name).unwrap())
+            .unwrap();
+        proc.append_chunk(b"boot\nwifi connected\n", false).unwrap();
+        proc.repository.release_storage();
+
+        let mut reopened = LogProcessor::<FileBackend>::new();
+        reopened
+            .set_sync_handle(SessionFiles::open_dir(&dir, name).unwrap())
+            .unwrap();
+        let window = RequestWindowCommand {
+            start_line: 0,
+            count: 10,
+        }
+        .window(&reopened)
+        .unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let WorkerMsg::LogWindow { lines, .. } = window else {
+            panic!("expected a window");
+        };
+        let texts: Vec<String> = lines.iter().map(|(_, line)| line.text()).collect();
+        assert_eq!(texts, ["boot", "wifi connected"]);
+    }
+}
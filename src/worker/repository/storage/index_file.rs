@@ -0,0 +1,155 @@
+use crate::worker::error::LogError;
+use crate::worker::repository::index::ByteOffset;
+use crate::worker::repository::storage::backend::{Compactable, Rebase, StorageBackend};
+use crate::worker::repository::storage::cut_file::CutFile;
+use crate::worker::repository::storage::opfs::OpfsBackend;
+use std::rc::Rc;
+
+const RECORD_SIZE: u64 = 8;
+const REBASE: Rebase = Rebase {
+    record: RECORD_SIZE as usize,
+    fields: &[0],
+};
+
+/// Sidecar file holding the line end offsets of a session as little-endian u64 records.
+/// Lets a session be reopened without scanning the whole log for newlines.
+pub struct IndexFile<B: StorageBackend = OpfsBackend> {
+    backend: Option<Compactable<B>>,
+    len: u64,
+    broken: bool,
+}
+
+impl<B: StorageBackend> IndexFile<B> {
+    pub fn new() -> Self {
+        Self {
+            backend: None,
+            len: 0,
+            broken: false,
+        }
+    }
+
+    /// Attaches the sidecar, dropping a torn trailing record from it when `writable`.
+    /// Without one, the index is simply not persisted.
+    pub fn attach(
+        &mut self,
+        backend: Option<B>,
+        writable: bool,
+        cuts: &Rc<CutFile<B>>,
+    ) -> Result<(), LogError> {
+        self.close();
+        let Some(backend) = backend else {
+            return Ok(());
+        };
+        let backend = Compactable::new(backend, Some(REBASE)).journaled(cuts, "idx")?;
+        let size = backend.get_file_size()?.0;
+        self.len = size - size % RECORD_SIZE;
+        if self.len != size && writable {
+            backend.truncate(self.len)?;
+        }
+        self.backend = Some(backend);
+        Ok(())
+    }
+
+    /// Releases the lock on the sidecar
+    pub fn close(&mut self) {
+        if let Some(mut backend) = self.backend.take() {
+            backend.close();
+        }
+        self.len = 0;
+        self.broken = false;
+    }
+
+    /// Reads all persisted offsets.
+    /// Returns `None` when the file ends in a torn record.
+    pub fn load(&self) -> Result<Option<Vec<ByteOffset>>, LogError> {
+        let Some(backend) = &self.backend else {
+            return Ok(None);
+        };
+        if !self.len.is_multiple_of(RECORD_SIZE) {
+            return Ok(None);
+        }
+        let mut buf = vec![0u8; self.len as usize];
+        if backend.read_at(ByteOffset(0), &mut buf)? != buf.len() {
+            return Ok(None);
+        }
+        Ok(Some(
+            buf.chunks_exact(RECORD_SIZE as usize)
+                .map(|b| ByteOffset(u64::from_le_bytes(b.try_into().unwrap_or_default())))
+                .collect(),
+        ))
+    }
+
+    /// Appends offsets to the sidecar.
+    /// After a failed write nothing more is appended, so the persisted offsets stay a
+    /// gapless prefix and the rest is recovered by rescanning the log tail on open.
+    pub fn append(&mut self, offsets: &[ByteOffset]) {
+        let Some(backend) = self.backend.as_ref().filter(|_| !self.broken) else {
+            return;
+        };
+        if offsets.is_empty() {
+            return;
+        }
+        let data: Vec<u8> = offsets.iter().flat_map(|o| o.0.to_le_bytes()).collect();
+        match backend.write_at(ByteOffset(self.len), &data) {
+            Ok(n) if n == data.len() => self.len += n as u64,
+            _ => self.broken = true,
+        }
+    }
+
+    /// Keeps only the first `count` offsets
+    pub fn truncate(&mut self, count: usize) -> Result<(), LogError> {
+        let len = (count as u64 * RECORD_SIZE).min(self.len);
+        if let Some(backend) = &self.backend {
+            backend.truncate(len)?;
+        }
+        self.len = len;
+        Ok(())
+    }
+
+    /// Drops the offsets of the first `lines` lines and shifts the rest down by
+    /// `cut` bytes, the size of the log dropped with them. The kept offsets move
+    /// down in [`Self::compact`] steps.
+    pub fn drop_front(&mut self, lines: usize, cut: u64) -> Result<(), LogError> {
+        let Some(backend) = &self.backend else {
+            return Ok(());
+        };
+        let len = (lines as u64 * RECORD_SIZE).min(self.len);
+        backend.drop_front(len, cut)?;
+        self.len -= len;
+        Ok(())
+    }
+
+    /// Moves a pending front drop along by up to `budget` bytes.
+    /// Returns whether one is still pending.
+    pub fn compact(&self, budget: u64) -> Result<bool, LogError> {
+        self.backend.as_ref().map_or(Ok(false), |b| b.step(budget))
+    }
+
+    /// Bytes stored
+    pub fn size(&self) -> u64 {
+        self.len
+    }
+
+    pub fn flush(&self) -> Result<(), LogError> {
+        match &self.backend {
+            Some(backend) => backend.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// Drops all persisted offsets
+    pub fn reset(&mut self) -> Result<(), LogError> {
+        self.len = 0;
+        self.broken = false;
+        if let Some(backend) = &self.backend {
+            backend.truncate(0)?;
+        }
+        Ok(())
+    }
+}
+
+impl<B: StorageBackend> Default for IndexFile<B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
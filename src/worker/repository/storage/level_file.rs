@@ -0,0 +1,127 @@
+use crate::types::LogLevel;
+use crate::worker::error::LogError;
+use crate::worker::repository::index::ByteOffset;
+use crate::worker::repository::storage::backend::{Compactable, StorageBackend};
+use crate::worker::repository::storage::cut_file::CutFile;
+use crate::worker::repository::storage::opfs::OpfsBackend;
+use std::rc::Rc;
+
+/// Sidecar holding the level detected on every line, one byte per line
+/// ([`LogLevel::as_byte`]), so reopening a session does not classify it again.
+pub struct LevelFile<B: StorageBackend = OpfsBackend> {
+    backend: Option<Compactable<B>>,
+    len: u64,
+}
+
+impl<B: StorageBackend> LevelFile<B> {
+    pub fn new() -> Self {
+        Self {
+            backend: None,
+            len: 0,
+        }
+    }
+
+    pub fn attach(&mut self, backend: Option<B>, cuts: &Rc<CutFile<B>>) -> Result<(), LogError> {
+        self.close();
+        let Some(backend) = backend else {
+            return Ok(());
+        };
+        let backend = Compactable::new(backend, None).journaled(cuts, "levels")?;
+        self.len = backend.get_file_size()?.0;
+        self.backend = Some(backend);
+        Ok(())
+    }
+
+    pub fn close(&mut self) {
+        if let Some(mut backend) = self.backend.take() {
+            backend.close();
+        }
+        self.len = 0;
+    }
+
+    /// Lines with a recorded level
+    pub fn count(&self) -> usize {
+        self.len as usize
+    }
+
+    pub fn append(&mut self, levels: &[LogLevel]) -> Result<(), LogError> {
+        let Some(backend) = &self.backend else {
+            return Ok(());
+        };
+        if levels.is_empty() {
+            return Ok(());
+        }
+        let data: Vec<u8> = levels.iter().map(|l| l.as_byte()).collect();
+        backend.write_at(ByteOffset(self.len), &data)?;
+        self.len += data.len() as u64;
+        Ok(())
+    }
+
+    /// Levels of `count` lines starting at `first`; shorter at the end of the file
+    pub fn read(&self, first: usize, count: usize) -> Result<Vec<LogLevel>, LogError> {
+        let Some(backend) = &self.backend else {
+            return Ok(Vec::new());
+        };
+        let count = count.min(self.count().saturating_sub(first));
+        let mut buf = vec![0u8; count];
+        if count > 0 {
+            backend.read_at(ByteOffset(first as u64), &mut buf)?;
+        }
+        Ok(buf.into_iter().map(LogLevel::from_byte).collect())
+    }
+
+    /// Drops the levels past the first `lines` lines
+    pub fn truncate(&mut self, lines: usize) -> Result<(), LogError> {
+        if let Some(backend) = &self.backend {
+            if (lines as u64) < self.len {
+                self.len = lines as u64;
+                backend.truncate(self.len)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Drops the levels of the first `lines` lines; the rest moves down in [`Self::compact`] steps
+    pub fn drop_front(&mut self, lines: usize) -> Result<(), LogError> {
+        let Some(backend) = &self.backend else {
+            return Ok(());
+        };
+        let cut = (lines as u64).min(self.len);
+        backend.drop_front(cut, 0)?;
+        self.len -= cut;
+        Ok(())
+    }
+
+    /// Moves a pending front drop along by up to `budget` bytes.
+    /// Returns whether one is still pending.
+    pub fn compact(&self, budget: u64) -> Result<bool, LogError> {
+        self.backend.as_ref().map_or(Ok(false), |b| b.step(budget))
+    }
+
+    /// Bytes stored
+    pub fn size(&self) -> u64 {
+        self.len
+    }
+
+    pub fn flush(&self) -> Result<(), LogError> {
+        match &self.backend {
+            Some(backend) => backend.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// Drops all recorded levels
+    pub fn reset(&mut self) -> Result<(), LogError> {
+        self.len = 0;
+        if let Some(backend) = &self.backend {
+            backend.truncate(0)?;
+        }
+        Ok(())
+    }
+}
+
+impl<B: StorageBackend> Default for LevelFile<B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,94 @@
+use crate::worker::error::LogError;
+use crate::worker::repository::index::ByteOffset;
+use crate::worker::repository::storage::backend::StorageBackend;
+use crate::worker::repository::storage::opfs::SessionFiles;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Storage kept in memory, for running the worker pipeline natively.
+/// Clones share the same bytes like handles to one file, so a session can be
+/// released and attached again.
+#[derive(Clone, Default)]
+pub struct MemoryBackend {
+    data: Rc<RefCell<Vec<u8>>>,
+}
+
+impl MemoryBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Copy of the stored bytes
+    pub fn bytes(&self) -> Vec<u8> {
+        self.data.borrow().clone()
+    }
+}
+
+impl StorageBackend for MemoryBackend {
+    fn read_at(&self, offset: ByteOffset, buf: &mut [u8]) -> Result<usize, LogError> {
+        let data = self.data.borrow();
+        let start = (offset.0 as usize).min(data.len());
+        let len = buf.len().min(data.len() - start);
+        buf[..len].copy_from_slice(&data[start..start + len]);
+        Ok(len)
+    }
+
+    fn write_at(&self, offset: ByteOffset, bytes: &[u8]) -> Result<usize, LogError> {
+        let mut data = self.data.borrow_mut();
+        let start = offset.0 as usize;
+        let end = start + bytes.len();
+        if data.len() < end {
+            data.resize(end, 0);
+        }
+        data[start..end].copy_from_slice(bytes);
+        Ok(bytes.len())
+    }
+
+    fn get_file_size(&self) -> Result<ByteOffset, LogError> {
+        Ok(ByteOffset(self.data.borrow().len() as u64))
+    }
+
+    fn truncate(&self, size: u64) -> Result<(), LogError> {
+        self.data.borrow_mut().resize(size as usize, 0);
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<(), LogError> {
+        Ok(())
+    }
+}
+
+impl SessionFiles<MemoryBackend> {
+    /// An empty session with every sidecar
+    pub fn in_memory() -> Self {
+        Self {
+            log: MemoryBackend::new(),
+            index: Some(MemoryBackend::new()),
+            meta: Some(MemoryBackend::new()),
+            raw: Some(MemoryBackend::new()),
+            raw_chunks: Some(MemoryBackend::new()),
+            raw_lines: Some(MemoryBackend::new()),
+            bookmarks: Some(MemoryBackend::new()),
+            times: Some(MemoryBackend::new()),
+            levels: Some(MemoryBackend::new()),
+            cuts: Some(MemoryBackend::new()),
+        }
+    }
+}
+
+impl Clone for SessionFiles<MemoryBackend> {
+    fn clone(&self) -> Self {
+        Self {
+            log: self.log.clone(),
+            index: self.index.clone(),
+            meta: self.meta.clone(),
+            raw: self.raw.clone(),
+            raw_chunks: self.raw_chunks.clone(),
+            raw_lines: self.raw_lines.clone(),
+            bookmarks: self.bookmarks.clone(),
+            times: self.times.clone(),
+            levels: self.levels.clone(),
+            cuts: self.cuts.clone(),
+        }
+    }
+}
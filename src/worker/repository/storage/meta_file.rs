@@ -0,0 +1,53 @@
+use crate::types::SessionMetadata;
+use crate::worker::error::LogError;
+use crate::worker::repository::index::ByteOffset;
+use crate::worker::repository::storage::backend::StorageBackend;
+use crate::worker::repository::storage::opfs::OpfsBackend;
+
+/// Sidecar JSON document describing how a session was captured
+pub struct MetaFile<B: StorageBackend = OpfsBackend> {
+    backend: Option<B>,
+}
+
+impl<B: StorageBackend> MetaFile<B> {
+    pub fn new() -> Self {
+        Self { backend: None }
+    }
+
+    pub fn attach(&mut self, backend: Option<B>) {
+        self.close();
+        self.backend = backend;
+    }
+
+    pub fn close(&mut self) {
+        if let Some(mut backend) = self.backend.take() {
+            backend.close();
+        }
+    }
+
+    /// Reads the stored metadata; sessions captured without one yield `None`
+    pub fn load(&self) -> Option<SessionMetadata> {
+        let backend = self.backend.as_ref()?;
+        let size = backend.get_file_size().ok()?;
+        let mut buf = vec![0u8; size.0 as usize];
+        backend.read_at(ByteOffset(0), &mut buf).ok()?;
+        serde_json::from_slice(&buf).ok()
+    }
+
+    /// Replaces the stored metadata
+    pub fn save(&self, metadata: &SessionMetadata) -> Result<(), LogError> {
+        let Some(backend) = &self.backend else {
+            return Ok(());
+        };
+        let data = serde_json::to_vec(metadata).map_err(|e| LogError::Encoding(e.to_string()))?;
+        backend.truncate(0)?;
+        backend.write_at(ByteOffset(0), &data)?;
+        backend.flush()
+    }
+}
+
+impl<B: StorageBackend> Default for MetaFile<B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
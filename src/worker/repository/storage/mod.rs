@@ -1,6 +1,34 @@
 pub mod backend;
+pub mod bookmark_file;
+pub mod cut_file;
+pub mod index_file;
+pub mod level_file;
+pub mod meta_file;
 pub mod opfs;
+pub mod raw_file;
+pub mod time_file;
+
+// Native backends for running the pipeline under `cargo test`
+#[cfg(test)]
+pub mod file;
+#[cfg(test)]
+pub mod memory;
 
 // Re-export commonly used items
 pub use backend::StorageBackend;
-pub use opfs::{get_opfs_root, init_opfs_session, new_session, LogStorage};
+pub use bookmark_file::BookmarkFile;
+pub use cut_file::CutFile;
+pub use index_file::IndexFile;
+pub use level_file::LevelFile;
+pub use meta_file::MetaFile;
+pub use opfs::{
+    delete_session, estimate_storage, get_file_handle, get_file_size, get_files, get_opfs_root,
+    init_opfs_session, new_session, open_session, read_session_bookmarks, read_session_metadata,
+    rename_session, session_label, session_timestamp, sidecar_file_name, LogStorage, OpfsBackend,
+    SessionFiles,
+};
+pub use raw_file::RawCapture;
+pub use time_file::TimeFile;
+
+#[cfg(test)]
+pub use memory::MemoryBackend;
@@ -1,16 +1,23 @@
 use crate::worker::error::LogError;
 use crate::worker::repository::index::ByteOffset;
-use crate::worker::repository::storage::backend::StorageBackend;
+use crate::worker::repository::storage::backend::{Compactable, StorageBackend};
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
-use web_sys::TextDecoder;
-use web_sys::TextEncoder;
 
 /// OPFS-based storage backend
+#[derive(Clone, Default)]
 pub struct OpfsBackend {
     pub handle: Option<web_sys::FileSystemSyncAccessHandle>,
 }
 
+impl From<web_sys::FileSystemSyncAccessHandle> for OpfsBackend {
+    fn from(handle: web_sys::FileSystemSyncAccessHandle) -> Self {
+        Self {
+            handle: Some(handle),
+        }
+    }
+}
+
 impl StorageBackend for OpfsBackend {
     fn read_at(&self, offset: ByteOffset, buf: &mut [u8]) -> Result<usize, LogError> {
         let handle = self
@@ -35,7 +42,7 @@ impl StorageBackend for OpfsBackend {
         handle
             .write_with_u8_array_and_options(data, &opts)
             .map(|n| n as usize)
-            .map_err(LogError::from)
+            .map_err(write_error)
     }
 
     fn get_file_size(&self) -> Result<ByteOffset, LogError> {
@@ -62,25 +69,94 @@ impl StorageBackend for OpfsBackend {
             .flush()
             .map_err(LogError::from)
     }
+
+    /// Releases the lock on the underlying file, if any
+    fn close(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            handle.close();
+        }
+    }
 }
 
-/// Log storage wrapper with encoder/decoder
-pub struct LogStorage {
-    pub backend: OpfsBackend,
-    pub encoder: TextEncoder,
-    pub decoder: TextDecoder,
+/// Turns a failed write into a readable error; running out of quota is the usual cause
+fn write_error(err: JsValue) -> LogError {
+    let name = js_sys::Reflect::get(&err, &"name".into())
+        .ok()
+        .and_then(|v| v.as_string());
+    if name.as_deref() == Some("QuotaExceededError") {
+        LogError::Storage("Storage quota exceeded; delete old sessions to free space".into())
+    } else {
+        LogError::from(err)
+    }
 }
 
-impl LogStorage {
-    pub fn new() -> Result<Self, LogError> {
-        Ok(Self {
-            backend: OpfsBackend { handle: None },
-            encoder: TextEncoder::new().map_err(LogError::from)?,
-            decoder: TextDecoder::new().map_err(LogError::from)?,
-        })
+/// Locks held on a session's log file and its sidecars
+pub struct SessionFiles<B: StorageBackend = OpfsBackend> {
+    pub log: B,
+    /// Persisted line index; `None` if the sidecar could not be locked
+    pub index: Option<B>,
+    /// Capture metadata document
+    pub meta: Option<B>,
+    /// Received bytes, chunk arrival times and the raw range of each line
+    pub raw: Option<B>,
+    pub raw_chunks: Option<B>,
+    pub raw_lines: Option<B>,
+    /// Bookmarks placed in the session
+    pub bookmarks: Option<B>,
+    /// Receive time of each line
+    pub times: Option<B>,
+    /// Level detected on each line
+    pub levels: Option<B>,
+    /// Front drops of the ring buffer still being moved down
+    pub cuts: Option<B>,
+}
+
+/// Extensions of the sidecar files stored next to each session log
+const SIDECAR_EXTENSIONS: &[&str] = &[
+    "idx", "json", "bin", "chunks", "rawmap", "marks", "times", "levels", "cuts",
+];
+
+/// Log text storage; lines are stored as UTF-8
+pub struct LogStorage<B: StorageBackend = OpfsBackend> {
+    /// Detached (`B::default()`) until a session is attached
+    pub backend: Compactable<B>,
+}
+
+impl<B: StorageBackend + Default> LogStorage<B> {
+    pub fn new() -> Self {
+        Self {
+            backend: Compactable::default(),
+        }
+    }
+
+    /// Releases the attached log file
+    pub fn close(&mut self) {
+        std::mem::take(&mut self.backend).close();
+    }
+}
+
+impl<B: StorageBackend + Default> Default for LogStorage<B> {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
+/// Origin storage usage and quota in bytes, from `navigator.storage.estimate()`
+pub async fn estimate_storage() -> Result<(u64, u64), JsValue> {
+    let global = js_sys::global();
+    let navigator = js_sys::Reflect::get(&global, &"navigator".into())?;
+    let storage = js_sys::Reflect::get(&navigator, &"storage".into())?;
+    let storage: web_sys::StorageManager = storage.unchecked_into();
+    let estimate = wasm_bindgen_futures::JsFuture::from(storage.estimate()?).await?;
+    let field = |name: &str| {
+        js_sys::Reflect::get(&estimate, &name.into())
+            .ok()
+            .and_then(|v| v.as_f64())
+            .unwrap_or(0.0) as u64
+    };
+    Ok((field("usage"), field("quota")))
+}
+
 /// Gets the OPFS root directory handle
 pub async fn get_opfs_root() -> Result<web_sys::FileSystemDirectoryHandle, JsValue> {
     let global = js_sys::global();
@@ -112,7 +188,7 @@ pub async fn get_lock(
 }
 
 /// Gets all log files from the root directory
-async fn get_files(
+pub async fn get_files(
     root: &web_sys::FileSystemDirectoryHandle,
 ) -> Result<Vec<(String, web_sys::FileSystemFileHandle)>, JsValue> {
     let mut files = Vec::new();
@@ -140,28 +216,203 @@ async fn get_files(
         }
     }
 
-    files.sort_by(|a, b| {
-        let ts_a = a.0[5..a.0.len() - 4].parse::<u64>().unwrap_or(0);
-        let ts_b = b.0[5..b.0.len() - 4].parse::<u64>().unwrap_or(0);
-        ts_b.cmp(&ts_a)
-    });
+    files.sort_by_key(|f| std::cmp::Reverse(session_timestamp(&f.0)));
 
     Ok(files)
 }
 
+/// Extracts the creation timestamp (ms) from `logs_<ts>[_<label>].txt`
+pub fn session_timestamp(name: &str) -> i64 {
+    session_stem(name)
+        .split('_')
+        .next()
+        .and_then(|ts| ts.parse::<i64>().ok())
+        .unwrap_or(0)
+}
+
+/// Extracts the user label from `logs_<ts>_<label>.txt`, if any
+pub fn session_label(name: &str) -> Option<String> {
+    session_stem(name)
+        .split_once('_')
+        .map(|(_, label)| label.to_string())
+        .filter(|label| !label.is_empty())
+}
+
+fn session_stem(name: &str) -> &str {
+    name.trim_start_matches("logs_").trim_end_matches(".txt")
+}
+
+/// Builds a session file name, keeping the timestamp prefix sortable
+pub fn session_file_name(timestamp: i64, label: Option<&str>) -> String {
+    let label: String = label
+        .unwrap_or_default()
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    if label.is_empty() {
+        format!("logs_{}.txt", timestamp)
+    } else {
+        format!("logs_{}_{}.txt", timestamp, label)
+    }
+}
+
+/// Names a sidecar after the session timestamp so it survives renames
+pub fn sidecar_file_name(name: &str, extension: &str) -> String {
+    format!("logs_{}.{}", session_timestamp(name), extension)
+}
+
+/// Locks a sidecar of the session `name`, creating it first if `create` is set
+async fn open_sidecar(
+    root: &web_sys::FileSystemDirectoryHandle,
+    name: &str,
+    extension: &str,
+    create: bool,
+) -> Result<OpfsBackend, JsValue> {
+    let opts = web_sys::FileSystemGetFileOptions::new();
+    opts.set_create(create);
+    let file_handle = wasm_bindgen_futures::JsFuture::from(
+        root.get_file_handle_with_options(&sidecar_file_name(name, extension), &opts),
+    )
+    .await?;
+    get_lock(file_handle.into()).await.map(OpfsBackend::from)
+}
+
+/// Locks the sidecars of a session whose log is already locked.
+/// Missing sidecars are created only for a `writable` session.
+async fn open_session_files(
+    root: &web_sys::FileSystemDirectoryHandle,
+    name: &str,
+    log: web_sys::FileSystemSyncAccessHandle,
+    writable: bool,
+) -> SessionFiles {
+    SessionFiles {
+        log: OpfsBackend::from(log),
+        index: open_sidecar(root, name, "idx", writable).await.ok(),
+        meta: open_sidecar(root, name, "json", writable).await.ok(),
+        raw: open_sidecar(root, name, "bin", writable).await.ok(),
+        raw_chunks: open_sidecar(root, name, "chunks", writable).await.ok(),
+        raw_lines: open_sidecar(root, name, "rawmap", writable).await.ok(),
+        bookmarks: open_sidecar(root, name, "marks", writable).await.ok(),
+        times: open_sidecar(root, name, "times", writable).await.ok(),
+        levels: open_sidecar(root, name, "levels", writable).await.ok(),
+        cuts: open_sidecar(root, name, "cuts", writable).await.ok(),
+    }
+}
+
+/// Reads the metadata of a session that is not open
+pub async fn read_session_metadata(
+    root: &web_sys::FileSystemDirectoryHandle,
+    name: &str,
+) -> Option<crate::types::SessionMetadata> {
+    read_sidecar_json(root, name, "json").await
+}
+
+/// Reads the bookmarks of a session that is not open
+pub async fn read_session_bookmarks(
+    root: &web_sys::FileSystemDirectoryHandle,
+    name: &str,
+) -> Vec<crate::types::Bookmark> {
+    read_sidecar_json(root, name, "marks")
+        .await
+        .unwrap_or_default()
+}
+
+/// Reads a JSON sidecar without locking it
+async fn read_sidecar_json<T: serde::de::DeserializeOwned>(
+    root: &web_sys::FileSystemDirectoryHandle,
+    name: &str,
+    extension: &str,
+) -> Option<T> {
+    let file_handle = get_file_handle(root, &sidecar_file_name(name, extension))
+        .await
+        .ok()?;
+    let file = wasm_bindgen_futures::JsFuture::from(file_handle.get_file())
+        .await
+        .ok()?;
+    let text = wasm_bindgen_futures::JsFuture::from(file.unchecked_into::<web_sys::Blob>().text())
+        .await
+        .ok()?
+        .as_string()?;
+    serde_json::from_str(&text).ok()
+}
+
+/// Gets the size of a stored session without locking it
+pub async fn get_file_size(file_handle: &web_sys::FileSystemFileHandle) -> Result<u64, JsValue> {
+    let file = wasm_bindgen_futures::JsFuture::from(file_handle.get_file()).await?;
+    let file: web_sys::File = file.unchecked_into();
+    Ok(file.size() as u64)
+}
+
+/// Looks up an existing session file handle by name
+pub async fn get_file_handle(
+    root: &web_sys::FileSystemDirectoryHandle,
+    name: &str,
+) -> Result<web_sys::FileSystemFileHandle, JsValue> {
+    let file_handle = wasm_bindgen_futures::JsFuture::from(root.get_file_handle(name)).await?;
+    Ok(file_handle.into())
+}
+
+/// Opens an existing session and acquires its locks; an archive opened for
+/// viewing is not `writable`, so none of its files is created
+pub async fn open_session(
+    root: &web_sys::FileSystemDirectoryHandle,
+    name: &str,
+    writable: bool,
+) -> Result<SessionFiles, JsValue> {
+    let log = get_lock(get_file_handle(root, name).await?).await?;
+    Ok(open_session_files(root, name, log, writable).await)
+}
+
+/// Renames a stored session using `FileSystemHandle.move()`.
+/// The file must not be locked while it is moved.
+pub async fn rename_session(
+    root: &web_sys::FileSystemDirectoryHandle,
+    name: &str,
+    label: &str,
+) -> Result<String, JsValue> {
+    let new_name = session_file_name(session_timestamp(name), Some(label));
+    if new_name == name {
+        return Ok(new_name);
+    }
+
+    let file_handle = get_file_handle(root, name).await?;
+    let move_fn = js_sys::Reflect::get(&file_handle, &"move".into())?;
+    if !move_fn.is_function() {
+        return Err("Renaming files is not supported by this browser".into());
+    }
+    let promise = js_sys::Function::from(move_fn).call1(&file_handle, &new_name.clone().into())?;
+    wasm_bindgen_futures::JsFuture::from(js_sys::Promise::from(promise)).await?;
+    Ok(new_name)
+}
+
+/// Deletes a stored session along with its sidecars
+pub async fn delete_session(
+    root: &web_sys::FileSystemDirectoryHandle,
+    name: &str,
+) -> Result<(), JsValue> {
+    wasm_bindgen_futures::JsFuture::from(root.remove_entry(name)).await?;
+    for ext in SIDECAR_EXTENSIONS {
+        // Older sessions may not have every sidecar
+        let _ =
+            wasm_bindgen_futures::JsFuture::from(root.remove_entry(&sidecar_file_name(name, ext)))
+                .await;
+    }
+    Ok(())
+}
+
 /// Creates a new OPFS session
 pub async fn new_session(
     root: &web_sys::FileSystemDirectoryHandle,
-    cleanup_current: bool,
     current_filename: &mut Option<String>,
-) -> Result<web_sys::FileSystemSyncAccessHandle, JsValue> {
-    if cleanup_current {
-        if let Some(name) = current_filename {
-            let _ = wasm_bindgen_futures::JsFuture::from(root.remove_entry(name)).await;
-        }
-    }
-
-    let filename = format!("logs_{}.txt", chrono::Utc::now().timestamp_millis());
+) -> Result<SessionFiles, JsValue> {
+    let filename = session_file_name(chrono::Utc::now().timestamp_millis(), None);
     let opts = web_sys::FileSystemGetFileOptions::new();
     opts.set_create(true);
     let file_handle =
@@ -170,38 +421,55 @@ pub async fn new_session(
     let file_handle: web_sys::FileSystemFileHandle = file_handle.into();
 
     let lock = get_lock(file_handle).await?;
+    let files = open_session_files(root, &filename, lock, true).await;
     *current_filename = Some(filename);
-    Ok(lock)
+    Ok(files)
 }
 
-/// Initializes an OPFS session, reusing existing file if possible
+/// Initializes an OPFS session.
+/// Past sessions are kept for the session library; only empty leftovers are pruned.
 pub async fn init_opfs_session(
     current_filename: &mut Option<String>,
-) -> Result<web_sys::FileSystemSyncAccessHandle, JsValue> {
+) -> Result<SessionFiles, JsValue> {
     let root = get_opfs_root().await?;
-    let files = get_files(&root).await?;
-
-    if let Some((name, handle)) = files.first().cloned() {
-        match get_lock(handle).await {
-            Ok(lock) => {
-                *current_filename = Some(name);
-                // Cleanup others
-                for file in files.iter().skip(1) {
-                    let _ = wasm_bindgen_futures::JsFuture::from(root.remove_entry(&file.0)).await;
-                }
-                Ok(lock)
-            }
-            Err(_) => {
-                // If lock fails, start new
-                let res = new_session(&root, false, current_filename).await;
-                // Cleanup all including the failed one
-                for file in &files {
-                    let _ = wasm_bindgen_futures::JsFuture::from(root.remove_entry(&file.0)).await;
-                }
-                res
-            }
+    for (name, handle) in get_files(&root).await? {
+        if let Ok(0) = get_file_size(&handle).await {
+            let _ = delete_session(&root, &name).await;
         }
-    } else {
-        new_session(&root, false, current_filename).await
+    }
+    new_session(&root, current_filename).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_names_round_trip() {
+        let name = session_file_name(1700000000000, Some("  bench run #2 "));
+        assert_eq!(name, "logs_1700000000000_bench-run--2.txt");
+        assert_eq!(session_timestamp(&name), 1700000000000);
+        assert_eq!(session_label(&name).as_deref(), Some("bench-run--2"));
+
+        // The separator in a label is replaced, so the name splits only once
+        let name = session_file_name(42, Some("uart_a"));
+        assert_eq!(session_timestamp(&name), 42);
+        assert_eq!(session_label(&name).as_deref(), Some("uart-a"));
+
+        // Letters of any script are kept
+        let name = session_file_name(42, Some("温度 로그"));
+        assert_eq!(session_label(&name).as_deref(), Some("温度-로그"));
+    }
+
+    #[test]
+    fn test_session_names_without_label() {
+        assert_eq!(session_file_name(42, None), "logs_42.txt");
+        assert_eq!(session_file_name(42, Some("   ")), "logs_42.txt");
+        assert_eq!(session_timestamp("logs_42.txt"), 42);
+        assert_eq!(session_label("logs_42.txt"), None);
+        assert_eq!(session_label("logs_42_.txt"), None);
+        // Names not written by us sort last
+        assert_eq!(session_timestamp("logs_notes.txt"), 0);
+        assert_eq!(sidecar_file_name("logs_42_bench.txt", "idx"), "logs_42.idx");
     }
 }
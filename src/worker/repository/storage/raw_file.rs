@@ -0,0 +1,337 @@
+use crate::worker::error::LogError;
+use crate::worker::repository::index::{ByteOffset, LineRange};
+use crate::worker::repository::storage::backend::{Compactable, Rebase, StorageBackend};
+use crate::worker::repository::storage::cut_file::CutFile;
+use crate::worker::repository::storage::opfs::OpfsBackend;
+use std::rc::Rc;
+use web_sys::FileSystemSyncAccessHandle;
+
+/// Chunk and line records are both a pair of little-endian 64-bit values
+const RECORD_SIZE: u64 = 16;
+
+fn encode_record(a: u64, b: u64) -> [u8; 16] {
+    let mut rec = [0u8; 16];
+    rec[..8].copy_from_slice(&a.to_le_bytes());
+    rec[8..].copy_from_slice(&b.to_le_bytes());
+    rec
+}
+
+fn decode_record(rec: &[u8]) -> (u64, u64) {
+    let a = u64::from_le_bytes(rec[..8].try_into().unwrap_or_default());
+    let b = u64::from_le_bytes(rec[8..16].try_into().unwrap_or_default());
+    (a, b)
+}
+
+/// A sidecar holding fixed-size records
+struct RecordFile<B: StorageBackend> {
+    backend: Option<Compactable<B>>,
+    len: u64,
+    /// Extension of the sidecar, naming its drops in the [`CutFile`]
+    name: &'static str,
+    /// Offset fields shifted when the raw data is cut
+    rebase: Rebase,
+}
+
+impl<B: StorageBackend> RecordFile<B> {
+    fn new(name: &'static str, fields: &'static [usize]) -> Self {
+        Self {
+            backend: None,
+            len: 0,
+            name,
+            rebase: Rebase {
+                record: RECORD_SIZE as usize,
+                fields,
+            },
+        }
+    }
+
+    fn attach(
+        &mut self,
+        backend: Option<B>,
+        writable: bool,
+        cuts: &Rc<CutFile<B>>,
+    ) -> Result<(), LogError> {
+        self.close();
+        let Some(backend) = backend else {
+            return Ok(());
+        };
+        let backend = Compactable::new(backend, Some(self.rebase)).journaled(cuts, self.name)?;
+        // Drop a torn trailing record
+        let size = backend.get_file_size()?.0;
+        self.len = size - size % RECORD_SIZE;
+        if self.len != size && writable {
+            backend.truncate(self.len)?;
+        }
+        self.backend = Some(backend);
+        Ok(())
+    }
+
+    fn close(&mut self) {
+        if let Some(mut backend) = self.backend.take() {
+            backend.close();
+        }
+        self.len = 0;
+    }
+
+    fn count(&self) -> u64 {
+        self.len / RECORD_SIZE
+    }
+
+    fn append(&mut self, records: &[[u8; 16]]) -> Result<(), LogError> {
+        let Some(backend) = &self.backend else {
+            return Ok(());
+        };
+        if records.is_empty() {
+            return Ok(());
+        }
+        let data = records.concat();
+        backend.write_at(ByteOffset(self.len), &data)?;
+        self.len += data.len() as u64;
+        Ok(())
+    }
+
+    fn get(&self, index: u64) -> Option<(u64, u64)> {
+        if index >= self.count() {
+            return None;
+        }
+        let mut rec = [0u8; 16];
+        self.backend
+            .as_ref()?
+            .read_at(ByteOffset(index * RECORD_SIZE), &mut rec)
+            .ok()?;
+        Some(decode_record(&rec))
+    }
+
+    fn truncate(&mut self, count: u64) -> Result<(), LogError> {
+        if let Some(backend) = &self.backend {
+            self.len = count * RECORD_SIZE;
+            backend.truncate(self.len)?;
+        }
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<(), LogError> {
+        match &self.backend {
+            Some(backend) => backend.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// Drops the first `count` records and shifts the offsets of the rest down
+    /// by `by`. The kept records move down in [`Self::compact`] steps.
+    fn drop_front(&mut self, count: u64, by: u64) -> Result<(), LogError> {
+        let Some(backend) = &self.backend else {
+            return Ok(());
+        };
+        let cut = count.min(self.count()) * RECORD_SIZE;
+        backend.drop_front(cut, by)?;
+        self.len -= cut;
+        Ok(())
+    }
+
+    fn compact(&self, budget: u64) -> Result<bool, LogError> {
+        self.backend.as_ref().map_or(Ok(false), |b| b.step(budget))
+    }
+}
+
+/// Lossless capture of the received bytes, kept next to the rendered text log.
+/// `data` holds every chunk as received, `chunks` records when each chunk arrived
+/// (raw offset, ms timestamp) and `lines` maps each text line to its raw byte range.
+pub struct RawCapture<B: StorageBackend = OpfsBackend> {
+    data: Option<Compactable<B>>,
+    data_len: u64,
+    chunks: RecordFile<B>,
+    lines: RecordFile<B>,
+}
+
+impl<B: StorageBackend> RawCapture<B> {
+    pub fn new() -> Self {
+        Self {
+            data: None,
+            data_len: 0,
+            chunks: RecordFile::new("chunks", &[0]),
+            // Both ends of each line range are raw offsets
+            lines: RecordFile::new("rawmap", &[0, 8]),
+        }
+    }
+
+    pub fn attach(
+        &mut self,
+        data: Option<B>,
+        chunks: Option<B>,
+        lines: Option<B>,
+        writable: bool,
+        cuts: &Rc<CutFile<B>>,
+    ) -> Result<(), LogError> {
+        self.close();
+        if let Some(data) = data {
+            let data = Compactable::new(data, None).journaled(cuts, "bin")?;
+            self.data_len = data.get_file_size()?.0;
+            self.data = Some(data);
+        }
+        self.chunks.attach(chunks, writable, cuts)?;
+        self.lines.attach(lines, writable, cuts)
+    }
+
+    pub fn close(&mut self) {
+        if let Some(mut data) = self.data.take() {
+            data.close();
+        }
+        self.data_len = 0;
+        self.chunks.close();
+        self.lines.close();
+    }
+
+    /// Size of the raw capture in bytes
+    pub fn size(&self) -> u64 {
+        self.data_len
+    }
+
+    /// Bytes stored for the capture, with the chunk and line records
+    pub fn stored_size(&self) -> u64 {
+        self.data_len + self.chunks.len + self.lines.len
+    }
+
+    /// Aligns the line map with the text index: extra records are dropped and
+    /// missing ones are filled with empty ranges
+    pub fn sync_lines(&mut self, line_count: usize) -> Result<(), LogError> {
+        let count = self.lines.count();
+        let line_count = line_count as u64;
+        if count > line_count {
+            self.lines.truncate(line_count)?;
+        } else if count < line_count {
+            let padding = vec![encode_record(0, 0); (line_count - count) as usize];
+            self.lines.append(&padding)?;
+        }
+        Ok(())
+    }
+
+    /// Stores a received chunk and returns the raw offset it was written at
+    pub fn append_chunk(&mut self, chunk: &[u8], timestamp: i64) -> Result<u64, LogError> {
+        let base = self.data_len;
+        let Some(data) = self.data.as_ref().filter(|_| !chunk.is_empty()) else {
+            return Ok(base);
+        };
+        data.write_at(ByteOffset(base), chunk)?;
+        self.data_len += chunk.len() as u64;
+        self.chunks
+            .append(&[encode_record(base, timestamp as u64)])?;
+        Ok(base)
+    }
+
+    /// Records the raw byte range of newly appended lines
+    pub fn append_lines(&mut self, ranges: &[LineRange]) -> Result<(), LogError> {
+        let records: Vec<[u8; 16]> = ranges
+            .iter()
+            .map(|r| encode_record(r.start.0, r.end.0))
+            .collect();
+        self.lines.append(&records)
+    }
+
+    /// Raw byte range a text line was decoded from
+    pub fn line_range(&self, line: usize) -> Option<LineRange> {
+        let (start, end) = self.lines.get(line as u64)?;
+        (end > start).then_some(LineRange {
+            start: ByteOffset(start),
+            end: ByteOffset(end),
+        })
+    }
+
+    /// Reads raw bytes
+    pub fn read(&self, range: LineRange) -> Result<Vec<u8>, LogError> {
+        let data = self
+            .data
+            .as_ref()
+            .ok_or_else(|| LogError::Storage("Raw capture is not available".into()))?;
+        let mut buf = vec![0u8; (range.end - range.start) as usize];
+        data.read_at(range.start, &mut buf)?;
+        Ok(buf)
+    }
+
+    /// Arrival time (ms since epoch) of the chunk containing `offset`
+    pub fn received_at(&self, offset: ByteOffset) -> Option<i64> {
+        let (_, timestamp) = self.chunks.get(self.chunk_containing(offset.0)?)?;
+        Some(timestamp as i64)
+    }
+
+    /// Index of the last chunk starting at or before `offset`
+    fn chunk_containing(&self, offset: u64) -> Option<u64> {
+        let (mut lo, mut hi) = (0, self.chunks.count());
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            if self.chunks.get(mid)?.0 <= offset {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        lo.checked_sub(1)
+    }
+
+    /// Drops the raw data behind the first `dropped` text lines.
+    /// Returns the number of raw bytes removed.
+    pub fn drop_lines(&mut self, dropped: usize) -> Result<u64, LogError> {
+        let cut = match self.lines.get(dropped as u64) {
+            Some((start, end)) if end > start => start,
+            // The next line is not mapped; cut after the last dropped line
+            _ => self
+                .lines
+                .get((dropped as u64).saturating_sub(1))
+                .map(|(_, end)| end)
+                .unwrap_or(0),
+        }
+        .min(self.data_len);
+
+        self.lines.drop_front(dropped as u64, cut)?;
+
+        // Keep the chunk covering `cut` so its timestamp still applies
+        let first_chunk = self.chunk_containing(cut).unwrap_or(0);
+        self.chunks.drop_front(first_chunk, cut)?;
+
+        if let Some(data) = self.data.as_ref().filter(|_| cut > 0) {
+            data.drop_front(cut, 0)?;
+            self.data_len -= cut;
+        }
+        Ok(cut)
+    }
+
+    /// Moves pending front drops along by up to `budget` bytes per file.
+    /// Returns whether any is still pending.
+    pub fn compact(&self, budget: u64) -> Result<bool, LogError> {
+        let data = self.data.as_ref().map_or(Ok(false), |d| d.step(budget))?;
+        let chunks = self.chunks.compact(budget)?;
+        Ok(self.lines.compact(budget)? || chunks || data)
+    }
+
+    /// Makes the appended bytes and records durable
+    pub fn flush(&self) -> Result<(), LogError> {
+        if let Some(data) = &self.data {
+            data.flush()?;
+        }
+        self.chunks.flush()?;
+        self.lines.flush()
+    }
+
+    /// Drops the whole capture
+    pub fn reset(&mut self) -> Result<(), LogError> {
+        if let Some(data) = &self.data {
+            data.truncate(0)?;
+        }
+        self.data_len = 0;
+        self.chunks.truncate(0)?;
+        self.lines.truncate(0)
+    }
+}
+
+impl RawCapture<OpfsBackend> {
+    /// Handle of the raw data file, for export
+    pub fn data_handle(&self) -> Option<&FileSystemSyncAccessHandle> {
+        self.data.as_ref()?.inner().handle.as_ref()
+    }
+}
+
+impl<B: StorageBackend> Default for RawCapture<B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
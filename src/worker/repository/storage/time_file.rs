@@ -0,0 +1,169 @@
+use crate::worker::error::LogError;
+use crate::worker::repository::index::ByteOffset;
+use crate::worker::repository::storage::backend::{Compactable, StorageBackend};
+use crate::worker::repository::storage::cut_file::CutFile;
+use crate::worker::repository::storage::opfs::OpfsBackend;
+use std::rc::Rc;
+
+const RECORD_SIZE: u64 = 8;
+
+/// Sidecar holding the receive time of every line as little-endian i64 microseconds
+/// since the epoch, one record per line. 0 marks a line with no known receive time
+/// (imported, or written before the sidecar existed).
+/// Times are kept out of the log text so they can be shown or hidden at any time.
+pub struct TimeFile<B: StorageBackend = OpfsBackend> {
+    backend: Option<Compactable<B>>,
+    len: u64,
+}
+
+impl<B: StorageBackend> TimeFile<B> {
+    pub fn new() -> Self {
+        Self {
+            backend: None,
+            len: 0,
+        }
+    }
+
+    /// Attaches the sidecar, dropping a torn trailing record from it when `writable`
+    pub fn attach(
+        &mut self,
+        backend: Option<B>,
+        writable: bool,
+        cuts: &Rc<CutFile<B>>,
+    ) -> Result<(), LogError> {
+        self.close();
+        let Some(backend) = backend else {
+            return Ok(());
+        };
+        let backend = Compactable::new(backend, None).journaled(cuts, "times")?;
+        let size = backend.get_file_size()?.0;
+        self.len = size - size % RECORD_SIZE;
+        if self.len != size && writable {
+            backend.truncate(self.len)?;
+        }
+        self.backend = Some(backend);
+        Ok(())
+    }
+
+    pub fn close(&mut self) {
+        if let Some(mut backend) = self.backend.take() {
+            backend.close();
+        }
+        self.len = 0;
+    }
+
+    fn count(&self) -> usize {
+        (self.len / RECORD_SIZE) as usize
+    }
+
+    /// Records the same receive time for `lines` new lines
+    pub fn append(&mut self, time: Option<i64>, lines: usize) -> Result<(), LogError> {
+        let Some(backend) = &self.backend else {
+            return Ok(());
+        };
+        if lines == 0 {
+            return Ok(());
+        }
+        let record = time.unwrap_or(0).to_le_bytes();
+        let data = record.repeat(lines);
+        backend.write_at(ByteOffset(self.len), &data)?;
+        self.len += data.len() as u64;
+        Ok(())
+    }
+
+    /// Receive time of a line in microseconds since the epoch
+    pub fn get(&self, line: usize) -> Option<i64> {
+        self.read(line, 1).pop().flatten()
+    }
+
+    /// Receive times of `count` lines starting at `first`; shorter at the end of the file
+    pub fn read(&self, first: usize, count: usize) -> Vec<Option<i64>> {
+        let Some(backend) = &self.backend else {
+            return Vec::new();
+        };
+        let count = count.min(self.count().saturating_sub(first));
+        let mut buf = vec![0u8; count * RECORD_SIZE as usize];
+        if count == 0
+            || backend
+                .read_at(ByteOffset(first as u64 * RECORD_SIZE), &mut buf)
+                .is_err()
+        {
+            return Vec::new();
+        }
+        buf.chunks_exact(RECORD_SIZE as usize)
+            .map(|b| {
+                let time = i64::from_le_bytes(b.try_into().unwrap_or_default());
+                (time != 0).then_some(time)
+            })
+            .collect()
+    }
+
+    /// Aligns the sidecar with the text index: extra records are dropped and
+    /// missing lines get an unknown time
+    pub fn sync_lines(&mut self, line_count: usize) -> Result<(), LogError> {
+        let count = self.count();
+        if count > line_count {
+            if let Some(backend) = &self.backend {
+                self.len = line_count as u64 * RECORD_SIZE;
+                backend.truncate(self.len)?;
+            }
+        } else if count < line_count {
+            self.append(None, line_count - count)?;
+        }
+        Ok(())
+    }
+
+    /// Drops the times of the first `lines` lines; the rest moves down in [`Self::compact`] steps
+    pub fn drop_front(&mut self, lines: usize) -> Result<(), LogError> {
+        let Some(backend) = &self.backend else {
+            return Ok(());
+        };
+        let cut = (lines.min(self.count()) as u64) * RECORD_SIZE;
+        backend.drop_front(cut, 0)?;
+        self.len -= cut;
+        Ok(())
+    }
+
+    /// Moves a pending front drop along by up to `budget` bytes.
+    /// Returns whether one is still pending.
+    pub fn compact(&self, budget: u64) -> Result<bool, LogError> {
+        self.backend.as_ref().map_or(Ok(false), |b| b.step(budget))
+    }
+
+    /// Bytes stored
+    pub fn size(&self) -> u64 {
+        self.len
+    }
+
+    pub fn flush(&self) -> Result<(), LogError> {
+        match &self.backend {
+            Some(backend) => backend.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// Drops all recorded times
+    pub fn reset(&mut self) -> Result<(), LogError> {
+        self.len = 0;
+        if let Some(backend) = &self.backend {
+            backend.truncate(0)?;
+        }
+        Ok(())
+    }
+}
+
+impl<B: StorageBackend + Clone> TimeFile<B> {
+    /// Reader sharing the sidecar, for streaming exports
+    pub fn reader(&self) -> Option<TimeFile<B>> {
+        Some(TimeFile {
+            backend: Some(self.backend.clone()?),
+            len: self.len,
+        })
+    }
+}
+
+impl<B: StorageBackend> Default for TimeFile<B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,109 +1,671 @@
+use crate::types::{FindMatch, LogLevel, SearchProgress, WorkerMsg};
 use crate::worker::error::LogError;
-use crate::worker::repository::index::{ActiveFilterBuilder, LineRange};
+use crate::worker::processor::LogProcessor;
+use crate::worker::repository::index::trigram::BLOCK_LINES;
+use crate::worker::repository::index::{ActiveFilterBuilder, LineIndex};
 use crate::worker::repository::storage::StorageBackend;
+use crate::worker::repository::LogRepository;
+use crate::worker::sort::LogSorter;
 use crate::worker::state::WorkerState;
 use gloo_timers::future::TimeoutFuture;
 use std::cell::RefCell;
+use std::ops::Range;
 use std::rc::Rc;
 
 pub struct LogSearcher;
 
 const SEARCH_BATCH_SIZE: usize = 5000;
+/// Most lines one search batch covers, counting those the search index skips
+const SEARCH_SKIP_LIMIT: usize = SEARCH_BATCH_SIZE * 64;
+/// Lines read one by one per find step, between yields to other messages
+const FIND_BATCH_SIZE: usize = 2000;
+
+/// Outcome of scanning one batch of lines for a find match
+#[derive(Debug, PartialEq)]
+pub enum FindStep {
+    Found(FindMatch),
+    /// View index the next batch starts at
+    Continue(usize),
+    Done,
+}
 
 impl LogSearcher {
+    /// Runs a search and reports its progress; a failure is reported as a
+    /// `SearchError` under the id of this search, even if a newer one started
     pub async fn search_async(
         state_rc: Rc<RefCell<WorkerState>>,
         query: String,
         match_case: bool,
         use_regex: bool,
         invert: bool,
+        context: (usize, usize),
+        min_level: Option<LogLevel>,
+    ) {
+        let id = {
+            let mut state = state_rc.borrow_mut();
+            state.current_search_id += 1;
+            state.current_search_id
+        };
+        let result: Result<(), LogError> = async {
+            let mut scan = {
+                let mut state = state_rc.borrow_mut();
+                let Some(from) = Self::begin(
+                    &mut state.proc.repository,
+                    query,
+                    match_case,
+                    use_regex,
+                    invert,
+                    context,
+                    min_level,
+                )?
+                else {
+                    // Without a query there is nothing to scan; a level filter
+                    // alone is taken from the level index at once
+                    let index = &state.proc.repository.index;
+                    let matches = index.match_count();
+                    state.send_msg(WorkerMsg::SearchProgress(SearchProgress {
+                        id,
+                        scanned: index.line_count,
+                        total: index.line_count,
+                        matches,
+                    }));
+                    state.send_msg(WorkerMsg::SearchDone {
+                        id,
+                        matches,
+                        completed: true,
+                    });
+                    drop(state);
+                    LogSorter::restart(&state_rc);
+                    return Ok(());
+                };
+                SearchScan::new(&state.proc.repository, id, from)
+            };
+            state_rc.borrow().send_msg(scan.progress());
+
+            let mut buf = vec![0u8; 512 * 1024];
+            loop {
+                let msg = {
+                    let mut state = state_rc.borrow_mut();
+                    let state = &mut *state;
+                    scan.advance(
+                        &mut state.proc.repository,
+                        state.current_search_id,
+                        &mut buf,
+                    )?
+                };
+                state_rc.borrow().send_msg(msg.clone());
+                match msg {
+                    WorkerMsg::SearchDone {
+                        completed: true, ..
+                    } => break,
+                    // A newer search took over the view
+                    WorkerMsg::SearchDone { .. } => return Ok(()),
+                    _ => {}
+                }
+                TimeoutFuture::new(16).await;
+            }
+            LogSorter::restart(&state_rc);
+            Ok(())
+        }
+        .await;
+        if let Err(e) = result {
+            state_rc.borrow().send_msg(WorkerMsg::SearchError {
+                id,
+                message: e.to_string(),
+            });
+        }
+    }
+
+    /// Walks the current view from line `from` until the next (or previous) find
+    /// match, and reports it. A newer find request cancels the walk.
+    pub async fn find_async(
+        state_rc: Rc<RefCell<WorkerState>>,
+        mut from: usize,
+        mut occurrence: Option<usize>,
+        forward: bool,
     ) -> Result<(), LogError> {
-        let (total_lines, search_id) = {
+        let find_id = {
             let mut state = state_rc.borrow_mut();
-            if query.trim().is_empty() {
-                state.proc.repository.index.clear_filter();
+            state.current_find_id += 1;
+            state.current_find_id
+        };
+
+        loop {
+            if state_rc.borrow().current_find_id != find_id {
                 return Ok(());
             }
 
-            state.proc.repository.index.active_filter = Some(
-                ActiveFilterBuilder::new(query)
-                    .case_sensitive(match_case)
-                    .regex(use_regex)
-                    .invert(invert)
-                    .build()
-                    .map_err(LogError::Regex)?,
-            );
-            state.proc.repository.index.is_filtering = true;
-            state.proc.repository.index.filtered_lines.clear();
+            {
+                let state = state_rc.borrow();
+                match Self::find_batch(&state.proc, from, occurrence, forward)? {
+                    FindStep::Found(hit) => {
+                        state.send_msg(WorkerMsg::FindMatch(hit));
+                        return Ok(());
+                    }
+                    FindStep::Continue(next) => {
+                        from = next;
+                        occurrence = None;
+                    }
+                    FindStep::Done => {
+                        state.send_msg(WorkerMsg::Notice("No more matches in this view".into()));
+                        return Ok(());
+                    }
+                }
+            }
 
-            state.current_search_id += 1;
-            (
-                state.proc.repository.index.line_count,
-                state.current_search_id,
-            )
+            TimeoutFuture::new(0).await;
+        }
+    }
+
+    /// Scans a batch of view lines from `from` for a find match. On the first
+    /// line, `occurrence` skips that match and the ones before (after, backwards).
+    pub fn find_batch<B: StorageBackend + Default>(
+        proc: &LogProcessor<B>,
+        from: usize,
+        occurrence: Option<usize>,
+        forward: bool,
+    ) -> Result<FindStep, LogError> {
+        let repo = &proc.repository;
+        let total = repo.get_line_count();
+        if proc.find.is_none() || total == 0 || (forward && from >= total) {
+            return Ok(FindStep::Done);
+        }
+        // Backwards from past the end starts at the last line
+        let (from, occurrence) = match from < total {
+            true => (from, occurrence),
+            false => (total - 1, None),
+        };
+        let lines: Vec<usize> = if forward {
+            (from..total.min(from + FIND_BATCH_SIZE)).collect()
+        } else {
+            (from.saturating_sub(FIND_BATCH_SIZE - 1)..=from)
+                .rev()
+                .collect()
         };
 
-        let mut idx = total_lines;
-        let mut buf = vec![0u8; 512 * 1024];
+        for &i in &lines {
+            let Some(range) = repo.get_line_range(LineIndex(i)) else {
+                continue;
+            };
+            let buf = repo.read_line(range)?;
+            let text = String::from_utf8_lossy(&buf);
+            let Some(spans) = proc.find_columns(LineIndex(i), text.trim_end_matches('\n')) else {
+                continue;
+            };
+            // A line matched by an inverted expression has no spans; it counts once
+            let count = spans.len().max(1);
+            let pick = match (i == from, occurrence, forward) {
+                (true, Some(k), true) => Some(k + 1).filter(|&n| n < count),
+                (true, Some(k), false) => k.checked_sub(1).map(|n| n.min(count - 1)),
+                (_, _, true) => Some(0),
+                (_, _, false) => Some(count - 1),
+            };
+            if let Some(n) = pick {
+                let (start, end) = spans.get(n).copied().unwrap_or((0, 0));
+                return Ok(FindStep::Found(FindMatch {
+                    line: i,
+                    occurrence: n,
+                    start,
+                    end,
+                }));
+            }
+        }
 
-        while idx > 0 {
-            if state_rc.borrow().current_search_id != search_id {
-                return Ok(());
+        let next = match forward {
+            true => lines.last().map(|&l| l + 1).filter(|&l| l < total),
+            false => lines.last().and_then(|&l| l.checked_sub(1)),
+        };
+        Ok(next.map_or(FindStep::Done, FindStep::Continue))
+    }
+
+    /// Installs the filter of a new search, or clears it for an empty query.
+    /// `context` is the number of lines shown before and after each match;
+    /// with `min_level`, only lines that severe or more match.
+    /// Returns the absolute line number the backward scan starts from, or `None`
+    /// when no scan is needed: without a query the level index gives the matches.
+    pub fn begin<B: StorageBackend>(
+        repo: &mut LogRepository<B>,
+        query: String,
+        match_case: bool,
+        use_regex: bool,
+        invert: bool,
+        context: (usize, usize),
+        min_level: Option<LogLevel>,
+    ) -> Result<Option<usize>, LogError> {
+        if query.trim().is_empty() {
+            repo.index.clear_filter();
+            if let Some(level) = min_level {
+                let index = &mut repo.index;
+                index.min_level = Some(level);
+                index.context = context;
+                index.is_filtering = true;
+                let matches = index.levels.at_least(level);
+                if index.has_context() {
+                    let matches: Vec<usize> = matches.iter().collect();
+                    index.add_matches(0..index.line_count, &matches);
+                } else {
+                    index.filtered_lines = matches;
+                }
             }
+            return Ok(None);
+        }
 
-            let batch_start = idx.saturating_sub(SEARCH_BATCH_SIZE);
-            let batch_end = idx;
+        let filter = ActiveFilterBuilder::new(query)
+            .case_sensitive(match_case)
+            .regex(use_regex)
+            .invert(invert)
+            .build()
+            .map_err(LogError::Regex)?;
+        repo.index.clear_filter();
+        repo.index.active_filter = Some(filter);
+        repo.index.min_level = min_level;
+        repo.index.context = context;
+        repo.index.is_filtering = true;
 
-            {
-                let mut state = state_rc.borrow_mut();
-                let repo = &mut state.proc.repository;
+        // Absolute line number; lines dropped by the ring buffer shift local indices
+        Ok(Some(repo.index.first_line + repo.index.line_count))
+    }
 
-                // Ensure index consistency (if cleared during search)
-                if batch_end > repo.index.line_count {
-                    break;
-                }
+    /// Matches the batch of lines ending before absolute line `idx` and prepends the hits.
+    /// Blocks the search index rules out are skipped unread, so a batch reads up
+    /// to [`SEARCH_BATCH_SIZE`] lines but may cover more.
+    /// Returns where the next batch ends, or `None` once the start of the log is reached.
+    pub fn search_batch<B: StorageBackend>(
+        repo: &mut LogRepository<B>,
+        idx: usize,
+        buf: &mut Vec<u8>,
+    ) -> Result<Option<usize>, LogError> {
+        let first_line = repo.index.first_line;
+        let batch_end = idx.saturating_sub(first_line);
+        // Ensure index consistency (if cleared during search)
+        if batch_end == 0 || batch_end > repo.index.line_count {
+            return Ok(None);
+        }
+        let Some(filter) = repo.index.active_filter.clone() else {
+            return Ok(None);
+        };
 
-                let (s_off, e_off) = {
-                    let off = &repo.index.line_offsets;
-                    (off[batch_start], off[batch_end])
-                };
-                let size = (e_off.0 - s_off.0) as usize;
+        // Walk back block by block, keeping the ranges that may hold a match
+        let mut ranges: Vec<Range<usize>> = Vec::new();
+        let (mut batch_start, mut read) = (batch_end, 0);
+        while batch_start > 0
+            && read < SEARCH_BATCH_SIZE
+            && batch_end - batch_start < SEARCH_SKIP_LIMIT
+        {
+            let line = first_line + batch_start - 1;
+            let block_start = (line - line % BLOCK_LINES).max(first_line) - first_line;
+            let start = block_start.max(batch_start.saturating_sub(SEARCH_BATCH_SIZE - read));
+            if repo.index.search_index.may_match(filter.prefilter(), line) {
+                match ranges.last_mut() {
+                    Some(last) if last.start == batch_start => last.start = start,
+                    _ => ranges.push(start..batch_start),
+                }
+                read += batch_start - start;
+            }
+            batch_start = start;
+        }
 
-                if buf.len() < size {
-                    buf.resize(size, 0);
+        let mut batch_matches = Vec::new();
+        for range in ranges.into_iter().rev() {
+            let (s_off, e_off) = {
+                let off = &repo.index.line_offsets;
+                (off.get(range.start), off.get(range.end))
+            };
+            let size = (e_off.0 - s_off.0) as usize;
+            if buf.len() < size {
+                buf.resize(size, 0);
+            }
+            repo.storage.backend.read_at(s_off, &mut buf[..size])?;
+            let text = String::from_utf8_lossy(&buf[..size]);
+            for (j, line) in text.trim_end_matches('\n').split('\n').enumerate() {
+                let i = range.start + j;
+                if i < range.end && repo.index.level_allows(i) && filter.matches(line) {
+                    batch_matches.push(i);
                 }
+            }
+        }
+        if repo.index.has_context() {
+            repo.index
+                .add_matches(batch_start..batch_end, &batch_matches);
+        } else {
+            repo.index.prepend_filtered(batch_matches);
+        }
 
-                repo.storage.backend.read_at(s_off, &mut buf[..size])?;
-
-                let text = repo
-                    .storage
-                    .decoder
-                    .decode_with_u8_array(&buf[..size])
-                    .map_err(LogError::Js)?;
-
-                let filter = repo.index.active_filter.as_ref().unwrap().clone();
-                let mut batch_matches = Vec::new();
-
-                for (j, line) in text.trim_end_matches('\n').split('\n').enumerate() {
-                    if filter.matches(line) {
-                        let off_ptr = &repo.index.line_offsets;
-                        let abs_line_idx = batch_start + j;
-
-                        if abs_line_idx + 1 < off_ptr.len() {
-                            let range = LineRange {
-                                start: off_ptr[abs_line_idx],
-                                end: off_ptr[abs_line_idx + 1],
-                            };
-                            batch_matches.push(range);
-                        }
-                    }
+        Ok(Some(first_line + batch_start))
+    }
+}
+
+/// Backward scan of a search installed by [`LogSearcher::begin`], one batch per step
+pub struct SearchScan {
+    id: u32,
+    /// Absolute line the next batch ends before
+    next: usize,
+    start: usize,
+    /// Lines stored when the search started
+    total: usize,
+    matches: usize,
+}
+
+impl SearchScan {
+    pub fn new<B: StorageBackend>(repo: &LogRepository<B>, id: u32, from: usize) -> Self {
+        Self {
+            id,
+            next: from,
+            start: from,
+            total: repo.index.line_count,
+            matches: repo.index.match_count(),
+        }
+    }
+
+    pub fn progress(&self) -> WorkerMsg {
+        WorkerMsg::SearchProgress(SearchProgress {
+            id: self.id,
+            scanned: (self.start - self.next).min(self.total),
+            total: self.total,
+            matches: self.matches,
+        })
+    }
+
+    /// Scans the next batch, unless search `current_id` has replaced this one.
+    /// Returns the report to send; the scan ends with `SearchDone`, which is
+    /// not `completed` when the scan was cancelled.
+    pub fn advance<B: StorageBackend>(
+        &mut self,
+        repo: &mut LogRepository<B>,
+        current_id: u32,
+        buf: &mut Vec<u8>,
+    ) -> Result<WorkerMsg, LogError> {
+        let done = |scan: &Self, completed| WorkerMsg::SearchDone {
+            id: scan.id,
+            matches: scan.matches,
+            completed,
+        };
+        if current_id != self.id {
+            // The filter now belongs to the newer search
+            return Ok(done(self, false));
+        }
+        let next = LogSearcher::search_batch(repo, self.next, buf)?;
+        self.matches = repo.index.match_count();
+        match next {
+            Some(next) => {
+                self.next = next;
+                Ok(self.progress())
+            }
+            None => Ok(done(self, true)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::worker::processor::LogProcessor;
+    use crate::worker::repository::index::LineIndex;
+    use crate::worker::repository::storage::{MemoryBackend, SessionFiles};
+
+    fn search(proc: &mut LogProcessor<MemoryBackend>, query: &str) {
+        search_with_context(proc, query, (0, 0));
+    }
+
+    fn search_with_context(
+        proc: &mut LogProcessor<MemoryBackend>,
+        query: &str,
+        context: (usize, usize),
+    ) {
+        let repo = &mut proc.repository;
+        let mut idx =
+            LogSearcher::begin(repo, query.into(), false, false, false, context, None).unwrap();
+        let mut buf = Vec::new();
+        while let Some(next) = idx {
+            idx = LogSearcher::search_batch(repo, next, &mut buf).unwrap();
+        }
+    }
+
+    fn find_all(proc: &LogProcessor<MemoryBackend>, forward: bool) -> Vec<(usize, usize)> {
+        let (mut from, mut occurrence) = (if forward { 0 } else { usize::MAX }, None);
+        let mut hits = Vec::new();
+        loop {
+            match LogSearcher::find_batch(proc, from, occurrence, forward).unwrap() {
+                FindStep::Found(hit) => {
+                    hits.push((hit.line, hit.start));
+                    (from, occurrence) = (hit.line, Some(hit.occurrence));
                 }
-                state.proc.repository.index.prepend_filtered(batch_matches);
+                FindStep::Continue(next) => (from, occurrence) = (next, None),
+                FindStep::Done => return hits,
+            }
+        }
+    }
+
+    #[test]
+    fn test_find_steps_through_occurrences() {
+        let mut proc = LogProcessor::new();
+        proc.set_sync_handle(SessionFiles::in_memory()).unwrap();
+        let mut text = String::new();
+        for i in 0..4500 {
+            match i {
+                10 => text.push_str("\x1b[31mERR\x1b[0m a err b\n"),
+                4400 => text.push_str("late err\n"),
+                _ => text.push_str(&format!("inf {}\n", i)),
+            }
+        }
+        proc.append_chunk(text.as_bytes(), false).unwrap();
+        proc.find = Some(
+            ActiveFilterBuilder::new("err".into())
+                .case_sensitive(false)
+                .build()
+                .unwrap(),
+        );
+
+        assert_eq!(find_all(&proc, true), [(10, 0), (10, 6), (4400, 5)]);
+        assert_eq!(find_all(&proc, false), [(4400, 5), (10, 6), (10, 0)]);
+
+        // Filtering keeps find positions in view indices
+        search(&mut proc, "err");
+        assert_eq!(find_all(&proc, true), [(0, 0), (0, 6), (1, 5)]);
+    }
+
+    #[test]
+    fn test_search_spans_batches() {
+        let mut proc = LogProcessor::new();
+        proc.set_sync_handle(SessionFiles::in_memory()).unwrap();
+        let mut text = String::new();
+        for i in 0..12_000 {
+            let level = if i % 1000 == 0 { "ERR" } else { "inf" };
+            text.push_str(&format!("{} line {}\n", level, i));
+        }
+        proc.append_chunk(text.as_bytes(), false).unwrap();
+
+        search(&mut proc, "err");
+        assert_eq!(proc.get_line_count(), 12);
+        let lines: Vec<usize> = (0..12)
+            .map(|i| proc.repository.session_line(LineIndex(i)).unwrap())
+            .collect();
+        assert_eq!(lines, (0..12).map(|i| i * 1000).collect::<Vec<_>>());
+
+        // Live lines are matched as they arrive
+        proc.append_chunk(b"ERR late\ninf late\n", false).unwrap();
+        assert_eq!(proc.get_line_count(), 13);
+
+        search(&mut proc, "  ");
+        assert!(!proc.repository.is_filtering());
+        assert_eq!(proc.get_line_count(), 12_002);
+
+        // Numbers shown in the gutter survive the ring buffer dropping old lines
+        let dropped = proc.drop_oldest(proc.repository.log_size() / 2).unwrap();
+        assert!(dropped > 1000);
+        let last = LineIndex(proc.get_line_count() as usize - 1);
+        assert_eq!(proc.repository.line_number(last), Some(12_001));
+        assert_eq!(proc.repository.session_line(last), Some(12_001 - dropped));
+    }
+
+    #[test]
+    fn test_search_index_skips_blocks() {
+        let mut proc = LogProcessor::new();
+        proc.set_sync_handle(SessionFiles::in_memory()).unwrap();
+        let mut text = String::new();
+        for i in 0..40_000 {
+            let word = if [7, 20_500, 39_999].contains(&i) {
+                "Panic"
+            } else {
+                "ok"
+            };
+            text.push_str(&format!("{} {}\n", word, i));
+        }
+        proc.repository.index.search_index.configure(8, 0);
+        proc.append_chunk(text.as_bytes(), false).unwrap();
+        let shown = |proc: &LogProcessor<MemoryBackend>| -> Vec<usize> {
+            (0..proc.get_line_count() as usize)
+                .map(|i| proc.repository.session_line(LineIndex(i)).unwrap())
+                .collect()
+        };
+
+        // Only the blocks holding a match are read, so one batch covers the log
+        let repo = &mut proc.repository;
+        let idx = LogSearcher::begin(repo, "panic".into(), false, false, false, (0, 0), None);
+        let mut buf = Vec::new();
+        let next = LogSearcher::search_batch(repo, idx.unwrap().unwrap(), &mut buf).unwrap();
+        assert_eq!(next, Some(0));
+        assert!(buf.len() < 3 * BLOCK_LINES * 12);
+        assert_eq!(shown(&proc), [7, 20_500, 39_999]);
+
+        // Without the index every line is read
+        proc.repository.index.search_index.configure(0, 40_000);
+        search(&mut proc, "panic");
+        assert_eq!(shown(&proc), [7, 20_500, 39_999]);
+        assert_eq!(proc.repository.index.search_index.memory_bytes(), 0);
+    }
+
+    #[test]
+    fn test_context_spans_batches_and_live_lines() {
+        let mut proc = LogProcessor::new();
+        proc.set_sync_handle(SessionFiles::in_memory()).unwrap();
+        let mut text = String::new();
+        for i in 0..6000 {
+            let word = if [999, 1000, 4000].contains(&i) {
+                "panic"
+            } else {
+                "ok"
+            };
+            text.push_str(&format!("{} {}\n", word, i));
+        }
+        proc.append_chunk(text.as_bytes(), false).unwrap();
+
+        // Line 999 ends the first backward batch; its context straddles both
+        search_with_context(&mut proc, "panic", (2, 1));
+        let shown = |proc: &LogProcessor<MemoryBackend>| -> Vec<usize> {
+            (0..proc.get_line_count() as usize)
+                .map(|i| proc.repository.session_line(LineIndex(i)).unwrap())
+                .collect()
+        };
+        assert_eq!(
+            shown(&proc),
+            [997, 998, 999, 1000, 1001, 3998, 3999, 4000, 4001]
+        );
+        let (context, gaps) = proc.repository.window_context(0, 9);
+        assert_eq!(context, [0, 1, 4, 5, 6, 8]);
+        assert_eq!(gaps, [5]);
+        assert_eq!(proc.repository.index.match_count(), 3);
+
+        // Trailing context of a live match arrives with the next chunk
+        proc.append_chunk(b"ok a\npanic b\n", false).unwrap();
+        proc.append_chunk(b"ok c\nok d\n", false).unwrap();
+        assert_eq!(shown(&proc)[9..], [5999, 6000, 6001, 6002]);
+    }
+
+    #[test]
+    fn test_level_filter_combines_with_query() {
+        let mut proc = LogProcessor::new();
+        proc.set_sync_handle(SessionFiles::in_memory()).unwrap();
+        let mut text = String::new();
+        for i in 0..5000 {
+            match i % 1000 {
+                0 => text.push_str(&format!("E (1{}) wifi: lost {}\n", i, i)),
+                500 => text.push_str(&format!("W (1{}) wifi: weak {}\n", i, i)),
+                _ => text.push_str(&format!("I (1{}) app: tick {}\n", i, i)),
             }
+        }
+        proc.append_chunk(text.as_bytes(), false).unwrap();
+        let counts = proc.repository.level_counts();
+        assert_eq!(counts.get(LogLevel::Error), 5);
+        assert_eq!(counts.at_least(LogLevel::Warn), 10);
+        let shown = |proc: &LogProcessor<MemoryBackend>| -> Vec<usize> {
+            (0..proc.get_line_count() as usize)
+                .map(|i| proc.repository.session_line(LineIndex(i)).unwrap())
+                .collect()
+        };
+
+        // A level alone is served from the level index without a scan
+        let repo = &mut proc.repository;
+        let next = LogSearcher::begin(
+            repo,
+            String::new(),
+            false,
+            false,
+            false,
+            (0, 0),
+            Some(LogLevel::Warn),
+        );
+        assert!(next.unwrap().is_none());
+        assert_eq!(shown(&proc).len(), 10);
+        assert_eq!(shown(&proc)[..3], [0, 500, 1000]);
 
-            idx = batch_start;
-            TimeoutFuture::new(16).await;
+        // With a query, both must match
+        let repo = &mut proc.repository;
+        let mut idx = LogSearcher::begin(
+            repo,
+            "wifi".into(),
+            false,
+            false,
+            false,
+            (0, 0),
+            Some(LogLevel::Error),
+        )
+        .unwrap();
+        let mut buf = Vec::new();
+        while let Some(next) = idx {
+            idx = LogSearcher::search_batch(repo, next, &mut buf).unwrap();
         }
-        Ok(())
+        assert_eq!(shown(&proc), [0, 1000, 2000, 3000, 4000]);
+
+        // Live lines follow the same filter
+        proc.append_chunk(b"W (9) wifi: weak\nE (9) wifi: lost\n", false)
+            .unwrap();
+        assert_eq!(shown(&proc)[5..], [5001]);
+
+        search(&mut proc, "");
+        assert!(!proc.repository.is_filtering());
+    }
+
+    #[test]
+    fn test_scan_reports_cancel() {
+        let mut proc = LogProcessor::new();
+        proc.set_sync_handle(SessionFiles::in_memory()).unwrap();
+        let mut text = String::new();
+        for i in 0..12_000 {
+            let word = if i % 1000 == 0 { "panic" } else { "ok" };
+            text.push_str(&format!("{} {}\n", word, i));
+        }
+        proc.append_chunk(text.as_bytes(), false).unwrap();
+        proc.repository.index.search_index.configure(0, 12_000);
+
+        let repo = &mut proc.repository;
+        let from = LogSearcher::begin(repo, "panic".into(), false, false, false, (0, 0), None);
+        let mut scan = SearchScan::new(repo, 1, from.unwrap().unwrap());
+        let mut buf = Vec::new();
+        let msg = scan.advance(repo, 1, &mut buf).unwrap();
+        let WorkerMsg::SearchProgress(progress) = msg else {
+            panic!("expected progress, got {:?}", msg);
+        };
+        assert_eq!((progress.scanned, progress.matches), (SEARCH_BATCH_SIZE, 5));
+
+        // A newer search stops this one where it is
+        assert_eq!(
+            scan.advance(repo, 2, &mut buf).unwrap(),
+            WorkerMsg::SearchDone {
+                id: 1,
+                matches: 5,
+                completed: false,
+            }
+        );
     }
 }
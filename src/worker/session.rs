@@ -0,0 +1,479 @@
+use crate::types::{SessionInfo, SessionMetadata, StorageUsage};
+use crate::worker::error::LogError;
+use crate::worker::export::LogExporter;
+use crate::worker::processor::LogProcessor;
+use crate::worker::repository::storage::{
+    delete_session, estimate_storage, get_file_handle, get_file_size, get_files, new_session,
+    open_session, read_session_bookmarks, read_session_metadata, rename_session, session_label,
+    session_timestamp, SessionFiles, StorageBackend,
+};
+use crate::worker::repository::Recovery;
+use crate::worker::sort::LogSorter;
+use crate::worker::state::WorkerState;
+use crate::worker::types::WorkerMsg;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Lists, reopens, renames, deletes and downloads capture sessions kept in OPFS
+pub struct SessionLibrary;
+
+impl SessionLibrary {
+    /// Closes the live session of a previous run that ended without finishing
+    /// it (a closed tab or a crash): repairs its files and stamps its end.
+    /// Returns a notice of what was recovered.
+    pub async fn recover_interrupted(
+        root: &web_sys::FileSystemDirectoryHandle,
+    ) -> Result<Option<String>, LogError> {
+        // That session is the newest one; an empty one is pruned anyway
+        let Some((name, handle)) = get_files(root).await?.into_iter().next() else {
+            return Ok(None);
+        };
+        let unfinished = read_session_metadata(root, &name)
+            .await
+            .is_some_and(|m| m.ended_at.is_none());
+        if !unfinished || get_file_size(&handle).await.unwrap_or(0) == 0 {
+            return Ok(None);
+        }
+
+        // Still locked when another tab is capturing into it
+        let Ok(files) = open_session(root, &name, true).await else {
+            return Ok(None);
+        };
+        let mut proc: LogProcessor = LogProcessor::new();
+        let recovery = proc.set_sync_handle(files)?;
+        let repo = &mut proc.repository;
+        if let Some(mut metadata) = repo.current_metadata() {
+            // The capture stopped some time after its last line arrived
+            let last = repo.index.line_count.checked_sub(1);
+            metadata.ended_at = Some(
+                last.and_then(|line| repo.times.get(line))
+                    .map_or_else(|| chrono::Utc::now().timestamp_millis(), |us| us / 1000),
+            );
+            repo.set_metadata(metadata)?;
+        }
+        repo.release_storage();
+
+        let repaired = recovery
+            .notice()
+            .unwrap_or_else(|| "no damage found".into());
+        Ok(Some(format!(
+            "Closed {} left open by the previous run: {}",
+            name, repaired
+        )))
+    }
+
+    /// Sends the list of stored sessions (newest first) to the main thread
+    pub async fn list(state_rc: Rc<RefCell<WorkerState>>) -> Result<(), LogError> {
+        let (root, open, live, attached) = {
+            let s = state_rc.borrow();
+            // The shown session and the live one captured in the background
+            let attached: Vec<_> = [s.filename.as_ref(), s.live_filename.as_ref()]
+                .into_iter()
+                .flatten()
+                .filter_map(|name| {
+                    let repo = &s.session_proc(name)?.repository;
+                    let size = repo.storage.backend.get_file_size().ok()?;
+                    Some((name.clone(), size.0, repo.current_metadata()))
+                })
+                .collect();
+            (
+                s.root.clone(),
+                s.filename.clone(),
+                s.live_filename.clone(),
+                attached,
+            )
+        };
+
+        let mut sessions = Vec::new();
+        for (name, handle) in get_files(&root).await? {
+            let is_open = open.as_deref() == Some(name.as_str());
+            // A locked file may refuse getFile(), so use the open handle when we have it
+            let (size, metadata) = match attached.iter().find(|(n, ..)| *n == name) {
+                Some((_, size, metadata)) => (*size, metadata.clone()),
+                None => (
+                    get_file_size(&handle).await.unwrap_or(0),
+                    read_session_metadata(&root, &name).await,
+                ),
+            };
+            sessions.push(SessionInfo {
+                label: session_label(&name),
+                created_at: session_timestamp(&name),
+                size,
+                is_live: live.as_deref() == Some(name.as_str()),
+                is_open,
+                metadata,
+                name,
+            });
+        }
+
+        state_rc.borrow().send_msg(WorkerMsg::SessionList(sessions));
+        Ok(())
+    }
+
+    /// Opens a stored session in the monitor.
+    /// Any session other than the live one is opened read-only, while the
+    /// live session keeps capturing in the background.
+    pub async fn open(state_rc: Rc<RefCell<WorkerState>>, name: String) -> Result<(), LogError> {
+        let (root, current, live_shown, live_stashed, writable) = {
+            let s = state_rc.borrow();
+            let is_live = s.live_filename.is_some() && s.live_filename == s.filename;
+            let opens_live = s.live_filename.as_deref() == Some(name.as_str());
+            (
+                s.root.clone(),
+                s.filename.clone(),
+                is_live && !s.read_only,
+                s.live.is_some() && opens_live,
+                opens_live,
+            )
+        };
+        if current.as_deref() == Some(name.as_str()) {
+            return Self::list(state_rc).await;
+        }
+        if live_stashed {
+            Self::restore_live(&state_rc);
+            return Self::list(state_rc).await;
+        }
+
+        if live_shown {
+            Self::stash_live(&state_rc);
+        } else {
+            Self::begin_switch(&state_rc);
+        }
+        match open_session(&root, &name, writable).await {
+            Ok(files) => Self::attach(&state_rc, files, name)?,
+            Err(e) => {
+                // Fall back to the session that was open before
+                match current {
+                    Some(_) if live_shown => Self::restore_live(&state_rc),
+                    Some(prev) => Self::reattach(&state_rc, &root, prev).await?,
+                    None => Self::abort_switch(&state_rc),
+                }
+                return Err(e.into());
+            }
+        }
+        Self::list(state_rc).await
+    }
+
+    /// Shows a new session for a file being imported, attached for writing to
+    /// a processor of its own. As when an archive is opened, the live session
+    /// keeps capturing in the background. Returns the name of the new session.
+    pub async fn open_import(
+        state_rc: &Rc<RefCell<WorkerState>>,
+        metadata: SessionMetadata,
+    ) -> Result<String, LogError> {
+        let (root, current, live_shown) = {
+            let s = state_rc.borrow();
+            let is_live = s.live_filename.is_some() && s.live_filename == s.filename;
+            (s.root.clone(), s.filename.clone(), is_live && !s.read_only)
+        };
+        if live_shown {
+            Self::stash_live(state_rc);
+        } else {
+            Self::begin_switch(state_rc);
+        }
+
+        let mut filename = None;
+        let files = match new_session(&root, &mut filename).await {
+            Ok(files) => files,
+            Err(e) => {
+                match current {
+                    Some(_) if live_shown => Self::restore_live(state_rc),
+                    Some(prev) => Self::reattach(state_rc, &root, prev).await?,
+                    None => Self::abort_switch(state_rc),
+                }
+                return Err(e.into());
+            }
+        };
+        // Set whenever the session is created
+        let name = filename.unwrap_or_default();
+
+        let attached = {
+            let mut guard = state_rc.borrow_mut();
+            let s = &mut *guard;
+            let mut view = LogProcessor::new();
+            view.copy_view_settings(&s.proc);
+            // The file is decoded like the serial data
+            view.set_encoding(s.live.as_ref().unwrap_or(&s.proc).encoding());
+            s.proc = view;
+            s.read_only = true;
+            s.proc.set_sync_handle(files).and_then(|_| {
+                s.proc.repository.set_metadata(SessionMetadata {
+                    encoding: s.proc.encoding(),
+                    ..metadata
+                })
+            })
+        };
+        Self::show(state_rc, name.clone());
+        attached?;
+        Ok(name)
+    }
+
+    /// Renames a stored session by giving it a label.
+    /// The open session is unlocked for the move and reopened under its new name.
+    pub async fn rename(
+        state_rc: Rc<RefCell<WorkerState>>,
+        name: String,
+        label: String,
+    ) -> Result<(), LogError> {
+        let (root, is_open, is_stashed) = {
+            let s = state_rc.borrow();
+            (
+                s.root.clone(),
+                s.filename.as_deref() == Some(name.as_str()),
+                s.live.is_some() && s.live_filename.as_deref() == Some(name.as_str()),
+            )
+        };
+
+        if is_open {
+            Self::begin_switch(&state_rc);
+        } else if is_stashed {
+            let mut s = state_rc.borrow_mut();
+            s.pending_chunks.get_or_insert_with(Vec::new);
+            if let Some(live) = s.live.as_mut() {
+                live.repository.release_storage();
+            }
+        }
+        let result = rename_session(&root, &name, &label).await;
+
+        if is_open || is_stashed {
+            let new_name = result.as_ref().cloned().unwrap_or_else(|_| name.clone());
+            {
+                let mut s = state_rc.borrow_mut();
+                if s.live_filename.as_deref() == Some(name.as_str()) {
+                    s.live_filename = Some(new_name.clone());
+                }
+            }
+            if is_open {
+                Self::reattach(&state_rc, &root, new_name).await?;
+            } else {
+                Self::reattach_live(&state_rc, &root, &new_name).await?;
+            }
+        }
+
+        result?;
+        Self::list(state_rc).await
+    }
+
+    /// Deletes a stored session. The open and live sessions cannot be deleted.
+    pub async fn delete(state_rc: Rc<RefCell<WorkerState>>, name: String) -> Result<(), LogError> {
+        let root = {
+            let s = state_rc.borrow();
+            if s.filename.as_deref() == Some(name.as_str())
+                || s.live_filename.as_deref() == Some(name.as_str())
+            {
+                return Err(LogError::Storage(
+                    "Cannot delete a session that is in use".into(),
+                ));
+            }
+            s.root.clone()
+        };
+
+        delete_session(&root, &name).await?;
+        Self::list(state_rc).await
+    }
+
+    /// Deletes the oldest sessions not in use until storage is no longer low.
+    /// Returns the number of sessions deleted.
+    pub async fn free_space(state_rc: Rc<RefCell<WorkerState>>) -> usize {
+        let (root, open, live) = {
+            let s = state_rc.borrow();
+            (s.root.clone(), s.filename.clone(), s.live_filename.clone())
+        };
+        let Ok(files) = get_files(&root).await else {
+            return 0;
+        };
+
+        let mut deleted = 0;
+        // Listed newest first
+        for (name, _) in files.into_iter().rev() {
+            if open.as_deref() == Some(name.as_str()) || live.as_deref() == Some(name.as_str()) {
+                continue;
+            }
+            if delete_session(&root, &name).await.is_ok() {
+                deleted += 1;
+            }
+            let low = estimate_storage()
+                .await
+                .map(|(usage, quota)| {
+                    StorageUsage {
+                        usage,
+                        quota,
+                        ..Default::default()
+                    }
+                    .is_low()
+                })
+                .unwrap_or(false);
+            if !low {
+                break;
+            }
+        }
+        deleted
+    }
+
+    /// Streams a stored session to the main thread for saving
+    pub async fn download(
+        state_rc: Rc<RefCell<WorkerState>>,
+        name: String,
+    ) -> Result<(), LogError> {
+        let root = {
+            let s = state_rc.borrow();
+            if let Some(proc) = s.session_proc(&name) {
+                // The export reads the file itself, so it must be in order
+                while proc.repository.compact(u64::MAX)? {}
+                let backend = &proc.repository.storage.backend;
+                let size = backend.get_file_size()?;
+                let handle =
+                    backend.inner().handle.as_ref().cloned().ok_or_else(|| {
+                        LogError::Storage("OPFS handle missing for export".into())
+                    })?;
+                let repo = &proc.repository;
+                let header =
+                    LogExporter::session_header(repo.current_metadata().as_ref(), &repo.bookmarks);
+                let stream = LogExporter::export_logs(handle, size, header)?;
+                s.post_export_stream(&stream, &name);
+                return Ok(());
+            }
+            s.root.clone()
+        };
+
+        let file_handle = get_file_handle(&root, &name).await?;
+        let file = wasm_bindgen_futures::JsFuture::from(file_handle.get_file()).await?;
+        let parts = js_sys::Array::new();
+        let metadata = read_session_metadata(&root, &name).await;
+        let bookmarks = read_session_bookmarks(&root, &name).await;
+        if let Some(header) = LogExporter::session_header(metadata.as_ref(), &bookmarks) {
+            parts.push(&header.into());
+        }
+        parts.push(&file);
+        let stream = web_sys::Blob::new_with_blob_sequence(&parts)?.stream();
+        state_rc.borrow().post_export_stream(&stream, &name);
+        Ok(())
+    }
+
+    /// Releases the open session; chunks arriving meanwhile are held back
+    fn begin_switch(state_rc: &Rc<RefCell<WorkerState>>) {
+        let mut s = state_rc.borrow_mut();
+        s.pending_chunks.get_or_insert_with(Vec::new);
+        s.proc.repository.release_storage();
+        s.filename = None;
+    }
+
+    /// Drops the switch state when no session could be reopened
+    fn abort_switch(state_rc: &Rc<RefCell<WorkerState>>) {
+        state_rc.borrow_mut().pending_chunks = None;
+    }
+
+    /// Moves the shown live session to the background, where it keeps
+    /// capturing, and leaves an empty view with the same settings
+    fn stash_live(state_rc: &Rc<RefCell<WorkerState>>) {
+        let mut s = state_rc.borrow_mut();
+        let mut view = LogProcessor::new();
+        view.copy_view_settings(&s.proc);
+        let mut live = std::mem::replace(&mut s.proc, view);
+        // Nothing looks at the live session until it is shown again
+        live.repository.index.clear_filter();
+        live.repository.index.sorted = None;
+        live.sort = None;
+        s.live = Some(live);
+        s.read_only = true;
+        s.filename = None;
+    }
+
+    /// Releases the shown archive and brings the live session back into view
+    pub fn restore_live(state_rc: &Rc<RefCell<WorkerState>>) {
+        let name = {
+            let mut s = state_rc.borrow_mut();
+            let Some(mut live) = s.live.take() else {
+                return;
+            };
+            s.proc.repository.release_storage();
+            live.copy_view_settings(&s.proc);
+            s.proc = live;
+            s.read_only = false;
+            s.live_filename.clone()
+        };
+        match name {
+            Some(name) => Self::show(state_rc, name),
+            None => Self::abort_switch(state_rc),
+        }
+    }
+
+    /// Locks the live session `name` again while an archive is shown
+    async fn reattach_live(
+        state_rc: &Rc<RefCell<WorkerState>>,
+        root: &web_sys::FileSystemDirectoryHandle,
+        name: &str,
+    ) -> Result<(), LogError> {
+        let files = match open_session(root, name, true).await {
+            Ok(files) => files,
+            Err(e) => {
+                // Capture cannot go on without its session
+                let mut s = state_rc.borrow_mut();
+                s.live = None;
+                s.pending_chunks = None;
+                return Err(e.into());
+            }
+        };
+        let mut s = state_rc.borrow_mut();
+        if let Some(live) = s.live.as_mut() {
+            live.set_sync_handle(files)?;
+        }
+        s.replay_pending_chunks();
+        Ok(())
+    }
+
+    /// Locks `name` again and attaches it
+    async fn reattach(
+        state_rc: &Rc<RefCell<WorkerState>>,
+        root: &web_sys::FileSystemDirectoryHandle,
+        name: String,
+    ) -> Result<(), LogError> {
+        let writable = state_rc.borrow().live_filename.as_deref() == Some(name.as_str());
+        match open_session(root, &name, writable).await {
+            Ok(files) => Self::attach(state_rc, files, name),
+            Err(e) => {
+                Self::abort_switch(state_rc);
+                Err(e.into())
+            }
+        }
+    }
+
+    /// Attaches a locked session to the processor and notifies the main thread
+    fn attach(
+        state_rc: &Rc<RefCell<WorkerState>>,
+        files: SessionFiles,
+        name: String,
+    ) -> Result<(), LogError> {
+        let recovery = {
+            let mut s = state_rc.borrow_mut();
+            s.read_only = s.live_filename.as_deref() != Some(name.as_str());
+            if s.read_only {
+                s.proc.set_read_only_handle(files)?;
+                Recovery::default()
+            } else {
+                s.proc.set_sync_handle(files)?
+            }
+        };
+        Self::show(state_rc, name);
+        if let Some(notice) = recovery.notice() {
+            state_rc.borrow().send_msg(WorkerMsg::Notice(notice));
+        }
+        Ok(())
+    }
+
+    /// Shows the session attached to the processor from its first line
+    fn show(state_rc: &Rc<RefCell<WorkerState>>, name: String) {
+        let mut s = state_rc.borrow_mut();
+        let read_only = s.read_only;
+        s.filename = Some(name.clone());
+        s.proc.repository.index.clear_filter();
+        s.replay_pending_chunks();
+
+        let count = s.proc.get_line_count() as usize;
+        s.last_reported_count = count;
+        s.send_msg(WorkerMsg::SessionOpened { name, read_only });
+        s.send_msg(WorkerMsg::TotalLines(count));
+        drop(s);
+        LogSorter::restart(state_rc);
+    }
+}
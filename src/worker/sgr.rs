@@ -0,0 +1,230 @@
+use crate::types::StyledSpan;
+
+/// Text color of the monitor view, used when inverse video swaps in the default
+const DEFAULT_FG: &str = "#d1d5db";
+/// Background of the monitor view
+const DEFAULT_BG: &str = "#0d0f10";
+
+/// The 16 basic colors, tuned to stay readable on the dark background.
+/// Black is drawn gray as text; see [`basic_color`].
+const BASIC_COLORS: [&str; 16] = [
+    "#9ca3af", "#ef4444", "#10b981", "#f59e0b", "#3b82f6", "#d946ef", "#06b6d4", "#f3f4f6",
+    "#d1d5db", "#f87171", "#34d399", "#fbbf24", "#60a5fa", "#e879f9", "#22d3ee", "#ffffff",
+];
+
+/// Graphic rendition in effect on a line, changed by SGR (`ESC [ ... m`) sequences
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Sgr {
+    pub color: Option<String>,
+    pub background: Option<String>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub inverse: bool,
+}
+
+impl Sgr {
+    /// Applies the parameters of one SGR sequence, e.g. `1;38;5;208`.
+    /// Extended colors may also use colon sub-parameters (`38:2::255:128:0`).
+    pub fn apply(&mut self, params: &str) {
+        if params.is_empty() {
+            *self = Self::default();
+            return;
+        }
+        let mut codes = params.split(';');
+        while let Some(code) = codes.next() {
+            if code.contains(':') {
+                let mut sub = code.split(':');
+                let target = sub.next().unwrap_or_default();
+                let mut sub: Vec<&str> = sub.collect();
+                // `38:2:<colorspace>:r:g:b` carries an (often empty) colorspace id
+                if sub.first() == Some(&"2") && sub.len() == 5 {
+                    sub.remove(1);
+                }
+                self.extended(target, &mut sub.into_iter());
+                continue;
+            }
+            // An empty parameter means 0; one out of range (`300`) is skipped
+            let code = match code {
+                "" => 0,
+                code => match code.parse::<u8>() {
+                    Ok(n) => n,
+                    Err(_) => continue,
+                },
+            };
+            match code {
+                0 => *self = Self::default(),
+                1 => self.bold = true,
+                3 => self.italic = true,
+                4 => self.underline = true,
+                7 => self.inverse = true,
+                22 => self.bold = false,
+                23 => self.italic = false,
+                24 => self.underline = false,
+                27 => self.inverse = false,
+                n @ 30..=37 => self.color = Some(basic_color(n - 30, false)),
+                n @ 90..=97 => self.color = Some(basic_color(n - 90 + 8, false)),
+                39 => self.color = None,
+                n @ 40..=47 => self.background = Some(basic_color(n - 40, true)),
+                n @ 100..=107 => self.background = Some(basic_color(n - 100 + 8, true)),
+                49 => self.background = None,
+                38 => self.extended("38", &mut codes),
+                48 => self.extended("48", &mut codes),
+                _ => {}
+            }
+        }
+    }
+
+    /// `38`/`48` followed by `5;n` (256-color) or `2;r;g;b` (truecolor)
+    fn extended<'a>(&mut self, target: &str, args: &mut impl Iterator<Item = &'a str>) {
+        let mut next = || args.next().and_then(|a| a.parse::<u8>().ok());
+        let color = match next() {
+            Some(5) => next().map(|n| indexed_color(n, target == "48")),
+            Some(2) => match (next(), next(), next()) {
+                (Some(r), Some(g), Some(b)) => Some(format!("#{:02x}{:02x}{:02x}", r, g, b)),
+                _ => None,
+            },
+            _ => None,
+        };
+        match target {
+            "38" => self.color = color.or(self.color.take()),
+            "48" => self.background = color.or(self.background.take()),
+            _ => {}
+        }
+    }
+
+    /// A span of `text` in this rendition; inverse video is resolved here
+    pub fn span(&self, text: &str) -> StyledSpan {
+        let (color, background) = if self.inverse {
+            (
+                Some(self.background.as_deref().unwrap_or(DEFAULT_BG).to_string()),
+                Some(self.color.as_deref().unwrap_or(DEFAULT_FG).to_string()),
+            )
+        } else {
+            (self.color.clone(), self.background.clone())
+        };
+        StyledSpan {
+            text: text.to_string(),
+            color,
+            background,
+            bold: self.bold,
+            italic: self.italic,
+            underline: self.underline,
+            mark: None,
+        }
+    }
+}
+
+/// One of the 16 basic colors; black stays black only as a background
+fn basic_color(index: u8, background: bool) -> String {
+    match (index, background) {
+        (0, true) => "#000000".to_string(),
+        (i, _) => BASIC_COLORS[i as usize & 15].to_string(),
+    }
+}
+
+/// Color of the xterm 256-color palette
+fn indexed_color(n: u8, background: bool) -> String {
+    match n {
+        0..=15 => basic_color(n, background),
+        16..=231 => {
+            let level = |v: u8| if v == 0 { 0 } else { 55 + v * 40 };
+            let n = n - 16;
+            format!(
+                "#{:02x}{:02x}{:02x}",
+                level(n / 36),
+                level(n / 6 % 6),
+                level(n % 6)
+            )
+        }
+        _ => {
+            let v = 8 + (n - 232) * 10;
+            format!("#{:02x}{:02x}{:02x}", v, v, v)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sgr(params: &[&str]) -> Sgr {
+        let mut sgr = Sgr::default();
+        for p in params {
+            sgr.apply(p);
+        }
+        sgr
+    }
+
+    #[test]
+    fn test_basic_colors() {
+        assert_eq!(sgr(&["31"]).color.as_deref(), Some("#ef4444"));
+        assert_eq!(sgr(&["91"]).color.as_deref(), Some("#f87171"));
+        assert_eq!(sgr(&["44"]).background.as_deref(), Some("#3b82f6"));
+        assert_eq!(sgr(&["40"]).background.as_deref(), Some("#000000"));
+        assert_eq!(sgr(&["102"]).background.as_deref(), Some("#34d399"));
+        assert_eq!(sgr(&["31;44", "39"]).color, None);
+        assert_eq!(sgr(&["31;44", "49"]).background, None);
+        assert_eq!(sgr(&["31;44", ""]), Sgr::default());
+    }
+
+    #[test]
+    fn test_empty_and_invalid_params() {
+        // An out-of-range parameter is skipped, not read as a reset
+        assert!(sgr(&["1;300"]).bold);
+        assert_eq!(sgr(&["31", "300;1"]).color.as_deref(), Some("#ef4444"));
+        // An empty parameter still resets
+        assert_eq!(sgr(&["1;31", ";4"]), sgr(&["4"]));
+    }
+
+    #[test]
+    fn test_256_colors() {
+        assert_eq!(sgr(&["38;5;1"]).color.as_deref(), Some("#ef4444"));
+        assert_eq!(sgr(&["38;5;208"]).color.as_deref(), Some("#ff8700"));
+        assert_eq!(sgr(&["48;5;16"]).background.as_deref(), Some("#000000"));
+        assert_eq!(sgr(&["38;5;244"]).color.as_deref(), Some("#808080"));
+        assert_eq!(sgr(&["38:5:196"]).color.as_deref(), Some("#ff0000"));
+        // Parameters after the color still apply
+        let s = sgr(&["38;5;21;1"]);
+        assert_eq!(s.color.as_deref(), Some("#0000ff"));
+        assert!(s.bold);
+    }
+
+    #[test]
+    fn test_truecolor() {
+        assert_eq!(sgr(&["38;2;255;128;0"]).color.as_deref(), Some("#ff8000"));
+        assert_eq!(sgr(&["48;2;1;2;3"]).background.as_deref(), Some("#010203"));
+        assert_eq!(sgr(&["38:2::10:20:30"]).color.as_deref(), Some("#0a141e"));
+        assert_eq!(sgr(&["38:2:10:20:30"]).color.as_deref(), Some("#0a141e"));
+        // A truncated color leaves the previous one
+        assert_eq!(sgr(&["32", "38;2;1"]).color.as_deref(), Some("#10b981"));
+    }
+
+    #[test]
+    fn test_bold_italic_underline() {
+        let s = sgr(&["1;3;4"]);
+        assert!(s.bold && s.italic && s.underline);
+        let span = s.span("x");
+        assert!(span.bold && span.italic && span.underline);
+
+        assert!(!sgr(&["1", "22"]).bold);
+        assert!(!sgr(&["3", "23"]).italic);
+        assert!(!sgr(&["4", "24"]).underline);
+        assert_eq!(sgr(&["1;3;4;31", "0"]), Sgr::default());
+    }
+
+    #[test]
+    fn test_inverse() {
+        let span = sgr(&["7"]).span("x");
+        assert_eq!(span.color.as_deref(), Some(DEFAULT_BG));
+        assert_eq!(span.background.as_deref(), Some(DEFAULT_FG));
+
+        let span = sgr(&["31;42;7"]).span("x");
+        assert_eq!(span.color.as_deref(), Some("#10b981"));
+        assert_eq!(span.background.as_deref(), Some("#ef4444"));
+
+        let span = sgr(&["31;7", "27"]).span("x");
+        assert_eq!(span.color.as_deref(), Some("#ef4444"));
+        assert_eq!(span.background, None);
+    }
+}
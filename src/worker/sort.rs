@@ -0,0 +1,456 @@
+use crate::types::{SortSpec, WorkerMsg};
+use crate::worker::commands::RequestWindowCommand;
+use crate::worker::error::LogError;
+use crate::worker::json_line::SortKey;
+use crate::worker::repository::index::sorted_view::SortedView;
+use crate::worker::repository::index::LineRange;
+use crate::worker::repository::storage::StorageBackend;
+use crate::worker::repository::LogRepository;
+use crate::worker::state::WorkerState;
+use gloo_timers::future::TimeoutFuture;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::rc::Rc;
+use wasm_bindgen_futures::spawn_local;
+
+/// Session lines read per sort step, between yields to other messages
+const SORT_BATCH_SIZE: usize = 5000;
+
+/// Orders the view by a field of its JSON lines. The keys are read from the
+/// stored log; the sorted view then keeps the order of its lines and their keys.
+pub struct LogSorter;
+
+/// A sort in progress. The view lines are read in batches, each kept as a run
+/// of lines sorted by key; the runs are then merged. Keys are held for one
+/// batch and for the head of each run, then for the merged lines only.
+pub struct SortScan {
+    /// Absolute line the next batch starts at
+    next: usize,
+    runs: Vec<Vec<usize>>,
+    /// Lines of each run already merged
+    taken: Vec<usize>,
+    /// Next line of each run, while merging
+    heads: BinaryHeap<Head>,
+    merging: bool,
+    /// Merged lines with their keys, in view order
+    merged: Vec<(usize, SortKey)>,
+}
+
+/// Next line of a run with its key; the heap yields the first line in view
+/// order, lines of earlier runs first on equal keys
+struct Head {
+    key: SortKey,
+    descending: bool,
+    run: usize,
+    line: usize,
+}
+
+impl Ord for Head {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .key
+            .compare(&self.key, self.descending)
+            .then_with(|| other.run.cmp(&self.run))
+    }
+}
+
+impl PartialOrd for Head {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Head {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for Head {}
+
+impl SortScan {
+    /// A scan of the view from absolute line `first_line`
+    pub fn new(first_line: usize) -> Self {
+        Self {
+            next: first_line,
+            runs: Vec::new(),
+            taken: Vec::new(),
+            heads: BinaryHeap::new(),
+            merging: false,
+            merged: Vec::new(),
+        }
+    }
+
+    /// Queues the next line of `run` that the ring buffer has not dropped
+    fn advance<B: StorageBackend + Default>(
+        &mut self,
+        repo: &LogRepository<B>,
+        spec: &SortSpec,
+        run: usize,
+    ) -> Result<(), LogError> {
+        while let Some(&line) = self.runs[run].get(self.taken[run]) {
+            self.taken[run] += 1;
+            if let Some(key) = LogSorter::line_key(repo, spec, line)? {
+                self.heads.push(Head {
+                    key,
+                    descending: spec.descending,
+                    run,
+                    line,
+                });
+                return Ok(());
+            }
+        }
+        // Merged runs are released as they run out
+        self.runs[run] = Vec::new();
+        Ok(())
+    }
+}
+
+impl LogSorter {
+    /// Sorts the view again if a sort is set, e.g. after the filter changed.
+    /// Runs once the caller releases the worker state.
+    pub fn restart(state_rc: &Rc<RefCell<WorkerState>>) {
+        let state_rc = state_rc.clone();
+        spawn_local(async move {
+            if state_rc.borrow().proc.sort.is_none() {
+                return;
+            }
+            if let Err(e) = Self::sort_async(state_rc.clone()).await {
+                state_rc.borrow().send_error(e.into());
+            }
+        });
+    }
+
+    /// Reads and merges the view lines in batches, then installs the order.
+    /// A newer sort or filter cancels the run.
+    pub async fn sort_async(state_rc: Rc<RefCell<WorkerState>>) -> Result<(), LogError> {
+        let (sort_id, search_id, spec) = {
+            let mut state = state_rc.borrow_mut();
+            state.current_sort_id += 1;
+            state.proc.repository.index.sorted = None;
+            let Some(spec) = state.proc.sort.clone() else {
+                Self::refresh(&mut state, false)?;
+                return Ok(());
+            };
+            state.send_msg(WorkerMsg::SortRunning(true));
+            (state.current_sort_id, state.current_search_id, spec)
+        };
+
+        let mut scan = SortScan::new(state_rc.borrow().proc.repository.index.first_line);
+        let mut buf = Vec::new();
+        loop {
+            {
+                let state = state_rc.borrow();
+                if state.current_sort_id != sort_id || state.current_search_id != search_id {
+                    return Ok(());
+                }
+                if Self::sort_batch(&state.proc.repository, &spec, &mut scan, &mut buf)? {
+                    break;
+                }
+            }
+            TimeoutFuture::new(16).await;
+        }
+        let mut state = state_rc.borrow_mut();
+        Self::install(&mut state.proc.repository, &spec, scan)?;
+        Self::refresh(&mut state, true)
+    }
+
+    /// Re-sends the visible window in the new order
+    fn refresh(state: &mut WorkerState, sorted: bool) -> Result<(), LogError> {
+        if sorted {
+            state.send_msg(WorkerMsg::SortRunning(false));
+        }
+        let Some((start_line, count)) = state.last_window else {
+            return Ok(());
+        };
+        let msg = RequestWindowCommand { start_line, count }.window(&state.proc)?;
+        state.send_msg(msg);
+        Ok(())
+    }
+
+    /// Takes one step of `scan`: sorts the view lines among the next batch of
+    /// session lines into a run, or once the end of the log is reached, merges
+    /// the next batch of lines of the runs. Returns whether the merge is done.
+    pub fn sort_batch<B: StorageBackend + Default>(
+        repo: &LogRepository<B>,
+        spec: &SortSpec,
+        scan: &mut SortScan,
+        buf: &mut Vec<u8>,
+    ) -> Result<bool, LogError> {
+        if scan.merging {
+            for _ in 0..SORT_BATCH_SIZE {
+                let Some(head) = scan.heads.pop() else {
+                    return Ok(true);
+                };
+                scan.merged.push((head.line, head.key));
+                scan.advance(repo, spec, head.run)?;
+            }
+            return Ok(scan.heads.is_empty());
+        }
+
+        let index = &repo.index;
+        // Lines dropped by the ring buffer meanwhile are skipped
+        let start = scan.next.saturating_sub(index.first_line);
+        let end = (start + SORT_BATCH_SIZE).min(index.line_count);
+        if start < end {
+            let range = LineRange {
+                start: index.line_offsets.get(start),
+                end: index.line_offsets.get(end),
+            };
+            let size = (range.end.0 - range.start.0) as usize;
+            buf.resize(size, 0);
+            repo.storage.backend.read_at(range.start, buf)?;
+            let text = String::from_utf8_lossy(buf);
+            let mut keys = Vec::new();
+            for (i, line) in text.split_terminator('\n').enumerate() {
+                if index.in_view(start + i) {
+                    let key = SortKey::of_line(line, &spec.field);
+                    keys.push((key, index.first_line + start + i));
+                }
+            }
+            // Stable, so equal keys stay in log order
+            keys.sort_by(|a, b| a.0.compare(&b.0, spec.descending));
+            scan.runs
+                .push(keys.into_iter().map(|(_, line)| line).collect());
+        }
+        scan.next = index.first_line + end;
+        if end >= index.line_count {
+            scan.merging = true;
+            scan.taken = vec![0; scan.runs.len()];
+            for run in 0..scan.runs.len() {
+                scan.advance(repo, spec, run)?;
+            }
+        }
+        Ok(false)
+    }
+
+    /// Installs the merged order of `scan`. View lines the scan did not see,
+    /// i.e. received while the runs were merged or shown as context of a later
+    /// match, are then placed like received lines.
+    pub fn install<B: StorageBackend + Default>(
+        repo: &mut LogRepository<B>,
+        spec: &SortSpec,
+        scan: SortScan,
+    ) -> Result<(), LogError> {
+        let first_line = repo.index.first_line;
+        let mut order = scan.merged;
+        order.retain(|&(line, _)| line >= first_line);
+        let sorted = SortedView::new(order, first_line);
+        let index = &repo.index;
+        let joined: Vec<usize> = (0..index.line_count)
+            .filter(|&l| index.in_view(l) && sorted.position(first_line + l).is_none())
+            .collect();
+        repo.index.sorted = Some(sorted);
+        Self::merge_lines(repo, spec, &joined)
+    }
+
+    /// Places session lines that joined a sorted view, e.g. received lines
+    /// matching the filter, at the position of their key
+    pub fn merge_lines<B: StorageBackend + Default>(
+        repo: &mut LogRepository<B>,
+        spec: &SortSpec,
+        lines: &[usize],
+    ) -> Result<(), LogError> {
+        if repo.index.sorted.is_none() || lines.is_empty() {
+            return Ok(());
+        }
+        let first_line = repo.index.first_line;
+        let mut new = Vec::with_capacity(lines.len());
+        for &line in lines {
+            let key = Self::line_key(repo, spec, first_line + line)?;
+            new.push((key.unwrap_or(SortKey::Missing), first_line + line));
+        }
+        new.sort_by(|a, b| a.0.compare(&b.0, spec.descending));
+
+        for (key, line) in new {
+            let Some(sorted) = &mut repo.index.sorted else {
+                return Ok(());
+            };
+            // Each new line goes after the lines with an equal key
+            let (mut low, mut high) = (0, sorted.len());
+            while low < high {
+                let mid = (low + high) / 2;
+                let shown = sorted.key(mid).unwrap_or(&SortKey::Missing);
+                if shown.compare(&key, spec.descending).is_le() {
+                    low = mid + 1;
+                } else {
+                    high = mid;
+                }
+            }
+            sorted.insert(low, line, key);
+        }
+        Ok(())
+    }
+
+    /// Sort key of absolute line `line`; `None` once the ring buffer dropped it
+    fn line_key<B: StorageBackend + Default>(
+        repo: &LogRepository<B>,
+        spec: &SortSpec,
+        line: usize,
+    ) -> Result<Option<SortKey>, LogError> {
+        let range = line
+            .checked_sub(repo.index.first_line)
+            .and_then(|line| repo.index.line_range(line));
+        let Some(range) = range else {
+            return Ok(None);
+        };
+        let bytes = repo.read_line(range)?;
+        let text = String::from_utf8_lossy(&bytes);
+        Ok(Some(SortKey::of_line(
+            text.trim_end_matches('\n'),
+            &spec.field,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::worker::processor::LogProcessor;
+    use crate::worker::repository::index::LineIndex;
+    use crate::worker::repository::storage::{MemoryBackend, SessionFiles};
+    use crate::worker::search::LogSearcher;
+
+    fn sort(proc: &mut LogProcessor<MemoryBackend>, field: &str, descending: bool) {
+        let spec = SortSpec {
+            field: field.into(),
+            descending,
+        };
+        proc.sort = Some(spec.clone());
+        let repo = &mut proc.repository;
+        let (mut scan, mut buf) = (SortScan::new(repo.index.first_line), Vec::new());
+        while !LogSorter::sort_batch(repo, &spec, &mut scan, &mut buf).unwrap() {}
+        LogSorter::install(repo, &spec, scan).unwrap();
+    }
+
+    fn temps(proc: &LogProcessor<MemoryBackend>) -> Vec<String> {
+        (0..proc.get_line_count() as usize)
+            .map(|i| {
+                let range = proc.repository.get_line_range(LineIndex(i)).unwrap();
+                let text = String::from_utf8(proc.repository.read_line(range).unwrap()).unwrap();
+                let object = crate::worker::json_line::parse_object(&text);
+                object
+                    .and_then(|o| o.get("temp").map(crate::worker::json_line::cell_text))
+                    .unwrap_or_else(|| "-".into())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_sort_and_merge_live_lines() {
+        let mut proc = LogProcessor::new();
+        proc.set_sync_handle(SessionFiles::in_memory()).unwrap();
+        let mut text = String::new();
+        for temp in [70, 85, 60, 90] {
+            text.push_str(&format!("{{\"temp\":{},\"id\":\"a\"}}\n", temp));
+        }
+        text.push_str("boot done\n");
+        proc.append_chunk(text.as_bytes(), false).unwrap();
+
+        sort(&mut proc, "temp", false);
+        assert_eq!(temps(&proc), ["60", "70", "85", "90", "-"]);
+        assert_eq!(proc.repository.view_index(1), Some(2));
+
+        // Received lines are placed by their key
+        proc.append_chunk(b"{\"temp\":80}\n{\"temp\":95}\nplain\n", false)
+            .unwrap();
+        assert_eq!(temps(&proc), ["60", "70", "80", "85", "90", "95", "-", "-"]);
+
+        sort(&mut proc, "temp", true);
+        assert_eq!(temps(&proc)[..3], ["95", "90", "85"]);
+
+        // The ring buffer drops lines from the sorted view too
+        proc.drop_oldest(20).unwrap();
+        assert_eq!(proc.get_line_count() as usize, temps(&proc).len());
+        assert!(!temps(&proc).contains(&"70".to_string()));
+    }
+
+    #[test]
+    fn test_lines_received_during_sort_are_kept() {
+        let mut proc = LogProcessor::new();
+        proc.set_sync_handle(SessionFiles::in_memory()).unwrap();
+        let mut text = String::new();
+        for i in 0..SORT_BATCH_SIZE + 10 {
+            text.push_str(&format!("{{\"temp\":{}}}\n", i * 31 % 500));
+        }
+        proc.append_chunk(text.as_bytes(), false).unwrap();
+
+        let spec = SortSpec {
+            field: "temp".into(),
+            descending: false,
+        };
+        proc.sort = Some(spec.clone());
+        let (mut scan, mut buf) = (SortScan::new(0), Vec::new());
+        LogSorter::sort_batch(&proc.repository, &spec, &mut scan, &mut buf).unwrap();
+        // Lines arrive while the scan reads, and again while it merges
+        proc.append_chunk(b"{\"temp\":1000}\n", false).unwrap();
+        LogSorter::sort_batch(&proc.repository, &spec, &mut scan, &mut buf).unwrap();
+        assert!(scan.merging);
+        proc.append_chunk(b"{\"temp\":-1}\nplain\n", false).unwrap();
+        while !LogSorter::sort_batch(&proc.repository, &spec, &mut scan, &mut buf).unwrap() {}
+        LogSorter::install(&mut proc.repository, &spec, scan).unwrap();
+
+        let temps = temps(&proc);
+        assert_eq!(temps.len(), SORT_BATCH_SIZE + 13);
+        assert_eq!(temps[0], "-1");
+        assert_eq!(temps[temps.len() - 2..], ["1000", "-"]);
+    }
+
+    #[test]
+    fn test_append_to_sorted_view() {
+        let mut proc = LogProcessor::new();
+        proc.set_sync_handle(SessionFiles::in_memory()).unwrap();
+        // More lines than one sort batch, so runs are merged
+        let mut text = String::new();
+        for i in 0..SORT_BATCH_SIZE * 2 + 100 {
+            text.push_str(&format!("{{\"temp\":{}}}\n", i * 7919 % 1000));
+        }
+        proc.append_chunk(text.as_bytes(), false).unwrap();
+        sort(&mut proc, "temp", false);
+
+        // Enough appended lines to merge the pending ones into the order
+        for chunk in 0..50 {
+            let mut text = String::new();
+            for i in 0..100 {
+                text.push_str(&format!("{{\"temp\":{}}}\n", (chunk * 100 + i) * 13 % 1000));
+            }
+            proc.append_chunk(text.as_bytes(), false).unwrap();
+        }
+        let temps: Vec<f64> = temps(&proc).iter().map(|t| t.parse().unwrap()).collect();
+        assert_eq!(temps.len(), SORT_BATCH_SIZE * 2 + 5100);
+        assert!(temps.windows(2).all(|w| w[0] <= w[1]));
+
+        // Every line is found at the index it is shown at
+        let repo = &proc.repository;
+        for index in (0..temps.len()).step_by(97) {
+            let line = repo.session_line(LineIndex(index)).unwrap();
+            assert_eq!(repo.view_index(line), Some(index));
+        }
+    }
+
+    #[test]
+    fn test_sort_filtered_view() {
+        let mut proc = LogProcessor::new();
+        proc.set_sync_handle(SessionFiles::in_memory()).unwrap();
+        let mut text = String::new();
+        for (i, temp) in [81, 20, 95, 85, 30].iter().enumerate() {
+            text.push_str(&format!("{{\"temp\":{},\"seq\":{}}}\n", temp, i));
+        }
+        proc.append_chunk(text.as_bytes(), false).unwrap();
+
+        let repo = &mut proc.repository;
+        let mut idx =
+            LogSearcher::begin(repo, "@temp>80".into(), false, false, false, (0, 0), None).unwrap();
+        let mut buf = Vec::new();
+        while let Some(next) = idx {
+            idx = LogSearcher::search_batch(repo, next, &mut buf).unwrap();
+        }
+        sort(&mut proc, "temp", true);
+        assert_eq!(temps(&proc), ["95", "85", "81"]);
+
+        proc.append_chunk(b"{\"temp\":10}\n{\"temp\":90}\n", false)
+            .unwrap();
+        assert_eq!(temps(&proc), ["95", "90", "85", "81"]);
+    }
+}
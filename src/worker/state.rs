@@ -1,5 +1,20 @@
+use crate::config::{
+    COMPACT_STEP_BYTES, FLUSH_INTERVAL_MS, SEARCH_INDEX_STEP_BLOCKS, STORAGE_ESTIMATE_INTERVAL_MS,
+    WORKER_UPDATE_INTERVAL_MS,
+};
+use crate::types::{
+    LevelCounts, LowSpacePolicy, SessionLimit, SessionMetadata, SizeLimitPolicy, StorageUsage,
+};
+use crate::utils::format_bytes;
+use crate::worker::commands::{RequestWindowCommand, WorkerCommand};
+use crate::worker::error::LogError;
 use crate::worker::processor::LogProcessor;
-use crate::worker::repository::storage::{get_opfs_root, init_opfs_session, new_session};
+use crate::worker::repository::storage::{
+    estimate_storage, get_opfs_root, init_opfs_session, new_session,
+};
+use crate::worker::repository::Recovery;
+use crate::worker::session::SessionLibrary;
+use crate::worker::sort::LogSorter;
 use crate::worker::types::WorkerMsg;
 use std::cell::RefCell;
 use std::rc::Rc;
@@ -8,12 +23,42 @@ use wasm_bindgen_futures::spawn_local;
 
 /// Worker state that manages the log processor and OPFS session
 pub(crate) struct WorkerState {
+    /// Processor of the session shown in the monitor
     pub(crate) proc: LogProcessor,
+    /// Processor of the live session while an archived one is shown, so
+    /// capture goes on in the background
+    pub(crate) live: Option<LogProcessor>,
+    /// Session currently shown in the monitor
     pub(crate) filename: Option<String>,
+    /// Session receiving serial data
+    pub(crate) live_filename: Option<String>,
+    /// Set while an archived session is shown; appends go to `live`
+    pub(crate) read_only: bool,
+    /// Chunks received while the storage lock is being swapped
+    pub(crate) pending_chunks: Option<Vec<(Vec<u8>, bool)>>,
+    /// Metadata for the session about to be created
+    pub(crate) pending_metadata: Option<SessionMetadata>,
+    pub(crate) size_limit: SessionLimit,
+    pub(crate) low_space_policy: LowSpacePolicy,
+    /// Last storage estimate; `paused` drops received data while space is low
+    pub(crate) storage_usage: StorageUsage,
+    /// Bytes received and dropped since capture was paused
+    pub(crate) paused_dropped: u64,
+    /// Last window requested by the UI, re-sent when line indices shift
+    pub(crate) last_window: Option<(usize, usize)>,
+    /// Set while a file import is streaming into the open session
+    pub(crate) importing: bool,
+    /// Set while ring trims are being compacted in the background
+    pub(crate) compacting: bool,
     pub(crate) root: web_sys::FileSystemDirectoryHandle,
     pub(crate) scope: web_sys::DedicatedWorkerGlobalScope,
     pub(crate) last_reported_count: usize,
+    pub(crate) last_reported_levels: LevelCounts,
     pub(crate) current_search_id: u32,
+    /// Bumped to cancel a find-in-log walk
+    pub(crate) current_find_id: u32,
+    /// Bumped to cancel the sort of the view
+    pub(crate) current_sort_id: u32,
     pub(crate) last_reported_active_line: Option<String>,
     pub(crate) current_active_line: Option<String>,
 }
@@ -21,37 +66,67 @@ pub(crate) struct WorkerState {
 impl WorkerState {
     /// Creates a new WorkerState instance
     pub(crate) async fn new() -> Result<Self, JsValue> {
-        let mut proc = LogProcessor::new()?;
+        let root = get_opfs_root().await?;
+        // Before a new session becomes the newest one
+        let recovered = SessionLibrary::recover_interrupted(&root).await;
+
+        let mut proc = LogProcessor::new();
         let mut filename: Option<String> = None;
-        if let Ok(lock) = init_opfs_session(&mut filename).await {
-            let _ = proc.set_sync_handle(lock);
+        let mut attached = Ok(Recovery::default());
+        if let Ok(files) = init_opfs_session(&mut filename).await {
+            attached = proc.set_sync_handle(files);
         }
 
         let scope = js_sys::global().unchecked_into::<web_sys::DedicatedWorkerGlobalScope>();
-        let root = get_opfs_root().await?;
 
-        Ok(Self {
+        let state = Self {
             proc,
+            live: None,
+            live_filename: filename.clone(),
             filename,
+            read_only: false,
+            pending_chunks: None,
+            pending_metadata: None,
+            size_limit: SessionLimit::default(),
+            low_space_policy: LowSpacePolicy::default(),
+            storage_usage: StorageUsage::default(),
+            paused_dropped: 0,
+            last_window: None,
+            importing: false,
+            compacting: false,
             root,
             scope,
             last_reported_count: 0,
+            last_reported_levels: LevelCounts::default(),
             current_search_id: 0,
+            current_find_id: 0,
+            current_sort_id: 0,
             last_reported_active_line: None,
             current_active_line: None,
-        })
+        };
+        match recovered {
+            Ok(Some(notice)) => state.send_msg(WorkerMsg::Notice(notice)),
+            Ok(None) => {}
+            Err(e) => state.send_error(e.into()),
+        }
+        state.report_attach(attached);
+        Ok(state)
     }
 
     /// Starts a periodic update loop to send TotalLines to the main thread
     pub(crate) fn start_periodic_updates(state_rc: Rc<RefCell<Self>>) {
+        Self::start_storage_monitor(state_rc.clone());
+        Self::start_checkpoints(state_rc.clone());
+        Self::start_search_indexing(state_rc.clone());
         spawn_local(async move {
             loop {
                 gloo_timers::future::TimeoutFuture::new(crate::config::WORKER_UPDATE_INTERVAL_MS)
                     .await; // ~60fps
-                let (count, active_line, scope) = {
+                let (count, levels, active_line, scope) = {
                     let state = state_rc.borrow();
                     (
                         state.proc.get_line_count() as usize,
+                        state.proc.repository.level_counts(),
                         state.current_active_line.clone(),
                         state.scope.clone(),
                     )
@@ -65,9 +140,17 @@ impl WorkerState {
                     }
                 }
 
+                if levels != state.last_reported_levels {
+                    state.last_reported_levels = levels;
+                    if let Ok(msg) = serde_json::to_string(&WorkerMsg::LevelCounts(levels)) {
+                        let _ = scope.post_message(&msg.into());
+                    }
+                }
+
                 if active_line != state.last_reported_active_line {
                     state.last_reported_active_line = active_line.clone();
-                    if let Ok(msg) = serde_json::to_string(&WorkerMsg::ActiveLine(active_line)) {
+                    let styled = active_line.map(|line| state.proc.style_line(&line, &[], true));
+                    if let Ok(msg) = serde_json::to_string(&WorkerMsg::ActiveLine(styled)) {
                         let _ = scope.post_message(&msg.into());
                     }
                 }
@@ -75,24 +158,346 @@ impl WorkerState {
         });
     }
 
+    /// Polls storage usage at a slower pace than the line count, since the estimate is async
+    fn start_storage_monitor(state_rc: Rc<RefCell<Self>>) {
+        spawn_local(async move {
+            loop {
+                Self::check_storage(&state_rc).await;
+                gloo_timers::future::TimeoutFuture::new(STORAGE_ESTIMATE_INTERVAL_MS).await;
+            }
+        });
+    }
+
+    /// Periodically flushes appended data so a crash loses at most one interval
+    fn start_checkpoints(state_rc: Rc<RefCell<Self>>) {
+        spawn_local(async move {
+            loop {
+                gloo_timers::future::TimeoutFuture::new(FLUSH_INTERVAL_MS).await;
+                let mut s = state_rc.borrow_mut();
+                let result = s.proc.repository.checkpoint().and(
+                    s.live
+                        .as_mut()
+                        .map_or(Ok(()), |live| live.repository.checkpoint()),
+                );
+                if let Err(e) = result {
+                    s.send_error(e.into());
+                }
+            }
+        });
+    }
+
+    /// Adds stored lines to the search indexes of attached sessions a few blocks
+    /// at a time, so reopening a session or turning the index on does not read
+    /// the whole log at once
+    fn start_search_indexing(state_rc: Rc<RefCell<Self>>) {
+        spawn_local(async move {
+            loop {
+                gloo_timers::future::TimeoutFuture::new(WORKER_UPDATE_INTERVAL_MS).await;
+                let mut s = state_rc.borrow_mut();
+                let s = &mut *s;
+                let result = std::iter::once(&mut s.proc)
+                    .chain(&mut s.live)
+                    .try_for_each(|proc| {
+                        proc.repository
+                            .backfill_search_index(SEARCH_INDEX_STEP_BLOCKS)
+                            .map(|_| ())
+                    });
+                if let Err(e) = result {
+                    s.send_error(e.into());
+                }
+            }
+        });
+    }
+
+    /// Reports storage usage and applies the low-space policy
+    async fn check_storage(state_rc: &Rc<RefCell<Self>>) {
+        // Browsers without the estimate API simply get no meter
+        let Ok((usage, quota)) = estimate_storage().await else {
+            return;
+        };
+        let mut usage = StorageUsage {
+            usage,
+            quota,
+            ..Default::default()
+        };
+        let policy = state_rc.borrow().low_space_policy;
+
+        if usage.is_low() && policy == LowSpacePolicy::DeleteOldestSessions {
+            let deleted = SessionLibrary::free_space(state_rc.clone()).await;
+            if deleted > 0 {
+                if let Ok((u, q)) = estimate_storage().await {
+                    (usage.usage, usage.quota) = (u, q);
+                }
+                state_rc.borrow().send_msg(WorkerMsg::Notice(format!(
+                    "Storage low: deleted {} old session(s)",
+                    deleted
+                )));
+                let _ = SessionLibrary::list(state_rc.clone()).await;
+            }
+        }
+
+        let mut s = state_rc.borrow_mut();
+        usage.paused = policy == LowSpacePolicy::Pause && usage.is_low();
+        let indexes = || {
+            std::iter::once(&s.proc)
+                .chain(&s.live)
+                .map(|p| &p.repository.index)
+        };
+        usage.index_bytes = indexes().map(|i| i.memory_bytes() as u64).sum();
+        usage.search_index_bytes = indexes()
+            .map(|i| i.search_index.memory_bytes() as u64)
+            .sum();
+        if usage.paused != s.storage_usage.paused {
+            let notice = if usage.paused {
+                "Storage almost full: received data is not recorded until space is freed".into()
+            } else {
+                match std::mem::take(&mut s.paused_dropped) {
+                    0 => "Storage freed: capture resumed".into(),
+                    dropped => format!(
+                        "Storage freed: capture resumed; {} received while paused were not recorded",
+                        format_bytes(dropped)
+                    ),
+                }
+            };
+            s.send_msg(WorkerMsg::Notice(notice));
+        }
+        if usage != s.storage_usage {
+            s.storage_usage = usage;
+            s.send_msg(WorkerMsg::StorageUsage(usage));
+        }
+    }
+
     /// Handles creating a new session asynchronously
     pub(crate) fn handle_new_session(state_rc: Rc<RefCell<Self>>) {
+        Self::start_session(state_rc, true);
+    }
+
+    /// Continues the live capture in a new session file
+    pub(crate) fn roll_over(state_rc: Rc<RefCell<Self>>) {
+        Self::start_session(state_rc, false);
+    }
+
+    /// Finishes the live session and attaches a new one, in a task that runs once
+    /// the caller releases the worker state (e.g. the dispatcher after an append).
+    /// Chunks arriving meanwhile are held back and replayed into the new session.
+    /// With `reset_stream` unset, a partially received line carries over.
+    fn start_session(state_rc: Rc<RefCell<Self>>, reset_stream: bool) {
         spawn_local(async move {
-            let (root, filename_opt) = {
-                let s = state_rc.borrow();
-                (s.root.clone(), s.filename.clone())
+            if let Err(e) = Self::open_new_session(state_rc.clone(), reset_stream).await {
+                state_rc.borrow().send_error(e.into());
+            }
+        });
+    }
+
+    /// Async body of [`Self::start_session`]; resolves once the new session is attached.
+    /// While an archived session is shown, the new one is captured in the background.
+    pub(crate) async fn open_new_session(
+        state_rc: Rc<RefCell<Self>>,
+        reset_stream: bool,
+    ) -> Result<(), LogError> {
+        let root = {
+            let mut s = state_rc.borrow_mut();
+            s.pending_chunks.get_or_insert_with(Vec::new);
+            if let Some(live) = s.live_proc() {
+                let _ = live.repository.finish_session();
+            }
+            s.root.clone()
+        };
+
+        let mut filename = None;
+        let files = match new_session(&root, &mut filename).await {
+            Ok(files) => files,
+            Err(e) => {
+                let mut s = state_rc.borrow_mut();
+                s.pending_metadata = None;
+                s.replay_pending_chunks();
+                return Err(e.into());
+            }
+        };
+
+        let shown = {
+            let mut guard = state_rc.borrow_mut();
+            let s = &mut *guard;
+            s.live_filename = filename.clone();
+            let shown = s.live.is_none();
+            let proc = match s.live.as_mut() {
+                Some(live) => live,
+                None => {
+                    s.filename = filename.clone();
+                    s.read_only = false;
+                    &mut s.proc
+                }
             };
-            let mut filename = filename_opt;
-            if let Ok(lock) = new_session(&root, true, &mut filename).await {
+            let attached = proc.set_sync_handle(files);
+            if reset_stream {
+                let _ = proc.clear();
+            }
+            let recorded = match s.pending_metadata.take() {
+                Some(metadata) => proc.repository.set_metadata(SessionMetadata {
+                    encoding: proc.encoding(),
+                    ..metadata
+                }),
+                None => Ok(()),
+            };
+            s.report_attach(attached);
+            if let Err(e) = recorded {
+                s.send_error(e.into());
+            }
+            s.replay_pending_chunks();
+
+            if shown {
+                let count = s.proc.get_line_count() as usize;
+                s.last_reported_count = count;
+                s.send_msg(WorkerMsg::TotalLines(count));
+                if let Some(name) = filename {
+                    s.send_msg(WorkerMsg::SessionOpened {
+                        name,
+                        read_only: false,
+                    });
+                }
+            }
+            shown
+        };
+        if shown {
+            LogSorter::restart(&state_rc);
+        }
+        SessionLibrary::list(state_rc).await
+    }
+
+    /// Processor of the live session, whether shown or captured in the background
+    pub(crate) fn live_proc(&mut self) -> Option<&mut LogProcessor> {
+        match self.read_only {
+            false => Some(&mut self.proc),
+            true => self.live.as_mut(),
+        }
+    }
+
+    /// Processor holding `name`, if that session is attached
+    pub(crate) fn session_proc(&self, name: &str) -> Option<&LogProcessor> {
+        if self.filename.as_deref() == Some(name) {
+            Some(&self.proc)
+        } else if self.live_filename.as_deref() == Some(name) {
+            self.live.as_ref()
+        } else {
+            None
+        }
+    }
+
+    /// Appends the chunks held back during a session switch to the live session
+    pub(crate) fn replay_pending_chunks(&mut self) {
+        let pending = self.pending_chunks.take().unwrap_or_default();
+        let Some(live) = self.live_proc() else {
+            return;
+        };
+        let result = pending
+            .iter()
+            .try_for_each(|(chunk, is_hex)| live.append_chunk(chunk, *is_hex).map(drop));
+        if let Err(e) = result {
+            self.send_error(e.into());
+        }
+    }
+
+    /// Applies the session size limit after an append to the live session
+    pub(crate) fn enforce_size_limit(
+        &mut self,
+        state_rc: &Rc<RefCell<Self>>,
+    ) -> Result<(), LogError> {
+        let limit = self.size_limit;
+        if limit.max_bytes == 0 {
+            return Ok(());
+        }
+        let switching = self.pending_chunks.is_some();
+        let shown = !self.read_only;
+        let Some(live) = self.live_proc() else {
+            return Ok(());
+        };
+        let size = live.repository.session_size();
+        let Some(policy) = limit_action(limit, size, switching) else {
+            return Ok(());
+        };
+
+        match policy {
+            SizeLimitPolicy::Rollover => {
+                self.pending_metadata = live.repository.metadata.clone().map(|m| SessionMetadata {
+                    started_at: chrono::Utc::now().timestamp_millis(),
+                    ended_at: None,
+                    line_count: 0,
+                    ..m
+                });
+                // Hold back chunks right away, so appends before the switch
+                // neither grow this session nor start another one
+                self.pending_chunks.get_or_insert_with(Vec::new);
+                Self::roll_over(state_rc.clone());
+            }
+            SizeLimitPolicy::Ring => {
+                // Trim the session to 3/4 of the limit so it is not compacted on every batch
+                let target = ring_log_target(limit, live.repository.log_size(), size);
+                let dropped = live.drop_oldest(target)?;
+                // A running compaction takes up the new drops as well
+                if dropped > 0 && !self.compacting {
+                    self.compacting = true;
+                    Self::compact_in_background(state_rc.clone());
+                }
+                if dropped > 0 && shown {
+                    let count = self.proc.get_line_count() as usize;
+                    self.last_reported_count = count;
+                    self.send_msg(WorkerMsg::TotalLines(count));
+                    if !self.proc.repository.bookmarks.is_empty() {
+                        self.send_msg(WorkerMsg::BookmarkList(
+                            self.proc.repository.bookmarks.clone(),
+                        ));
+                    }
+                    // Line indices shifted; refresh the window the UI is showing
+                    if let Some((start_line, count)) = self.last_window {
+                        RequestWindowCommand { start_line, count }.execute(self, state_rc)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Moves the data kept by ring trims to the front of its files a step at a
+    /// time, so appends and window requests are served in between
+    fn compact_in_background(state_rc: Rc<RefCell<Self>>) {
+        spawn_local(async move {
+            loop {
+                gloo_timers::future::TimeoutFuture::new(0).await;
                 let mut s = state_rc.borrow_mut();
-                s.filename = filename;
-                let _ = s.proc.set_sync_handle(lock);
-                let _ = s.proc.clear();
-                s.send_msg(WorkerMsg::TotalLines(0));
+                let mut pending = Ok(false);
+                for proc in std::iter::once(&s.proc).chain(&s.live) {
+                    let step = proc.repository.compact(COMPACT_STEP_BYTES);
+                    pending = pending.and_then(|p| step.map(|more| p || more));
+                }
+                match pending {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        s.compacting = false;
+                        break;
+                    }
+                    Err(e) => {
+                        s.compacting = false;
+                        s.send_error(e.into());
+                        break;
+                    }
+                }
             }
         });
     }
 
+    /// Tells the user what had to be repaired while attaching a session, or why
+    /// it could not be attached
+    fn report_attach(&self, attached: Result<Recovery, LogError>) {
+        match attached {
+            Ok(recovery) => {
+                if let Some(notice) = recovery.notice() {
+                    self.send_msg(WorkerMsg::Notice(notice));
+                }
+            }
+            Err(e) => self.send_error(e.into()),
+        }
+    }
+
     /// Sends a message to the main thread
     pub(crate) fn send_msg(&self, msg: WorkerMsg) {
         if let Ok(s) = serde_json::to_string(&msg) {
@@ -100,9 +505,74 @@ impl WorkerState {
         }
     }
 
+    /// Hands an export stream to the main thread, which saves it under `name`
+    pub(crate) fn post_export_stream(&self, stream: &JsValue, name: &str) {
+        let resp = js_sys::Object::new();
+        let _ = js_sys::Reflect::set(&resp, &"type".into(), &"EXPORT_STREAM".into());
+        let _ = js_sys::Reflect::set(&resp, &"stream".into(), stream);
+        let _ = js_sys::Reflect::set(&resp, &"name".into(), &name.into());
+        let _ = self
+            .scope
+            .post_message_with_transfer(&resp, &js_sys::Array::of1(stream));
+    }
+
     /// Sends an error message to the main thread
     pub(crate) fn send_error(&self, err: JsValue) {
         let msg = format!("{:?}", err);
         self.send_msg(WorkerMsg::Error(msg));
     }
 }
+
+/// Policy the size limit applies after an append that brought the session to
+/// `size` bytes. Nothing applies while a session switch holds back chunks.
+fn limit_action(limit: SessionLimit, size: u64, switching: bool) -> Option<SizeLimitPolicy> {
+    (limit.max_bytes > 0 && size >= limit.max_bytes && !switching).then_some(limit.policy)
+}
+
+/// Log size a ring trim keeps so the session, sidecars included, ends up at 3/4
+/// of the limit. The sidecars grow with the log, so they shrink in proportion.
+fn ring_log_target(limit: SessionLimit, log_size: u64, session_size: u64) -> u64 {
+    let target = limit.max_bytes - limit.max_bytes / 4;
+    (log_size as u128 * target as u128 / session_size.max(1) as u128) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rollover_once_when_crossing_limit() {
+        let limit = SessionLimit {
+            max_bytes: 1000,
+            policy: SizeLimitPolicy::Rollover,
+        };
+        // Appends keep arriving until the new session is attached
+        let mut switching = false;
+        let mut rollovers = 0;
+        for size in [400, 900, 1000, 1300, 1800] {
+            if limit_action(limit, size, switching) == Some(SizeLimitPolicy::Rollover) {
+                rollovers += 1;
+                switching = true;
+            }
+        }
+        assert_eq!(rollovers, 1);
+        assert_eq!(limit_action(limit, 999, false), None);
+
+        let unlimited = SessionLimit {
+            max_bytes: 0,
+            ..limit
+        };
+        assert_eq!(limit_action(unlimited, u64::MAX, false), None);
+    }
+
+    #[test]
+    fn test_ring_target_counts_sidecars() {
+        let limit = SessionLimit {
+            max_bytes: 1000,
+            policy: SizeLimitPolicy::Ring,
+        };
+        // A log of 600 bytes with 400 bytes of sidecars keeps 450 + 300
+        assert_eq!(ring_log_target(limit, 600, 1000), 450);
+        assert_eq!(ring_log_target(limit, 1200, 1200), 750);
+    }
+}